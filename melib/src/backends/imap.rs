@@ -1568,6 +1568,7 @@ impl ImapType {
             )));
         }
         let _timeout = get_conf_val!(s["timeout"], 16_u64)?;
+        let _managesieve_port: u16 = get_conf_val!(s["managesieve_port"], 4190)?;
         Ok(())
     }
 
@@ -1729,10 +1730,10 @@ async fn fetch_hlpr(state: &mut FetchState) -> Result<Vec<Envelope>> {
                 if max_uid_left > 0 {
                     debug!("{} max_uid_left= {}", mailbox_hash, max_uid_left);
                     let command = if max_uid_left == 1 {
-                        "UID FETCH 1 (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)".to_string()
+                        "UID FETCH 1 (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)".to_string()
                     } else {
                         format!(
-                            "UID FETCH {}:{} (UID FLAGS ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
+                            "UID FETCH {}:{} (UID FLAGS RFC822.SIZE ENVELOPE BODY.PEEK[HEADER.FIELDS (REFERENCES)] BODYSTRUCTURE)",
                                 std::cmp::max(max_uid_left.saturating_sub(chunk_size), 1),
                             max_uid_left
                         )
@@ -316,6 +316,12 @@ impl ImapStream {
             )
             .await?;
             ret.read_response(&mut res).await?;
+            let (_, status) = super::managesieve::response(&res)?;
+            if status.kind != super::managesieve::StatusKind::Ok {
+                return Err(MeliError::new(status.text())
+                    .set_summary("ManageSieve authentication failed")
+                    .set_err_kind(crate::error::ErrorKind::Authentication));
+            }
             return Ok((Default::default(), ret));
         }
 
@@ -444,12 +450,39 @@ impl ImapStream {
     pub async fn read_response(&mut self, ret: &mut Vec<u8>) -> Result<()> {
         let id = match self.protocol {
             ImapProtocol::IMAP { .. } => format!("M{} ", self.cmd_id - 1).into_bytes(),
-            ImapProtocol::ManageSieve => Vec::new(),
+            ImapProtocol::ManageSieve => {
+                return self.read_managesieve_response(ret).await;
+            }
         };
         self.read_lines(ret, &id, true).await?;
         Ok(())
     }
 
+    /// ManageSieve responses aren't tagged; read until the status line, skipping over any literal
+    /// strings in the response data.
+    async fn read_managesieve_response(&mut self, ret: &mut Vec<u8>) -> Result<()> {
+        let mut buf: Vec<u8> = vec![0; Connection::IO_BUF_SIZE];
+        ret.clear();
+        loop {
+            match timeout(self.timeout, self.stream.read(&mut buf)).await? {
+                Ok(0) => {
+                    return Err(MeliError::new("Disconnected")
+                        .set_err_kind(crate::error::ErrorKind::Network));
+                }
+                Ok(b) => {
+                    ret.extend_from_slice(&buf[0..b]);
+                    if super::managesieve::is_complete_response(ret) {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    return Err(MeliError::from(e).set_err_kind(crate::error::ErrorKind::Network));
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub async fn read_lines(
         &mut self,
         ret: &mut Vec<u8>,
@@ -19,13 +19,21 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use super::protocol_parser::RequiredResponses;
 use super::{ImapConnection, ImapProtocol, ImapServerConf, UIDStore};
 use crate::conf::AccountSettings;
+use crate::email::parser::BytesExt;
 use crate::error::{MeliError, Result};
 use crate::get_conf_val;
 use nom::{
-    branch::alt, bytes::complete::tag, combinator::map, error::ErrorKind,
-    multi::separated_nonempty_list, sequence::separated_pair, IResult,
+    branch::alt,
+    bytes::complete::{tag, tag_no_case, take_while},
+    character::complete::digit1,
+    combinator::{map, map_opt, opt},
+    error::ErrorKind,
+    multi::separated_nonempty_list,
+    sequence::{delimited, preceded, separated_pair, terminated},
+    IResult,
 };
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
@@ -72,17 +80,202 @@ pub fn quoted_raw(input: &[u8]) -> IResult<&[u8], &[u8]> {
     Err(nom::Err::Error((input, ErrorKind::Tag)))
 }
 
-pub trait ManageSieve {
-    fn havespace(&mut self) -> Result<()>;
-    fn putscript(&mut self) -> Result<()>;
+/// A quoted string with its escapes removed, or a literal string (`{N}` or `{N+}`, CRLF and then
+/// N octets).
+pub fn sieve_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    alt((
+        map(quoted_raw, |q| {
+            let mut ret = Vec::with_capacity(q.len());
+            let mut escaped = false;
+            for &c in q {
+                if c == b'\\' && !escaped {
+                    escaped = true;
+                    continue;
+                }
+                escaped = false;
+                ret.push(c);
+            }
+            ret
+        }),
+        literal,
+    ))(input)
+}
+
+fn literal(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let (input, len) = delimited(
+        tag("{"),
+        terminated(
+            map_opt(digit1, |d: &[u8]| {
+                std::str::from_utf8(d)
+                    .ok()
+                    .and_then(|d| d.parse::<usize>().ok())
+            }),
+            opt(tag("+")),
+        ),
+        tag("}\r\n"),
+    )(input)?;
+    if input.len() < len {
+        return Err(nom::Err::Error((input, ErrorKind::Eof)));
+    }
+    Ok((&input[len..], input[..len].to_vec()))
+}
+
+/// Length of the literal string announced at the end of `line`, if any.
+fn literal_length(line: &[u8]) -> Option<usize> {
+    if !line.ends_with(b"}") {
+        return None;
+    }
+    let start = line.rfind(b"{")?;
+    let mut digits = &line[start + 1..line.len() - 1];
+    if digits.ends_with(b"+") {
+        digits = &digits[..digits.len() - 1];
+    }
+    std::str::from_utf8(digits).ok()?.parse::<usize>().ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusKind {
+    Ok,
+    No,
+    Bye,
+}
+
+/// The final line of a ManageSieve response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub kind: StatusKind,
+    /// Response code without the parentheses, e.g. `WARNINGS` or `NONEXISTENT`.
+    pub code: Option<String>,
+    /// Human readable text sent by the server.
+    pub message: Option<String>,
+}
+
+impl Status {
+    /// The server's explanation, or the code if it didn't send one.
+    pub fn text(&self) -> String {
+        self.message
+            .clone()
+            .or_else(|| self.code.clone())
+            .unwrap_or_default()
+    }
+}
+
+pub fn status(input: &[u8]) -> IResult<&[u8], Status> {
+    let (input, kind) = alt((
+        map(tag_no_case("OK"), |_| StatusKind::Ok),
+        map(tag_no_case("NO"), |_| StatusKind::No),
+        map(tag_no_case("BYE"), |_| StatusKind::Bye),
+    ))(input)?;
+    let (input, code) = opt(preceded(
+        tag(" "),
+        delimited(tag("("), take_while(|c| c != b')'), tag(")")),
+    ))(input)?;
+    let (input, message) = opt(preceded(tag(" "), sieve_string))(input)?;
+    let (input, _) = tag("\r\n")(input)?;
+    Ok((
+        input,
+        Status {
+            kind,
+            code: code.map(|c| String::from_utf8_lossy(c).into_owned()),
+            message: message.map(|m| String::from_utf8_lossy(&m).into_owned()),
+        },
+    ))
+}
+
+/// Offset of the status line of a response, skipping over literal strings in the data lines.
+fn status_line_offset(input: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < input.len() {
+        let line_end = i + input[i..].find(b"\r\n")?;
+        let line = &input[i..line_end];
+        if line.starts_with(b"OK") || line.starts_with(b"NO") || line.starts_with(b"BYE") {
+            return Some(i);
+        }
+        i = line_end + b"\r\n".len() + literal_length(line).unwrap_or(0);
+    }
+    None
+}
+
+/// Whether `input` holds a whole response, i.e. it ends with a complete status line.
+pub fn is_complete_response(input: &[u8]) -> bool {
+    status_line_offset(input)
+        .map(|offset| status(&input[offset..]).is_ok())
+        .unwrap_or(false)
+}
+
+/// Splits a complete response into its data lines and status.
+pub fn response(input: &[u8]) -> Result<(&[u8], Status)> {
+    let offset = status_line_offset(input)
+        .ok_or_else(|| MeliError::new("Incomplete ManageSieve response"))?;
+    let (_, status) = status(&input[offset..])?;
+    Ok((&input[..offset], status))
+}
+
+/// Parses the data of a `LISTSCRIPTS` response into script names and whether each is active.
+pub fn listscripts(mut input: &[u8]) -> Result<Vec<(String, bool)>> {
+    let mut ret = vec![];
+    while !input.is_empty() {
+        let (rest, name) = sieve_string(input)?;
+        let (rest, active) = opt(tag_no_case(" ACTIVE"))(rest)?;
+        let (rest, _) = tag("\r\n")(rest)?;
+        ret.push((
+            String::from_utf8_lossy(&name).into_owned(),
+            active.is_some(),
+        ));
+        input = rest;
+    }
+    Ok(ret)
+}
+
+/// Quotes `s` as a ManageSieve or Sieve quoted string.
+pub fn quote(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            ret.push('\\');
+        }
+        ret.push(c);
+    }
+    ret.push('"');
+    ret
+}
 
-    fn listscripts(&mut self) -> Result<()>;
-    fn setactive(&mut self) -> Result<()>;
+#[test]
+fn test_managesieve_responses() {
+    assert!(!is_complete_response(b"\"a\" ACTIVE\r\n"));
+    assert!(!is_complete_response(b"{12}\r\nOK\r\nNO\r\n"));
+    assert!(is_complete_response(b"{12}\r\nOK\r\nNO\r\nab\r\nOK\r\n"));
+    assert!(!is_complete_response(b"NO {10}\r\nline 1: "));
 
-    fn getscript(&mut self) -> Result<()>;
+    let (data, status) =
+        response(b"\"summer_script\"\r\n\"vac\\\"ation\" ACTIVE\r\n{4}\r\nab\r\n\r\nOK\r\n")
+            .unwrap();
+    assert_eq!(status.kind, StatusKind::Ok);
+    assert_eq!(
+        listscripts(data).unwrap(),
+        vec![
+            ("summer_script".to_string(), false),
+            ("vac\"ation".to_string(), true),
+            ("ab\r\n".to_string(), false),
+        ]
+    );
 
-    fn deletescript(&mut self) -> Result<()>;
-    fn renamescript(&mut self) -> Result<()>;
+    let (data, status) =
+        response(b"NO (NONEXISTENT) \"There is no script by that name\"\r\n").unwrap();
+    assert!(data.is_empty());
+    assert_eq!(
+        status,
+        Status {
+            kind: StatusKind::No,
+            code: Some("NONEXISTENT".to_string()),
+            message: Some("There is no script by that name".to_string()),
+        }
+    );
+    assert_eq!(
+        status_line_offset(b"{11}\r\nkeep;\r\nNO\r\n\r\nOK\r\n"),
+        Some(19)
+    );
 }
 
 pub fn new_managesieve_connection(
@@ -93,8 +286,28 @@ pub fn new_managesieve_connection(
 ) -> Result<ImapConnection> {
     let server_hostname = get_conf_val!(s["server_hostname"])?;
     let server_username = get_conf_val!(s["server_username"])?;
-    let server_password = get_conf_val!(s["server_password"])?;
-    let server_port = get_conf_val!(s["server_port"], 4190)?;
+    let server_password = if !s.extra.contains_key("server_password_command") {
+        get_conf_val!(s["server_password"])?.to_string()
+    } else {
+        let invocation = get_conf_val!(s["server_password_command"])?;
+        let output = std::process::Command::new("sh")
+            .args(&["-c", invocation])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .output()?;
+        if !output.status.success() {
+            return Err(MeliError::new(format!(
+                "({}) server_password_command `{}` returned {}: {}",
+                s.name,
+                invocation,
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        std::str::from_utf8(&output.stdout)?.trim_end().to_string()
+    };
+    let server_port = get_conf_val!(s["managesieve_port"], 4190)?;
     let danger_accept_invalid_certs: bool = get_conf_val!(s["danger_accept_invalid_certs"], false)?;
     let timeout = get_conf_val!(s["timeout"], 16_u64)?;
     let timeout = if timeout == 0 {
@@ -105,7 +318,7 @@ pub fn new_managesieve_connection(
     let server_conf = ImapServerConf {
         server_hostname: server_hostname.to_string(),
         server_username: server_username.to_string(),
-        server_password: server_password.to_string(),
+        server_password,
         server_port,
         use_starttls: true,
         use_tls: true,
@@ -128,29 +341,188 @@ pub fn new_managesieve_connection(
     Ok(ImapConnection::new_connection(&server_conf, uid_store))
 }
 
-impl ManageSieve for ImapConnection {
-    fn havespace(&mut self) -> Result<()> {
-        Ok(())
+/// A ManageSieve (RFC 5804) session, for managing the Sieve scripts of an account.
+#[derive(Debug)]
+pub struct ManageSieveConnection {
+    pub inner: ImapConnection,
+}
+
+impl ManageSieveConnection {
+    pub fn new(
+        account_hash: crate::backends::AccountHash,
+        account_name: String,
+        s: &AccountSettings,
+        event_consumer: crate::backends::BackendEventConsumer,
+    ) -> Result<Self> {
+        Ok(ManageSieveConnection {
+            inner: new_managesieve_connection(account_hash, account_name, s, event_consumer)?,
+        })
     }
-    fn putscript(&mut self) -> Result<()> {
-        Ok(())
+
+    pub async fn connect(&mut self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    /// Sends `command` and, if present, `literal` as its last argument, and returns the data lines
+    /// of a successful response and the text of its status line.
+    async fn command(
+        &mut self,
+        command: &str,
+        literal: Option<&[u8]>,
+    ) -> Result<(Vec<u8>, Option<String>)> {
+        let mut res = Vec::with_capacity(8 * 1024);
+        if let Some(literal) = literal {
+            self.inner
+                .send_command(format!("{} {{{}+}}", command, literal.len()).as_bytes())
+                .await?;
+            self.inner.send_literal(literal).await?;
+        } else {
+            self.inner.send_command(command.as_bytes()).await?;
+        }
+        self.inner
+            .read_response(&mut res, RequiredResponses::empty())
+            .await?;
+        let (data, status) = response(&res)?;
+        match status.kind {
+            StatusKind::Ok => Ok((data.to_vec(), status.message)),
+            StatusKind::No => Err(MeliError::new(status.text())
+                .set_summary(format!("ManageSieve server refused {}", command))),
+            StatusKind::Bye => {
+                self.inner.stream = Err(MeliError::new("Offline: received BYE"));
+                Err(MeliError::new(status.text())
+                    .set_summary("ManageSieve server closed the connection")
+                    .set_kind(crate::error::ErrorKind::Network))
+            }
+        }
     }
 
-    fn listscripts(&mut self) -> Result<()> {
+    pub async fn capabilities(&mut self) -> Result<Vec<(String, String)>> {
+        let (data, _) = self.command("CAPABILITY", None).await?;
+        Ok(managesieve_capabilities(&data)?
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    String::from_utf8_lossy(k).into_owned(),
+                    String::from_utf8_lossy(v).into_owned(),
+                )
+            })
+            .collect())
+    }
+
+    /// Checks whether a script of `size` octets named `name` can be uploaded.
+    pub async fn havespace(&mut self, name: &str, size: usize) -> Result<()> {
+        self.command(&format!("HAVESPACE {} {}", quote(name), size), None)
+            .await?;
         Ok(())
     }
-    fn setactive(&mut self) -> Result<()> {
+
+    /// Uploads `script` as `name`, replacing any script with that name. Returns warnings issued by
+    /// the server, if any.
+    pub async fn putscript(&mut self, name: &str, script: &[u8]) -> Result<Option<String>> {
+        let (_, warnings) = self
+            .command(&format!("PUTSCRIPT {}", quote(name)), Some(script))
+            .await?;
+        Ok(warnings)
+    }
+
+    /// Validates `script` without storing it. Returns warnings issued by the server, if any.
+    pub async fn checkscript(&mut self, script: &[u8]) -> Result<Option<String>> {
+        let (_, warnings) = self.command("CHECKSCRIPT", Some(script)).await?;
+        Ok(warnings)
+    }
+
+    /// Names of the stored scripts and whether each one is the active script.
+    pub async fn listscripts(&mut self) -> Result<Vec<(String, bool)>> {
+        let (data, _) = self.command("LISTSCRIPTS", None).await?;
+        listscripts(&data)
+    }
+
+    /// Makes `name` the active script. An empty name deactivates all scripts.
+    pub async fn setactive(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("SETACTIVE {}", quote(name)), None)
+            .await?;
         Ok(())
     }
 
-    fn getscript(&mut self) -> Result<()> {
+    pub async fn getscript(&mut self, name: &str) -> Result<Vec<u8>> {
+        let (data, _) = self
+            .command(&format!("GETSCRIPT {}", quote(name)), None)
+            .await?;
+        let (_, script) = sieve_string(&data)?;
+        Ok(script)
+    }
+
+    pub async fn deletescript(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("DELETESCRIPT {}", quote(name)), None)
+            .await?;
         Ok(())
     }
 
-    fn deletescript(&mut self) -> Result<()> {
+    pub async fn renamescript(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        self.command(
+            &format!("RENAMESCRIPT {} {}", quote(old_name), quote(new_name)),
+            None,
+        )
+        .await?;
         Ok(())
     }
-    fn renamescript(&mut self) -> Result<()> {
+
+    pub async fn logout(&mut self) -> Result<()> {
+        self.command("LOGOUT", None).await?;
         Ok(())
     }
 }
+
+/// A Sieve `vacation` (RFC 5230) auto-reply.
+#[derive(Debug, Clone, Default)]
+pub struct Vacation {
+    /// Minimum number of days between replies to the same sender.
+    pub days: Option<u64>,
+    pub subject: Option<String>,
+    pub from: Option<String>,
+    /// Additional addresses of the user, so that mail sent to them gets replies too.
+    pub addresses: Vec<String>,
+    pub reason: String,
+}
+
+impl Vacation {
+    pub fn to_script(&self) -> String {
+        let mut ret = String::from("require [\"vacation\"];\r\n\r\nvacation");
+        if let Some(days) = self.days {
+            ret.push_str(&format!(" :days {}", days));
+        }
+        if let Some(ref subject) = self.subject {
+            ret.push_str(&format!(" :subject {}", quote(subject)));
+        }
+        if let Some(ref from) = self.from {
+            ret.push_str(&format!(" :from {}", quote(from)));
+        }
+        if !self.addresses.is_empty() {
+            ret.push_str(&format!(
+                " :addresses [{}]",
+                self.addresses
+                    .iter()
+                    .map(|a| quote(a))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+        ret.push_str(&format!(" {};\r\n", quote(&self.reason)));
+        ret
+    }
+}
+
+#[test]
+fn test_managesieve_vacation() {
+    assert_eq!(
+        Vacation {
+            days: Some(7),
+            subject: Some("Out of \"office\"".to_string()),
+            from: None,
+            addresses: vec!["me@example.com".to_string(), "me@example.org".to_string()],
+            reason: "I'm away until Monday.\r\nMail is not forwarded.".to_string(),
+        }
+        .to_script(),
+        "require [\"vacation\"];\r\n\r\nvacation :days 7 :subject \"Out of \\\"office\\\"\" :addresses [\"me@example.com\", \"me@example.org\"] \"I'm away until Monday.\r\nMail is not forwarded.\";\r\n"
+    );
+}
@@ -578,7 +578,8 @@ pub fn fetch_response(input: &[u8]) -> ImapParseResult<FetchResponse<'_>> {
     eat_whitespace!();
     should_start_with!(&input[i..], b"FETCH (");
     i += b"FETCH (".len();
-    let mut has_attachments = false;
+    let mut attachment_count = None;
+    let mut size = None;
     while i < input.len() {
         eat_whitespace!(break);
         bounds!(break);
@@ -657,9 +658,22 @@ pub fn fetch_response(input: &[u8]) -> ImapParseResult<FetchResponse<'_>> {
         } else if input[i..].starts_with(b"BODYSTRUCTURE ") {
             i += b"BODYSTRUCTURE ".len();
 
-            let (rest, _has_attachments) = bodystructure_has_attachments(&input[i..])?;
-            has_attachments = _has_attachments;
+            let (rest, count) = bodystructure_attachment_count(&input[i..])?;
+            attachment_count = Some(count);
             i += input[i..].len() - rest.len();
+        } else if input[i..].starts_with(b"RFC822.SIZE ") {
+            i += b"RFC822.SIZE ".len();
+            if let Ok((rest, _size)) =
+                take_while::<_, &[u8], (&[u8], nom::error::ErrorKind)>(is_digit)(&input[i..])
+            {
+                i += input.len() - i - rest.len();
+                size = usize::from_str(to_str!(_size)).ok();
+            } else {
+                return debug!(Err(MeliError::new(format!(
+                    "Unexpected input while parsing RFC822.SIZE in UID FETCH response. Got: `{:.40}`",
+                    String::from_utf8_lossy(&input)
+                ))));
+            }
         } else if input[i..].starts_with(b"BODY[HEADER.FIELDS (REFERENCES)] ") {
             i += b"BODY[HEADER.FIELDS (REFERENCES)] ".len();
             if let Ok((rest, mut references)) = astring_token(&input[i..]) {
@@ -693,7 +707,12 @@ pub fn fetch_response(input: &[u8]) -> ImapParseResult<FetchResponse<'_>> {
     ret.raw_fetch_value = &input[..i];
 
     if let Some(env) = ret.envelope.as_mut() {
-        env.set_has_attachments(has_attachments);
+        if let Some(count) = attachment_count {
+            env.set_attachment_count(count);
+        }
+        if let Some(size) = size {
+            env.set_size(size);
+        }
     }
 
     Ok((&input[i..], ret, None))
@@ -1477,33 +1496,34 @@ pub fn uid_fetch_envelopes_response(
             let (input, _) = tag(" ENVELOPE ")(input)?;
             let (input, env) = envelope(input.ltrim())?;
             let (input, _) = tag("BODYSTRUCTURE ")(input)?;
-            let (input, has_attachments) = bodystructure_has_attachments(input)?;
+            let (input, attachment_count) = bodystructure_attachment_count(input)?;
             let (input, _) = tag(")\r\n")(input)?;
             Ok((input, {
                 let mut env = env;
-                env.set_has_attachments(has_attachments);
+                env.set_attachment_count(attachment_count);
                 (uid_flags.0, uid_flags.1, env)
             }))
         },
     )(input)
 }
 
-pub fn bodystructure_has_attachments(input: &[u8]) -> IResult<&[u8], bool> {
+/// Counts the parts with an `attachment` disposition in a BODYSTRUCTURE.
+pub fn bodystructure_attachment_count(input: &[u8]) -> IResult<&[u8], usize> {
     let (input, _) = eat_whitespace(input)?;
     let (input, _) = tag("(")(input)?;
     let (mut input, _) = eat_whitespace(input)?;
-    let mut has_attachments = false;
+    let mut attachment_count = 0;
     let mut first_in_line = true;
     while !input.is_empty() && !input.starts_with(b")") {
         if input.starts_with(b"\"") || input[0].is_ascii_alphanumeric() || input[0] == b'{' {
             let (_input, token) = astring_token(input)?;
             input = _input;
-            if first_in_line {
-                has_attachments |= token.eq_ignore_ascii_case(b"attachment");
+            if first_in_line && token.eq_ignore_ascii_case(b"attachment") {
+                attachment_count += 1;
             }
         } else if input.starts_with(b"(") {
-            let (_input, _has_attachments) = bodystructure_has_attachments(input)?;
-            has_attachments |= _has_attachments;
+            let (_input, count) = bodystructure_attachment_count(input)?;
+            attachment_count += count;
             input = _input;
         }
         let (_input, _) = eat_whitespace(input)?;
@@ -1511,7 +1531,22 @@ pub fn bodystructure_has_attachments(input: &[u8]) -> IResult<&[u8], bool> {
         first_in_line = false;
     }
     let (input, _) = tag(")")(input)?;
-    Ok((input, has_attachments))
+    Ok((input, attachment_count))
+}
+
+#[test]
+fn test_imap_bodystructure_attachment_count() {
+    assert_eq!(
+        bodystructure_attachment_count(
+            b"(\"TEXT\" \"PLAIN\" (\"CHARSET\" \"US-ASCII\") NIL NIL \"7BIT\" 3028 92)"
+        )
+        .map(|(_, v)| v),
+        Ok(0)
+    );
+    assert_eq!(
+        bodystructure_attachment_count(b"((\"text\" \"plain\" (\"charset\" \"utf-8\") NIL NIL \"7bit\" 12 1 NIL NIL NIL)(\"application\" \"pdf\" (\"name\" \"a.pdf\") NIL NIL \"base64\" 4096 NIL (\"attachment\" (\"filename\" \"a.pdf\")) NIL)(\"image\" \"png\" NIL NIL NIL \"base64\" 1024 NIL (\"attachment\" NIL) NIL) \"mixed\" (\"boundary\" \"b\") NIL NIL)").map(|(_, v)| v),
+        Ok(2)
+    );
 }
 
 fn eat_whitespace(mut input: &[u8]) -> IResult<&[u8], ()> {
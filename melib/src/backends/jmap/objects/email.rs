@@ -283,7 +283,11 @@ impl std::convert::From<EmailObject> for crate::Envelope {
                 env.set_datetime(d);
             }
         }
+        env.set_attachment_count(t.attachments.len());
         env.set_has_attachments(t.has_attachment);
+        if t.size > 0 {
+            env.set_size(t.size as usize);
+        }
         if let Some(ref mut subject) = t.subject {
             env.set_subject(std::mem::replace(subject, String::new()).into_bytes());
         }
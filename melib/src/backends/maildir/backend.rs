@@ -1016,6 +1016,9 @@ impl MailBackend for MaildirType {
         std::fs::create_dir(&path)?;
         /* create_dir does not create intermediate directories (like `mkdir -p`), so the parent must be a valid
          * mailbox at this point. */
+        for d in &["cur", "new", "tmp"] {
+            std::fs::create_dir(path.join(d))?;
+        }
 
         let parent = path.parent().and_then(|p| {
             self.mailboxes
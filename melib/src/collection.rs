@@ -66,6 +66,7 @@ pub struct Collection {
     pub threads: Arc<RwLock<HashMap<MailboxHash, Threads>>>,
     sent_mailbox: Arc<RwLock<Option<MailboxHash>>>,
     pub mailboxes: Arc<RwLock<HashMap<MailboxHash, HashSet<EnvelopeHash>>>>,
+    pub thread_rules: ThreadRules,
 }
 
 impl Default for Collection {
@@ -119,6 +120,27 @@ impl Collection {
             threads,
             mailboxes,
             sent_mailbox: Arc::new(RwLock::new(None)),
+            thread_rules: Default::default(),
+        }
+    }
+
+    /// Returns an empty `Threads` that applies the collection's thread rules.
+    pub fn new_threads(&self, length: usize) -> Threads {
+        let mut ret = Threads::new(length);
+        ret.set_rules(self.thread_rules.clone());
+        ret
+    }
+
+    /// Sets or removes the rule of the thread whose root is `message_id` in every mailbox.
+    pub fn set_thread_rule(&self, message_id: String, rule: Option<ThreadRule>) {
+        for threads in self.threads.write().unwrap().values_mut() {
+            threads.set_rule(&message_id, rule);
+        }
+        let mut rules = self.thread_rules.write().unwrap();
+        if let Some(rule) = rule {
+            rules.insert(message_id, rule);
+        } else {
+            rules.remove(&message_id);
         }
     }
 
@@ -143,7 +165,7 @@ impl Collection {
         let mut threads_lck = self.threads.write().unwrap();
         threads_lck
             .entry(mailbox_hash)
-            .or_default()
+            .or_insert_with(|| self.new_threads(0))
             .remove(envelope_hash);
         for (h, t) in threads_lck.iter_mut() {
             if *h == mailbox_hash {
@@ -177,7 +199,7 @@ impl Collection {
         {
             if threads_lck
                 .entry(mailbox_hash)
-                .or_default()
+                .or_insert_with(|| self.new_threads(0))
                 .update_envelope(&self.envelopes, old_hash, new_hash)
                 .is_ok()
             {
@@ -187,7 +209,7 @@ impl Collection {
         /* envelope is not in threads, so insert it */
         threads_lck
             .entry(mailbox_hash)
-            .or_default()
+            .or_insert_with(|| self.new_threads(0))
             .insert(&self.envelopes, new_hash);
         for (h, t) in threads_lck.iter_mut() {
             if *h == mailbox_hash {
@@ -221,7 +243,7 @@ impl Collection {
         let mut threads_lck = threads.write().unwrap();
         let mut mailboxes_lck = mailboxes.write().unwrap();
         if !threads_lck.contains_key(&mailbox_hash) {
-            threads_lck.insert(mailbox_hash, Threads::new(new_envelopes.len()));
+            threads_lck.insert(mailbox_hash, self.new_threads(new_envelopes.len()));
             mailboxes_lck.insert(mailbox_hash, new_envelopes.keys().cloned().collect());
             for (h, e) in new_envelopes {
                 envelopes.write().unwrap().insert(h, e);
@@ -278,7 +300,7 @@ impl Collection {
                 for h in ordered_hash_set {
                     updated |= threads_lck
                         .entry(t_fh)
-                        .or_default()
+                        .or_insert_with(|| self.new_threads(0))
                         .insert_reply(envelopes, h);
                 }
                 if updated {
@@ -309,7 +331,7 @@ impl Collection {
                 for h in ordered_hash_set {
                     updated |= threads_lck
                         .entry(mailbox_hash)
-                        .or_default()
+                        .or_insert_with(|| self.new_threads(0))
                         .insert_reply(envelopes, h);
                 }
                 if updated {
@@ -358,7 +380,7 @@ impl Collection {
         {
             if threads_lck
                 .entry(mailbox_hash)
-                .or_default()
+                .or_insert_with(|| self.new_threads(0))
                 .update_envelope(&self.envelopes, old_hash, new_hash)
                 .is_ok()
             {
@@ -368,7 +390,7 @@ impl Collection {
         /* envelope is not in threads, so insert it */
         threads_lck
             .entry(mailbox_hash)
-            .or_default()
+            .or_insert_with(|| self.new_threads(0))
             .insert(&self.envelopes, new_hash);
         for (h, t) in threads_lck.iter_mut() {
             if *h == mailbox_hash {
@@ -397,7 +419,7 @@ impl Collection {
         {
             if threads_lck
                 .entry(mailbox_hash)
-                .or_default()
+                .or_insert_with(|| self.new_threads(0))
                 .update_envelope(&self.envelopes, env_hash, env_hash)
                 .is_ok()
             {
@@ -407,7 +429,7 @@ impl Collection {
         /* envelope is not in threads, so insert it */
         threads_lck
             .entry(mailbox_hash)
-            .or_default()
+            .or_insert_with(|| self.new_threads(0))
             .insert(&self.envelopes, env_hash);
         for (h, t) in threads_lck.iter_mut() {
             if *h == mailbox_hash {
@@ -433,7 +455,7 @@ impl Collection {
             .write()
             .unwrap()
             .entry(mailbox_hash)
-            .or_default()
+            .or_insert_with(|| self.new_threads(0))
             .insert(&self.envelopes, hash);
         if self
             .sent_mailbox
@@ -488,7 +510,7 @@ impl Collection {
             self.threads
                 .write()
                 .unwrap()
-                .insert(mailbox_hash, Threads::default());
+                .insert(mailbox_hash, self.new_threads(0));
         }
     }
 }
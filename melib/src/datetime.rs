@@ -37,7 +37,7 @@
 //! let s = timestamp_to_string(timestamp, Some("%Y-%m-%d"));
 //! assert_eq!(s, "2020-01-08");
 //! ```
-use crate::error::Result;
+use crate::error::{MeliError, Result};
 use std::convert::TryInto;
 use std::ffi::{CStr, CString};
 
@@ -355,11 +355,213 @@ pub fn now() -> UnixTimestamp {
     }
 }
 
+/// Parses a date relative to `now`, such as `in 2 hours`, `tomorrow`, `monday 9:30`,
+/// `friday 5pm`, `next week`, `tonight` or `2020-09-21 17:30`, in the local timezone.
+///
+/// Dates without a time are at 09:00, except `tonight` which is at 20:00. A time without a date
+/// is its next occurrence.
+pub fn natural_to_timestamp(s: &str, now: UnixTimestamp) -> Result<UnixTimestamp> {
+    const WEEKDAYS: &[&str] = &[
+        "sunday",
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+    ];
+    let invalid = || MeliError::new(format!("Could not understand date `{}`.", s.trim()));
+    let s = s.trim().to_lowercase();
+    let words = s
+        .split_whitespace()
+        .filter(|w| *w != "at")
+        .collect::<Vec<&str>>();
+    if words.first() == Some(&"in") {
+        if words.len() != 3 {
+            return Err(invalid());
+        }
+        let n = words[1].parse::<u64>().map_err(|_| invalid())?;
+        let unit = match words[2].trim_end_matches('s') {
+            "minute" | "min" => 60,
+            "hour" => 60 * 60,
+            "day" => 24 * 60 * 60,
+            "week" => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        return n
+            .checked_mul(unit)
+            .and_then(|d| now.checked_add(d))
+            .ok_or_else(|| MeliError::new(format!("Date `{}` is too far in the future.", s)));
+    }
+
+    let mut tm: ::libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let i: i64 = now.try_into().unwrap_or(0);
+        localtime_r(&i as *const i64, &mut tm as *mut ::libc::tm);
+    }
+    let wday = tm.tm_wday;
+    let weekday = |w: &str| {
+        WEEKDAYS
+            .iter()
+            .position(|d| w == *d || (w.len() == 3 && d.starts_with(w)))
+            .map(|d| {
+                let days = (d as i32 - wday + 7) % 7;
+                if days == 0 {
+                    7
+                } else {
+                    days
+                }
+            })
+    };
+    let mut default_time = (9, 0);
+    let mut has_date = true;
+    let first = words.first().cloned().unwrap_or_default();
+    let second = words.get(1).cloned().unwrap_or_default();
+    let rest = if first == "today" {
+        &words[1..]
+    } else if first == "tonight" {
+        default_time = (20, 0);
+        &words[1..]
+    } else if first == "tomorrow" {
+        tm.tm_mday += 1;
+        &words[1..]
+    } else if first == "next" && second == "week" {
+        tm.tm_mday += weekday("monday").unwrap();
+        &words[2..]
+    } else if let (Some(days), true) = (weekday(second), first == "next") {
+        tm.tm_mday += days;
+        &words[2..]
+    } else if let Some(days) = weekday(first) {
+        tm.tm_mday += days;
+        &words[1..]
+    } else if first.len() == 10 && first.as_bytes()[4] == b'-' && first.as_bytes()[7] == b'-' {
+        let parse = |range: std::ops::Range<usize>| {
+            first
+                .get(range)
+                .and_then(|n| n.parse::<i32>().ok())
+                .ok_or_else(invalid)
+        };
+        tm.tm_year = parse(0..4)? - 1900;
+        tm.tm_mon = parse(5..7)? - 1;
+        tm.tm_mday = parse(8..10)?;
+        if tm.tm_mon < 0 || tm.tm_mon > 11 || tm.tm_mday < 1 || tm.tm_mday > 31 {
+            return Err(invalid());
+        }
+        &words[1..]
+    } else {
+        has_date = false;
+        &words[..]
+    };
+    let (hour, minute) = match rest {
+        [] if has_date => default_time,
+        [time] => parse_time(time).ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+    let past_today = !has_date && (hour, minute) <= (tm.tm_hour, tm.tm_min);
+    if past_today {
+        tm.tm_mday += 1;
+    }
+    tm.tm_hour = hour;
+    tm.tm_min = minute;
+    tm.tm_sec = 0;
+    tm.tm_isdst = -1;
+    let ret = unsafe { mktime(&tm as *const _) };
+    if ret < 0 {
+        return Err(invalid());
+    }
+    Ok(ret as UnixTimestamp)
+}
+
+/// Parses a time of day such as `17:30`, `9`, `9am` or `5:30pm`.
+fn parse_time(s: &str) -> Option<(i32, i32)> {
+    let (s, offset) = if s.ends_with("am") {
+        (&s[..s.len() - 2], Some(0))
+    } else if s.ends_with("pm") {
+        (&s[..s.len() - 2], Some(12))
+    } else {
+        (s, None)
+    };
+    let mut parts = s.splitn(2, ':');
+    let hour = parts.next()?.parse::<i32>().ok()?;
+    let minute = match parts.next() {
+        Some(m) if m.len() == 2 => m.parse::<i32>().ok()?,
+        Some(_) => return None,
+        None => 0,
+    };
+    if minute > 59 {
+        return None;
+    }
+    match offset {
+        None if hour < 24 => Some((hour, minute)),
+        Some(offset) if hour >= 1 && hour <= 12 => Some((hour % 12 + offset, minute)),
+        _ => None,
+    }
+}
+
 #[test]
 fn test_timestamp() {
     timestamp_to_string(0, None);
 }
 
+#[test]
+fn test_natural_to_timestamp() {
+    let base = rfc822_to_timestamp("Wed, 8 Jan 2020 10:44:03 -0800").unwrap();
+    let fmt =
+        |s: &str| timestamp_to_string(natural_to_timestamp(s, base).unwrap(), Some("%a %H:%M"));
+
+    assert_eq!(
+        natural_to_timestamp("in 2 hours", base).unwrap(),
+        base + 2 * 60 * 60
+    );
+    assert_eq!(
+        natural_to_timestamp("in 1 day", base).unwrap(),
+        base + 24 * 60 * 60
+    );
+    assert_eq!(
+        natural_to_timestamp("In 30 Minutes", base).unwrap(),
+        base + 30 * 60
+    );
+    assert_eq!(
+        timestamp_to_string(
+            natural_to_timestamp("tomorrow", base).unwrap(),
+            Some("%Y-%m-%d %H:%M")
+        ),
+        format!(
+            "{} 09:00",
+            timestamp_to_string(base + 24 * 60 * 60, Some("%Y-%m-%d"))
+        )
+    );
+    assert_eq!(fmt("tonight"), timestamp_to_string(base, Some("%a 20:00")));
+    assert_eq!(fmt("monday"), "Mon 09:00");
+    assert_eq!(fmt("next week"), "Mon 09:00");
+    assert_eq!(fmt("fri 5pm"), "Fri 17:00");
+    assert_eq!(fmt("friday at 12:30am"), "Fri 00:30");
+    for s in &["monday", "next week", "tomorrow 8:00", "17:00", "9am"] {
+        let t = natural_to_timestamp(s, base).unwrap();
+        assert!(t > base && t < base + 8 * 24 * 60 * 60, "{}", s);
+    }
+    assert_eq!(
+        timestamp_to_string(
+            natural_to_timestamp("2020-02-01 17:30", base).unwrap(),
+            Some("%Y-%m-%d %H:%M")
+        ),
+        "2020-02-01 17:30"
+    );
+    for s in &[
+        "",
+        "whenever",
+        "in 2 fortnights",
+        "25:00",
+        "13pm",
+        "tomorrow 9 10",
+        "2020-13-01",
+        "in 18446744073709551615 weeks",
+        "in 300000000000000 days",
+    ] {
+        assert!(natural_to_timestamp(s, base).is_err(), "{}", s);
+    }
+}
+
 #[test]
 fn test_rfcs() {
     /* Some tests were lazily stolen from https://rachelbythebay.com/w/2013/06/11/time/ */
@@ -92,12 +92,17 @@
 pub mod address;
 pub mod attachment_types;
 pub mod attachments;
+pub mod autocrypt;
+pub mod charset_detection;
 pub mod compose;
+#[cfg(feature = "dkim")]
+pub mod dkim;
 pub mod headers;
 pub mod list_management;
 pub mod mailto;
 pub mod parser;
 pub mod pgp;
+pub mod tnef;
 
 pub use address::{Address, MessageID, References, StrBuild, StrBuilder};
 pub use attachments::{Attachment, AttachmentBuilder};
@@ -143,6 +148,23 @@ impl PartialEq<&str> for Flag {
     }
 }
 
+impl std::str::FromStr for Flag {
+    type Err = MeliError;
+
+    /// Parse a flag name, accepting the same aliases as `PartialEq<&str>`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "passed" => Ok(Flag::PASSED),
+            "replied" => Ok(Flag::REPLIED),
+            "seen" | "read" => Ok(Flag::SEEN),
+            "junk" | "trash" | "trashed" => Ok(Flag::TRASHED),
+            "draft" => Ok(Flag::DRAFT),
+            "flagged" => Ok(Flag::FLAGGED),
+            _ => Err(MeliError::new(format!("`{}` is not a valid flag name.", s))),
+        }
+    }
+}
+
 ///`Mail` holds both the envelope info of an email in its `envelope` field and the raw bytes that
 ///describe the email in `bytes`. Its body as an `melib::email::Attachment` can be parsed on demand
 ///with the `melib::email::Mail::body` method.
@@ -207,6 +229,10 @@ pub struct Envelope {
     pub flags: Flag,
     pub has_attachments: bool,
     pub labels: SmallVec<[u64; 8]>,
+    /// Size of the message in bytes, if known.
+    pub size: Option<usize>,
+    /// Number of attachments, if known.
+    pub attachment_count: Option<usize>,
 }
 
 impl core::fmt::Debug for Envelope {
@@ -250,6 +276,8 @@ impl Envelope {
             has_attachments: false,
             flags: Flag::default(),
             labels: SmallVec::new(),
+            size: None,
+            attachment_count: None,
         }
     }
 
@@ -266,6 +294,10 @@ impl Envelope {
             if let Some(f) = flags {
                 e.flags = f;
             }
+            e.size = Some(bytes.len());
+            if e.attachment_count.is_none() {
+                e.attachment_count = Some(0);
+            }
             return Ok(e);
         }
         Err(MeliError::new("Couldn't parse mail."))
@@ -354,8 +386,9 @@ impl Envelope {
                             }
                         }
                         if let Some(boundary) = boundary {
-                            self.has_attachments =
-                                Attachment::check_if_has_attachments_quick(body, boundary);
+                            let count = Attachment::count_attachments_quick(body, boundary);
+                            self.has_attachments = count > 0;
+                            self.attachment_count = Some(count);
                         } else {
                             debug!("{:?} has no boundary field set in multipart/mixed content-type field.", &self);
                         }
@@ -738,6 +771,24 @@ impl Envelope {
         self.has_attachments
     }
 
+    pub fn set_size(&mut self, new_val: usize) {
+        self.size = Some(new_val);
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        self.size
+    }
+
+    pub fn set_attachment_count(&mut self, new_val: usize) {
+        self.attachment_count = Some(new_val);
+        self.has_attachments = new_val > 0;
+    }
+
+    /// Returns the number of attachments, if known.
+    pub fn attachment_count(&self) -> Option<usize> {
+        self.attachment_count
+    }
+
     pub fn labels(&self) -> &SmallVec<[u64; 8]> {
         &self.labels
     }
@@ -25,6 +25,8 @@ use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
 
+pub mod idna;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GroupAddress {
     pub raw: Vec<u8>,
@@ -194,6 +196,12 @@ impl Address {
         }
     }
 
+    /// Get the address spec with its domain converted to ASCII (Punycode), for transports that
+    /// don't support internationalized addresses. Fails if the local part is not ASCII.
+    pub fn address_spec_ascii(&self) -> Result<String> {
+        idna::address_spec_to_ascii(&self.get_email())
+    }
+
     pub fn get_fqdn(&self) -> Option<String> {
         match self {
             Address::Mailbox(m) => {
@@ -327,7 +335,7 @@ impl core::fmt::Display for Address {
                 f,
                 "{} <{}>",
                 m.display_name.display(&m.raw),
-                m.address_spec.display(&m.raw)
+                idna::address_spec_to_unicode(&m.address_spec.display(&m.raw))
             ),
             Address::Group(g) => {
                 let attachment_strings: Vec<String> =
@@ -339,7 +347,11 @@ impl core::fmt::Display for Address {
                     attachment_strings.join(", ")
                 )
             }
-            Address::Mailbox(m) => write!(f, "{}", m.address_spec.display(&m.raw)),
+            Address::Mailbox(m) => write!(
+                f,
+                "{}",
+                idna::address_spec_to_unicode(&m.address_spec.display(&m.raw))
+            ),
         }
     }
 }
@@ -0,0 +1,396 @@
+/*
+ * meli - email module
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Internationalized domain names.
+ *
+ * Conversion between the Unicode form of a domain (`例子.测试`) and its ASCII compatible
+ * encoding (`xn--fsqu00a.xn--0zwm56d`).
+ *
+ * - [RFC3492 "Punycode: A Bootstring encoding of Unicode for IDNA"](https://tools.ietf.org/html/rfc3492)
+ * - [RFC5890 "Internationalized Domain Names for Applications (IDNA): Definitions and Document Framework"](https://tools.ietf.org/html/rfc5890)
+ * - [RFC5891 "Internationalized Domain Names in Applications (IDNA): Protocol"](https://tools.ietf.org/html/rfc5891)
+ *
+ * Labels are lowercased before conversion but not NFC normalized; input is expected to already
+ * be in NFC, which is what input methods produce in practice.
+ */
+
+use crate::error::{MeliError, Result};
+
+/// Prefix of ASCII compatible encoded labels.
+pub const ACE_PREFIX: &str = "xn--";
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+const MAX_LABEL_LENGTH: usize = 63;
+const MAX_DOMAIN_LENGTH: usize = 253;
+
+/// Bias adaptation function. [rfc3492#section-6.1]
+fn adapt(mut delta: u32, num_points: u32, first_time: bool) -> u32 {
+    delta /= if first_time { DAMP } else { 2 };
+    delta += delta / num_points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (((BASE - T_MIN + 1) * delta) / (delta + SKEW))
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias {
+        T_MIN
+    } else if k >= bias + T_MAX {
+        T_MAX
+    } else {
+        k - bias
+    }
+}
+
+fn encode_digit(d: u32) -> char {
+    if d < 26 {
+        (b'a' + d as u8) as char
+    } else {
+        (b'0' + (d - 26) as u8) as char
+    }
+}
+
+fn decode_digit(b: u8) -> Option<u32> {
+    match b {
+        b'0'..=b'9' => Some((b - b'0') as u32 + 26),
+        b'A'..=b'Z' => Some((b - b'A') as u32),
+        b'a'..=b'z' => Some((b - b'a') as u32),
+        _ => None,
+    }
+}
+
+/// Encode a Unicode label with Punycode, without the ACE prefix. Returns `None` on overflow.
+pub fn punycode_encode(input: &str) -> Option<String> {
+    let input: Vec<u32> = input.chars().map(|c| c as u32).collect();
+    let mut output: String = input
+        .iter()
+        .filter(|&&c| c < INITIAL_N)
+        .map(|&c| c as u8 as char)
+        .collect();
+    let basic_len = output.len() as u32;
+    let mut handled = basic_len;
+    if basic_len > 0 {
+        output.push('-');
+    }
+    let mut n = INITIAL_N;
+    let mut delta: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    while (handled as usize) < input.len() {
+        let m = *input.iter().filter(|&&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+        for &c in &input {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = threshold(k, bias);
+                    if q < t {
+                        break;
+                    }
+                    output.push(encode_digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled + 1, handled == basic_len);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+    Some(output)
+}
+
+/// Decode a Punycode label, without the ACE prefix. Returns `None` if `input` is not valid
+/// Punycode.
+pub fn punycode_decode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(pos) => (&input[..pos], &input[pos + 1..]),
+        None => ("", input),
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut bytes = extended.bytes().peekable();
+    while bytes.peek().is_some() {
+        let old_i = i;
+        let mut w: u32 = 1;
+        let mut k = BASE;
+        loop {
+            let digit = decode_digit(bytes.next()?)?;
+            i = i.checked_add(digit.checked_mul(w)?)?;
+            let t = threshold(k, bias);
+            if digit < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+        let len = output.len() as u32 + 1;
+        bias = adapt(i - old_i, len, old_i == 0);
+        n = n.checked_add(i / len)?;
+        i %= len;
+        output.insert(i as usize, std::char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+/// Label separators that are equivalent to FULL STOP. [rfc3490#section-3.1]
+fn is_label_separator(c: char) -> bool {
+    match c {
+        '.' | '\u{3002}' | '\u{ff0e}' | '\u{ff61}' => true,
+        _ => false,
+    }
+}
+
+/// Check the length and hyphens of an ASCII label.
+fn check_label(label: &str, domain: &str) -> Result<()> {
+    if label.is_empty() || label.len() > MAX_LABEL_LENGTH {
+        return Err(MeliError::new(format!(
+            "Invalid domain `{}`: labels must be between 1 and {} characters long.",
+            domain, MAX_LABEL_LENGTH
+        )));
+    }
+    if label.starts_with('-') || label.ends_with('-') {
+        return Err(MeliError::new(format!(
+            "Invalid domain `{}`: labels must not start or end with a hyphen.",
+            domain
+        )));
+    }
+    Ok(())
+}
+
+/// Convert a domain to its ASCII form, encoding non-ASCII labels with Punycode. Domain literals
+/// (`[127.0.0.1]`) are returned as they are.
+///
+/// Labels are not NFC normalized: a decomposed label, eg. `bu\u{308}cher` instead of `bücher`,
+/// is encoded as it is and won't match the domain's registered ACE form.
+pub fn domain_to_ascii(domain: &str) -> Result<String> {
+    if domain.starts_with('[') || (domain.is_ascii() && !domain.contains(ACE_PREFIX)) {
+        return Ok(domain.to_string());
+    }
+    let domain = domain.trim_end_matches(is_label_separator);
+    let mut labels = Vec::new();
+    for label in domain.split(is_label_separator) {
+        let label = label.to_lowercase();
+        if label.is_ascii() {
+            if label.starts_with(ACE_PREFIX)
+                && punycode_decode(&label[ACE_PREFIX.len()..]).is_none()
+            {
+                return Err(MeliError::new(format!(
+                    "Invalid domain `{}`: `{}` is not valid Punycode.",
+                    domain, label
+                )));
+            }
+            check_label(&label, domain)?;
+            labels.push(label);
+            continue;
+        }
+        if label.starts_with('-')
+            || label.ends_with('-')
+            || label
+                .chars()
+                .any(|c| c.is_ascii() && !(c.is_ascii_alphanumeric() || c == '-'))
+        {
+            return Err(MeliError::new(format!(
+                "Invalid domain `{}`: internationalized labels may only contain letters, digits \
+                 and inner hyphens.",
+                domain
+            )));
+        }
+        let encoded = punycode_encode(&label)
+            .map(|e| format!("{}{}", ACE_PREFIX, e))
+            .ok_or_else(|| {
+                MeliError::new(format!("Invalid domain `{}`: label is too long.", domain))
+            })?;
+        check_label(&encoded, domain)?;
+        labels.push(encoded);
+    }
+    let ret = labels.join(".");
+    if ret.len() > MAX_DOMAIN_LENGTH {
+        return Err(MeliError::new(format!(
+            "Invalid domain `{}`: domains must be at most {} characters long.",
+            domain, MAX_DOMAIN_LENGTH
+        )));
+    }
+    Ok(ret)
+}
+
+/// Whether a decoded label can be displayed as is: control and bidirectional formatting characters
+/// could make a domain look like another one.
+fn is_displayable_label(label: &str) -> bool {
+    !label.chars().any(|c| {
+        c.is_control()
+            || match c {
+                '\u{061c}'
+                | '\u{200e}'
+                | '\u{200f}'
+                | '\u{202a}'..='\u{202e}'
+                | '\u{2066}'..='\u{2069}' => true,
+                _ => false,
+            }
+    })
+}
+
+/// Convert a domain to its Unicode form for display, decoding Punycode labels. Labels that are not
+/// valid Punycode, or that decode to control or bidirectional formatting characters, are left as
+/// they are.
+pub fn domain_to_unicode(domain: &str) -> String {
+    if !domain.contains(ACE_PREFIX) && !domain.contains("XN--") {
+        return domain.to_string();
+    }
+    domain
+        .split('.')
+        .map(|label| {
+            if label.len() > ACE_PREFIX.len()
+                && label.as_bytes()[..ACE_PREFIX.len()].eq_ignore_ascii_case(ACE_PREFIX.as_bytes())
+            {
+                if let Some(decoded) = punycode_decode(&label[ACE_PREFIX.len()..])
+                    .filter(|decoded| is_displayable_label(decoded))
+                {
+                    return decoded;
+                }
+            }
+            label.to_string()
+        })
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/// Convert the domain of an `addr-spec` to its ASCII form. A non-ASCII local part can't be
+/// converted and is an error.
+pub fn address_spec_to_ascii(address_spec: &str) -> Result<String> {
+    let at_pos = match address_spec.rfind('@') {
+        Some(pos) => pos,
+        None => return Ok(address_spec.to_string()),
+    };
+    let (local_part, domain) = (&address_spec[..at_pos], &address_spec[at_pos + 1..]);
+    if !local_part.is_ascii() {
+        return Err(MeliError::new(format!(
+            "`{}` has a non-ASCII local part and can only be delivered with SMTPUTF8.",
+            address_spec
+        )));
+    }
+    Ok(format!("{}@{}", local_part, domain_to_ascii(domain)?))
+}
+
+/// Convert the domain of an `addr-spec` to its Unicode form for display.
+pub fn address_spec_to_unicode(address_spec: &str) -> String {
+    match address_spec.rfind('@') {
+        Some(pos) => format!(
+            "{}@{}",
+            &address_spec[..pos],
+            domain_to_unicode(&address_spec[pos + 1..])
+        ),
+        None => address_spec.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idna_punycode() {
+        for (unicode, ascii) in &[
+            ("bücher", "bcher-kva"),
+            ("münchen", "mnchen-3ya"),
+            ("例子", "fsqu00a"),
+            ("测试", "0zwm56d"),
+            ("他们为什么不说中文", "ihqwcrb4cv8a8dqg056pqjye"),
+        ] {
+            assert_eq!(punycode_encode(unicode).as_deref(), Some(*ascii));
+            assert_eq!(punycode_decode(ascii).as_deref(), Some(*unicode));
+        }
+        assert_eq!(punycode_decode("a-!"), None);
+    }
+
+    #[test]
+    fn test_idna_domain() {
+        assert_eq!(
+            domain_to_ascii("例子.测试").unwrap().as_str(),
+            "xn--fsqu00a.xn--0zwm56d"
+        );
+        assert_eq!(
+            domain_to_ascii("Bücher.Example.").unwrap().as_str(),
+            "xn--bcher-kva.example"
+        );
+        assert_eq!(
+            domain_to_ascii("example.com").unwrap().as_str(),
+            "example.com"
+        );
+        assert!(domain_to_ascii("-bücher.example").is_err());
+        /* No NFC normalization */
+        assert_ne!(
+            domain_to_ascii("bu\u{308}cher.example").unwrap().as_str(),
+            "xn--bcher-kva.example"
+        );
+        assert_eq!(
+            domain_to_unicode("xn--fsqu00a.XN--0zwm56d").as_str(),
+            "例子.测试"
+        );
+        assert_eq!(domain_to_unicode("xn--!.com").as_str(), "xn--!.com");
+        /* Multibyte labels next to ACE labels */
+        assert_eq!(domain_to_unicode("xn--a.用户").as_str(), "xn--a.用户");
+        assert_eq!(
+            address_spec_to_unicode("x@xn--fsqu00a.用户x").as_str(),
+            "x@例子.用户x"
+        );
+        /* Labels with bidi or control characters keep their ACE form */
+        for spoof in &["moc\u{202e}elpmaxe", "exa\u{200f}mple", "exa\u{7}mple"] {
+            let ace = format!("xn--{}", punycode_encode(spoof).unwrap());
+            assert_eq!(punycode_decode(&ace[4..]), Some(spoof.to_string()));
+            let domain = format!("{}.com", ace);
+            assert_eq!(domain_to_unicode(&domain), domain);
+        }
+        assert_eq!(
+            address_spec_to_ascii("user@bücher.example")
+                .unwrap()
+                .as_str(),
+            "user@xn--bcher-kva.example"
+        );
+        assert!(address_spec_to_ascii("用户@例子.测试").is_err());
+        assert_eq!(
+            address_spec_to_unicode("用户@xn--fsqu00a.xn--0zwm56d").as_str(),
+            "用户@例子.测试"
+        );
+    }
+}
@@ -46,13 +46,22 @@ pub enum Charset {
     Windows1251,
     Windows1252,
     Windows1253,
+    Windows1254,
+    Windows1255,
+    Windows1256,
+    Windows1257,
+    Windows1258,
     GBK,
     GB2312,
+    GB18030,
     BIG5,
     ISO2022JP,
     EUCJP,
+    ShiftJIS,
+    EUCKR,
     KOI8R,
     KOI8U,
+    KOI8RU,
 }
 
 impl Default for Charset {
@@ -142,15 +151,59 @@ impl<'a> From<&'a [u8]> for Charset {
             {
                 Charset::Windows1253
             }
+            b if b.eq_ignore_ascii_case(b"windows-1254")
+                || b.eq_ignore_ascii_case(b"windows1254") =>
+            {
+                Charset::Windows1254
+            }
+            b if b.eq_ignore_ascii_case(b"windows-1255")
+                || b.eq_ignore_ascii_case(b"windows1255") =>
+            {
+                Charset::Windows1255
+            }
+            b if b.eq_ignore_ascii_case(b"windows-1256")
+                || b.eq_ignore_ascii_case(b"windows1256") =>
+            {
+                Charset::Windows1256
+            }
+            b if b.eq_ignore_ascii_case(b"windows-1257")
+                || b.eq_ignore_ascii_case(b"windows1257") =>
+            {
+                Charset::Windows1257
+            }
+            b if b.eq_ignore_ascii_case(b"windows-1258")
+                || b.eq_ignore_ascii_case(b"windows1258") =>
+            {
+                Charset::Windows1258
+            }
             b if b.eq_ignore_ascii_case(b"gbk") => Charset::GBK,
             b if b.eq_ignore_ascii_case(b"gb2312") || b.eq_ignore_ascii_case(b"gb-2312") => {
                 Charset::GB2312
             }
+            b if b.eq_ignore_ascii_case(b"gb18030") || b.eq_ignore_ascii_case(b"gb-18030") => {
+                Charset::GB18030
+            }
             b if b.eq_ignore_ascii_case(b"big5") => Charset::BIG5,
             b if b.eq_ignore_ascii_case(b"iso-2022-jp") => Charset::ISO2022JP,
             b if b.eq_ignore_ascii_case(b"euc-jp") => Charset::EUCJP,
+            b if b.eq_ignore_ascii_case(b"shift_jis")
+                || b.eq_ignore_ascii_case(b"shift-jis")
+                || b.eq_ignore_ascii_case(b"sjis")
+                || b.eq_ignore_ascii_case(b"x-sjis")
+                || b.eq_ignore_ascii_case(b"windows-31j")
+                || b.eq_ignore_ascii_case(b"cp932") =>
+            {
+                Charset::ShiftJIS
+            }
+            b if b.eq_ignore_ascii_case(b"euc-kr")
+                || b.eq_ignore_ascii_case(b"ks_c_5601-1987")
+                || b.eq_ignore_ascii_case(b"cp949") =>
+            {
+                Charset::EUCKR
+            }
             b if b.eq_ignore_ascii_case(b"koi8-r") => Charset::KOI8R,
             b if b.eq_ignore_ascii_case(b"koi8-u") => Charset::KOI8U,
+            b if b.eq_ignore_ascii_case(b"koi8-ru") => Charset::KOI8RU,
             _ => {
                 debug!("unknown tag is {:?}", str::from_utf8(b));
                 Charset::Ascii
@@ -182,13 +235,22 @@ impl Display for Charset {
             Charset::Windows1251 => write!(f, "windows-1251"),
             Charset::Windows1252 => write!(f, "windows-1252"),
             Charset::Windows1253 => write!(f, "windows-1253"),
+            Charset::Windows1254 => write!(f, "windows-1254"),
+            Charset::Windows1255 => write!(f, "windows-1255"),
+            Charset::Windows1256 => write!(f, "windows-1256"),
+            Charset::Windows1257 => write!(f, "windows-1257"),
+            Charset::Windows1258 => write!(f, "windows-1258"),
             Charset::GBK => write!(f, "gbk"),
             Charset::GB2312 => write!(f, "gb2312"),
+            Charset::GB18030 => write!(f, "gb18030"),
             Charset::BIG5 => write!(f, "big5"),
             Charset::ISO2022JP => write!(f, "iso-2022-jp"),
             Charset::EUCJP => write!(f, "euc-jp"),
+            Charset::ShiftJIS => write!(f, "shift_jis"),
+            Charset::EUCKR => write!(f, "euc-kr"),
             Charset::KOI8R => write!(f, "koi8-r"),
             Charset::KOI8U => write!(f, "koi8-u"),
+            Charset::KOI8RU => write!(f, "koi8-ru"),
         }
     }
 }
@@ -201,6 +263,8 @@ pub enum MultipartType {
     Mixed,
     Related,
     Signed,
+    /// Virtual multipart of the attachments expanded from an `application/ms-tnef` part.
+    Tnef,
 }
 
 impl Default for MultipartType {
@@ -221,6 +285,7 @@ impl Display for MultipartType {
                 MultipartType::Mixed => "multipart/mixed",
                 MultipartType::Related => "multipart/related",
                 MultipartType::Signed => "multipart/signed",
+                MultipartType::Tnef => "application/ms-tnef",
             }
         )
     }
@@ -337,6 +402,13 @@ impl PartialEq<&str> for ContentType {
                 },
                 "multipart/signed",
             ) => true,
+            (
+                ContentType::Multipart {
+                    kind: MultipartType::Tnef,
+                    ..
+                },
+                "application/ms-tnef",
+            ) => true,
             (ContentType::PGPSignature, "application/pgp-signature") => true,
             (ContentType::CMSSignature, "application/pkcs7-signature") => true,
             (ContentType::MessageRfc822, "message/rfc822") => true,
@@ -383,6 +455,26 @@ impl ContentType {
         }
     }
 
+    pub fn is_image(&self) -> bool {
+        if let ContentType::Other { ref tag, .. } = self {
+            tag.len() > b"image/".len() && tag[..b"image/".len()].eq_ignore_ascii_case(b"image/")
+        } else {
+            false
+        }
+    }
+
+    pub fn is_calendar(&self) -> bool {
+        if let ContentType::Text {
+            kind: Text::Other { ref tag },
+            ..
+        } = self
+        {
+            tag.eq_ignore_ascii_case(b"calendar")
+        } else {
+            false
+        }
+    }
+
     pub fn make_boundary(parts: &[AttachmentBuilder]) -> String {
         use crate::email::compose::random::gen_boundary;
         let mut boundary = "bzz_bzz__bzz__".to_string();
@@ -163,13 +163,13 @@ impl AttachmentBuilder {
                     }
                 } else if ct.eq_ignore_ascii_case(b"text") {
                     self.content_type = ContentType::default();
-                    for (n, v) in params {
+                    for (n, v) in parser::attachments::rfc2231_parameters(&params) {
                         if n.eq_ignore_ascii_case(b"charset") {
                             if let ContentType::Text {
                                 charset: ref mut c, ..
                             } = self.content_type
                             {
-                                *c = Charset::from(v);
+                                *c = Charset::from(v.as_slice());
                             }
                         }
                         if let ContentType::Text {
@@ -177,7 +177,7 @@ impl AttachmentBuilder {
                             ..
                         } = self.content_type
                         {
-                            p.push((n.to_vec(), v.to_vec()));
+                            p.push((n, v));
                         }
                     }
                     if cst.eq_ignore_ascii_case(b"html") {
@@ -203,12 +203,13 @@ impl AttachmentBuilder {
                 {
                     self.content_type = ContentType::PGPSignature;
                 } else if ct.eq_ignore_ascii_case(b"application")
-                    && cst.eq_ignore_ascii_case(b"pkcs7-signature")
+                    && (cst.eq_ignore_ascii_case(b"pkcs7-signature")
+                        || cst.eq_ignore_ascii_case(b"x-pkcs7-signature"))
                 {
                     self.content_type = ContentType::CMSSignature;
                 } else {
                     let mut name: Option<String> = None;
-                    for (n, v) in params {
+                    for (n, v) in parser::attachments::rfc2231_parameters(&params) {
                         if n.eq_ignore_ascii_case(b"name") {
                             if let Ok(v) = crate::email::parser::encodings::phrase(v.trim(), false)
                                 .as_ref()
@@ -216,7 +217,7 @@ impl AttachmentBuilder {
                             {
                                 name = Some(v);
                             } else {
-                                name = Some(String::from_utf8_lossy(v).into());
+                                name = Some(String::from_utf8_lossy(&v).into());
                             }
                             break;
                         }
@@ -240,13 +241,31 @@ impl AttachmentBuilder {
     }
 
     pub fn build(self) -> Attachment {
-        Attachment {
+        let mut ret = Attachment {
             content_type: self.content_type,
             content_transfer_encoding: self.content_transfer_encoding,
             content_disposition: self.content_disposition,
             raw: self.raw,
             body: self.body,
+        };
+        /* Expand TNEF attachments into their contents. */
+        if ret.content_type == "application/ms-tnef"
+            || ret.content_type == "application/vnd.ms-tnef"
+        {
+            match crate::email::tnef::Tnef::parse(&decode(&ret, None)) {
+                Ok(tnef) => {
+                    ret.content_type = ContentType::Multipart {
+                        boundary: Vec::new(),
+                        kind: MultipartType::Tnef,
+                        parts: tnef.into_attachments(),
+                    };
+                }
+                Err(err) => {
+                    debug!("could not decode TNEF attachment: {}", err);
+                }
+            }
         }
+        ret
     }
 
     pub fn parts(raw: &[u8], boundary: &[u8]) -> Vec<Attachment> {
@@ -551,6 +570,60 @@ impl Attachment {
         false
     }
 
+    /* Like `check_if_has_attachments_quick` but counts every attachment instead of stopping at the
+     * first one */
+    pub fn count_attachments_quick(bytes: &[u8], boundary: &[u8]) -> usize {
+        if bytes.is_empty() {
+            return 0;
+        }
+
+        let mut count = 0;
+        match parser::attachments::multipart_parts(bytes, boundary) {
+            Ok((_, parts)) => {
+                for p in parts {
+                    let (body, headers) = match parser::headers::headers_raw(p.display_bytes(bytes))
+                    {
+                        Ok(v) => v,
+                        Err(_err) => return count,
+                    };
+                    let headers = crate::email::parser::generic::HeaderIterator(headers)
+                        .collect::<SmallVec<[(&[u8], &[u8]); 16]>>();
+                    let disposition = headers
+                        .iter()
+                        .find(|(n, _)| n.eq_ignore_ascii_case(b"content-disposition"))
+                        .map(|(_, v)| ContentDisposition::from(*v))
+                        .unwrap_or_default();
+                    if disposition.kind.is_attachment() {
+                        count += 1;
+                        continue;
+                    }
+                    if let Some(boundary) = headers
+                        .iter()
+                        .find(|(n, _)| n.eq_ignore_ascii_case(b"content-type"))
+                        .and_then(|(_, v)| match parser::attachments::content_type(v) {
+                            Ok((_, (ct, _cst, params)))
+                                if ct.eq_ignore_ascii_case(b"multipart") =>
+                            {
+                                params
+                                    .into_iter()
+                                    .find(|(n, _)| n.eq_ignore_ascii_case(b"boundary"))
+                                    .map(|(_, v)| v)
+                            }
+                            _ => None,
+                        })
+                    {
+                        count += Attachment::count_attachments_quick(body, boundary);
+                    }
+                }
+            }
+            Err(e) => {
+                debug!("error in parsing multipart_parts");
+                debug!("{:?}\n", e);
+            }
+        }
+        count
+    }
+
     fn get_text_recursive(&self, text: &mut Vec<u8>) {
         match self.content_type {
             ContentType::Text { .. } | ContentType::PGPSignature | ContentType::CMSSignature => {
@@ -630,6 +703,44 @@ impl Attachment {
             _ => false,
         }
     }
+    /// If this is a `text/plain; format=flowed` part, returns whether the `DelSp` parameter is set.
+    pub fn format_flowed(&self) -> Option<bool> {
+        match self.content_type {
+            ContentType::Text {
+                kind: Text::Plain,
+                ref parameters,
+                ..
+            } => {
+                let param = |name: &[u8], value: &[u8]| {
+                    parameters.iter().any(|(n, v)| {
+                        n.eq_ignore_ascii_case(name) && v.trim().eq_ignore_ascii_case(value)
+                    })
+                };
+                if param(b"format", b"flowed") {
+                    Some(param(b"delsp", b"yes"))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+    /// Decode all text parts as `charset`, regardless of their declared charset.
+    pub fn force_charset(&mut self, charset: Charset) {
+        match self.content_type {
+            ContentType::Text {
+                charset: ref mut c, ..
+            } => {
+                *c = charset;
+            }
+            ContentType::Multipart { ref mut parts, .. } => {
+                for p in parts.iter_mut() {
+                    p.force_charset(charset);
+                }
+            }
+            _ => {}
+        }
+    }
     pub fn is_html(&self) -> bool {
         match self.content_type {
             ContentType::Text {
@@ -649,6 +760,14 @@ impl Attachment {
         }
     }
 
+    pub fn is_image(&self) -> bool {
+        self.content_type.is_image()
+    }
+
+    pub fn is_calendar(&self) -> bool {
+        self.content_type.is_calendar()
+    }
+
     pub fn is_encrypted(&self) -> bool {
         match self.content_type {
             ContentType::Multipart {
@@ -702,6 +821,14 @@ impl Attachment {
                     ret.push_str("\r\n\r\n");
                     ret.push_str(&String::from_utf8_lossy(a.body()));
                 }
+                ContentType::Multipart {
+                    kind: MultipartType::Tnef,
+                    ..
+                } => {
+                    /* Emit the original TNEF stream instead of its expanded parts */
+                    ret.push_str(&format!("Content-Type: {}\r\n\r\n", a.content_type));
+                    ret.push_str(&String::from_utf8_lossy(a.body()));
+                }
                 ContentType::Multipart {
                     boundary,
                     kind,
@@ -710,7 +837,18 @@ impl Attachment {
                     let boundary = String::from_utf8_lossy(boundary);
                     ret.push_str(&format!("Content-Type: {}; boundary={}", kind, boundary));
                     if *kind == MultipartType::Signed {
-                        ret.push_str("; micalg=pgp-sha512; protocol=\"application/pgp-signature\"");
+                        if parts
+                            .iter()
+                            .any(|p| p.content_type == ContentType::CMSSignature)
+                        {
+                            ret.push_str(
+                                "; micalg=sha-256; protocol=\"application/pkcs7-signature\"",
+                            );
+                        } else {
+                            ret.push_str(
+                                "; micalg=pgp-sha512; protocol=\"application/pgp-signature\"",
+                            );
+                        }
                     }
                     ret.push_str("\r\n");
 
@@ -725,10 +863,17 @@ impl Attachment {
                     ret.push_str(&format!("Content-Type: {}\r\n\r\n", a.content_type));
                     ret.push_str(&String::from_utf8_lossy(a.body()));
                 }
-                ContentType::CMSSignature | ContentType::PGPSignature => {
+                ContentType::PGPSignature => {
                     ret.push_str(&format!("Content-Type: {}\r\n\r\n", a.content_type));
                     ret.push_str(&String::from_utf8_lossy(a.body()));
                 }
+                ContentType::CMSSignature => {
+                    ret.push_str(&format!(
+                        "Content-Type: {}; name=\"smime.p7s\"\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+                        a.content_type
+                    ));
+                    ret.push_str(&BASE64_MIME.encode(a.body()).trim());
+                }
                 ContentType::OctetStream { ref name } => {
                     if let Some(name) = name {
                         ret.push_str(&format!(
@@ -800,8 +945,14 @@ impl Attachment {
     }
 }
 
-pub fn interpret_format_flowed(_t: &str) -> String {
-    unimplemented!()
+/// Join the soft line breaks of a `format=flowed` text, so that each paragraph is on a single line
+/// prefixed by its quote indicators.
+pub fn interpret_format_flowed(t: &str, delsp: bool) -> String {
+    crate::text_processing::format_flowed::decode(t, delsp)
+        .iter()
+        .map(|p| format!("{}{}", p.quote_prefix(), p.text()))
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
 type Filter<'a> = Box<dyn FnMut(&Attachment, &mut Vec<u8>) -> () + 'a>;
@@ -896,8 +1047,20 @@ fn decode_helper<'a, 'b>(a: &'a Attachment, filter: &mut Option<Filter<'b>>) ->
     };
 
     let mut ret = if a.content_type.is_text() {
+        /* Text labelled as (or defaulting to) ASCII or UTF-8 that isn't valid UTF-8 is most likely
+         * unlabelled legacy text. */
+        let charset = match charset {
+            Charset::Ascii | Charset::UTF8 if std::str::from_utf8(&bytes).is_err() => {
+                super::charset_detection::detect(&bytes)
+            }
+            c => c,
+        };
         if let Ok(v) = parser::encodings::decode_charset(&bytes, charset) {
             v.into_bytes()
+        } else if let Ok(v) =
+            parser::encodings::decode_charset(&bytes, super::charset_detection::detect(&bytes))
+        {
+            v.into_bytes()
         } else {
             a.body().to_vec()
         }
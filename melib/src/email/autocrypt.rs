@@ -0,0 +1,164 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Parsing and generation of [Autocrypt Level 1](https://autocrypt.org/level1.html) `Autocrypt`
+ * and `Autocrypt-Gossip` headers.
+ */
+use crate::error::{MeliError, Result};
+use data_encoding::{BASE64, BASE64_MIME};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreferEncrypt {
+    NoPreference,
+    Mutual,
+}
+
+impl Default for PreferEncrypt {
+    fn default() -> Self {
+        PreferEncrypt::NoPreference
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutocryptHeader {
+    pub addr: String,
+    pub prefer_encrypt: PreferEncrypt,
+    /// Binary OpenPGP transferable public key.
+    pub keydata: Vec<u8>,
+}
+
+impl AutocryptHeader {
+    pub fn new(addr: String, prefer_encrypt: PreferEncrypt, keydata: Vec<u8>) -> Self {
+        AutocryptHeader {
+            addr,
+            prefer_encrypt,
+            keydata,
+        }
+    }
+
+    /// Parse the value of an `Autocrypt` or `Autocrypt-Gossip` header. Unknown attributes are
+    /// ignored if they start with an underscore, otherwise the header is invalid.
+    pub fn parse(value: &[u8]) -> Result<Self> {
+        let value = String::from_utf8_lossy(value);
+        let mut addr = None;
+        let mut prefer_encrypt = PreferEncrypt::NoPreference;
+        let mut keydata = None;
+        for attr in value.split(';') {
+            let attr = attr.trim();
+            if attr.is_empty() {
+                continue;
+            }
+            let (name, val) = match attr.find('=') {
+                Some(pos) => (attr[..pos].trim(), attr[pos + 1..].trim()),
+                None => {
+                    return Err(MeliError::new(format!(
+                        "Invalid Autocrypt attribute `{}`.",
+                        attr
+                    )))
+                }
+            };
+            match name {
+                "addr" => addr = Some(val.trim_matches('"').to_lowercase()),
+                "prefer-encrypt" => {
+                    if val == "mutual" {
+                        prefer_encrypt = PreferEncrypt::Mutual;
+                    }
+                }
+                "keydata" => {
+                    let val = val
+                        .chars()
+                        .filter(|c| !c.is_ascii_whitespace())
+                        .collect::<String>();
+                    keydata = Some(BASE64.decode(val.as_bytes()).map_err(|err| {
+                        MeliError::new(format!("Invalid Autocrypt keydata: {}", err))
+                    })?);
+                }
+                other if other.starts_with('_') => {}
+                other => {
+                    return Err(MeliError::new(format!(
+                        "Unknown critical Autocrypt attribute `{}`.",
+                        other
+                    )))
+                }
+            }
+        }
+        match (addr, keydata) {
+            (Some(addr), Some(keydata)) if !addr.is_empty() && !keydata.is_empty() => {
+                Ok(AutocryptHeader {
+                    addr,
+                    prefer_encrypt,
+                    keydata,
+                })
+            }
+            _ => Err(MeliError::new(
+                "Autocrypt header is missing the addr or keydata attribute.",
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AutocryptHeader {
+    /// Header value, with `keydata` folded so that it can be emitted as is.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "addr={};", self.addr)?;
+        if self.prefer_encrypt == PreferEncrypt::Mutual {
+            write!(f, " prefer-encrypt=mutual;")?;
+        }
+        write!(f, " keydata=")?;
+        for line in BASE64_MIME.encode(&self.keydata).trim().lines() {
+            write!(f, "\r\n {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autocrypt_header() {
+        let header = AutocryptHeader::parse(
+            b"addr=alice@autocrypt.example; prefer-encrypt=mutual; keydata=\r\n mQGNBFn+zzUBDADBo2D+\r\n lCMQ4SBR",
+        )
+        .unwrap();
+        assert_eq!(header.addr.as_str(), "alice@autocrypt.example");
+        assert_eq!(header.prefer_encrypt, PreferEncrypt::Mutual);
+        assert_eq!(
+            header.keydata,
+            BASE64.decode(b"mQGNBFn+zzUBDADBo2D+lCMQ4SBR").unwrap()
+        );
+
+        let reparsed = AutocryptHeader::parse(header.to_string().as_bytes()).unwrap();
+        assert_eq!(reparsed, header);
+
+        assert!(AutocryptHeader::parse(
+            b"addr=bob@autocrypt.example; _ignored=1; keydata=mQGNBFn+zzUB"
+        )
+        .is_ok());
+        assert!(AutocryptHeader::parse(
+            b"addr=bob@autocrypt.example; critical=1; keydata=mQGNBFn+zzUB"
+        )
+        .is_err());
+        assert!(AutocryptHeader::parse(b"addr=bob@autocrypt.example").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+/*
+ * meli - email module
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Detection of the charset of text that is unlabelled or mislabelled.
+ *
+ * Every candidate charset that can decode the text is scored by how plausible the decoded text
+ * is: letters frequent in the languages written with the charset score high, control characters
+ * and symbols score low, and so do case changes inside words (which tell apart eg. KOI8-R and
+ * windows-1251, whose letters have swapped cases) and runs of accented Latin letters (which is
+ * what multibyte text looks like when decoded as a Latin charset).
+ */
+
+use super::attachment_types::Charset;
+use super::parser::encodings::decode_charset;
+
+/// Maximum number of bytes to examine.
+const SAMPLE_LENGTH: usize = 32 * 1024;
+
+const WESTERN: &str = "éèàçùâêîôûëïüöäßñáíóúãõåæøœ";
+const CENTRAL_EUROPEAN: &str = "ąćęłńóśźżčďěňřšťůžáéíýőűäöüâîăşţ";
+/* Only the most frequent letters of non-Latin alphabets, since any byte sequence decodes to
+ * letters of the alphabet in their charsets. */
+const CYRILLIC: &str = "оеаинтсрвлкмдпуяі";
+const GREEK: &str = "αοειτνσρηκπμυλάόέίς";
+const TURKISH: &str = "çğıöşüâîû";
+const HEBREW: &str = "יוהלארמבנתשםןכ";
+const ARABIC: &str = "اليمونرتبةعهف";
+const BALTIC: &str = "ąčęėįšųūžāēģīķļņōõäöü";
+
+const JAPANESE: &str = "のにはをたがでてとしれさいるなかもっあすうこらりまど日本人一年会社";
+const KOREAN: &str = "이의다는에가을하고지를기한로서도리사있정대시수그아니습";
+const SIMPLIFIED_CHINESE: &str =
+    "的一是不了人我在有他这中大来上国个到说们为子和你地出道也时年会就要以";
+const TRADITIONAL_CHINESE: &str =
+    "的一是不了人我在有他這中大來上國個到說們為子和你地出道也時年會就要以";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Script {
+    Latin,
+    Alphabetic,
+    Japanese,
+    Korean,
+    Chinese,
+}
+
+/// Candidates in order of preference when their scores are equal.
+const CANDIDATES: &[(Charset, Script, &str)] = &[
+    (Charset::Windows1252, Script::Latin, WESTERN),
+    (Charset::Windows1250, Script::Latin, CENTRAL_EUROPEAN),
+    (Charset::Windows1251, Script::Alphabetic, CYRILLIC),
+    (Charset::KOI8R, Script::Alphabetic, CYRILLIC),
+    (Charset::Windows1253, Script::Alphabetic, GREEK),
+    (Charset::Windows1254, Script::Latin, TURKISH),
+    (Charset::Windows1255, Script::Alphabetic, HEBREW),
+    (Charset::Windows1256, Script::Alphabetic, ARABIC),
+    (Charset::Windows1257, Script::Latin, BALTIC),
+    (Charset::ShiftJIS, Script::Japanese, JAPANESE),
+    (Charset::EUCJP, Script::Japanese, JAPANESE),
+    (Charset::EUCKR, Script::Korean, KOREAN),
+    (Charset::GB18030, Script::Chinese, SIMPLIFIED_CHINESE),
+    (Charset::BIG5, Script::Chinese, TRADITIONAL_CHINESE),
+];
+
+fn is_kana(c: char) -> bool {
+    ('\u{3040}'..='\u{30ff}').contains(&c) || ('\u{ff66}'..='\u{ff9f}').contains(&c)
+}
+
+fn is_hangul(c: char) -> bool {
+    ('\u{ac00}'..='\u{d7a3}').contains(&c)
+}
+
+fn is_han(c: char) -> bool {
+    ('\u{4e00}'..='\u{9fff}').contains(&c)
+}
+
+/// CJK and fullwidth punctuation.
+fn is_cjk_punctuation(c: char) -> bool {
+    ('\u{3000}'..='\u{303f}').contains(&c) || ('\u{ff00}'..='\u{ff65}').contains(&c)
+}
+
+fn score(text: &str, script: Script, frequent: &str) -> i64 {
+    let mut ret = 0;
+    let mut prev = ' ';
+    for c in text.chars() {
+        if !c.is_ascii() {
+            let lowercase = c.to_lowercase().next().unwrap_or(c);
+            ret += if script == Script::Latin && !prev.is_ascii() && c.is_alphabetic() {
+                -1
+            } else if frequent.contains(lowercase) {
+                /* Text is rarely written in all caps. */
+                if prev.is_uppercase() && c.is_uppercase() {
+                    1
+                } else {
+                    4
+                }
+            } else if (script == Script::Japanese && is_kana(c))
+                || (script == Script::Korean && is_hangul(c))
+                || (script == Script::Chinese && is_han(c))
+            {
+                2
+            } else if (script == Script::Japanese && is_han(c))
+                || (script != Script::Latin
+                    && script != Script::Alphabetic
+                    && is_cjk_punctuation(c))
+            {
+                1
+            } else if c.is_control() || ('\u{e000}'..='\u{f8ff}').contains(&c) {
+                -10
+            } else if c.is_alphabetic() {
+                0
+            } else {
+                -1
+            };
+        }
+        /* Case changes inside words are unlikely, and so are words mixing Latin letters with
+         * letters of other alphabets. */
+        if prev.is_lowercase() && c.is_uppercase() {
+            ret -= 2;
+        }
+        if script == Script::Alphabetic
+            && c.is_alphabetic()
+            && prev.is_alphabetic()
+            && c.is_ascii() != prev.is_ascii()
+        {
+            ret -= 4;
+        }
+        prev = c;
+    }
+    ret
+}
+
+/// Guess the charset of `bytes`. Valid UTF-8 is always detected as UTF-8 (or ASCII), otherwise
+/// the most plausible legacy charset is returned, defaulting to windows-1252.
+pub fn detect(bytes: &[u8]) -> Charset {
+    if bytes.is_ascii() {
+        /* 7-bit ISO-2022-JP text is the only legacy charset that's valid ASCII. */
+        if bytes.windows(3).any(|w| w == b"\x1b$B" || w == b"\x1b$@") {
+            return Charset::ISO2022JP;
+        }
+        return Charset::Ascii;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return Charset::UTF8;
+    }
+    if bytes.starts_with(b"\xff\xfe") {
+        return Charset::UTF16;
+    }
+    let sample = if bytes.len() > SAMPLE_LENGTH {
+        /* Cut at a line end so that no multibyte character is split. */
+        match bytes[..SAMPLE_LENGTH].iter().rposition(|&b| b == b'\n') {
+            Some(pos) => &bytes[..=pos],
+            None => &bytes[..SAMPLE_LENGTH],
+        }
+    } else {
+        bytes
+    };
+    let mut best = (Charset::Windows1252, std::i64::MIN);
+    for &(charset, script, frequent) in CANDIDATES {
+        if let Ok(text) = decode_charset(sample, charset) {
+            let s = score(&text, script, frequent);
+            if s > best.1 {
+                best = (charset, s);
+            }
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charset_detection() {
+        use encoding::all::*;
+        use encoding::{EncoderTrap, Encoding};
+        for (encoding, charset, text) in &[
+            (
+                WINDOWS_1252 as encoding::EncodingRef,
+                Charset::Windows1252,
+                "Voilà, le café est prêt. Ça va être très bon à déguster.",
+            ),
+            (
+                WINDOWS_1250,
+                Charset::Windows1250,
+                "Příliš žluťoučký kůň úpěl ďábelské ódy. Zażółć gęślą jaźń.",
+            ),
+            (
+                WINDOWS_1251,
+                Charset::Windows1251,
+                "Съешь же ещё этих мягких французских булок, да выпей чаю.",
+            ),
+            (
+                KOI8_R,
+                Charset::KOI8R,
+                "Съешь же ещё этих мягких французских булок, да выпей чаю.",
+            ),
+            (
+                WINDOWS_1253,
+                Charset::Windows1253,
+                "Ξεσκεπάζω την ψυχοφθόρα βδελυγμία.",
+            ),
+            (
+                WINDOWS_31J,
+                Charset::ShiftJIS,
+                "日本語のテキストです。これは文字コードの判定のテストです。",
+            ),
+            (
+                WINDOWS_949,
+                Charset::EUCKR,
+                "이것은 문자 인코딩 감지를 위한 한국어 문장입니다.",
+            ),
+            (
+                GB18030,
+                Charset::GB18030,
+                "这是一个用来测试字符集检测的中文句子，我们希望它能被正确地识别。",
+            ),
+        ] {
+            let bytes = encoding.encode(text, EncoderTrap::Strict).unwrap();
+            assert_eq!(detect(&bytes), *charset, "{}", text);
+        }
+        assert_eq!(detect("καλημέρα".as_bytes()), Charset::UTF8);
+        assert_eq!(detect(b"plain text"), Charset::Ascii);
+    }
+}
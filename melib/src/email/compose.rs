@@ -24,7 +24,7 @@ use super::*;
 use crate::email::attachment_types::{
     Charset, ContentTransferEncoding, ContentType, MultipartType,
 };
-use crate::email::attachments::{decode, decode_rec, AttachmentBuilder};
+use crate::email::attachments::{decode, decode_rec, interpret_format_flowed, AttachmentBuilder};
 use crate::shellexpand::ShellExpandTrait;
 use data_encoding::BASE64_MIME;
 use std::ffi::OsStr;
@@ -172,7 +172,17 @@ impl Draft {
         );
         let body = envelope.body_bytes(bytes);
         ret.body = {
-            let reply_body_bytes = decode_rec(&body, None);
+            /* Join format=flowed paragraphs before quoting them, so that they can be reflowed at
+             * their new quote depth. */
+            let reply_body_bytes = decode_rec(
+                &body,
+                Some(Box::new(|a: &Attachment, v: &mut Vec<u8>| {
+                    if let Some(delsp) = a.format_flowed() {
+                        *v = interpret_format_flowed(&String::from_utf8_lossy(v), delsp)
+                            .into_bytes();
+                    }
+                })),
+            );
             let reply_body = String::from_utf8_lossy(&reply_body_bytes);
             let lines: Vec<&str> = reply_body.lines().collect();
             let mut ret = format!(
@@ -237,6 +247,7 @@ impl Draft {
             if let Ok((_, addr)) = super::parser::address::mailbox(self.headers["From"].as_bytes())
             {
                 if let Some(fqdn) = addr.get_fqdn() {
+                    let fqdn = super::address::idna::domain_to_ascii(&fqdn).unwrap_or(fqdn);
                     self.headers.insert(
                         HeaderName::new_unchecked("Message-ID"),
                         random::gen_message_id(&fqdn),
@@ -247,6 +258,12 @@ impl Draft {
         for (k, v) in self.headers.deref() {
             if v.is_ascii() {
                 ret.push_str(&format!("{}: {}\r\n", k, v));
+            } else if let Some(v) = ["From", "To", "Cc", "Bcc", "Reply-To", "Sender"]
+                .iter()
+                .find(|h| *k == **h)
+                .and_then(|_| mime::encode_address_list(v))
+            {
+                ret.push_str(&format!("{}: {}\r\n", k, v));
             } else {
                 ret.push_str(&format!("{}: {}\r\n", k, mime::encode_header(v)));
             }
@@ -296,6 +313,15 @@ fn build_multipart(ret: &mut String, kind: MultipartType, parts: Vec<AttachmentB
     ));
     if kind == MultipartType::Encrypted {
         ret.push_str(r#"; protocol="application/pgp-encrypted""#);
+    } else if kind == MultipartType::Signed {
+        if parts
+            .iter()
+            .any(|p| p.content_type == ContentType::CMSSignature)
+        {
+            ret.push_str(r#"; micalg=sha-256; protocol="application/pkcs7-signature""#);
+        } else {
+            ret.push_str(r#"; micalg=pgp-sha512; protocol="application/pgp-signature""#);
+        }
     }
     ret.push_str("\r\n\r\n");
     /* rfc1341 */
@@ -325,7 +351,11 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
                 ret.push_str("\r\n");
             }
         }
-        Text { .. } => {
+        Text { .. }
+        | Multipart {
+            kind: MultipartType::Tnef,
+            ..
+        } => {
             for line in a.build().into_raw().lines() {
                 ret.push_str(line);
                 ret.push_str("\r\n");
@@ -370,6 +400,20 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
                 ret.push_str("\r\n");
             }
         }
+        CMSSignature => {
+            ret.push_str(&format!(
+                "Content-Type: {}; name=\"smime.p7s\"\r\n",
+                a.content_type
+            ));
+            ret.push_str("Content-Description: S/MIME Cryptographic Signature\r\n");
+            ret.push_str("Content-Disposition: attachment; filename=\"smime.p7s\"\r\n");
+            ret.push_str("Content-Transfer-Encoding: base64\r\n");
+            ret.push_str("\r\n");
+            for line in BASE64_MIME.encode(a.raw()).trim().lines() {
+                ret.push_str(line);
+                ret.push_str("\r\n");
+            }
+        }
         _ => {
             let content_transfer_encoding: ContentTransferEncoding = if a.raw().is_ascii() {
                 ContentTransferEncoding::_8Bit
@@ -378,17 +422,21 @@ fn print_attachment(ret: &mut String, a: AttachmentBuilder) {
             };
             if let Some(name) = a.content_type().name() {
                 ret.push_str(&format!(
-                    "Content-Type: {}; name=\"{}\"; charset=\"utf-8\"\r\n",
+                    "Content-Type: {}; {}; charset=\"utf-8\"\r\n",
                     a.content_type(),
-                    name
+                    mime::encode_parameter("name", name)
+                ));
+                ret.push_str(&format!(
+                    "Content-Disposition: attachment; {}\r\n",
+                    mime::encode_parameter("filename", name)
                 ));
             } else {
                 ret.push_str(&format!(
                     "Content-Type: {}; charset=\"utf-8\"\r\n",
                     a.content_type()
                 ));
+                ret.push_str("Content-Disposition: attachment\r\n");
             }
-            ret.push_str("Content-Disposition: attachment\r\n");
             ret.push_str(&format!(
                 "Content-Transfer-Encoding: {}\r\n",
                 content_transfer_encoding
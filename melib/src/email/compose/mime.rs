@@ -151,6 +151,134 @@ pub fn encode_header(value: &str) -> String {
     }
     ret
 }
+/// Format a MIME parameter, using [RFC2231](https://tools.ietf.org/html/rfc2231) extended
+/// values and continuations if `value` is not ASCII or doesn't fit in a line, eg.
+/// `filename*0*=utf-8''%CE%94...;\r\n filename*1*=...`.
+pub fn encode_parameter(name: &str, value: &str) -> String {
+    /* Leave enough room in each line for the parameter name and the continuation number. */
+    const MAX_SECTION_LENGTH: usize = 60;
+    if value.is_ascii() && !value.contains(|c: char| c.is_ascii_control()) && value.len() < 64 {
+        return format!(
+            "{}=\"{}\"",
+            name,
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+    }
+    let mut encoded = String::with_capacity(3 * value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    if encoded.len() + "utf-8''".len() <= MAX_SECTION_LENGTH {
+        return format!("{}*=utf-8''{}", name, encoded);
+    }
+    let mut sections = vec![];
+    let mut rest = encoded.as_str();
+    while !rest.is_empty() {
+        let mut end = std::cmp::min(MAX_SECTION_LENGTH, rest.len());
+        /* Don't split a percent-encoded octet. */
+        if let Some(pos) = rest[..end].rfind('%') {
+            if pos + 3 > end {
+                end = pos;
+            }
+        }
+        sections.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    sections
+        .into_iter()
+        .enumerate()
+        .map(|(i, s)| {
+            if i == 0 {
+                format!("{}*0*=utf-8''{}", name, s)
+            } else {
+                format!("{}*{}*={}", name, i, s)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(";\r\n ")
+}
+
+#[test]
+fn test_encode_parameter() {
+    assert_eq!(
+        encode_parameter("filename", "report \"2020\".pdf").as_str(),
+        "filename=\"report \\\"2020\\\".pdf\""
+    );
+    assert_eq!(
+        encode_parameter("filename", "Δελτίο.pdf").as_str(),
+        "filename*=utf-8''%CE%94%CE%B5%CE%BB%CF%84%CE%AF%CE%BF.pdf"
+    );
+    let long = "Δελτίο τύπου για την παρουσίαση.pdf";
+    let encoded = encode_parameter("filename", long);
+    assert!(encoded.starts_with("filename*0*=utf-8''%CE%94"));
+    assert!(encoded.contains(";\r\n filename*1*=%"));
+    let (_, (_, _, params)) = crate::email::parser::attachments::content_type(
+        format!("application/pdf; {}", encoded).as_bytes(),
+    )
+    .unwrap();
+    assert_eq!(
+        crate::email::parser::attachments::rfc2231_parameters(&params),
+        vec![(b"filename".to_vec(), long.as_bytes().to_vec())]
+    );
+}
+
+/// Encode the display names of an address list header value, leaving addr-specs as they are.
+/// Internationalized addresses can't be put in encoded-words, so they are kept in UTF-8 as
+/// [RFC6532](https://tools.ietf.org/html/rfc6532) specifies. Returns `None` if `value` is not an
+/// address list.
+pub fn encode_address_list(value: &str) -> Option<String> {
+    fn encode_display_name(name: &str) -> String {
+        if !name.is_ascii() {
+            encode_header(name)
+        } else if name.contains(|c| "()<>[]:;@\\,.\"".contains(c)) {
+            format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+        } else {
+            name.to_string()
+        }
+    }
+    fn encode_address(addr: &Address) -> String {
+        match (addr, addr.get_display_name()) {
+            (Address::Group(g), name) => format!(
+                "{}: {};",
+                encode_display_name(&name.unwrap_or_default()),
+                g.mailbox_list
+                    .iter()
+                    .map(encode_address)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            (Address::Mailbox(_), Some(name)) => {
+                format!("{} <{}>", encode_display_name(&name), addr.get_email())
+            }
+            (Address::Mailbox(_), None) => addr.get_email(),
+        }
+    }
+    let (rest, list) = parser::address::rfc2822address_list(value.as_bytes()).ok()?;
+    if !rest.iter().all(|b| b.is_ascii_whitespace()) {
+        return None;
+    }
+    Some(
+        list.iter()
+            .map(encode_address)
+            .collect::<Vec<String>>()
+            .join(", "),
+    )
+}
+
+#[test]
+fn test_encode_address_list() {
+    assert_eq!(
+        encode_address_list("Σταύρος <用户@例子.广告>, \"Stedfast, Jeffrey\" <fejj@helixcode.com>")
+            .unwrap()
+            .as_str(),
+        "=?UTF-8?B?zqPPhM6xz43Pgc6/z4I=?= <用户@例子.广告>, \"Stedfast, Jeffrey\" <fejj@helixcode.com>"
+    );
+}
+
 #[test]
 fn test_encode_header() {
     let words = "compilers/2020a σε Rust";
@@ -0,0 +1,927 @@
+/*
+ * meli - email module
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Verification of DKIM ([RFC 6376](https://tools.ietf.org/html/rfc6376)) signatures and ARC
+ * ([RFC 8617](https://tools.ietf.org/html/rfc8617)) chains.
+ *
+ * Only the `rsa-sha256` and `ed25519-sha256` ([RFC 8463](https://tools.ietf.org/html/rfc8463))
+ * algorithms are supported. Public keys are retrieved with a [`KeyResolver`](resolver::KeyResolver):
+ * [`DnsResolver`](resolver::DnsResolver) queries the name servers of the system, and
+ * [`StaticKeys`](resolver::StaticKeys) reads keys from a file, eg. to verify messages offline.
+ */
+
+use crate::error::{MeliError, Result};
+use data_encoding::BASE64;
+
+pub mod authentication_results;
+pub mod resolver;
+
+pub use authentication_results::{AuthenticationResults, MethodResult};
+pub use resolver::{DnsResolver, KeyResolver, StaticKeys};
+
+/// ARC chains longer than this are invalid.
+const ARC_MAX_INSTANCE: u32 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    RsaSha256,
+    Ed25519Sha256,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Canonicalization {
+    Simple,
+    Relaxed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// `DKIM-Signature`
+    Dkim,
+    /// `ARC-Message-Signature`
+    ArcMessageSignature,
+    /// `ARC-Seal`, which signs the ARC headers instead of the message.
+    ArcSeal,
+}
+
+impl SignatureKind {
+    pub fn header_name(self) -> &'static str {
+        match self {
+            SignatureKind::Dkim => "DKIM-Signature",
+            SignatureKind::ArcMessageSignature => "ARC-Message-Signature",
+            SignatureKind::ArcSeal => "ARC-Seal",
+        }
+    }
+}
+
+/// A parsed `DKIM-Signature`, `ARC-Message-Signature` or `ARC-Seal` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub kind: SignatureKind,
+    pub algorithm: Algorithm,
+    pub header_canonicalization: Canonicalization,
+    pub body_canonicalization: Canonicalization,
+    /// Signing domain, the `d=` tag.
+    pub domain: String,
+    pub selector: String,
+    /// Names of the signed header fields, the `h=` tag. Empty for `ARC-Seal`.
+    pub headers: Vec<String>,
+    pub body_hash: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// Number of signed body octets, the `l=` tag.
+    pub body_length: Option<usize>,
+    /// Expiration as a UNIX timestamp, the `x=` tag.
+    pub expiration: Option<u64>,
+    /// Instance number of ARC headers, the `i=` tag.
+    pub instance: Option<u32>,
+    /// Chain validation status of `ARC-Seal`, the `cv=` tag.
+    pub chain_validation: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationResult {
+    Pass,
+    /// The signature doesn't match the message.
+    Fail(String),
+    /// The key could not be retrieved, verification might succeed later.
+    TempError(String),
+    /// The signature or its key is malformed, revoked or unsupported.
+    PermError(String),
+}
+
+impl VerificationResult {
+    pub fn is_pass(&self) -> bool {
+        *self == VerificationResult::Pass
+    }
+}
+
+impl std::fmt::Display for VerificationResult {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VerificationResult::Pass => write!(fmt, "pass"),
+            VerificationResult::Fail(reason) => write!(fmt, "fail ({})", reason),
+            VerificationResult::TempError(reason) => write!(fmt, "temperror ({})", reason),
+            VerificationResult::PermError(reason) => write!(fmt, "permerror ({})", reason),
+        }
+    }
+}
+
+/// Result of verifying one `DKIM-Signature` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureVerification {
+    pub domain: String,
+    pub selector: String,
+    pub result: VerificationResult,
+}
+
+/// Splits a tag-value list (`tag=value; tag=value`) into its tags.
+pub fn tag_list(value: &str) -> Result<Vec<(&str, &str)>> {
+    let mut ret: Vec<(&str, &str)> = Vec::new();
+    for spec in value.split(';') {
+        if spec.trim().is_empty() {
+            continue;
+        }
+        let eq = spec
+            .find('=')
+            .ok_or_else(|| MeliError::new(format!("Invalid tag {}", spec.trim())))?;
+        let (name, value) = (spec[..eq].trim(), spec[eq + 1..].trim());
+        if ret.iter().any(|(n, _)| *n == name) {
+            return Err(MeliError::new(format!("Duplicate tag {}", name)));
+        }
+        ret.push((name, value));
+    }
+    Ok(ret)
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    BASE64
+        .decode(value.as_bytes())
+        .map_err(|err| MeliError::new(format!("Invalid base64 value: {}", err)))
+}
+
+impl Signature {
+    /// Parse the value of a signature header of `kind`.
+    pub fn parse(kind: SignatureKind, value: &str) -> Result<Signature> {
+        let tags = tag_list(value)?;
+        let tag = |name: &str| tags.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+        let required = |name: &str| {
+            tag(name).ok_or_else(|| {
+                MeliError::new(format!(
+                    "{} is missing the {}= tag",
+                    kind.header_name(),
+                    name
+                ))
+            })
+        };
+        if kind == SignatureKind::Dkim && required("v")? != "1" {
+            return Err(MeliError::new("Unsupported DKIM-Signature version"));
+        }
+        let algorithm = match required("a")? {
+            "rsa-sha256" => Algorithm::RsaSha256,
+            "ed25519-sha256" => Algorithm::Ed25519Sha256,
+            other => {
+                return Err(MeliError::new(format!(
+                    "Unsupported signature algorithm {}",
+                    other
+                )))
+            }
+        };
+        let canonicalization = |value: &str| match value {
+            "simple" => Ok(Canonicalization::Simple),
+            "relaxed" => Ok(Canonicalization::Relaxed),
+            other => Err(MeliError::new(format!(
+                "Unsupported canonicalization {}",
+                other
+            ))),
+        };
+        let (header_canonicalization, body_canonicalization) = match tag("c") {
+            _ if kind == SignatureKind::ArcSeal => {
+                (Canonicalization::Relaxed, Canonicalization::Relaxed)
+            }
+            None => (Canonicalization::Simple, Canonicalization::Simple),
+            Some(c) => {
+                let mut parts = c.splitn(2, '/');
+                let header = canonicalization(parts.next().unwrap_or_default())?;
+                let body = parts
+                    .next()
+                    .map(canonicalization)
+                    .unwrap_or(Ok(Canonicalization::Simple))?;
+                (header, body)
+            }
+        };
+        let (headers, body_hash) = if kind == SignatureKind::ArcSeal {
+            if tag("h").is_some() {
+                return Err(MeliError::new("ARC-Seal must not have the h= tag"));
+            }
+            (vec![], vec![])
+        } else {
+            let headers: Vec<String> = required("h")?
+                .split(':')
+                .map(|h| h.trim().to_ascii_lowercase())
+                .collect();
+            if kind == SignatureKind::Dkim && !headers.iter().any(|h| h == "from") {
+                return Err(MeliError::new(
+                    "DKIM-Signature does not sign the From header",
+                ));
+            }
+            (headers, decode_base64(required("bh")?)?)
+        };
+        let number = |name: &str| -> Result<Option<u64>> {
+            tag(name)
+                .map(|v| {
+                    v.parse::<u64>()
+                        .map_err(|_| MeliError::new(format!("Invalid {}= tag {}", name, v)))
+                })
+                .transpose()
+        };
+        let instance = if kind == SignatureKind::Dkim {
+            None
+        } else {
+            match number("i")? {
+                Some(i) if i >= 1 && i <= u64::from(ARC_MAX_INSTANCE) => Some(i as u32),
+                _ => {
+                    return Err(MeliError::new(format!(
+                        "{} has an invalid i= tag",
+                        kind.header_name()
+                    )))
+                }
+            }
+        };
+        let chain_validation = if kind == SignatureKind::ArcSeal {
+            Some(required("cv")?.to_ascii_lowercase())
+        } else {
+            None
+        };
+        Ok(Signature {
+            kind,
+            algorithm,
+            header_canonicalization,
+            body_canonicalization,
+            domain: required("d")?.to_ascii_lowercase(),
+            selector: required("s")?.to_string(),
+            headers,
+            body_hash,
+            signature: decode_base64(required("b")?)?,
+            body_length: number("l")?.map(|l| l as usize),
+            expiration: number("x")?,
+            instance,
+            chain_validation,
+        })
+    }
+}
+
+/// Converts bare LF line endings to CRLF.
+fn to_crlf(bytes: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(bytes.len() + bytes.len() / 32);
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'\n' && (i == 0 || bytes[i - 1] != b'\r') {
+            ret.push(b'\r');
+        }
+        ret.push(b);
+    }
+    ret
+}
+
+/// Splits a message with CRLF line endings into its raw header fields (each with its
+/// terminating CRLF) and its body.
+fn split_message(bytes: &[u8]) -> (Vec<&[u8]>, &[u8]) {
+    let (headers, body) = if bytes.starts_with(b"\r\n") {
+        (&bytes[..0], &bytes[2..])
+    } else {
+        match bytes.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(pos) => (&bytes[..pos + 2], &bytes[pos + 4..]),
+            None => (bytes, &bytes[bytes.len()..]),
+        }
+    };
+    let mut fields: Vec<&[u8]> = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < headers.len() {
+        let line_end = headers[pos..]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .map(|p| pos + p + 2)
+            .unwrap_or_else(|| headers.len());
+        let next_is_continuation =
+            line_end < headers.len() && (headers[line_end] == b' ' || headers[line_end] == b'\t');
+        if !next_is_continuation {
+            let field = &headers[start..line_end];
+            /* Skip the mbox "From " separator line. */
+            if !(start == 0 && field.starts_with(b"From ")) {
+                fields.push(field);
+            }
+            start = line_end;
+        }
+        pos = line_end;
+    }
+    (fields, body)
+}
+
+fn field_name(field: &[u8]) -> &[u8] {
+    let colon = field
+        .iter()
+        .position(|&b| b == b':')
+        .unwrap_or_else(|| field.len());
+    let mut name = &field[..colon];
+    while name.last().map(|b| *b == b' ' || *b == b'\t') == Some(true) {
+        name = &name[..name.len() - 1];
+    }
+    name
+}
+
+fn field_value(field: &[u8]) -> &[u8] {
+    match field.iter().position(|&b| b == b':') {
+        Some(colon) => &field[colon + 1..],
+        None => &field[field.len()..],
+    }
+}
+
+/// Replaces runs of whitespace with a single space and removes whitespace at the end.
+fn compress_whitespace(line: &[u8], out: &mut Vec<u8>) {
+    let mut in_whitespace = false;
+    for &b in line {
+        if b == b' ' || b == b'\t' {
+            in_whitespace = true;
+        } else {
+            if in_whitespace {
+                out.push(b' ');
+            }
+            in_whitespace = false;
+            out.push(b);
+        }
+    }
+}
+
+fn canonicalize_header(field: &[u8], canonicalization: Canonicalization) -> Vec<u8> {
+    match canonicalization {
+        Canonicalization::Simple => field.to_vec(),
+        Canonicalization::Relaxed => {
+            let mut ret = field_name(field).to_ascii_lowercase();
+            ret.push(b':');
+            let unfolded: Vec<u8> = field_value(field)
+                .iter()
+                .cloned()
+                .filter(|&b| b != b'\r' && b != b'\n')
+                .collect();
+            let start = unfolded
+                .iter()
+                .position(|&b| b != b' ' && b != b'\t')
+                .unwrap_or_else(|| unfolded.len());
+            compress_whitespace(&unfolded[start..], &mut ret);
+            ret.extend_from_slice(b"\r\n");
+            ret
+        }
+    }
+}
+
+fn canonicalize_body(body: &[u8], canonicalization: Canonicalization) -> Vec<u8> {
+    let mut lines: Vec<&[u8]> = Vec::new();
+    let mut rest = body;
+    while let Some(pos) = rest.windows(2).position(|w| w == b"\r\n") {
+        lines.push(&rest[..pos]);
+        rest = &rest[pos + 2..];
+    }
+    if !rest.is_empty() {
+        lines.push(rest);
+    }
+    let mut lines: Vec<Vec<u8>> = lines
+        .into_iter()
+        .map(|l| match canonicalization {
+            Canonicalization::Simple => l.to_vec(),
+            Canonicalization::Relaxed => {
+                let mut ret = Vec::with_capacity(l.len());
+                compress_whitespace(l, &mut ret);
+                ret
+            }
+        })
+        .collect();
+    while lines.last().map(Vec::is_empty) == Some(true) {
+        lines.pop();
+    }
+    if lines.is_empty() && canonicalization == Canonicalization::Simple {
+        return b"\r\n".to_vec();
+    }
+    let mut ret = Vec::with_capacity(body.len());
+    for l in lines {
+        ret.extend(l);
+        ret.extend_from_slice(b"\r\n");
+    }
+    ret
+}
+
+/// Empties the value of the `b=` tag of a signature header field, which is signed as if the
+/// signature was empty.
+fn remove_signature_value(field: &[u8]) -> Vec<u8> {
+    let value_start = field.len() - field_value(field).len();
+    let mut ret = field[..value_start].to_vec();
+    let mut rest = &field[value_start..];
+    while !rest.is_empty() {
+        let end = rest
+            .iter()
+            .position(|&b| b == b';')
+            .unwrap_or_else(|| rest.len());
+        let spec = &rest[..end];
+        let eq = spec.iter().position(|&b| b == b'=');
+        let is_signature = eq
+            .map(|eq| {
+                spec[..eq]
+                    .iter()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .eq(b"b".iter())
+            })
+            .unwrap_or(false);
+        if is_signature {
+            ret.extend_from_slice(&spec[..=eq.unwrap()]);
+        } else {
+            ret.extend_from_slice(spec);
+        }
+        if end < rest.len() {
+            ret.push(b';');
+            rest = &rest[end + 1..];
+        } else {
+            rest = &rest[end..];
+        }
+    }
+    ret
+}
+
+/// The canonicalized signature header field itself, which is hashed last and without its
+/// terminating CRLF.
+fn canonicalize_signature_field(field: &[u8], canonicalization: Canonicalization) -> Vec<u8> {
+    let field = field
+        .iter()
+        .rposition(|&b| b != b'\r' && b != b'\n')
+        .map(|end| &field[..=end])
+        .unwrap_or(field);
+    let mut ret = canonicalize_header(&remove_signature_value(field), canonicalization);
+    if ret.ends_with(b"\r\n") {
+        ret.truncate(ret.len() - 2);
+    }
+    ret
+}
+
+/// Hash of the header fields signed by `signature`, which is in `fields[signature_idx]`.
+fn header_hash(fields: &[&[u8]], signature_idx: usize, signature: &Signature) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut used = vec![false; fields.len()];
+    used[signature_idx] = true;
+    /* Multiple instances of a header are signed from the bottom up. */
+    for name in &signature.headers {
+        if let Some(i) = (0..fields.len())
+            .rev()
+            .find(|&i| !used[i] && field_name(fields[i]).eq_ignore_ascii_case(name.as_bytes()))
+        {
+            used[i] = true;
+            data.extend(canonicalize_header(
+                fields[i],
+                signature.header_canonicalization,
+            ));
+        }
+    }
+    data.extend(canonicalize_signature_field(
+        fields[signature_idx],
+        signature.header_canonicalization,
+    ));
+    sha256(&data)
+}
+
+fn body_hash_matches(body: &[u8], signature: &Signature) -> bool {
+    let mut body = canonicalize_body(body, signature.body_canonicalization);
+    if let Some(length) = signature.body_length {
+        if length > body.len() {
+            return false;
+        }
+        body.truncate(length);
+    }
+    sha256(&body) == signature.body_hash
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(data).to_vec()
+}
+
+/// Retrieves the public key of `signature` and checks it against `hash`.
+fn verify_hash(
+    signature: &Signature,
+    hash: &[u8],
+    resolver: &dyn KeyResolver,
+) -> VerificationResult {
+    let name = format!("{}._domainkey.{}", signature.selector, signature.domain);
+    let records = match resolver.txt_records(&name) {
+        Ok(records) => records,
+        Err(err) => return VerificationResult::TempError(err.to_string()),
+    };
+    let tags = match records
+        .iter()
+        .filter_map(|r| tag_list(r).ok())
+        .find(|tags| tags.iter().all(|(n, v)| *n != "v" || *v == "DKIM1"))
+    {
+        Some(tags) => tags,
+        None => return VerificationResult::PermError(format!("no key found at {}", name)),
+    };
+    let tag = |name: &str| tags.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+    let key = match tag("p").map(decode_base64) {
+        None => return VerificationResult::PermError(format!("no key found at {}", name)),
+        Some(Ok(ref key)) if key.is_empty() => {
+            return VerificationResult::PermError("key is revoked".to_string())
+        }
+        Some(Ok(key)) => key,
+        Some(Err(err)) => return VerificationResult::PermError(err.to_string()),
+    };
+    match (tag("k").unwrap_or("rsa"), signature.algorithm) {
+        ("rsa", Algorithm::RsaSha256) => {
+            use rsa::{Hash, PaddingScheme, PublicKey, PublicKeyParts, RSAPublicKey};
+            let key =
+                match RSAPublicKey::from_pkcs8(&key).or_else(|_| RSAPublicKey::from_pkcs1(&key)) {
+                    Ok(key) => key,
+                    Err(err) => {
+                        return VerificationResult::PermError(format!("invalid RSA key: {}", err))
+                    }
+                };
+            /* RFC 8301: keys shorter than 1024 bits must not be considered valid. */
+            if key.n().bits() < 1024 {
+                return VerificationResult::PermError("RSA key is too short".to_string());
+            }
+            match key.verify(
+                PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256)),
+                hash,
+                &signature.signature,
+            ) {
+                Ok(()) => VerificationResult::Pass,
+                Err(_) => VerificationResult::Fail("signature does not match".to_string()),
+            }
+        }
+        ("ed25519", Algorithm::Ed25519Sha256) => {
+            use ed25519_dalek::Verifier;
+            use std::convert::TryFrom;
+            let key = match ed25519_dalek::PublicKey::from_bytes(&key) {
+                Ok(key) => key,
+                Err(err) => {
+                    return VerificationResult::PermError(format!("invalid Ed25519 key: {}", err))
+                }
+            };
+            let ed25519_signature =
+                match ed25519_dalek::Signature::try_from(signature.signature.as_slice()) {
+                    Ok(s) => s,
+                    Err(_) => {
+                        return VerificationResult::Fail("signature does not match".to_string())
+                    }
+                };
+            match key.verify(hash, &ed25519_signature) {
+                Ok(()) => VerificationResult::Pass,
+                Err(_) => VerificationResult::Fail("signature does not match".to_string()),
+            }
+        }
+        (k, _) => VerificationResult::PermError(format!(
+            "key type {} does not match the signature algorithm",
+            k
+        )),
+    }
+}
+
+fn is_expired(signature: &Signature) -> bool {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    signature.expiration.map(|x| x < now).unwrap_or(false)
+}
+
+/// Verifies a `DKIM-Signature` or `ARC-Message-Signature` in `fields[idx]`.
+fn verify_message_signature(
+    fields: &[&[u8]],
+    body: &[u8],
+    idx: usize,
+    signature: &Signature,
+    resolver: &dyn KeyResolver,
+) -> VerificationResult {
+    if is_expired(signature) {
+        return VerificationResult::PermError("signature has expired".to_string());
+    }
+    if !body_hash_matches(body, signature) {
+        return VerificationResult::Fail("body hash does not match".to_string());
+    }
+    verify_hash(signature, &header_hash(fields, idx, signature), resolver)
+}
+
+/// Verify every `DKIM-Signature` of the message `bytes`.
+pub fn verify(bytes: &[u8], resolver: &dyn KeyResolver) -> Vec<SignatureVerification> {
+    let bytes = to_crlf(bytes);
+    let (fields, body) = split_message(&bytes);
+    let mut ret = vec![];
+    for (idx, field) in fields.iter().enumerate() {
+        if !field_name(field).eq_ignore_ascii_case(b"DKIM-Signature") {
+            continue;
+        }
+        let value = String::from_utf8_lossy(field_value(field));
+        match Signature::parse(SignatureKind::Dkim, &value) {
+            Ok(signature) => ret.push(SignatureVerification {
+                result: verify_message_signature(&fields, body, idx, &signature, resolver),
+                domain: signature.domain,
+                selector: signature.selector,
+            }),
+            Err(err) => {
+                let tags = tag_list(&value).unwrap_or_default();
+                let tag = |name: &str| {
+                    tags.iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, v)| v.to_string())
+                        .unwrap_or_default()
+                };
+                ret.push(SignatureVerification {
+                    domain: tag("d"),
+                    selector: tag("s"),
+                    result: VerificationResult::PermError(err.to_string()),
+                });
+            }
+        }
+    }
+    ret
+}
+
+/// Field indices of the `ARC-Authentication-Results`, `ARC-Message-Signature` and `ARC-Seal`
+/// headers of an ARC instance.
+type ArcSet = (Vec<usize>, Vec<(usize, Signature)>, Vec<(usize, Signature)>);
+
+/// Validate the ARC chain of the message `bytes`. Returns `None` if the message has no ARC
+/// headers.
+pub fn verify_arc(bytes: &[u8], resolver: &dyn KeyResolver) -> Option<VerificationResult> {
+    let bytes = to_crlf(bytes);
+    let (fields, body) = split_message(&bytes);
+    let mut sets: Vec<ArcSet> = vec![];
+    fn set(sets: &mut Vec<ArcSet>, instance: u32) -> usize {
+        while sets.len() < instance as usize {
+            sets.push((vec![], vec![], vec![]));
+        }
+        instance as usize - 1
+    }
+    for (idx, field) in fields.iter().enumerate() {
+        let name = field_name(field);
+        let value = String::from_utf8_lossy(field_value(field));
+        let kind = if name.eq_ignore_ascii_case(b"ARC-Seal") {
+            SignatureKind::ArcSeal
+        } else if name.eq_ignore_ascii_case(b"ARC-Message-Signature") {
+            SignatureKind::ArcMessageSignature
+        } else if name.eq_ignore_ascii_case(b"ARC-Authentication-Results") {
+            match authentication_results::arc_instance(&value) {
+                Some(i) if i >= 1 && i <= ARC_MAX_INSTANCE => {
+                    let i = set(&mut sets, i);
+                    sets[i].0.push(idx);
+                    continue;
+                }
+                _ => {
+                    return Some(VerificationResult::Fail(
+                        "invalid ARC-Authentication-Results".to_string(),
+                    ))
+                }
+            }
+        } else {
+            continue;
+        };
+        match Signature::parse(kind, &value) {
+            Ok(signature) => {
+                let i = set(&mut sets, signature.instance.unwrap_or_default());
+                if kind == SignatureKind::ArcSeal {
+                    sets[i].2.push((idx, signature));
+                } else {
+                    sets[i].1.push((idx, signature));
+                }
+            }
+            Err(err) => return Some(VerificationResult::Fail(err.to_string())),
+        }
+    }
+    if sets.is_empty() {
+        return None;
+    }
+    if sets
+        .iter()
+        .any(|(aar, ams, seal)| aar.len() != 1 || ams.len() != 1 || seal.len() != 1)
+    {
+        return Some(VerificationResult::Fail(
+            "ARC sets are incomplete".to_string(),
+        ));
+    }
+    for (i, (_, _, seal)) in sets.iter().enumerate() {
+        let expected = if i == 0 { "none" } else { "pass" };
+        if seal[0].1.chain_validation.as_ref().map(String::as_str) != Some(expected) {
+            return Some(VerificationResult::Fail(format!(
+                "ARC-Seal {} has cv={}",
+                i + 1,
+                seal[0].1.chain_validation.as_ref().unwrap()
+            )));
+        }
+    }
+    /* Only the latest message signature has to be valid. */
+    let (ams_idx, ref ams) = sets.last().unwrap().1[0];
+    match verify_message_signature(&fields, body, ams_idx, ams, resolver) {
+        VerificationResult::Pass => {}
+        other => return Some(other),
+    }
+    /* Each seal signs all ARC sets up to its own. */
+    for n in (0..sets.len()).rev() {
+        let (seal_idx, ref seal) = sets[n].2[0];
+        if is_expired(seal) {
+            return Some(VerificationResult::Fail(format!(
+                "ARC-Seal {} has expired",
+                n + 1
+            )));
+        }
+        let mut data = vec![];
+        for (i, (aar, ams, seal)) in sets[..=n].iter().enumerate() {
+            data.extend(canonicalize_header(
+                fields[aar[0]],
+                Canonicalization::Relaxed,
+            ));
+            data.extend(canonicalize_header(
+                fields[ams[0].0],
+                Canonicalization::Relaxed,
+            ));
+            if i < n {
+                data.extend(canonicalize_header(
+                    fields[seal[0].0],
+                    Canonicalization::Relaxed,
+                ));
+            }
+        }
+        data.extend(canonicalize_signature_field(
+            fields[seal_idx],
+            Canonicalization::Relaxed,
+        ));
+        match verify_hash(seal, &sha256(&data), resolver) {
+            VerificationResult::Pass => {}
+            VerificationResult::Fail(reason) => {
+                return Some(VerificationResult::Fail(format!(
+                    "ARC-Seal {}: {}",
+                    n + 1,
+                    reason
+                )))
+            }
+            other => return Some(other),
+        }
+    }
+    Some(VerificationResult::Pass)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dkim_canonicalization() {
+        let field = b"Subject :  A  test\r\n\tof\t canonicalization  \r\n";
+        assert_eq!(
+            canonicalize_header(field, Canonicalization::Relaxed),
+            b"subject:A test of canonicalization\r\n".to_vec()
+        );
+        assert_eq!(
+            canonicalize_header(field, Canonicalization::Simple),
+            field.to_vec()
+        );
+        let body = b" C \r\nD \t E\r\n\r\n\r\n";
+        assert_eq!(
+            canonicalize_body(body, Canonicalization::Relaxed),
+            b" C\r\nD E\r\n".to_vec()
+        );
+        assert_eq!(
+            canonicalize_body(body, Canonicalization::Simple),
+            b" C \r\nD \t E\r\n".to_vec()
+        );
+        assert_eq!(
+            BASE64.encode(&sha256(&canonicalize_body(b"", Canonicalization::Simple))),
+            "frcCV1k9oG9oKj3dpUqdJg1PxRT2RSN/XKdLCPjaYaY="
+        );
+        assert_eq!(
+            BASE64.encode(&sha256(&canonicalize_body(
+                b"\r\n",
+                Canonicalization::Relaxed
+            ))),
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+        assert_eq!(
+            canonicalize_signature_field(
+                b"DKIM-Signature: v=1; b=abc\r\n\tdef; bh=xyz;\r\n",
+                Canonicalization::Relaxed
+            ),
+            b"dkim-signature:v=1; b=; bh=xyz;".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_dkim_verify() {
+        use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+        let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        let keypair = Keypair { secret, public };
+        let mut keys = StaticKeys::default();
+        keys.insert(
+            "brisbane._domainkey.football.example.com",
+            &format!("v=DKIM1; k=ed25519; p={}", BASE64.encode(public.as_bytes())),
+        );
+
+        let body = "Hi.\n\nWe lost the game.  Are you hungry yet?\n\nJoe.\n";
+        let headers = "From: Joe SixPack <joe@football.example.com>\nTo: Suzie Q <suzie@shopping.example.net>\nSubject: Is dinner ready?\nDate: Fri, 11 Jul 2003 21:00:37 -0700 (PDT)\n";
+        let body_hash = BASE64.encode(&sha256(&canonicalize_body(
+            &to_crlf(body.as_bytes()),
+            Canonicalization::Relaxed,
+        )));
+        let unsigned = format!(
+            "DKIM-Signature: v=1; a=ed25519-sha256; c=relaxed/relaxed;\n d=football.example.com; s=brisbane; h=from:to:subject:date;\n bh={}; b=",
+            body_hash
+        );
+        let message = format!("{}\n{}\n{}", unsigned, headers, body);
+        let crlf = to_crlf(message.as_bytes());
+        let (fields, _) = split_message(&crlf);
+        let signature = Signature::parse(
+            SignatureKind::Dkim,
+            &String::from_utf8_lossy(field_value(fields[0])),
+        )
+        .unwrap();
+        let hash = header_hash(&fields, 0, &signature);
+        let b = BASE64.encode(&keypair.sign(&hash).to_bytes());
+
+        let message = format!("{}{}\n{}\n{}", unsigned, b, headers, body);
+        let results = verify(message.as_bytes(), &keys);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].domain, "football.example.com");
+        assert_eq!(results[0].result, VerificationResult::Pass);
+
+        let tampered = message.replace("Is dinner ready?", "Is lunch ready?");
+        assert_eq!(
+            verify(tampered.as_bytes(), &keys)[0].result,
+            VerificationResult::Fail("signature does not match".to_string())
+        );
+        let tampered = message.replace("hungry", "thirsty");
+        assert_eq!(
+            verify(tampered.as_bytes(), &keys)[0].result,
+            VerificationResult::Fail("body hash does not match".to_string())
+        );
+        assert_eq!(
+            verify(message.as_bytes(), &StaticKeys::default())[0].result,
+            VerificationResult::PermError(
+                "no key found at brisbane._domainkey.football.example.com".to_string()
+            )
+        );
+        assert_eq!(verify_arc(message.as_bytes(), &keys), None);
+
+        /* Seal the signed message with a single ARC set. */
+        let aar = "ARC-Authentication-Results: i=1; mx.shopping.example.net; dkim=pass header.d=football.example.com";
+        let ams = format!(
+            "ARC-Message-Signature: i=1; a=ed25519-sha256; c=relaxed/relaxed;\n d=football.example.com; s=brisbane; h=from:to:subject;\n bh={}; b=",
+            body_hash
+        );
+        let crlf = to_crlf(format!("{}\n{}\n{}", ams, aar, message).as_bytes());
+        let (fields, _) = split_message(&crlf);
+        let signature = Signature::parse(
+            SignatureKind::ArcMessageSignature,
+            &String::from_utf8_lossy(field_value(fields[0])),
+        )
+        .unwrap();
+        let ams = format!(
+            "{}{}",
+            ams,
+            BASE64.encode(
+                &keypair
+                    .sign(&header_hash(&fields, 0, &signature))
+                    .to_bytes()
+            )
+        );
+        let seal =
+            "ARC-Seal: i=1; a=ed25519-sha256; cv=none; d=football.example.com; s=brisbane; b=";
+        let mut data = canonicalize_header(
+            &to_crlf(format!("{}\n", aar).as_bytes()),
+            Canonicalization::Relaxed,
+        );
+        data.extend(canonicalize_header(
+            &to_crlf(format!("{}\n", ams).as_bytes()),
+            Canonicalization::Relaxed,
+        ));
+        data.extend(canonicalize_signature_field(
+            &to_crlf(format!("{}\n", seal).as_bytes()),
+            Canonicalization::Relaxed,
+        ));
+        let seal = format!(
+            "{}{}",
+            seal,
+            BASE64.encode(&keypair.sign(&sha256(&data)).to_bytes())
+        );
+        let sealed = format!("{}\n{}\n{}\n{}", seal, ams, aar, message);
+        assert_eq!(
+            verify_arc(sealed.as_bytes(), &keys),
+            Some(VerificationResult::Pass)
+        );
+        let tampered = sealed.replace("dkim=pass", "dkim=fail");
+        assert_eq!(
+            verify_arc(tampered.as_bytes(), &keys),
+            Some(VerificationResult::Fail(
+                "ARC-Seal 1: signature does not match".to_string()
+            ))
+        );
+        let incomplete = format!("{}\n{}", ams, message);
+        assert_eq!(
+            verify_arc(incomplete.as_bytes(), &keys),
+            Some(VerificationResult::Fail(
+                "ARC sets are incomplete".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ * meli - email module
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Parsing of `Authentication-Results` ([RFC 8601](https://tools.ietf.org/html/rfc8601)) and
+ * `ARC-Authentication-Results` headers.
+ *
+ * These headers are added by the receiving servers and can be forged by anyone before them, so
+ * they should only be trusted if their `authserv_id` is the user's own server.
+ */
+
+/// The results of an `Authentication-Results` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticationResults {
+    /// The server that performed the checks.
+    pub authserv_id: String,
+    pub results: Vec<MethodResult>,
+}
+
+/// A result like `dkim=pass reason="..." header.d=example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodResult {
+    /// The authentication method, eg. `dkim`, `spf`, `dmarc` or `arc`.
+    pub method: String,
+    /// The result, eg. `pass`, `fail`, `softfail`, `neutral` or `none`.
+    pub result: String,
+    pub reason: Option<String>,
+    /// Properties like `("header.d", "example.com")` or `("smtp.mailfrom", "example.com")`.
+    pub properties: Vec<(String, String)>,
+}
+
+impl MethodResult {
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Removes comments and splits `value` into statements separated by `;`, keeping quoted strings
+/// as they are.
+fn statements(value: &str) -> Vec<String> {
+    let mut ret = vec![String::new()];
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut comment_depth = 0;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+            if comment_depth == 0 {
+                ret.last_mut().unwrap().push(c);
+            }
+        } else if c == '\\' && (quoted || comment_depth > 0) {
+            escaped = true;
+            if comment_depth == 0 {
+                ret.last_mut().unwrap().push(c);
+            }
+        } else if quoted {
+            quoted = c != '"';
+            ret.last_mut().unwrap().push(c);
+        } else if c == '(' {
+            comment_depth += 1;
+        } else if c == ')' && comment_depth > 0 {
+            comment_depth -= 1;
+        } else if comment_depth > 0 {
+        } else if c == ';' {
+            ret.push(String::new());
+        } else {
+            quoted = c == '"';
+            ret.last_mut().unwrap().push(c);
+        }
+    }
+    ret
+}
+
+/// Splits a statement into unquoted words, with each `=` as a separate word.
+fn words(statement: &str) -> Vec<String> {
+    let mut ret = vec![];
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in statement.chars() {
+        if escaped {
+            escaped = false;
+            word.push(c);
+        } else if quoted {
+            match c {
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                c => word.push(c),
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else if c.is_whitespace() || c == '=' {
+            if !word.is_empty() {
+                ret.push(std::mem::replace(&mut word, String::new()));
+            }
+            if c == '=' {
+                ret.push("=".to_string());
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        ret.push(word);
+    }
+    ret
+}
+
+/// Returns the `i=` instance number that begins an `ARC-Authentication-Results` value.
+pub fn arc_instance(value: &str) -> Option<u32> {
+    let first = statements(value).into_iter().next()?;
+    let first = first.trim();
+    if first.starts_with("i=") {
+        first[2..].trim().parse().ok()
+    } else {
+        None
+    }
+}
+
+impl AuthenticationResults {
+    /// Parse the value of an `Authentication-Results` or `ARC-Authentication-Results` header.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut statements = statements(value).into_iter();
+        let mut first = statements.next()?;
+        if arc_instance(value).is_some() {
+            first = statements.next()?;
+        }
+        let authserv_id = first.split_whitespace().next()?.to_string();
+        let mut results = vec![];
+        for statement in statements {
+            let words = words(&statement);
+            let mut pairs = vec![];
+            let mut i = 0;
+            while i + 2 < words.len() {
+                if words[i + 1] == "=" && words[i] != "=" && words[i + 2] != "=" {
+                    pairs.push((words[i].clone(), words[i + 2].clone()));
+                    i += 3;
+                } else {
+                    i += 1;
+                }
+            }
+            let mut pairs = pairs.into_iter();
+            let (method, result) = match pairs.next() {
+                Some((method, result)) => (method, result),
+                /* `none` means no checks were performed. */
+                None => continue,
+            };
+            let mut reason = None;
+            let mut properties = vec![];
+            for (name, value) in pairs {
+                if name.eq_ignore_ascii_case("reason") {
+                    reason = Some(value);
+                } else {
+                    properties.push((name, value));
+                }
+            }
+            results.push(MethodResult {
+                /* Strip the method version, eg. `dkim/1`. */
+                method: method
+                    .split('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase(),
+                result: result.to_ascii_lowercase(),
+                reason,
+                properties,
+            });
+        }
+        Some(AuthenticationResults {
+            authserv_id,
+            results,
+        })
+    }
+
+    /// The first result of `method`.
+    pub fn get(&self, method: &str) -> Option<&MethodResult> {
+        self.results
+            .iter()
+            .find(|r| r.method.eq_ignore_ascii_case(method))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authentication_results() {
+        let results = AuthenticationResults::parse(
+            "mx.example.org (version 1.2);\r\n dkim=pass (2048-bit key; secure) header.d=example.com header.s=sel header.b=\"AbC/+12=\";\r\n spf=softfail (domain of transitioning sender) smtp.mailfrom=example.com;\r\n dmarc = fail reason=\"policy; quarantine\" header.from=example.com",
+        )
+        .unwrap();
+        assert_eq!(results.authserv_id, "mx.example.org");
+        assert_eq!(results.results.len(), 3);
+        let dkim = results.get("dkim").unwrap();
+        assert_eq!(dkim.result, "pass");
+        assert_eq!(dkim.property("header.d"), Some("example.com"));
+        assert_eq!(dkim.property("header.b"), Some("AbC/+12="));
+        assert_eq!(results.get("spf").unwrap().result, "softfail");
+        let dmarc = results.get("dmarc").unwrap();
+        assert_eq!(dmarc.result, "fail");
+        assert_eq!(
+            dmarc.reason.as_ref().map(String::as_str),
+            Some("policy; quarantine")
+        );
+
+        let results =
+            AuthenticationResults::parse("i=2; mx.example.net; arc=pass; spf=none").unwrap();
+        assert_eq!(results.authserv_id, "mx.example.net");
+        assert_eq!(results.get("arc").unwrap().result, "pass");
+        assert_eq!(arc_instance("i=2; mx.example.net; arc=pass"), Some(2));
+
+        let results = AuthenticationResults::parse("example.org 1; none").unwrap();
+        assert!(results.results.is_empty());
+    }
+}
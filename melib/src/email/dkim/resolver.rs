@@ -0,0 +1,301 @@
+/*
+ * meli - email module
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Retrieval of DKIM public key records.
+
+use crate::error::{ErrorKind, MeliError, Result};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+
+const TYPE_TXT: u16 = 16;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u8 = 3;
+
+/// Looks up the TXT records that hold DKIM public keys.
+pub trait KeyResolver: Send + Sync + std::fmt::Debug {
+    /// Returns the TXT records of `name`, or an empty vector if it doesn't exist.
+    fn txt_records(&self, name: &str) -> Result<Vec<String>>;
+}
+
+/// Queries name servers over UDP, falling back to TCP for truncated responses.
+#[derive(Debug, Clone)]
+pub struct DnsResolver {
+    pub nameservers: Vec<SocketAddr>,
+    pub timeout: Duration,
+}
+
+impl Default for DnsResolver {
+    /// Uses the name servers of `/etc/resolv.conf`.
+    fn default() -> Self {
+        let mut nameservers: Vec<SocketAddr> = std::fs::read_to_string("/etc/resolv.conf")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| {
+                let mut words = l.split_whitespace();
+                if words.next() != Some("nameserver") {
+                    return None;
+                }
+                /* Strip the zone index of IPv6 link-local addresses. */
+                let addr = words.next()?.split('%').next()?;
+                addr.parse::<IpAddr>()
+                    .ok()
+                    .map(|ip| SocketAddr::new(ip, 53))
+            })
+            .collect();
+        if nameservers.is_empty() {
+            nameservers.push(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53));
+        }
+        DnsResolver {
+            nameservers,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl DnsResolver {
+    pub fn new(nameservers: Vec<SocketAddr>) -> Self {
+        DnsResolver {
+            nameservers,
+            ..Default::default()
+        }
+    }
+
+    fn query_udp(&self, nameserver: SocketAddr, query: &[u8]) -> std::io::Result<Vec<u8>> {
+        let local: SocketAddr = if nameserver.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.set_read_timeout(Some(self.timeout))?;
+        socket.connect(nameserver)?;
+        socket.send(query)?;
+        let mut buf = vec![0; 4096];
+        loop {
+            let len = socket.recv(&mut buf)?;
+            /* Ignore stray datagrams with a different query id. */
+            if len >= 2 && buf[..2] == query[..2] {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+        }
+    }
+
+    fn query_tcp(&self, nameserver: SocketAddr, query: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut stream = TcpStream::connect_timeout(&nameserver, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let mut msg = (query.len() as u16).to_be_bytes().to_vec();
+        msg.extend_from_slice(query);
+        stream.write_all(&msg)?;
+        let mut len = [0; 2];
+        stream.read_exact(&mut len)?;
+        let mut buf = vec![0; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl KeyResolver for DnsResolver {
+    fn txt_records(&self, name: &str) -> Result<Vec<String>> {
+        let id = {
+            let uuid = uuid::Uuid::new_v4();
+            u16::from_be_bytes([uuid.as_bytes()[0], uuid.as_bytes()[1]])
+        };
+        let query = txt_query(id, name)?;
+        let mut last_err = MeliError::new("No name servers configured");
+        for nameserver in &self.nameservers {
+            let response = match self.query_udp(*nameserver, &query) {
+                Ok(response) if is_truncated(&response) => self.query_tcp(*nameserver, &query),
+                other => other,
+            };
+            match response {
+                Ok(response) => return parse_txt_response(id, &response),
+                Err(err) => {
+                    last_err = MeliError::new(format!(
+                        "Could not query name server {}: {}",
+                        nameserver, err
+                    ))
+                    .set_kind(
+                        if err.kind() == std::io::ErrorKind::WouldBlock
+                            || err.kind() == std::io::ErrorKind::TimedOut
+                        {
+                            ErrorKind::Timeout
+                        } else {
+                            ErrorKind::Network
+                        },
+                    );
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+/// Builds a recursive query for the TXT records of `name`.
+fn txt_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut ret = id.to_be_bytes().to_vec();
+    /* Flags with RD set, one question and one additional (EDNS) record. */
+    ret.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 1]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 || !label.is_ascii() {
+            return Err(MeliError::new(format!("Invalid domain name {}", name)));
+        }
+        ret.push(label.len() as u8);
+        ret.extend_from_slice(label.as_bytes());
+    }
+    ret.push(0);
+    ret.extend_from_slice(&TYPE_TXT.to_be_bytes());
+    ret.extend_from_slice(&CLASS_IN.to_be_bytes());
+    /* EDNS OPT pseudo-record advertising a 4096 byte UDP payload, so that large keys fit. */
+    ret.extend_from_slice(&[0, 0, 41, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+    Ok(ret)
+}
+
+fn is_truncated(response: &[u8]) -> bool {
+    response.len() > 2 && response[2] & 0x02 != 0
+}
+
+/// Returns the position after the (possibly compressed) domain name at `pos`.
+fn skip_name(response: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *response.get(pos)?;
+        if len & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        } else if len == 0 {
+            return Some(pos + 1);
+        }
+        pos += 1 + len as usize;
+    }
+}
+
+fn parse_txt_response(id: u16, response: &[u8]) -> Result<Vec<String>> {
+    let malformed = || MeliError::new("Malformed DNS response").set_kind(ErrorKind::Network);
+    if response.len() < 12 || response[..2] != id.to_be_bytes() {
+        return Err(malformed());
+    }
+    match response[3] & 0x0f {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(vec![]),
+        rcode => {
+            return Err(
+                MeliError::new(format!("DNS query failed with response code {}", rcode))
+                    .set_kind(ErrorKind::Network),
+            )
+        }
+    }
+    let count = |pos: usize| u16::from_be_bytes([response[pos], response[pos + 1]]);
+    let (questions, answers) = (count(4), count(6));
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(response, pos).ok_or_else(malformed)? + 4;
+    }
+    let mut ret = vec![];
+    for _ in 0..answers {
+        pos = skip_name(response, pos).ok_or_else(malformed)?;
+        if pos + 10 > response.len() {
+            return Err(malformed());
+        }
+        let (rtype, rdlength) = (count(pos), count(pos + 8) as usize);
+        pos += 10;
+        let rdata = response.get(pos..pos + rdlength).ok_or_else(malformed)?;
+        pos += rdlength;
+        /* Answers to CNAME queries also contain the records of the target. */
+        if rtype != TYPE_TXT {
+            continue;
+        }
+        /* A TXT record is a sequence of length-prefixed strings, that are concatenated. */
+        let mut record = Vec::with_capacity(rdlength);
+        let mut i = 0;
+        while i < rdata.len() {
+            let len = rdata[i] as usize;
+            record.extend_from_slice(rdata.get(i + 1..i + 1 + len).ok_or_else(malformed)?);
+            i += 1 + len;
+        }
+        ret.push(String::from_utf8_lossy(&record).into_owned());
+    }
+    Ok(ret)
+}
+
+/// Key records read from a file instead of DNS.
+///
+/// Each line of the file holds a record name followed by its value, in plain text or as quoted
+/// strings like in DNS zone files. Empty lines and lines starting with `#` are ignored:
+///
+/// ```text
+/// brisbane._domainkey.example.com v=DKIM1; k=ed25519; p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=
+/// selector._domainkey.example.org "v=DKIM1; k=rsa; " "p=MIIBIjANBgkqhkiG9w0BAQEFAAOC..."
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StaticKeys {
+    records: HashMap<String, Vec<String>>,
+}
+
+impl StaticKeys {
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| MeliError::new(format!("Could not read {}: {}", path.display(), err)))?;
+        Ok(Self::parse(&contents))
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut ret = StaticKeys::default();
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let split = line.find(char::is_whitespace).unwrap_or_else(|| line.len());
+            let (name, value) = (&line[..split], line[split..].trim());
+            if value.starts_with('"') {
+                let record: String = value
+                    .split('"')
+                    .enumerate()
+                    .filter(|(i, _)| i % 2 == 1)
+                    .map(|(_, s)| s)
+                    .collect();
+                ret.insert(name, &record);
+            } else {
+                ret.insert(name, value);
+            }
+        }
+        ret
+    }
+
+    pub fn insert(&mut self, name: &str, record: &str) {
+        self.records
+            .entry(name.trim_end_matches('.').to_ascii_lowercase())
+            .or_default()
+            .push(record.to_string());
+    }
+}
+
+impl KeyResolver for StaticKeys {
+    fn txt_records(&self, name: &str) -> Result<Vec<String>> {
+        Ok(self
+            .records
+            .get(&name.trim_end_matches('.').to_ascii_lowercase())
+            .cloned()
+            .unwrap_or_default())
+    }
+}
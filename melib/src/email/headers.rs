@@ -191,6 +191,12 @@ impl<S: AsRef<[u8]>> HeaderNameType<S> {
             "Authentication-Results"
         } else if self == &b"dkim-signature"[..] {
             "DKIM-Signature"
+        } else if self == &b"arc-seal"[..] {
+            "ARC-Seal"
+        } else if self == &b"arc-message-signature"[..] {
+            "ARC-Message-Signature"
+        } else if self == &b"arc-authentication-results"[..] {
+            "ARC-Authentication-Results"
         } else if self == &b"delivered-to"[..] {
             "Delivered-To"
         } else if self == &b"message-id"[..] {
@@ -1472,7 +1472,7 @@ pub mod attachments {
     /*! Email attachments */
     use super::*;
     use crate::email::address::*;
-    use crate::email::attachment_types::{ContentDisposition, ContentDispositionKind};
+    use crate::email::attachment_types::{Charset, ContentDisposition, ContentDispositionKind};
     pub fn attachment(input: &[u8]) -> IResult<&[u8], (std::vec::Vec<(&[u8], &[u8])>, &[u8])> {
         alt((
             separated_pair(
@@ -1695,6 +1695,109 @@ pub mod attachments {
         Ok((input, (name, value)))
     }
 
+    /// Collapse [RFC2231](https://tools.ietf.org/html/rfc2231) parameter continuations
+    /// (`name*0=...; name*1=...`) and decode extended values (`name*=charset'language'value`,
+    /// percent-encoded). Other parameters are returned as they are, in order of appearance.
+    pub fn rfc2231_parameters(parameters: &[(&[u8], &[u8])]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        /* (name, is continued, sections as (section number, is extended, value)) */
+        let mut params: Vec<(&[u8], bool, Vec<(usize, bool, &[u8])>)> =
+            Vec::with_capacity(parameters.len());
+        for &(name, value) in parameters {
+            let name = name.trim();
+            let (name, extended) = if name.ends_with(b"*") {
+                (&name[..name.len() - 1], true)
+            } else {
+                (name, false)
+            };
+            let section = name.iter().rposition(|&b| b == b'*').and_then(|pos| {
+                std::str::from_utf8(&name[pos + 1..])
+                    .ok()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .map(|n| (pos, n))
+            });
+            match section {
+                Some((pos, section)) => {
+                    let name = &name[..pos];
+                    if let Some(entry) = params
+                        .iter_mut()
+                        .find(|(n, continued, _)| *continued && n.eq_ignore_ascii_case(name))
+                    {
+                        entry.2.push((section, extended, value));
+                    } else {
+                        params.push((name, true, vec![(section, extended, value)]));
+                    }
+                }
+                None => params.push((name, false, vec![(0, extended, value)])),
+            }
+        }
+        params
+            .into_iter()
+            .map(|(name, _, mut sections)| {
+                sections.sort_by_key(|s| s.0);
+                let mut charset = None;
+                let mut bytes = Vec::new();
+                for (i, &(_, extended, value)) in sections.iter().enumerate() {
+                    if !extended {
+                        bytes.extend_from_slice(value);
+                        continue;
+                    }
+                    let mut value = value.trim();
+                    if i == 0 {
+                        let mut fields = value.splitn(3, |&b| b == b'\'');
+                        if let (Some(c), Some(_language), Some(v)) =
+                            (fields.next(), fields.next(), fields.next())
+                        {
+                            if !c.is_empty() {
+                                charset = Some(Charset::from(c));
+                            }
+                            value = v;
+                        }
+                    }
+                    let mut i = 0;
+                    while i < value.len() {
+                        if value[i] == b'%' && i + 2 < value.len() {
+                            if let Some(b) = std::str::from_utf8(&value[i + 1..i + 3])
+                                .ok()
+                                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                            {
+                                bytes.push(b);
+                                i += 3;
+                                continue;
+                            }
+                        }
+                        bytes.push(value[i]);
+                        i += 1;
+                    }
+                }
+                let value = match charset {
+                    Some(charset) => super::encodings::decode_charset(&bytes, charset)
+                        .map(String::into_bytes)
+                        .unwrap_or(bytes),
+                    None => bytes,
+                };
+                (name.to_vec(), value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rfc2231_parameters() {
+        let (_, (_, _, params)) = content_type(
+            b"application/pdf; name*0*=utf-8''%CE%94%CE%B5%CE%BB%CF%84%CE%AF%CE%BF;\r\n name*1=\" 2020.pdf\"; charset=us-ascii",
+        )
+        .unwrap();
+        assert_eq!(
+            rfc2231_parameters(&params),
+            vec![
+                (b"name".to_vec(), "Δελτίο 2020.pdf".as_bytes().to_vec()),
+                (b"charset".to_vec(), b"us-ascii".to_vec()),
+            ]
+        );
+        let (_, disposition) =
+            content_disposition(b"attachment; filename*=iso-8859-1'en'%A3%20rates.txt").unwrap();
+        assert_eq!(disposition.filename.as_deref(), Some("£ rates.txt"));
+    }
+
     pub fn content_disposition(input: &[u8]) -> IResult<&[u8], ContentDisposition> {
         let (input, kind) = alt((take_until(";"), take_while(|_| true)))(input.trim())?;
         let mut ret = ContentDisposition {
@@ -1710,25 +1813,25 @@ pub mod attachments {
             return Ok((input, ret));
         }
         let (input, parameters) = many0(content_disposition_parameter)(input.ltrim())?;
-        for (k, v) in parameters {
+        let phrase = |v: &[u8]| -> String {
+            match super::encodings::phrase(v, false) {
+                Ok((_, v)) => String::from_utf8_lossy(&v).into(),
+                Err(_) => String::from_utf8_lossy(v).into(),
+            }
+        };
+        for (k, v) in rfc2231_parameters(&parameters) {
             if k.eq_ignore_ascii_case(b"filename") {
-                ret.filename =
-                    Some(String::from_utf8_lossy(&super::encodings::phrase(v, false)?.1).into());
+                ret.filename = Some(phrase(&v));
             } else if k.eq_ignore_ascii_case(b"size") {
-                ret.size =
-                    Some(String::from_utf8_lossy(&super::encodings::phrase(v, false)?.1).into());
+                ret.size = Some(phrase(&v));
             } else if k.eq_ignore_ascii_case(b"creation-date") {
-                ret.creation_date =
-                    Some(String::from_utf8_lossy(&super::encodings::phrase(v, false)?.1).into());
+                ret.creation_date = Some(phrase(&v));
             } else if k.eq_ignore_ascii_case(b"modification-date") {
-                ret.modification_date =
-                    Some(String::from_utf8_lossy(&super::encodings::phrase(v, false)?.1).into());
+                ret.modification_date = Some(phrase(&v));
             } else if k.eq_ignore_ascii_case(b"read-date") {
-                ret.read_date =
-                    Some(String::from_utf8_lossy(&super::encodings::phrase(v, false)?.1).into());
+                ret.read_date = Some(phrase(&v));
             } else {
-                ret.parameter
-                    .push(String::from_utf8_lossy(&super::encodings::phrase(v, false)?.1).into());
+                ret.parameter.push(phrase(&v));
             }
         }
         Ok((input, ret))
@@ -1893,8 +1996,14 @@ pub mod encodings {
             Charset::Windows1251 => Ok(WINDOWS_1251.decode(s, DecoderTrap::Strict)?),
             Charset::Windows1252 => Ok(WINDOWS_1252.decode(s, DecoderTrap::Strict)?),
             Charset::Windows1253 => Ok(WINDOWS_1253.decode(s, DecoderTrap::Strict)?),
+            Charset::Windows1254 => Ok(WINDOWS_1254.decode(s, DecoderTrap::Strict)?),
+            Charset::Windows1255 => Ok(WINDOWS_1255.decode(s, DecoderTrap::Strict)?),
+            Charset::Windows1256 => Ok(WINDOWS_1256.decode(s, DecoderTrap::Strict)?),
+            Charset::Windows1257 => Ok(WINDOWS_1257.decode(s, DecoderTrap::Strict)?),
+            Charset::Windows1258 => Ok(WINDOWS_1258.decode(s, DecoderTrap::Strict)?),
             Charset::KOI8R => Ok(KOI8_R.decode(s, DecoderTrap::Strict)?),
-            Charset::KOI8U => Ok(KOI8_U.decode(s, DecoderTrap::Strict)?),
+            /* KOI8-U covers the Belarusian letters of KOI8-RU as well. */
+            Charset::KOI8U | Charset::KOI8RU => Ok(KOI8_U.decode(s, DecoderTrap::Strict)?),
             Charset::BIG5 => Ok(BIG5_2003.decode(s, DecoderTrap::Strict)?),
             Charset::GB2312 => {
                 Ok(encoding::codec::simpchinese::GBK_ENCODING.decode(s, DecoderTrap::Strict)?)
@@ -1904,6 +2013,9 @@ pub mod encodings {
             }
             Charset::ISO2022JP => Ok(ISO_2022_JP.decode(s, DecoderTrap::Strict)?),
             Charset::EUCJP => Ok(EUC_JP.decode(s, DecoderTrap::Strict)?),
+            Charset::ShiftJIS => Ok(WINDOWS_31J.decode(s, DecoderTrap::Strict)?),
+            Charset::EUCKR => Ok(WINDOWS_949.decode(s, DecoderTrap::Strict)?),
+            Charset::GB18030 => Ok(GB18030.decode(s, DecoderTrap::Strict)?),
         }
     }
 
@@ -2619,6 +2731,15 @@ mod tests {
             "=?iso-8859-7?B?0/Th/fHv8iDM4ev03ebv8g==?= <maltezos@central.ntua.gr>"
         );
         assert_parse!("", "user@domain", "user@domain");
+        assert_parse!("", "用户@例子.广告", "用户@例子.广告");
+        assert_parse!("用户", "用户@例子.广告", "用户 <用户@例子.广告>");
+        assert_eq!(
+            address("user@xn--fsqu00a.xn--0zwm56d".as_bytes())
+                .unwrap()
+                .1
+                .to_string(),
+            "user@例子.测试"
+        );
         assert_parse!("", "user@domain", "<user@domain>");
         assert_parse!("", "user@domain", "  <user@domain>");
         assert_parse!("Name", "user@domain", "Name <user@domain>");
@@ -19,7 +19,7 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-/*! Verification of OpenPGP signatures */
+/*! Verification of OpenPGP and S/MIME signatures */
 use crate::email::{
     attachment_types::{ContentType, MultipartType},
     attachments::Attachment,
@@ -136,6 +136,40 @@ pub fn verify_signature(a: &Attachment) -> Result<(Vec<u8>, &Attachment)> {
     }
 }
 
+/// Kind of an `application/pkcs7-mime` entity, taken from its `smime-type` parameter
+/// ([rfc8551](https://tools.ietf.org/html/rfc8551#section-3.2.2)).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SMimeType {
+    EnvelopedData,
+    SignedData,
+    CompressedData,
+    CertsOnly,
+    Other,
+}
+
+/// Returns the `smime-type` of `a` if it is an `application/pkcs7-mime` entity. Missing
+/// `smime-type` parameters default to `enveloped-data`, which is what older clients omit it for.
+pub fn smime_type(a: &Attachment) -> Option<SMimeType> {
+    match a.content_type {
+        ContentType::Other { ref tag, .. }
+            if tag.eq_ignore_ascii_case(b"application/pkcs7-mime")
+                || tag.eq_ignore_ascii_case(b"application/x-pkcs7-mime") => {}
+        _ => return None,
+    }
+    for (n, v) in a.parameters() {
+        if n.eq_ignore_ascii_case(b"smime-type") {
+            return Some(match v.to_ascii_lowercase().as_slice() {
+                b"enveloped-data" | b"authenveloped-data" => SMimeType::EnvelopedData,
+                b"signed-data" => SMimeType::SignedData,
+                b"compressed-data" => SMimeType::CompressedData,
+                b"certs-only" => SMimeType::CertsOnly,
+                _ => SMimeType::Other,
+            });
+        }
+    }
+    Some(SMimeType::EnvelopedData)
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct DecryptionMetadata {
     pub recipients: Vec<Recipient>,
@@ -156,3 +190,56 @@ pub struct SignatureMetadata {
     pub file_name: Option<String>,
     pub is_mime: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email::attachments::{decode, AttachmentBuilder};
+
+    #[test]
+    fn test_smime_verify_signature() {
+        let raw = b"Content-Type: multipart/signed; protocol=\"application/x-pkcs7-signature\"; micalg=sha-256; boundary=\"smime_boundary\"\r\n\
+\r\n\
+This is an S/MIME signed message\r\n\
+--smime_boundary\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Hello\r\n\
+--smime_boundary\r\n\
+Content-Type: application/x-pkcs7-signature; name=\"smime.p7s\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+MIIB\r\n\
+--smime_boundary--\r\n";
+        let a = AttachmentBuilder::new(raw).build();
+        let (data, sig) = verify_signature(&a).unwrap();
+        assert_eq!(
+            data.as_slice(),
+            &b"Content-Type: text/plain; charset=utf-8\r\n\r\nHello\r\n"[..]
+        );
+        assert_eq!(sig.content_type, ContentType::CMSSignature);
+        assert_eq!(decode(sig, None), vec![0x30, 0x82, 0x01]);
+    }
+
+    #[test]
+    fn test_smime_type() {
+        let a = AttachmentBuilder::new(
+            b"Content-Type: application/pkcs7-mime; smime-type=signed-data; name=smime.p7m\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+MIIB\r\n",
+        )
+        .build();
+        assert_eq!(smime_type(&a), Some(SMimeType::SignedData));
+        let a = AttachmentBuilder::new(
+            b"Content-Type: application/x-pkcs7-mime; name=smime.p7m\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+MIIB\r\n",
+        )
+        .build();
+        assert_eq!(smime_type(&a), Some(SMimeType::EnvelopedData));
+        let a = AttachmentBuilder::new(b"Content-Type: text/plain\r\n\r\nHello\r\n").build();
+        assert_eq!(smime_type(&a), None);
+    }
+}
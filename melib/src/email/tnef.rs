@@ -0,0 +1,622 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Decoding of Transport Neutral Encapsulation Format (`application/ms-tnef`, usually named
+ * `winmail.dat`) attachments, as described in
+ * [MS-OXTNEF](https://docs.microsoft.com/en-us/openspecs/exchange_server_protocols/ms-oxtnef/).
+ *
+ * The file attachments and message body of a TNEF stream are expanded into virtual child
+ * `Attachment`s when the TNEF part is built, see `AttachmentBuilder::build`.
+ */
+
+use crate::email::attachment_types::{
+    Charset, ContentDisposition, ContentDispositionKind, ContentType, Text,
+};
+use crate::email::attachments::{Attachment, AttachmentBuilder};
+use crate::error::{MeliError, Result};
+
+pub const TNEF_SIGNATURE: u32 = 0x223E_9F78;
+
+const LVL_MESSAGE: u8 = 0x01;
+const LVL_ATTACHMENT: u8 = 0x02;
+
+/* TNEF attributes, type in the high word and id in the low word. */
+const ATT_BODY: u32 = 0x0002_800C;
+const ATT_ATTACH_DATA: u32 = 0x0006_800F;
+const ATT_ATTACH_TITLE: u32 = 0x0001_8010;
+const ATT_ATTACH_REND_DATA: u32 = 0x0006_9002;
+const ATT_MAPI_PROPS: u32 = 0x0006_9003;
+const ATT_ATTACHMENT: u32 = 0x0006_9005;
+const ATT_OEM_CODEPAGE: u32 = 0x0006_9007;
+
+/* MAPI property ids */
+const PR_BODY: u16 = 0x1000;
+const PR_RTF_COMPRESSED: u16 = 0x1009;
+const PR_BODY_HTML: u16 = 0x1013;
+const PR_ATTACH_DATA_OBJ: u16 = 0x3701;
+const PR_ATTACH_FILENAME: u16 = 0x3704;
+const PR_ATTACH_LONG_FILENAME: u16 = 0x3707;
+const PR_ATTACH_MIME_TAG: u16 = 0x370E;
+
+/* MAPI property types */
+const PT_STRING8: u16 = 0x001E;
+const PT_UNICODE: u16 = 0x001F;
+const PT_BINARY: u16 = 0x0102;
+const PT_OBJECT: u16 = 0x000D;
+const MV_FLAG: u16 = 0x1000;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TnefAttachment {
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tnef {
+    /// Plain text body, in `charset`.
+    pub body: Option<Vec<u8>>,
+    pub html_body: Option<Vec<u8>>,
+    /// Decompressed RTF body.
+    pub rtf_body: Option<Vec<u8>>,
+    pub charset: Charset,
+    pub attachments: Vec<TnefAttachment>,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Reader { buf, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len().saturating_sub(self.pos)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.remaining() < len {
+            return Err(MeliError::new("Unexpected end of TNEF data."));
+        }
+        let ret = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(ret)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Variable length values are padded to a multiple of 4 bytes.
+    fn padded_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let ret = self.bytes(len)?;
+        self.bytes((4 - len % 4) % 4)?;
+        Ok(ret)
+    }
+}
+
+struct MapiProperty<'a> {
+    id: u16,
+    kind: u16,
+    values: Vec<&'a [u8]>,
+}
+
+/// Parse an `attMAPIProps` or `attAttachment` attribute. Parsing stops at the first property of
+/// unknown type, since its length can't be known.
+fn mapi_properties(data: &[u8]) -> Vec<MapiProperty> {
+    let mut ret = Vec::new();
+    let mut r = Reader::new(data);
+    let count = match r.u32() {
+        Ok(c) => c,
+        Err(_) => return ret,
+    };
+    for _ in 0..count {
+        match mapi_property(&mut r) {
+            Ok(Some(p)) => ret.push(p),
+            Ok(None) | Err(_) => break,
+        }
+    }
+    ret
+}
+
+fn mapi_property<'a>(r: &mut Reader<'a>) -> Result<Option<MapiProperty<'a>>> {
+    let kind = r.u16()?;
+    let id = r.u16()?;
+    if id >= 0x8000 {
+        /* Named property: GUID, then a numeric id or a UTF-16 name. */
+        r.bytes(16)?;
+        match r.u32()? {
+            0 => {
+                r.u32()?;
+            }
+            _ => {
+                let len = r.u32()? as usize;
+                r.padded_bytes(len)?;
+            }
+        }
+    }
+    let base = kind & !MV_FLAG;
+    let fixed_size = match base {
+        0x0001 | 0x0002 | 0x0003 | 0x0004 | 0x000A | 0x000B => Some(4),
+        0x0005 | 0x0006 | 0x0007 | 0x0014 | 0x0040 => Some(8),
+        0x0048 => Some(16),
+        PT_STRING8 | PT_UNICODE | PT_BINARY | PT_OBJECT => None,
+        _ => return Ok(None),
+    };
+    let count = if kind & MV_FLAG != 0 || fixed_size.is_none() {
+        r.u32()? as usize
+    } else {
+        1
+    };
+    /* `count` comes from the input, don't trust it for the allocation. */
+    let mut values = Vec::with_capacity(std::cmp::min(count, r.remaining()));
+    for _ in 0..count {
+        values.push(match fixed_size {
+            Some(size) => r.bytes(size)?,
+            None => {
+                let len = r.u32()? as usize;
+                r.padded_bytes(len)?
+            }
+        });
+    }
+    Ok(Some(MapiProperty { id, kind, values }))
+}
+
+/// Decode a `PT_STRING8` or `PT_UNICODE` value.
+fn mapi_string(p: &MapiProperty, charset: Charset) -> Option<String> {
+    let v = p.values.first()?;
+    match p.kind {
+        PT_UNICODE => {
+            let units = v
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&u| u != 0)
+                .collect::<Vec<u16>>();
+            Some(String::from_utf16_lossy(&units))
+        }
+        PT_STRING8 => Some(decode_string8(v, charset)),
+        _ => None,
+    }
+}
+
+fn decode_string8(v: &[u8], charset: Charset) -> String {
+    let v = match v.iter().position(|&b| b == 0) {
+        Some(pos) => &v[..pos],
+        None => v,
+    };
+    crate::email::parser::encodings::decode_charset(v, charset)
+        .unwrap_or_else(|_| String::from_utf8_lossy(v).to_string())
+}
+
+fn codepage_to_charset(codepage: u32) -> Charset {
+    match codepage {
+        1250 => Charset::Windows1250,
+        1251 => Charset::Windows1251,
+        1253 => Charset::Windows1253,
+        1254 => Charset::Windows1254,
+        1255 => Charset::Windows1255,
+        1256 => Charset::Windows1256,
+        1257 => Charset::Windows1257,
+        1258 => Charset::Windows1258,
+        20866 => Charset::KOI8R,
+        21866 => Charset::KOI8U,
+        28591 => Charset::ISO8859_1,
+        28592 => Charset::ISO8859_2,
+        28605 => Charset::ISO8859_15,
+        932 => Charset::ShiftJIS,
+        936 => Charset::GBK,
+        949 => Charset::EUCKR,
+        950 => Charset::BIG5,
+        50220 => Charset::ISO2022JP,
+        51932 => Charset::EUCJP,
+        51949 => Charset::EUCKR,
+        54936 => Charset::GB18030,
+        65001 => Charset::UTF8,
+        _ => Charset::Windows1252,
+    }
+}
+
+impl Tnef {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = Reader::new(data);
+        if r.u32()? != TNEF_SIGNATURE {
+            return Err(MeliError::new("Not a TNEF stream: invalid signature."));
+        }
+        /* Legacy key */
+        r.u16()?;
+        let mut ret = Tnef {
+            charset: Charset::Windows1252,
+            ..Tnef::default()
+        };
+        while !r.is_empty() {
+            let level = r.u8()?;
+            let id = r.u32()?;
+            let len = r.u32()? as usize;
+            let value = r.bytes(len)?;
+            /* Checksum */
+            r.u16()?;
+            match (level, id) {
+                (LVL_MESSAGE, ATT_OEM_CODEPAGE) if value.len() >= 4 => {
+                    ret.charset = codepage_to_charset(u32::from_le_bytes([
+                        value[0], value[1], value[2], value[3],
+                    ]));
+                }
+                (LVL_MESSAGE, ATT_BODY) => {
+                    let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+                    ret.body = Some(value[..end].to_vec());
+                }
+                (LVL_MESSAGE, ATT_MAPI_PROPS) => {
+                    for p in mapi_properties(value) {
+                        match p.id {
+                            PR_BODY if ret.body.is_none() => {
+                                if p.kind == PT_UNICODE {
+                                    ret.body = mapi_string(&p, ret.charset)
+                                        .map(|s| s.into_bytes())
+                                        .filter(|b| !b.is_empty());
+                                    if ret.body.is_some() {
+                                        ret.charset = Charset::UTF8;
+                                    }
+                                } else {
+                                    ret.body = p.values.first().map(|v| {
+                                        let end = v.iter().position(|&b| b == 0).unwrap_or(v.len());
+                                        v[..end].to_vec()
+                                    });
+                                }
+                            }
+                            PR_BODY_HTML => {
+                                ret.html_body = p.values.first().map(|v| v.to_vec());
+                            }
+                            PR_RTF_COMPRESSED => {
+                                ret.rtf_body =
+                                    p.values.first().and_then(|v| decompress_rtf(v).ok());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                (LVL_ATTACHMENT, ATT_ATTACH_REND_DATA) => {
+                    /* Starts a new attachment */
+                    ret.attachments.push(TnefAttachment::default());
+                }
+                (LVL_ATTACHMENT, ATT_ATTACH_TITLE) => {
+                    if let Some(a) = ret.attachments.last_mut() {
+                        if a.filename.is_none() {
+                            a.filename = Some(decode_string8(value, ret.charset));
+                        }
+                    }
+                }
+                (LVL_ATTACHMENT, ATT_ATTACH_DATA) => {
+                    if let Some(a) = ret.attachments.last_mut() {
+                        a.data = value.to_vec();
+                    }
+                }
+                (LVL_ATTACHMENT, ATT_ATTACHMENT) => {
+                    let charset = ret.charset;
+                    if let Some(a) = ret.attachments.last_mut() {
+                        for p in mapi_properties(value) {
+                            match p.id {
+                                PR_ATTACH_LONG_FILENAME => {
+                                    if let Some(name) = mapi_string(&p, charset) {
+                                        a.filename = Some(name);
+                                    }
+                                }
+                                PR_ATTACH_FILENAME if a.filename.is_none() => {
+                                    a.filename = mapi_string(&p, charset);
+                                }
+                                PR_ATTACH_MIME_TAG => {
+                                    a.mime_type = mapi_string(&p, charset);
+                                }
+                                PR_ATTACH_DATA_OBJ if a.data.is_empty() => {
+                                    if let Some(v) = p.values.first() {
+                                        /* Objects are prefixed with their interface GUID. */
+                                        a.data = if p.kind == PT_OBJECT && v.len() >= 16 {
+                                            v[16..].to_vec()
+                                        } else {
+                                            v.to_vec()
+                                        };
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Expand into virtual attachments: the message body followed by the file attachments.
+    pub fn into_attachments(self) -> Vec<Attachment> {
+        let mut ret = Vec::with_capacity(self.attachments.len() + 2);
+        if let Some(body) = self.body {
+            ret.push(virtual_attachment(
+                body,
+                ContentType::Text {
+                    kind: Text::Plain,
+                    parameters: Vec::new(),
+                    charset: self.charset,
+                },
+                None,
+            ));
+        } else if let Some(html_body) = self.html_body {
+            ret.push(virtual_attachment(
+                html_body,
+                ContentType::Text {
+                    kind: Text::Html,
+                    parameters: Vec::new(),
+                    charset: self.charset,
+                },
+                None,
+            ));
+        }
+        if let Some(rtf_body) = self.rtf_body {
+            ret.push(virtual_attachment(
+                rtf_body,
+                ContentType::Other {
+                    tag: b"application/rtf".to_vec(),
+                    name: Some("body.rtf".to_string()),
+                },
+                Some("body.rtf".to_string()),
+            ));
+        }
+        for a in self.attachments {
+            let mut builder = AttachmentBuilder::default();
+            let mime_type = a
+                .mime_type
+                .clone()
+                .or_else(|| a.filename.as_ref().and_then(|f| guess_mime_type(f)))
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            match &a.filename {
+                Some(name) => {
+                    builder.set_content_type_from_bytes(
+                        format!("{}; name=\"{}\"", mime_type, name.replace('"', "")).as_bytes(),
+                    );
+                }
+                None => {
+                    builder.set_content_type_from_bytes(mime_type.as_bytes());
+                }
+            }
+            let content_type = builder.content_type().clone();
+            ret.push(virtual_attachment(a.data, content_type, a.filename));
+        }
+        ret
+    }
+}
+
+fn virtual_attachment(
+    data: Vec<u8>,
+    content_type: ContentType,
+    filename: Option<String>,
+) -> Attachment {
+    let mut builder = AttachmentBuilder::default();
+    builder
+        .set_raw(data)
+        .set_body_to_raw()
+        .set_content_type(content_type);
+    if filename.is_some() {
+        builder.set_content_disposition(ContentDisposition {
+            kind: ContentDispositionKind::Attachment,
+            filename,
+            ..ContentDisposition::default()
+        });
+    }
+    builder.build()
+}
+
+fn guess_mime_type(filename: &str) -> Option<String> {
+    let ext = filename.rsplit('.').next()?.to_ascii_lowercase();
+    Some(
+        match ext.as_str() {
+            "txt" => "text/plain",
+            "htm" | "html" => "text/html",
+            "ics" => "text/calendar",
+            "rtf" => "application/rtf",
+            "pdf" => "application/pdf",
+            "zip" => "application/zip",
+            "doc" => "application/msword",
+            "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "xls" => "application/vnd.ms-excel",
+            "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "ppt" => "application/vnd.ms-powerpoint",
+            "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            "png" => "image/png",
+            "gif" => "image/gif",
+            "jpg" | "jpeg" => "image/jpeg",
+            _ => return None,
+        }
+        .to_string(),
+    )
+}
+
+/// Decompress a `PR_RTF_COMPRESSED` value. [MS-OXRTFCP]
+pub fn decompress_rtf(data: &[u8]) -> Result<Vec<u8>> {
+    const PREBUF: &[u8] = b"{\\rtf1\\ansi\\mac\\deff0\\deftab720{\\fonttbl;}{\\f0\\fnil \\froman \\fswiss \\fmodern \\fscript \\fdecor MS Sans SerifSymbolArialTimes New RomanCourier{\\colortbl\\red0\\green0\\blue0\r\n\\par \\pard\\plain\\f0\\fs20\\b\\i\\u\\tab\\tx";
+    const COMPRESSED: u32 = 0x7546_5A4C;
+    const UNCOMPRESSED: u32 = 0x414C_454D;
+
+    let mut r = Reader::new(data);
+    let comp_size = r.u32()? as usize;
+    let raw_size = r.u32()? as usize;
+    let comp_type = r.u32()?;
+    /* CRC */
+    r.u32()?;
+    let end = std::cmp::min(data.len(), comp_size.saturating_add(4));
+    let input = data
+        .get(16..std::cmp::max(end, 16))
+        .ok_or_else(|| MeliError::new("Invalid compressed RTF size."))?;
+    match comp_type {
+        UNCOMPRESSED => return Ok(input[..std::cmp::min(raw_size, input.len())].to_vec()),
+        COMPRESSED => {}
+        _ => return Err(MeliError::new("Unknown compressed RTF format.")),
+    }
+
+    let mut dict = [0u8; 4096];
+    dict[..PREBUF.len()].copy_from_slice(PREBUF);
+    let mut write_pos = PREBUF.len();
+    let mut ret = Vec::with_capacity(std::cmp::min(raw_size, input.len()));
+    let mut r = Reader::new(input);
+    'outer: while !r.is_empty() {
+        let control = r.u8()?;
+        for bit in 0..8 {
+            if r.is_empty() {
+                break 'outer;
+            }
+            if control & (1 << bit) == 0 {
+                let b = r.u8()?;
+                ret.push(b);
+                dict[write_pos] = b;
+                write_pos = (write_pos + 1) % 4096;
+            } else {
+                let hi = r.u8()? as usize;
+                let lo = r.u8()? as usize;
+                let reference = (hi << 8) | lo;
+                let offset = reference >> 4;
+                let length = (reference & 0xF) + 2;
+                if offset == write_pos {
+                    break 'outer;
+                }
+                for i in 0..length {
+                    let b = dict[(offset + i) % 4096];
+                    ret.push(b);
+                    dict[write_pos] = b;
+                    write_pos = (write_pos + 1) % 4096;
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(level: u8, id: u32, value: &[u8]) -> Vec<u8> {
+        let mut ret = vec![level];
+        ret.extend(&id.to_le_bytes());
+        ret.extend(&(value.len() as u32).to_le_bytes());
+        ret.extend(value);
+        let checksum = value
+            .iter()
+            .fold(0u16, |acc, &b| acc.wrapping_add(b as u16));
+        ret.extend(&checksum.to_le_bytes());
+        ret
+    }
+
+    #[test]
+    fn test_tnef_parse() {
+        let mut data = TNEF_SIGNATURE.to_le_bytes().to_vec();
+        data.extend(&0x0001u16.to_le_bytes());
+        data.extend(attribute(LVL_MESSAGE, ATT_BODY, b"Hello there\r\n\0"));
+        data.extend(attribute(LVL_ATTACHMENT, ATT_ATTACH_REND_DATA, &[0; 14]));
+        data.extend(attribute(
+            LVL_ATTACHMENT,
+            ATT_ATTACH_TITLE,
+            b"REPORT~1.PDF\0",
+        ));
+        data.extend(attribute(LVL_ATTACHMENT, ATT_ATTACH_DATA, b"%PDF-1.4"));
+        /* attAttachment with PR_ATTACH_LONG_FILENAME as PT_STRING8 */
+        let mut props = 1u32.to_le_bytes().to_vec();
+        props.extend(&PT_STRING8.to_le_bytes());
+        props.extend(&PR_ATTACH_LONG_FILENAME.to_le_bytes());
+        props.extend(&1u32.to_le_bytes());
+        props.extend(&11u32.to_le_bytes());
+        props.extend(b"report.pdf\0\0");
+        data.extend(attribute(LVL_ATTACHMENT, ATT_ATTACHMENT, &props));
+
+        let tnef = Tnef::parse(&data).unwrap();
+        assert_eq!(tnef.body.as_deref(), Some(&b"Hello there\r\n"[..]));
+        assert_eq!(tnef.attachments.len(), 1);
+        assert_eq!(tnef.attachments[0].filename.as_deref(), Some("report.pdf"));
+        assert_eq!(tnef.attachments[0].data.as_slice(), b"%PDF-1.4");
+
+        let attachments = tnef.into_attachments();
+        assert_eq!(attachments.len(), 2);
+        assert!(attachments[0].is_text());
+        assert_eq!(attachments[1].filename().as_deref(), Some("report.pdf"));
+        assert_eq!(attachments[1].content_type().to_string(), "application/pdf");
+
+        assert!(Tnef::parse(b"not tnef").is_err());
+    }
+
+    #[test]
+    fn test_tnef_decompress_rtf() {
+        /* Example from [MS-OXRTFCP] section 3.1.1 */
+        let data = b"\x2d\x00\x00\x00\x2b\x00\x00\x00\x4c\x5a\x46\x75\xf1\xc5\xc7\xa7\x03\x00\x0a\x00\x72\x63\x70\x67\x31\x32\x35\x42\x32\x0a\xf3\x20\x68\x65\x6c\x09\x00\x20\x62\x77\x05\xb0\x6c\x64\x7d\x0a\x80\x0f\xa0";
+        assert_eq!(
+            String::from_utf8_lossy(&decompress_rtf(data).unwrap()),
+            "{\\rtf1\\ansi\\ansicpg1252\\pard hello world}\r\n"
+        );
+    }
+
+    #[test]
+    fn test_tnef_malformed() {
+        /* Truncated input */
+        let mut data = TNEF_SIGNATURE.to_le_bytes().to_vec();
+        data.extend(&0x0001u16.to_le_bytes());
+        data.extend(attribute(LVL_MESSAGE, ATT_BODY, b"Hello there\r\n\0"));
+        data.extend(attribute(LVL_ATTACHMENT, ATT_ATTACH_DATA, b"%PDF-1.4"));
+        for len in 0..data.len() {
+            let _ = Tnef::parse(&data[..len]);
+        }
+        assert!(decompress_rtf(b"\x2d\x00\x00").is_err());
+
+        /* comp_size below the 12 bytes of the header that follow it */
+        let mut rtf = 4u32.to_le_bytes().to_vec();
+        rtf.extend(&0xFFFF_FFFFu32.to_le_bytes());
+        rtf.extend(&0x7546_5A4Cu32.to_le_bytes());
+        rtf.extend(&0u32.to_le_bytes());
+        rtf.extend(b"\x00abcdefgh");
+        assert_eq!(decompress_rtf(&rtf).unwrap(), Vec::<u8>::new());
+
+        /* A property count far larger than the data */
+        let mut props = 1u32.to_le_bytes().to_vec();
+        props.extend(&(PT_BINARY | MV_FLAG).to_le_bytes());
+        props.extend(&PR_ATTACH_DATA_OBJ.to_le_bytes());
+        props.extend(&0xFFFF_FFFFu32.to_le_bytes());
+        props.extend(&4u32.to_le_bytes());
+        props.extend(b"data");
+        assert!(mapi_properties(&props).is_empty());
+        let mut data = TNEF_SIGNATURE.to_le_bytes().to_vec();
+        data.extend(&0x0001u16.to_le_bytes());
+        data.extend(attribute(LVL_ATTACHMENT, ATT_ATTACHMENT, &props));
+        assert!(Tnef::parse(&data).is_ok());
+    }
+}
@@ -2023,12 +2023,9 @@ pub type gpgme_get_ctx_flag = unsafe extern "C" fn(
     ctx: gpgme_ctx_t,
     name: *const ::std::os::raw::c_char,
 ) -> *const ::std::os::raw::c_char;
-extern "C" {
-    pub fn gpgme_set_protocol(ctx: gpgme_ctx_t, proto: gpgme_protocol_t) -> gpgme_error_t;
-}
-extern "C" {
-    pub fn gpgme_get_protocol(ctx: gpgme_ctx_t) -> gpgme_protocol_t;
-}
+pub type gpgme_set_protocol =
+    unsafe extern "C" fn(ctx: gpgme_ctx_t, proto: gpgme_protocol_t) -> gpgme_error_t;
+pub type gpgme_get_protocol = unsafe extern "C" fn(ctx: gpgme_ctx_t) -> gpgme_protocol_t;
 extern "C" {
     pub fn gpgme_set_sub_protocol(ctx: gpgme_ctx_t, proto: gpgme_protocol_t) -> gpgme_error_t;
 }
@@ -3783,9 +3780,8 @@ pub type gpgme_import_result_t = *mut _gpgme_op_import_result;
 extern "C" {
     pub fn gpgme_op_import_result(ctx: gpgme_ctx_t) -> gpgme_import_result_t;
 }
-extern "C" {
-    pub fn gpgme_op_import_start(ctx: gpgme_ctx_t, keydata: gpgme_data_t) -> gpgme_error_t;
-}
+pub type gpgme_op_import_start =
+    unsafe extern "C" fn(ctx: gpgme_ctx_t, keydata: gpgme_data_t) -> gpgme_error_t;
 extern "C" {
     pub fn gpgme_op_import(ctx: gpgme_ctx_t, keydata: gpgme_data_t) -> gpgme_error_t;
 }
@@ -3795,14 +3791,12 @@ extern "C" {
 extern "C" {
     pub fn gpgme_op_import_keys(ctx: gpgme_ctx_t, keys: *mut gpgme_key_t) -> gpgme_error_t;
 }
-extern "C" {
-    pub fn gpgme_op_export_start(
-        ctx: gpgme_ctx_t,
-        pattern: *const ::std::os::raw::c_char,
-        mode: gpgme_export_mode_t,
-        keydata: gpgme_data_t,
-    ) -> gpgme_error_t;
-}
+pub type gpgme_op_export_start = unsafe extern "C" fn(
+    ctx: gpgme_ctx_t,
+    pattern: *const ::std::os::raw::c_char,
+    mode: gpgme_export_mode_t,
+    keydata: gpgme_data_t,
+) -> gpgme_error_t;
 extern "C" {
     pub fn gpgme_op_export(
         ctx: gpgme_ctx_t,
@@ -72,6 +72,15 @@ pub enum GpgmeFlag {
     AsciiArmor,
 }
 
+/// Cryptographic protocol used by a [`Context`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    /// OpenPGP, used for PGP/MIME ([rfc3156](https://tools.ietf.org/html/rfc3156)).
+    OpenPGP,
+    /// Cryptographic Message Syntax, used for S/MIME ([rfc8551](https://tools.ietf.org/html/rfc8551)).
+    CMS,
+}
+
 bitflags! {
     pub struct LocateKey: u8 {
         /// Locate a key using DNS CERT, as specified in RFC-4398.
@@ -403,6 +412,29 @@ impl Context {
         Ok(val)
     }
 
+    pub fn set_protocol(&self, protocol: Protocol) -> Result<()> {
+        let raw_protocol = match protocol {
+            Protocol::OpenPGP => gpgme_protocol_t_GPGME_PROTOCOL_OpenPGP,
+            Protocol::CMS => gpgme_protocol_t_GPGME_PROTOCOL_CMS,
+        };
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_set_protocol)(self.inner.inner.as_ptr(), raw_protocol),
+            )
+        }
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        if unsafe { call!(&self.inner.lib, gpgme_get_protocol)(self.inner.inner.as_ptr()) }
+            == gpgme_protocol_t_GPGME_PROTOCOL_CMS
+        {
+            Protocol::CMS
+        } else {
+            Protocol::OpenPGP
+        }
+    }
+
     pub fn new_data_mem(&self, bytes: &[u8]) -> Result<Data> {
         let mut ptr = core::ptr::null_mut();
         unsafe {
@@ -460,6 +492,7 @@ impl Context {
         mut signature: Data,
         mut text: Data,
     ) -> Result<impl Future<Output = Result<()>> + Send> {
+        let protocol = self.protocol();
         unsafe {
             gpgme_error_try(
                 &self.inner.lib,
@@ -547,7 +580,9 @@ impl Context {
                     )
                     .set_err_kind(ErrorKind::External));
                 }
-                drop(verify_result);
+                if protocol == Protocol::CMS {
+                    Self::check_cms_signatures(&ctx, verify_result)?;
+                }
             }
             let io_state_lck = io_state.lock().unwrap();
             let ret = io_state_lck
@@ -560,6 +595,34 @@ impl Context {
         })
     }
 
+    /// gpgsm completes the verification of a bad CMS signature successfully and only sets the
+    /// status of the signature, so every signature's status has to be checked.
+    fn check_cms_signatures(
+        ctx: &ContextInner,
+        verify_result: gpgme_verify_result_t,
+    ) -> Result<()> {
+        let mut sig = unsafe { (*verify_result).signatures };
+        if sig.is_null() {
+            return Err(MeliError::new("No signatures found.").set_err_kind(ErrorKind::External));
+        }
+        while !sig.is_null() {
+            let status = unsafe { (*sig).status };
+            if status != 0 {
+                let fpr = unsafe {
+                    if (*sig).fpr.is_null() {
+                        "unknown key".into()
+                    } else {
+                        CStr::from_ptr((*sig).fpr).to_string_lossy()
+                    }
+                };
+                return gpgme_error_try(&ctx.lib, status)
+                    .chain_err_summary(|| format!("Bad signature by {}", fpr));
+            }
+            sig = unsafe { (*sig).next };
+        }
+        Ok(())
+    }
+
     pub fn keylist(
         &mut self,
         secret: bool,
@@ -797,6 +860,205 @@ impl Context {
         })
     }
 
+    /// Export the public key matching `pattern` in its minimal form, e.g. for Autocrypt
+    /// `keydata`. Output is binary unless [`GpgmeFlag::AsciiArmor`] is set.
+    pub fn export_key(&mut self, pattern: &str) -> Result<impl Future<Output = Result<Vec<u8>>>> {
+        let pattern = CString::new(pattern)?;
+        let mut keydata: gpgme_data_t = std::ptr::null_mut();
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_data_new)(&mut keydata),
+            )?;
+        }
+        let mut keydata = Data {
+            lib: self.inner.lib.clone(),
+            kind: DataKind::Memory,
+            inner: core::ptr::NonNull::new(keydata).ok_or_else(|| {
+                MeliError::new("internal libgpgme error").set_kind(ErrorKind::Bug)
+            })?,
+        };
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_op_export_start)(
+                    self.inner.inner.as_ptr(),
+                    pattern.as_ptr(),
+                    GPGME_EXPORT_MODE_MINIMAL,
+                    keydata.inner.as_mut(),
+                ),
+            )?;
+        }
+
+        let io_state = self.io_state.clone();
+        let io_state_lck = self.io_state.lock().unwrap();
+        let done = io_state_lck.done.clone();
+        let fut = io_state_lck
+            .ops
+            .values()
+            .map(|a| Async::new(a.clone()).unwrap())
+            .collect::<Vec<Async<GpgmeFd>>>();
+        drop(io_state_lck);
+        Ok(async move {
+            futures::future::join_all(fut.iter().map(|fut| {
+                let done = done.clone();
+                if fut.get_ref().write {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.write_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                } else {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.read_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                }
+            }))
+            .await;
+            let rcv = {
+                let io_state_lck = io_state.lock().unwrap();
+                io_state_lck.receiver.clone()
+            };
+            let _ = rcv.recv().await;
+            let io_state_lck = io_state.lock().unwrap();
+            io_state_lck
+                .done
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_else(|| Err(MeliError::new("Unspecified libgpgme error")))?;
+            keydata
+                .seek(std::io::SeekFrom::Start(0))
+                .chain_err_summary(|| "libgpgme error: could not perform seek on key data")?;
+            let keydata = keydata.into_bytes()?;
+            if keydata.is_empty() {
+                return Err(MeliError::new(format!(
+                    "No key found for `{}`.",
+                    pattern.to_string_lossy()
+                )));
+            }
+            Ok(keydata)
+        })
+    }
+
+    /// Import the keys contained in `keydata` into the keyring.
+    pub fn import_key(&mut self, mut keydata: Data) -> Result<impl Future<Output = Result<()>>> {
+        unsafe {
+            gpgme_error_try(
+                &self.inner.lib,
+                call!(&self.inner.lib, gpgme_op_import_start)(
+                    self.inner.inner.as_ptr(),
+                    keydata.inner.as_mut(),
+                ),
+            )?;
+        }
+
+        let io_state = self.io_state.clone();
+        let io_state_lck = self.io_state.lock().unwrap();
+        let done = io_state_lck.done.clone();
+        let fut = io_state_lck
+            .ops
+            .values()
+            .map(|a| Async::new(a.clone()).unwrap())
+            .collect::<Vec<Async<GpgmeFd>>>();
+        drop(io_state_lck);
+        Ok(async move {
+            let _k = keydata;
+            futures::future::join_all(fut.iter().map(|fut| {
+                let done = done.clone();
+                if fut.get_ref().write {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.write_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                } else {
+                    futures::future::select(
+                        fut.get_ref().receiver.recv().boxed(),
+                        fut.read_with(move |_f| {
+                            if done.lock().unwrap().is_some() {
+                                return Ok(());
+                            }
+                            unsafe {
+                                (fut.get_ref().fnc.unwrap())(
+                                    fut.get_ref().fnc_data,
+                                    fut.get_ref().fd,
+                                )
+                            };
+                            if done.lock().unwrap().is_none() {
+                                return Err(std::io::ErrorKind::WouldBlock.into());
+                            }
+                            Ok(())
+                        })
+                        .boxed(),
+                    )
+                    .boxed()
+                }
+            }))
+            .await;
+            let rcv = {
+                let io_state_lck = io_state.lock().unwrap();
+                io_state_lck.receiver.clone()
+            };
+            let _ = rcv.recv().await;
+            let io_state_lck = io_state.lock().unwrap();
+            io_state_lck
+                .done
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_else(|| Err(MeliError::new("Unspecified libgpgme error")))?;
+            Ok(())
+        })
+    }
+
     pub fn decrypt(
         &mut self,
         mut cipher: Data,
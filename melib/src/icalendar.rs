@@ -0,0 +1,706 @@
+/*
+ * meli - melib crate.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Parsing of iCalendar objects ([RFC 5545](https://tools.ietf.org/html/rfc5545)) and
+ * generation of iTIP ([RFC 5546](https://tools.ietf.org/html/rfc5546)) replies to meeting
+ * invitations.
+ */
+
+use crate::datetime::{self, UnixTimestamp};
+use crate::error::{MeliError, Result};
+use std::fmt;
+
+/// A content line: `NAME;PARAM=VALUE:value`. The value is kept escaped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl Property {
+    pub fn new(name: &str, value: String) -> Self {
+        Property {
+            name: name.to_ascii_uppercase(),
+            params: Vec::new(),
+            value,
+        }
+    }
+
+    /// Parse an unfolded content line.
+    pub fn parse(line: &str) -> Result<Self> {
+        let err = || MeliError::new(format!("Invalid iCalendar content line: `{}`", line));
+        let name_end = line.find(|c| c == ';' || c == ':').ok_or_else(err)?;
+        let mut ret = Property {
+            name: line[..name_end].trim().to_ascii_uppercase(),
+            params: Vec::new(),
+            value: String::new(),
+        };
+        if ret.name.is_empty() {
+            return Err(err());
+        }
+        let mut rest = &line[name_end..];
+        while rest.starts_with(';') {
+            rest = &rest[1..];
+            let eq = rest.find('=').ok_or_else(err)?;
+            let param_name = rest[..eq].trim().to_ascii_uppercase();
+            rest = &rest[eq + 1..];
+            let param_value = if rest.starts_with('"') {
+                let end = rest[1..].find('"').ok_or_else(err)? + 1;
+                let v = &rest[1..end];
+                rest = &rest[end + 1..];
+                v
+            } else {
+                let end = rest.find(|c| c == ';' || c == ':').ok_or_else(err)?;
+                let v = &rest[..end];
+                rest = &rest[end..];
+                v
+            };
+            ret.params.push((param_name, param_value.to_string()));
+        }
+        if !rest.starts_with(':') {
+            return Err(err());
+        }
+        ret.value = rest[1..].to_string();
+        Ok(ret)
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_param(&mut self, name: &str, value: String) {
+        self.params.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.params.push((name.to_ascii_uppercase(), value));
+    }
+
+    /// The value of a `TEXT` property, unescaped.
+    pub fn text(&self) -> String {
+        let mut ret = String::with_capacity(self.value.len());
+        let mut chars = self.value.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                ret.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') | Some('N') => ret.push('\n'),
+                Some(c) => ret.push(c),
+                None => ret.push('\\'),
+            }
+        }
+        ret
+    }
+}
+
+impl fmt::Display for Property {
+    /// Content line folded at 75 octets, terminated with CRLF.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut line = self.name.clone();
+        for (n, v) in &self.params {
+            if v.contains(|c| c == ';' || c == ':' || c == ',') {
+                line.push_str(&format!(";{}=\"{}\"", n, v));
+            } else {
+                line.push_str(&format!(";{}={}", n, v));
+            }
+        }
+        line.push(':');
+        line.push_str(&self.value);
+        let mut rest = line.as_str();
+        let mut limit = 75;
+        while rest.len() > limit {
+            let mut idx = limit;
+            while !rest.is_char_boundary(idx) {
+                idx -= 1;
+            }
+            write!(f, "{}\r\n ", &rest[..idx])?;
+            rest = &rest[idx..];
+            /* Continuation lines start with a space. */
+            limit = 74;
+        }
+        write!(f, "{}\r\n", rest)
+    }
+}
+
+/// A `BEGIN:NAME`/`END:NAME` block, such as `VCALENDAR` or `VEVENT`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub name: String,
+    pub properties: Vec<Property>,
+    pub components: Vec<Component>,
+}
+
+impl Component {
+    pub fn new(name: &str) -> Self {
+        Component {
+            name: name.to_ascii_uppercase(),
+            properties: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn properties<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> + 'a {
+        self.properties
+            .iter()
+            .filter(move |p| p.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BEGIN:{}\r\n", self.name)?;
+        for p in &self.properties {
+            write!(f, "{}", p)?;
+        }
+        for c in &self.components {
+            write!(f, "{}", c)?;
+        }
+        write!(f, "END:{}\r\n", self.name)
+    }
+}
+
+/// A `VCALENDAR` object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calendar {
+    pub root: Component,
+}
+
+impl Calendar {
+    pub fn parse(input: &str) -> Result<Self> {
+        /* Unfold lines. [rfc5545#section-3.1] */
+        let mut lines: Vec<String> = Vec::new();
+        for l in input.lines() {
+            if l.starts_with(' ') || l.starts_with('\t') {
+                if let Some(last) = lines.last_mut() {
+                    last.push_str(&l[1..]);
+                    continue;
+                }
+            }
+            lines.push(l.to_string());
+        }
+
+        let mut stack: Vec<Component> = Vec::new();
+        for l in lines.iter().filter(|l| !l.trim().is_empty()) {
+            let prop = Property::parse(l)?;
+            match prop.name.as_str() {
+                "BEGIN" => stack.push(Component::new(prop.value.trim())),
+                "END" => {
+                    let c = match stack.pop() {
+                        Some(c) if c.name.eq_ignore_ascii_case(prop.value.trim()) => c,
+                        _ => {
+                            return Err(MeliError::new(format!(
+                                "Unexpected `END:{}` in iCalendar object.",
+                                prop.value
+                            )))
+                        }
+                    };
+                    if let Some(parent) = stack.last_mut() {
+                        parent.components.push(c);
+                    } else if c.name == "VCALENDAR" {
+                        return Ok(Calendar { root: c });
+                    } else {
+                        return Err(MeliError::new(format!(
+                            "Expected VCALENDAR object, found {}.",
+                            c.name
+                        )));
+                    }
+                }
+                _ => stack
+                    .last_mut()
+                    .ok_or_else(|| {
+                        MeliError::new("iCalendar property found outside of a component.")
+                    })?
+                    .properties
+                    .push(prop),
+            }
+        }
+        Err(MeliError::new("Unterminated iCalendar object."))
+    }
+
+    /// The iTIP method, eg. `REQUEST`, `REPLY` or `CANCEL`.
+    pub fn method(&self) -> Option<String> {
+        self.root
+            .property("METHOD")
+            .map(|p| p.value.trim().to_ascii_uppercase())
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.root
+            .components
+            .iter()
+            .filter(|c| c.name == "VEVENT")
+            .map(Event::new)
+            .collect()
+    }
+}
+
+impl fmt::Display for Calendar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.root.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeZone {
+    Utc,
+    /// A `TZID` parameter, referencing a `VTIMEZONE` component or an Olson name.
+    Tzid(String),
+    /// Local time of whoever is reading the calendar.
+    Floating,
+}
+
+/// A `DATE` or `DATE-TIME` value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateTime {
+    pub year: u32,
+    pub month: u32,
+    pub day: u32,
+    /// Hour, minute and second, or `None` for `DATE` values.
+    pub time: Option<(u32, u32, u32)>,
+    pub timezone: TimeZone,
+}
+
+impl DateTime {
+    pub fn from_property(p: &Property) -> Result<Self> {
+        let err = || MeliError::new(format!("Invalid iCalendar date `{}`.", p.value));
+        let v = p.value.trim();
+        let num = |range: std::ops::Range<usize>| -> Result<u32> {
+            v.get(range)
+                .and_then(|s| s.parse::<u32>().ok())
+                .ok_or_else(err)
+        };
+        let mut ret = DateTime {
+            year: num(0..4)?,
+            month: num(4..6)?,
+            day: num(6..8)?,
+            time: None,
+            timezone: TimeZone::Floating,
+        };
+        if v.len() > 8 {
+            if v.as_bytes()[8] != b'T' {
+                return Err(err());
+            }
+            ret.time = Some((num(9..11)?, num(11..13)?, num(13..15)?));
+            ret.timezone = if v.ends_with('Z') {
+                TimeZone::Utc
+            } else if let Some(tzid) = p.param("TZID") {
+                TimeZone::Tzid(tzid.to_string())
+            } else {
+                TimeZone::Floating
+            };
+        }
+        Ok(ret)
+    }
+
+    /// The timestamp of a UTC time.
+    pub fn timestamp(&self) -> Option<UnixTimestamp> {
+        match (self.time, &self.timezone) {
+            (Some((h, m, s)), TimeZone::Utc) => datetime::rfc3339_to_timestamp(format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+00:00",
+                self.year, self.month, self.day, h, m, s
+            ))
+            .ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// UTC times are shown in local time.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(timestamp) = self.timestamp() {
+            return write!(
+                f,
+                "{}",
+                datetime::timestamp_to_string(timestamp, Some("%Y-%m-%d %H:%M %Z"))
+            );
+        }
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)?;
+        match (self.time, &self.timezone) {
+            (None, _) => write!(f, " (all day)"),
+            (Some((h, m, _)), TimeZone::Tzid(tzid)) => write!(f, " {:02}:{:02} {}", h, m, tzid),
+            (Some((h, m, _)), _) => write!(f, " {:02}:{:02}", h, m),
+        }
+    }
+}
+
+/// Participation status of an attendee. [rfc5545#section-3.2.12]
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartStat {
+    NeedsAction,
+    Accepted,
+    Declined,
+    Tentative,
+    Delegated,
+    Other(String),
+}
+
+impl PartStat {
+    pub fn new(s: &str) -> Self {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "NEEDS-ACTION" => PartStat::NeedsAction,
+            "ACCEPTED" => PartStat::Accepted,
+            "DECLINED" => PartStat::Declined,
+            "TENTATIVE" => PartStat::Tentative,
+            "DELEGATED" => PartStat::Delegated,
+            other => PartStat::Other(other.to_string()),
+        }
+    }
+
+    /// The `PARTSTAT` parameter value.
+    pub fn as_str(&self) -> &str {
+        match self {
+            PartStat::NeedsAction => "NEEDS-ACTION",
+            PartStat::Accepted => "ACCEPTED",
+            PartStat::Declined => "DECLINED",
+            PartStat::Tentative => "TENTATIVE",
+            PartStat::Delegated => "DELEGATED",
+            PartStat::Other(s) => s.as_str(),
+        }
+    }
+}
+
+impl Default for PartStat {
+    fn default() -> Self {
+        PartStat::NeedsAction
+    }
+}
+
+impl fmt::Display for PartStat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PartStat::NeedsAction => write!(f, "needs action"),
+            PartStat::Accepted => write!(f, "accepted"),
+            PartStat::Declined => write!(f, "declined"),
+            PartStat::Tentative => write!(f, "tentatively accepted"),
+            PartStat::Delegated => write!(f, "delegated"),
+            PartStat::Other(s) => write!(f, "{}", s.to_ascii_lowercase()),
+        }
+    }
+}
+
+/// An `ORGANIZER` or `ATTENDEE` property.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attendee {
+    pub address: String,
+    pub name: Option<String>,
+    pub role: Option<String>,
+    pub partstat: PartStat,
+}
+
+impl Attendee {
+    pub fn from_property(p: &Property) -> Self {
+        let value = p.value.trim();
+        let address = if value.len() > "mailto:".len()
+            && value[.."mailto:".len()].eq_ignore_ascii_case("mailto:")
+        {
+            &value["mailto:".len()..]
+        } else {
+            value
+        };
+        Attendee {
+            address: address.to_string(),
+            name: p.param("CN").map(str::to_string),
+            role: p.param("ROLE").map(str::to_string),
+            partstat: p.param("PARTSTAT").map(PartStat::new).unwrap_or_default(),
+        }
+    }
+
+    /// `Name <address>` form, suitable for an address header.
+    pub fn mailbox(&self) -> String {
+        match self.name {
+            Some(ref name) if !name.is_empty() => format!("{} <{}>", name, self.address),
+            _ => self.address.clone(),
+        }
+    }
+}
+
+/// A `VEVENT` component.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub status: Option<String>,
+    pub organizer: Option<Attendee>,
+    pub attendees: Vec<Attendee>,
+    pub start: Option<DateTime>,
+    pub end: Option<DateTime>,
+    /// Raw `RRULE` value.
+    pub recurrence: Option<String>,
+    pub component: Component,
+}
+
+impl Event {
+    pub fn new(component: &Component) -> Self {
+        let text = |name: &str| component.property(name).map(Property::text);
+        Event {
+            uid: component
+                .property("UID")
+                .map(|p| p.value.trim().to_string()),
+            summary: text("SUMMARY"),
+            description: text("DESCRIPTION"),
+            location: text("LOCATION"),
+            status: component
+                .property("STATUS")
+                .map(|p| p.value.trim().to_ascii_uppercase()),
+            organizer: component.property("ORGANIZER").map(Attendee::from_property),
+            attendees: component
+                .properties("ATTENDEE")
+                .map(Attendee::from_property)
+                .collect(),
+            start: component
+                .property("DTSTART")
+                .and_then(|p| DateTime::from_property(p).ok()),
+            end: component
+                .property("DTEND")
+                .and_then(|p| DateTime::from_property(p).ok()),
+            recurrence: component.property("RRULE").map(|p| p.value.clone()),
+            component: component.clone(),
+        }
+    }
+
+    /// Build an iTIP `REPLY` to this event, stating the participation status of attendee
+    /// `address`. [rfc5546#section-3.2.3]
+    pub fn reply(&self, address: &str, partstat: PartStat) -> Calendar {
+        let mut event = Component::new("VEVENT");
+        for name in &[
+            "UID",
+            "SEQUENCE",
+            "RECURRENCE-ID",
+            "DTSTART",
+            "DTEND",
+            "DURATION",
+            "ORGANIZER",
+            "SUMMARY",
+        ] {
+            if let Some(p) = self.component.property(name) {
+                event.properties.push(p.clone());
+            }
+        }
+        let mut attendee = self
+            .component
+            .properties("ATTENDEE")
+            .find(|p| {
+                Attendee::from_property(p)
+                    .address
+                    .eq_ignore_ascii_case(address)
+            })
+            .cloned()
+            .unwrap_or_else(|| Property::new("ATTENDEE", format!("mailto:{}", address)));
+        attendee
+            .params
+            .retain(|(n, _)| n != "RSVP" && n != "PARTSTAT");
+        attendee.set_param("PARTSTAT", partstat.as_str().to_string());
+        event.properties.push(attendee);
+        event
+            .properties
+            .push(Property::new("DTSTAMP", utc_date_time(datetime::now())));
+
+        let mut root = Component::new("VCALENDAR");
+        root.properties
+            .push(Property::new("PRODID", "-//meli//meli//EN".to_string()));
+        root.properties
+            .push(Property::new("VERSION", "2.0".to_string()));
+        root.properties
+            .push(Property::new("METHOD", "REPLY".to_string()));
+        root.components.push(event);
+        Calendar { root }
+    }
+}
+
+/// Format a timestamp as a UTC `DATE-TIME` value, eg. `20201020T120000Z`.
+fn utc_date_time(timestamp: UnixTimestamp) -> String {
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+    /* Civil date from days since the epoch, see
+     * http://howardhinnant.github.io/date_algorithms.html#civil_from_days */
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// Describe a recurrence rule, eg. `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO` as
+/// `every 2 weeks on MO`. [rfc5545#section-3.3.10]
+pub fn describe_recurrence(rrule: &str) -> String {
+    let mut freq = None;
+    let mut interval = 1;
+    let mut ret = String::new();
+    let mut suffix = String::new();
+    for part in rrule.split(';') {
+        let mut kv = part.splitn(2, '=');
+        match (
+            kv.next().map(|k| k.trim().to_ascii_uppercase()),
+            kv.next().map(str::trim),
+        ) {
+            (Some(k), Some(v)) if k == "FREQ" => {
+                freq = Some(match v.to_ascii_uppercase().as_str() {
+                    "SECONDLY" => "second",
+                    "MINUTELY" => "minute",
+                    "HOURLY" => "hour",
+                    "DAILY" => "day",
+                    "WEEKLY" => "week",
+                    "MONTHLY" => "month",
+                    "YEARLY" => "year",
+                    _ => return rrule.to_string(),
+                })
+            }
+            (Some(k), Some(v)) if k == "INTERVAL" => {
+                interval = v.parse::<u32>().unwrap_or(1);
+            }
+            (Some(k), Some(v)) if k == "BYDAY" => {
+                suffix.push_str(&format!(" on {}", v));
+            }
+            (Some(k), Some(v)) if k == "COUNT" => {
+                suffix.push_str(&format!(", {} times", v));
+            }
+            (Some(k), Some(v)) if k == "UNTIL" => {
+                let until = DateTime::from_property(&Property::new("UNTIL", v.to_string()))
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|_| v.to_string());
+                suffix.push_str(&format!(", until {}", until));
+            }
+            _ => {}
+        }
+    }
+    match freq {
+        None => return rrule.to_string(),
+        Some(freq) if interval == 1 => ret.push_str(&format!("every {}", freq)),
+        Some(freq) => ret.push_str(&format!("every {} {}s", interval, freq)),
+    }
+    ret.push_str(&suffix);
+    ret
+}
+
+impl fmt::Display for Event {
+    /// Multi-line summary of the event, for display.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Summary: {}",
+            self.summary.as_ref().map(String::as_str).unwrap_or("")
+        )?;
+        match (&self.start, &self.end) {
+            (Some(start), Some(end)) => writeln!(f, "When: {} – {}", start, end)?,
+            (Some(start), None) => writeln!(f, "When: {}", start)?,
+            _ => {}
+        }
+        if let Some(ref rrule) = self.recurrence {
+            writeln!(f, "Repeats: {}", describe_recurrence(rrule))?;
+        }
+        if let Some(ref location) = self.location {
+            writeln!(f, "Where: {}", location)?;
+        }
+        if let Some(ref organizer) = self.organizer {
+            writeln!(f, "Organizer: {}", organizer.mailbox())?;
+        }
+        if !self.attendees.is_empty() {
+            writeln!(f, "Attendees:")?;
+            for a in &self.attendees {
+                writeln!(f, "  {} ({})", a.mailbox(), a.partstat)?;
+            }
+        }
+        if let Some(ref status) = self.status {
+            writeln!(f, "Status: {}", status.to_ascii_lowercase())?;
+        }
+        if let Some(ref description) = self.description {
+            writeln!(f, "\n{}", description)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icalendar_parse() {
+        let input = "BEGIN:VCALENDAR\r\nPRODID:-//Example//EN\r\nVERSION:2.0\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\nUID:1234@example.com\r\nSEQUENCE:0\r\nDTSTART;TZID=Europe/Berlin:20201020T140000\r\nDTEND;TZID=Europe/Berlin:20201020T150000\r\nRRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=TU;COUNT=5\r\nSUMMARY:Planning\\, again\r\nLOCATION:Room 1\r\nORGANIZER;CN=Alice:mailto:alice@example.com\r\nATTENDEE;CN=\"Bob; Builder\";PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:bob@exam\r\n ple.com\r\nDESCRIPTION:First line\\nSecond line\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let calendar = Calendar::parse(input).unwrap();
+        assert_eq!(calendar.method().as_deref(), Some("REQUEST"));
+        let events = calendar.events();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.summary.as_deref(), Some("Planning, again"));
+        assert_eq!(
+            event.description.as_deref(),
+            Some("First line\nSecond line")
+        );
+        assert_eq!(
+            event.organizer.as_ref().map(Attendee::mailbox).as_deref(),
+            Some("Alice <alice@example.com>")
+        );
+        assert_eq!(event.attendees.len(), 1);
+        assert_eq!(event.attendees[0].address.as_str(), "bob@example.com");
+        assert_eq!(event.attendees[0].name.as_deref(), Some("Bob; Builder"));
+        assert_eq!(event.attendees[0].partstat, PartStat::NeedsAction);
+        assert_eq!(
+            event.start.as_ref().map(DateTime::to_string).as_deref(),
+            Some("2020-10-20 14:00 Europe/Berlin")
+        );
+        assert_eq!(
+            describe_recurrence(event.recurrence.as_ref().unwrap()),
+            "every 2 weeks on TU, 5 times"
+        );
+
+        let reply = event.reply("BOB@example.com", PartStat::Accepted);
+        let reply = Calendar::parse(&reply.to_string()).unwrap();
+        assert_eq!(reply.method().as_deref(), Some("REPLY"));
+        let reply_event = &reply.events()[0];
+        assert_eq!(reply_event.uid.as_deref(), Some("1234@example.com"));
+        assert_eq!(reply_event.attendees.len(), 1);
+        assert_eq!(reply_event.attendees[0].partstat, PartStat::Accepted);
+        assert_eq!(
+            reply_event.attendees[0].name.as_deref(),
+            Some("Bob; Builder")
+        );
+        assert!(reply_event.component.property("DTSTAMP").is_some());
+        assert_eq!(utc_date_time(1603195200).as_str(), "20201020T120000Z");
+
+        assert!(Calendar::parse("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nEND:VCALENDAR\r\n").is_err());
+    }
+}
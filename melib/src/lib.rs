@@ -116,6 +116,7 @@ pub use crate::error::*;
 pub mod thread;
 pub use thread::*;
 pub mod connections;
+pub mod icalendar;
 pub mod parsec;
 pub mod search;
 
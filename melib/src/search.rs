@@ -70,10 +70,22 @@ impl QueryTrait for crate::Envelope {
                 self.date() > timestamp.saturating_sub(60 * 60 * 24)
                     && self.date() < *timestamp + 60 * 60 * 24
             }
-            From(s) => self.other_headers()["From"].contains(s),
-            To(s) => self.other_headers()["To"].contains(s),
-            Cc(s) => self.other_headers()["Cc"].contains(s),
-            Bcc(s) => self.other_headers()["Bcc"].contains(s),
+            From(s) => self
+                .other_headers()
+                .get("From")
+                .map_or(false, |v| v.contains(s)),
+            To(s) => self
+                .other_headers()
+                .get("To")
+                .map_or(false, |v| v.contains(s)),
+            Cc(s) => self
+                .other_headers()
+                .get("Cc")
+                .map_or(false, |v| v.contains(s)),
+            Bcc(s) => self
+                .other_headers()
+                .get("Bcc")
+                .map_or(false, |v| v.contains(s)),
             AllAddresses(s) => {
                 self.is_match(&From(s.clone()))
                     || self.is_match(&To(s.clone()))
@@ -81,7 +93,10 @@ impl QueryTrait for crate::Envelope {
                     || self.is_match(&Bcc(s.clone()))
             }
             Flags(v) => v.iter().any(|s| self.flags() == s.as_str()),
-            Subject(s) => self.other_headers()["Subject"].contains(s),
+            Subject(s) => self
+                .other_headers()
+                .get("Subject")
+                .map_or(false, |v| v.contains(s)),
             HasAttachment => self.has_attachments(),
             And(q_a, q_b) => self.is_match(q_a) && self.is_match(q_b),
             Or(q_a, q_b) => self.is_match(q_a) || self.is_match(q_b),
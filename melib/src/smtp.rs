@@ -72,7 +72,7 @@
  */
 
 use crate::connections::{lookup_ipv4, Connection};
-use crate::email::{parser::BytesExt, Address, Envelope};
+use crate::email::{address::idna, Address, Envelope};
 use crate::error::{MeliError, Result, ResultIntoMeliError};
 use futures::io::{AsyncReadExt, AsyncWriteExt};
 use native_tls::TlsConnector;
@@ -619,24 +619,61 @@ impl SmtpConnection {
         if tos.is_empty() {
             return Err(MeliError::new("SMTP submission was aborted because there was no e-mail address found in the To: header field. Consider adding recipients."));
         }
-        let mut current_command: SmallVec<[&[u8]; 16]> = SmallVec::new();
-        //first step in the procedure is the MAIL command.
-        // MAIL FROM:<reverse-path> [SP <mail-parameters> ] <CRLF>
-        current_command.push(b"MAIL FROM:<");
-        if !envelope_from.is_empty() {
-            current_command.push(envelope_from.trim().as_bytes());
+        let mut mail_from = if !envelope_from.is_empty() {
+            envelope_from.trim().to_string()
         } else {
             if envelope.from().is_empty() {
                 return Err(MeliError::new("SMTP submission was aborted because there was no e-mail address found in the From: header field. Consider adding a valid value or setting `envelope_from` in SMTP client settings"));
             } else if envelope.from().len() != 1 {
                 return Err(MeliError::new("SMTP submission was aborted because there was more than one e-mail address found in the From: header field. Consider setting `envelope_from` in SMTP client settings"));
             }
-            current_command.push(envelope.from()[0].address_spec_raw().trim());
+            envelope.from()[0].get_email().trim().to_string()
+        };
+        let mut rcpts = tos
+            .iter()
+            .map(|addr| addr.get_email().trim().to_string())
+            .collect::<Vec<String>>();
+        let header_addresses = envelope
+            .from()
+            .iter()
+            .chain(envelope.to().iter())
+            .chain(envelope.cc.iter())
+            .chain(envelope.bcc.iter())
+            .flat_map(|addr| match addr {
+                Address::Group(g) => g.mailbox_list.iter().collect::<Vec<&Address>>(),
+                Address::Mailbox(_) => vec![addr],
+            })
+            .filter(|addr| !addr.get_email().is_ascii())
+            .collect::<Vec<&Address>>();
+        let needs_smtputf8 = !mail_from.is_ascii()
+            || rcpts.iter().any(|r| !r.is_ascii())
+            || !header_addresses.is_empty();
+        let mut mail = Cow::Borrowed(mail);
+        if needs_smtputf8 && !self.server_conf.extensions.smtputf8 {
+            /* The server can't relay internationalized addresses, so send the ASCII form of the
+             * domains instead. Non-ASCII local parts have no ASCII form. */
+            let summary = || {
+                "SMTP submission was aborted because the server does not support internationalized e-mail addresses (SMTPUTF8)"
+            };
+            mail_from = idna::address_spec_to_ascii(&mail_from).chain_err_summary(summary)?;
+            for rcpt in rcpts.iter_mut() {
+                *rcpt = idna::address_spec_to_ascii(rcpt).chain_err_summary(summary)?;
+            }
+            mail =
+                Cow::Owned(downgrade_headers(&mail, &header_addresses).chain_err_summary(summary)?);
         }
+        let mut current_command: SmallVec<[&[u8]; 16]> = SmallVec::new();
+        //first step in the procedure is the MAIL command.
+        // MAIL FROM:<reverse-path> [SP <mail-parameters> ] <CRLF>
+        current_command.push(b"MAIL FROM:<");
+        current_command.push(mail_from.as_bytes());
         current_command.push(b">");
         if self.server_conf.extensions.prdr {
             current_command.push(b" PRDR");
         }
+        if needs_smtputf8 && self.server_conf.extensions.smtputf8 {
+            current_command.push(b" SMTPUTF8");
+        }
         self.send_command(&current_command).await?;
         current_command.clear();
         if !self.server_conf.extensions.pipelining {
@@ -651,10 +688,10 @@ impl SmtpConnection {
         //return a reply indicating whether the failure is permanent (i.e., will occur again if
         //the client tries to send the same address again) or temporary (i.e., the address might
         //be accepted if the client tries again later).
-        for addr in tos {
+        for rcpt in rcpts.iter() {
             current_command.clear();
             current_command.push(b"RCPT TO:<");
-            current_command.push(addr.address_spec_raw().trim());
+            current_command.push(rcpt.as_bytes());
             if let Some(dsn_notify) = dsn_notify.as_ref() {
                 current_command.push(b"> NOTIFY=");
                 current_command.push(dsn_notify.as_bytes());
@@ -951,6 +988,23 @@ impl<'s> Reply<'s> {
     }
 }
 
+/// Rewrite the internationalized domains of `addresses` in the header section of `mail` to their
+/// ASCII form, for servers that don't support SMTPUTF8.
+fn downgrade_headers(mail: &str, addresses: &[&Address]) -> Result<String> {
+    let header_end = mail
+        .find("\r\n\r\n")
+        .or_else(|| mail.find("\n\n"))
+        .unwrap_or(mail.len());
+    let (headers, body) = mail.split_at(header_end);
+    let mut headers = headers.to_string();
+    for addr in addresses {
+        let email = addr.get_email();
+        headers = headers.replace(email.trim(), &addr.address_spec_ascii()?);
+    }
+    headers.push_str(body);
+    Ok(headers)
+}
+
 async fn read_lines<'r>(
     _self: &mut (impl futures::io::AsyncRead + std::marker::Unpin),
     ret: &'r mut String,
@@ -0,0 +1,269 @@
+/*
+ * meli - text_processing mod.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Decoding and encoding of `text/plain; format=flowed` bodies.
+ *
+ * rfc3676 - The Text/Plain Format and DelSp Parameters
+ * https://tools.ietf.org/html/rfc3676
+ */
+
+use super::grapheme_clusters::TextProcessing;
+use super::line_break::linear;
+
+/// Maximum line length recommended by [rfc3676#section-4.2].
+pub const MAX_LINE_LENGTH: usize = 78;
+
+/// A logical line of flowed text: consecutive lines of the same quote depth joined at their soft
+/// line breaks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paragraph {
+    pub quote_depth: usize,
+    pub text: String,
+    /// `true` if the last line of the paragraph was flowed, ie. it ended because the quote depth
+    /// changed or the text ended instead of on a fixed line.
+    pub flowed: bool,
+}
+
+impl Paragraph {
+    /// Quote indicators to display before the paragraph's text, eg. `">> "`.
+    pub fn quote_prefix(&self) -> String {
+        if self.quote_depth == 0 {
+            String::new()
+        } else {
+            format!("{} ", ">".repeat(self.quote_depth))
+        }
+    }
+
+    /// The paragraph's text without trailing soft break spaces.
+    pub fn text(&self) -> &str {
+        if self.flowed {
+            self.text.trim_end_matches(' ')
+        } else {
+            &self.text
+        }
+    }
+
+    /// Break the paragraph into display lines of at most `width` columns, each prefixed with the
+    /// paragraph's quote indicators.
+    pub fn reflow(&self, width: Option<usize>) -> Vec<String> {
+        let prefix = self.quote_prefix();
+        let text = self.text();
+        match width {
+            Some(width) if !text.is_empty() && prefix.len() + text.grapheme_len() > width => {
+                linear(text, width.saturating_sub(prefix.len()).max(1))
+                    .into_iter()
+                    .map(|l| format!("{}{}", prefix, l))
+                    .collect()
+            }
+            _ => vec![format!("{}{}", prefix, text)],
+        }
+    }
+}
+
+/// Returns quote depth and content of a line, with space stuffing removed. [rfc3676#section-4.4]
+fn parse_line(line: &str) -> (usize, &str) {
+    let quote_depth = line.bytes().take_while(|&b| b == b'>').count();
+    let content = &line[quote_depth..];
+    if content.starts_with(' ') {
+        (quote_depth, &content[1..])
+    } else {
+        (quote_depth, content)
+    }
+}
+
+/// Decode the first paragraph of `text`. Returns the paragraph and the number of bytes of `text`
+/// it spans.
+///
+/// A paragraph ends on a fixed line (one that doesn't end in a space), or before a line of
+/// different quote depth. If `delsp` is set, the space of each soft line break is deleted.
+pub fn next_paragraph(text: &str, delsp: bool) -> Option<(Paragraph, usize)> {
+    let mut consumed = 0;
+    let mut paragraph: Option<Paragraph> = None;
+    while consumed < text.len() {
+        let rest = &text[consumed..];
+        let (line, line_len) = match rest.find('\n') {
+            Some(pos) => (&rest[..pos], pos + 1),
+            None => (rest, rest.len()),
+        };
+        let (quote_depth, content) = parse_line(line.trim_end_matches('\r'));
+        if let Some(ref p) = paragraph {
+            if p.quote_depth != quote_depth {
+                /* Malformed text, different quote depths can't be in the same paragraph.
+                 * [rfc3676#section-4.5] */
+                break;
+            }
+        }
+        consumed += line_len;
+        /* The signature separator is never flowed. [rfc3676#section-4.3] */
+        let flowed = content.ends_with(' ') && content != "-- ";
+        let content = if flowed && delsp {
+            &content[..content.len() - 1]
+        } else {
+            content
+        };
+        let p = paragraph.get_or_insert_with(|| Paragraph {
+            quote_depth,
+            text: String::new(),
+            flowed: false,
+        });
+        p.text.push_str(content);
+        p.flowed = flowed;
+        if !flowed {
+            break;
+        }
+    }
+    paragraph.map(|p| (p, consumed))
+}
+
+/// Decode all paragraphs of `text`.
+pub fn decode(text: &str, delsp: bool) -> Vec<Paragraph> {
+    let mut ret = Vec::new();
+    let mut offset = 0;
+    while let Some((p, consumed)) = next_paragraph(&text[offset..], delsp) {
+        ret.push(p);
+        offset += consumed;
+    }
+    ret
+}
+
+/// Encode plain text as `format=flowed` (without DelSp), breaking lines longer than `width` at
+/// spaces. Lines starting with `>` are treated as quoted; trailing whitespace of fixed lines is
+/// removed and lines are space-stuffed where needed.
+pub fn encode(text: &str, width: usize) -> String {
+    let mut ret = String::with_capacity(text.len());
+    for line in text.lines() {
+        let quote_depth = line.bytes().take_while(|&b| b == b'>').count();
+        let mut content = &line[quote_depth..];
+        if quote_depth > 0 && content.starts_with(' ') {
+            content = &content[1..];
+        }
+        let quotes = ">".repeat(quote_depth);
+        if content == "-- " {
+            ret.push_str(&quotes);
+            ret.push_str("-- \n");
+            continue;
+        }
+        let content = content.trim_end();
+        let limit = width.saturating_sub(quote_depth + 1).max(1);
+        for l in wrap(content, limit) {
+            ret.push_str(&quotes);
+            /* Space-stuff quoted lines and lines that would otherwise be misinterpreted.
+             * [rfc3676#section-4.4] */
+            if quote_depth > 0 || l.starts_with(' ') || l.starts_with('>') || l.starts_with("From ")
+            {
+                ret.push(' ');
+            }
+            ret.push_str(l);
+            ret.push('\n');
+        }
+    }
+    ret
+}
+
+/// Split `content` into lines of at most `limit` characters where possible. Every line but the
+/// last ends in the space it was broken at, which makes it a soft line break.
+fn wrap(content: &str, limit: usize) -> Vec<&str> {
+    let mut ret = Vec::new();
+    let mut rest = content;
+    while rest.chars().count() > limit {
+        let mut cut = None;
+        for (i, c) in rest.char_indices().take(limit) {
+            if c == ' ' && i > 0 {
+                cut = Some(i);
+            }
+        }
+        /* If there's no space to break at within the limit, break at the first one after it
+         * instead. */
+        let cut = cut.or_else(|| {
+            rest.char_indices()
+                .skip(1)
+                .find(|&(_, c)| c == ' ')
+                .map(|(i, _)| i)
+        });
+        let cut = match cut {
+            Some(i) if i + 1 < rest.len() => i,
+            _ => break,
+        };
+        ret.push(&rest[..=cut]);
+        rest = &rest[cut + 1..];
+    }
+    ret.push(rest);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_flowed_decode() {
+        let text = "`Take some more tea,' the March Hare said to Alice, very \r\nearnestly.\r\n\r\n>> I've had nothing yet, \r\n>> so I can't take more.\r\n> -- \r\n>From here\r\n";
+        let paragraphs = decode(text, false);
+        assert_eq!(
+            paragraphs
+                .iter()
+                .map(|p| (p.quote_depth, p.text()))
+                .collect::<Vec<(usize, &str)>>(),
+            vec![
+                (
+                    0,
+                    "`Take some more tea,' the March Hare said to Alice, very earnestly."
+                ),
+                (0, ""),
+                (2, "I've had nothing yet, so I can't take more."),
+                (1, "-- "),
+                (1, "From here"),
+            ]
+        );
+
+        let paragraphs = decode("Supercali \nfragilistic\n", true);
+        assert_eq!(paragraphs.len(), 1);
+        assert_eq!(paragraphs[0].text(), "Supercalifragilistic");
+
+        /* Quote depth changes end a paragraph even after a soft line break. */
+        let paragraphs = decode("> quoted \nunquoted\n", false);
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[0].text(), "quoted");
+        assert_eq!(paragraphs[1].text(), "unquoted");
+    }
+
+    #[test]
+    fn test_format_flowed_encode() {
+        let text = "From the very beginning, the quick brown fox jumps over the lazy dog. \n>> A quoted line\n -- \n-- \n";
+        let encoded = encode(text, 30);
+        assert_eq!(
+            encoded.as_str(),
+            " From the very beginning, the \nquick brown fox jumps over \nthe lazy dog.\n>> A quoted line\n  --\n-- \n"
+        );
+        assert_eq!(
+            decode(&encoded, false)
+                .iter()
+                .map(|p| p.quote_prefix() + p.text())
+                .collect::<Vec<String>>(),
+            vec![
+                "From the very beginning, the quick brown fox jumps over the lazy dog.",
+                ">> A quoted line",
+                " --",
+                "-- ",
+            ]
+        );
+    }
+}
@@ -21,6 +21,7 @@
 
 extern crate unicode_segmentation;
 use self::unicode_segmentation::UnicodeSegmentation;
+use super::format_flowed;
 use super::grapheme_clusters::TextProcessing;
 use super::tables::LINE_BREAK_RULES;
 use super::types::LineBreakClass;
@@ -1016,75 +1017,10 @@ pub fn split_lines_reflow(text: &str, reflow: Reflow, width: Option<usize>) -> V
         Reflow::FormatFlowed => {
             /* rfc3676 - The Text/Plain Format and DelSp Parameters
              * https://tools.ietf.org/html/rfc3676 */
-
-            let mut ret = Vec::new();
-            /*
-             * - Split lines with indices using str::match_indices()
-             * - Iterate and reflow flow regions, and pass fixed regions through
-             */
-            let lines_indices: Vec<usize> = text.match_indices('\n').map(|(i, _)| i).collect();
-            let mut prev_index = 0;
-            let mut in_paragraph = false;
-            let mut paragraph_start = 0;
-
-            let mut prev_quote_depth = 0;
-            for i in &lines_indices {
-                let line = &text[prev_index..*i];
-                let mut trimmed = line.trim_start().lines().next().unwrap_or("");
-                let mut quote_depth = 0;
-                let p_str: usize = trimmed
-                    .as_bytes()
-                    .iter()
-                    .position(|&b| {
-                        if b != b'>' {
-                            /* position() is short-circuiting */
-                            true
-                        } else {
-                            quote_depth += 1;
-                            false
-                        }
-                    })
-                    .unwrap_or(0);
-                trimmed = &trimmed[p_str..];
-                if trimmed.starts_with(' ') {
-                    /* Remove space stuffing before checking for ending space character.
-                     * [rfc3676#section-4.4] */
-                    trimmed = &trimmed[1..];
-                }
-
-                if trimmed.ends_with(' ') {
-                    if !in_paragraph {
-                        in_paragraph = true;
-                        paragraph_start = prev_index;
-                    } else if prev_quote_depth == quote_depth {
-                        /* This becomes part of the paragraph we're in */
-                    } else {
-                        /*Malformed line, different quote depths can't be in the same paragraph. */
-                        let paragraph = &text[paragraph_start..prev_index];
-                        reflow_helper(&mut ret, paragraph, prev_quote_depth, in_paragraph, width);
-
-                        paragraph_start = prev_index;
-                    }
-                } else {
-                    if prev_quote_depth == quote_depth || !in_paragraph {
-                        let paragraph = &text[paragraph_start..*i];
-                        reflow_helper(&mut ret, paragraph, quote_depth, in_paragraph, width);
-                    } else {
-                        /*Malformed line, different quote depths can't be in the same paragraph. */
-                        let paragraph = &text[paragraph_start..prev_index];
-                        reflow_helper(&mut ret, paragraph, prev_quote_depth, in_paragraph, width);
-                        let paragraph = &text[prev_index..*i];
-                        reflow_helper(&mut ret, paragraph, quote_depth, false, width);
-                    }
-                    paragraph_start = *i;
-                    in_paragraph = false;
-                }
-                prev_quote_depth = quote_depth;
-                prev_index = *i;
-            }
-            let paragraph = &text[paragraph_start..text.len()];
-            reflow_helper(&mut ret, paragraph, prev_quote_depth, in_paragraph, width);
-            ret
+            format_flowed::decode(text, false)
+                .iter()
+                .flat_map(|p| p.reflow(width))
+                .collect()
         }
         Reflow::All => {
             if let Some(width) = width {
@@ -1167,49 +1103,6 @@ fn split(ret: &mut Vec<String>, mut line: &str, width: usize) {
         line = &line[chop_index..];
     }
 }
-fn reflow_helper(
-    ret: &mut Vec<String>,
-    paragraph: &str,
-    quote_depth: usize,
-    in_paragraph: bool,
-    width: Option<usize>,
-) {
-    if quote_depth > 0 {
-        let quotes: String = ">".repeat(quote_depth);
-        let paragraph = paragraph
-            .trim_start_matches(&quotes)
-            .replace(&format!("\n{}", &quotes), "")
-            .replace("\n", "")
-            .replace("\r", "");
-        if in_paragraph {
-            if let Some(width) = width {
-                ret.extend(
-                    linear(&paragraph, width.saturating_sub(quote_depth))
-                        .into_iter()
-                        .map(|l| format!("{}{}", &quotes, l)),
-                );
-            } else {
-                ret.push(format!("{}{}", &quotes, &paragraph));
-            }
-        } else {
-            ret.push(format!("{}{}", &quotes, &paragraph));
-        }
-    } else {
-        let paragraph = paragraph.replace("\n", "").replace("\r", "");
-
-        if in_paragraph {
-            if let Some(width) = width {
-                let ex = linear(&paragraph, width);
-                ret.extend(ex.into_iter());
-            } else {
-                ret.push(paragraph);
-            }
-        } else {
-            ret.push(paragraph);
-        }
-    }
-}
-
 #[test]
 fn test_reflow() {
     let text = r#"`Take some more tea,' the March Hare said to Alice, very 
@@ -1246,7 +1139,6 @@ easy to take MORE than nothing.'"#;
     }
 }
 
-
 mod segment_tree {
     /*! Simple segment tree implementation for maximum in range queries. This is useful if given an
      *  array of numbers you want to get the maximum value inside an interval quickly.
@@ -1465,114 +1357,14 @@ impl Iterator for LineBreakText {
             ReflowState::ReflowFormatFlowed { ref mut cur_index } => {
                 /* rfc3676 - The Text/Plain Format and DelSp Parameters
                  * https://tools.ietf.org/html/rfc3676 */
-
-                /*
-                 * - Split lines with indices using str::match_indices()
-                 * - Iterate and reflow flow regions, and pass fixed regions through
-                 */
                 self.paragraph_start_index = *cur_index;
-                let line_indices_iter = self.text[*cur_index..].match_indices('\n').map(|(i, _)| i);
-                let start_offset = *cur_index;
-                let mut prev_index = *cur_index;
-                let mut in_paragraph = false;
-                let mut paragraph_start = *cur_index;
-
-                let mut prev_quote_depth = 0;
-                let mut paragraph = VecDeque::new();
-                for i in line_indices_iter {
-                    let i = i + start_offset + 1;
-                    let line = &self.text[prev_index..i];
-                    let mut trimmed = line.trim_start().lines().next().unwrap_or("");
-                    let mut quote_depth = 0;
-                    let p_str: usize = trimmed
-                        .as_bytes()
-                        .iter()
-                        .position(|&b| {
-                            if b != b'>' {
-                                /* position() is short-circuiting */
-                                true
-                            } else {
-                                quote_depth += 1;
-                                false
-                            }
-                        })
-                        .unwrap_or(0);
-                    trimmed = &trimmed[p_str..];
-                    if trimmed.starts_with(' ') {
-                        /* Remove space stuffing before checking for ending space character.
-                         * [rfc3676#section-4.4] */
-                        trimmed = &trimmed[1..];
-                    }
-
-                    if trimmed.ends_with(' ') {
-                        if !in_paragraph {
-                            in_paragraph = true;
-                            paragraph_start = prev_index;
-                        } else if prev_quote_depth == quote_depth {
-                            /* This becomes part of the paragraph we're in */
-                        } else {
-                            /*Malformed line, different quote depths can't be in the same paragraph. */
-                            let paragraph_s = &self.text[paragraph_start..prev_index];
-                            reflow_helper2(
-                                &mut paragraph,
-                                paragraph_s,
-                                prev_quote_depth,
-                                in_paragraph,
-                                self.width,
-                            );
-
-                            paragraph_start = prev_index;
-                        }
-                    } else {
-                        if prev_quote_depth == quote_depth || !in_paragraph {
-                            let paragraph_s = &self.text[paragraph_start..i];
-                            reflow_helper2(
-                                &mut paragraph,
-                                paragraph_s,
-                                quote_depth,
-                                in_paragraph,
-                                self.width,
-                            );
-                        } else {
-                            /*Malformed line, different quote depths can't be in the same paragraph. */
-                            let paragraph_s = &self.text[paragraph_start..prev_index];
-                            reflow_helper2(
-                                &mut paragraph,
-                                paragraph_s,
-                                prev_quote_depth,
-                                in_paragraph,
-                                self.width,
-                            );
-                            let paragraph_s = &self.text[prev_index..i];
-                            reflow_helper2(
-                                &mut paragraph,
-                                paragraph_s,
-                                quote_depth,
-                                false,
-                                self.width,
-                            );
-                        }
-                        *cur_index = i;
-                        std::mem::swap(&mut self.paragraph, &mut paragraph);
-                        paragraph_start = i;
-                        in_paragraph = false;
-                        break;
-                    }
-                    *cur_index = i;
-                    prev_quote_depth = quote_depth;
-                    prev_index = i;
-                }
-                if in_paragraph {
-                    let paragraph_s = &self.text[paragraph_start..self.text.len()];
+                if let Some((paragraph, consumed)) =
+                    format_flowed::next_paragraph(&self.text[*cur_index..], false)
+                {
+                    *cur_index += consumed;
+                    self.paragraph = paragraph.reflow(self.width).into();
+                } else {
                     *cur_index = self.text.len();
-                    reflow_helper2(
-                        &mut paragraph,
-                        paragraph_s,
-                        prev_quote_depth,
-                        in_paragraph,
-                        self.width,
-                    );
-                    self.paragraph = paragraph;
                 }
                 return self.paragraph.pop_front();
             }
@@ -1753,46 +1545,3 @@ impl Iterator for LineBreakText {
         }
     }
 }
-
-fn reflow_helper2(
-    ret: &mut VecDeque<String>,
-    paragraph: &str,
-    quote_depth: usize,
-    in_paragraph: bool,
-    width: Option<usize>,
-) {
-    if quote_depth > 0 {
-        let quotes: String = ">".repeat(quote_depth);
-        let paragraph = paragraph
-            .trim_start_matches(&quotes)
-            .replace(&format!("\n{}", &quotes), "")
-            .replace("\n", "")
-            .replace("\r", "");
-        if in_paragraph {
-            if let Some(width) = width {
-                ret.extend(
-                    linear(&paragraph, width.saturating_sub(quote_depth))
-                        .into_iter()
-                        .map(|l| format!("{}{}", &quotes, l)),
-                );
-            } else {
-                ret.push_back(format!("{}{}", &quotes, &paragraph));
-            }
-        } else {
-            ret.push_back(format!("{}{}", &quotes, &paragraph));
-        }
-    } else {
-        let paragraph = paragraph.replace("\n", "").replace("\r", "");
-
-        if in_paragraph {
-            if let Some(width) = width {
-                let ex = linear(&paragraph, width);
-                ret.extend(ex.into_iter());
-            } else {
-                ret.push_back(paragraph);
-            }
-        } else {
-            ret.push_back(paragraph);
-        }
-    }
-}
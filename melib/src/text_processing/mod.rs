@@ -19,6 +19,7 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
+pub mod format_flowed;
 pub mod grapheme_clusters;
 pub mod line_break;
 pub mod search;
@@ -148,6 +148,7 @@ macro_rules! make {
                         ref mut unseen,
                         ref mut snoozed,
                         ref mut attachments,
+                        ref mut rule,
                         ..
                     }, ThreadGroup::Root(Thread {
                         date: old_date,
@@ -155,6 +156,7 @@ macro_rules! make {
                         unseen: old_unseen,
                         snoozed: old_snoozed,
                         attachments: old_attachments,
+                        rule: old_rule,
                         ..
                     })) => {
                         *date = std::cmp::max(old_date, *date);
@@ -162,6 +164,7 @@ macro_rules! make {
                         *unseen += old_unseen;
                         *attachments += old_attachments;
                         *snoozed |= old_snoozed;
+                        *rule = rule.or(old_rule);
                     }
                     _ => unreachable!(),
                  }
@@ -278,6 +281,19 @@ impl FromStr for SortOrder {
     }
 }
 
+/// What happens to new messages of a thread, set by the user on the thread's root Message-ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadRule {
+    /// New messages are marked as read and the thread has no unread messages.
+    Muted,
+    /// New messages are notified about even if notifications are off.
+    Watched,
+}
+
+/// Thread rules by root Message-ID, shared by the `Threads` of every mailbox of an account.
+pub type ThreadRules = Arc<RwLock<HashMap<String, ThreadRule>>>;
+
 #[derive(Default, Clone, Debug, Deserialize, Serialize)]
 pub struct Thread {
     pub root: ThreadNodeHash,
@@ -287,6 +303,7 @@ pub struct Thread {
     pub attachments: usize,
 
     pub snoozed: bool,
+    pub rule: Option<ThreadRule>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -322,14 +339,34 @@ macro_rules! property {
 impl Thread {
     property!(root: ThreadNodeHash);
     property!(len: usize);
-    property!(unseen: usize);
     property!(snoozed: bool);
     property!(date: UnixTimestamp);
 
+    /// Number of unread messages; muted threads are never unread.
+    pub fn unseen(&self) -> usize {
+        if self.is_muted() {
+            0
+        } else {
+            self.unseen
+        }
+    }
+
     pub fn has_attachments(&self) -> bool {
         self.attachments > 0
     }
 
+    pub fn rule(&self) -> Option<ThreadRule> {
+        self.rule
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.rule == Some(ThreadRule::Muted)
+    }
+
+    pub fn is_watched(&self) -> bool {
+        self.rule == Some(ThreadRule::Watched)
+    }
+
     pub fn set_snoozed(&mut self, val: bool) {
         self.snoozed = val;
     }
@@ -421,6 +458,8 @@ pub struct Threads {
     pub hash_set: HashSet<EnvelopeHash>,
     sort: RefCell<(SortField, SortOrder)>,
     subsort: RefCell<(SortField, SortOrder)>,
+    #[serde(skip)]
+    rules: ThreadRules,
 }
 
 impl PartialEq for ThreadNode {
@@ -437,6 +476,47 @@ impl Threads {
         self.thread_ref(self.thread_nodes[&h].group).snoozed()
     }
 
+    /// Shares the thread rules of the account; they are applied to messages inserted from now on.
+    pub fn set_rules(&mut self, rules: ThreadRules) {
+        self.rules = rules;
+    }
+
+    /// Returns the Message-ID of the root of `thread`, which identifies it in `ThreadRules`.
+    pub fn root_message_id(&self, thread: ThreadHash) -> Option<String> {
+        let root = self.thread_ref(thread).root();
+        self.message_ids
+            .iter()
+            .find(|(_, h)| **h == root)
+            .map(|(message_id, _)| String::from_utf8_lossy(message_id).into_owned())
+    }
+
+    /// Sets the rule of the thread whose root is `message_id`, if it is in this mailbox.
+    pub fn set_rule(&mut self, message_id: &str, rule: Option<ThreadRule>) {
+        if let Some(node) = self.message_ids.get(message_id.as_bytes()) {
+            let group = self.thread_nodes[node].group;
+            self.thread_ref_mut(group).rule = rule;
+        }
+    }
+
+    /// The rule of a thread one of the message's Message-ID, In-Reply-To or References is the
+    /// root of.
+    fn find_rule(&self, envelope: &Envelope) -> Option<ThreadRule> {
+        let rules = self.rules.read().unwrap();
+        if rules.is_empty() {
+            return None;
+        }
+        let references = envelope.references();
+        for id in std::iter::once(envelope.message_id().raw())
+            .chain(envelope.in_reply_to().map(StrBuild::raw))
+            .chain(references.iter().map(|r| r.raw()))
+        {
+            if let Some(rule) = rules.get(String::from_utf8_lossy(id).as_ref()) {
+                return Some(*rule);
+            }
+        }
+        None
+    }
+
     pub fn thread_ref(&self, h: ThreadHash) -> &Thread {
         match self.groups[&self.find_group(h)] {
             ThreadGroup::Root(ref root) => root,
@@ -738,6 +818,7 @@ impl Threads {
                         0
                     },
                     snoozed: false,
+                    rule: None,
                 }),
             );
         } else {
@@ -781,6 +862,7 @@ impl Threads {
                     unseen: 0,
                     attachments: 0,
                     snoozed: false,
+                    rule: None,
                 }),
             );
             make!((reply_to_id) parent of (new_id), self);
@@ -824,6 +906,7 @@ impl Threads {
                             unseen: 0,
                             attachments: 0,
                             snoozed: false,
+                            rule: None,
                         }),
                     );
                     make!((id) parent of (current_descendant_id), self);
@@ -834,6 +917,9 @@ impl Threads {
                 }
             }
         }
+        if let Some(rule) = self.find_rule(&envelopes_lck[&env_hash]) {
+            self.thread_ref_mut(self.thread_nodes[&new_id].group).rule = Some(rule);
+        }
         drop(envelopes_lck);
         self.update_show_subject(new_id, env_hash, envelopes);
         envelopes
@@ -1391,3 +1477,147 @@ fn save_graph(
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(message_id: &str, date: &str, extra_headers: &str) -> Envelope {
+        Envelope::from_bytes(
+            format!(
+                "From: user@example.com\r\nMessage-ID: <{}>\r\nDate: Mon, 1 Jun 2020 {} +0000\r\n{}Subject: test\r\n\r\nbody\r\n",
+                message_id, date, extra_headers
+            )
+            .as_bytes(),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn add(envelopes: &Envelopes, envelope: Envelope) -> EnvelopeHash {
+        let hash = envelope.hash();
+        envelopes.write().unwrap().insert(hash, envelope);
+        hash
+    }
+
+    fn thread_of(threads: &Threads, envelopes: &Envelopes, hash: EnvelopeHash) -> ThreadHash {
+        let node = envelopes.read().unwrap()[&hash].thread();
+        threads.find_group(threads.thread_nodes()[&node].group)
+    }
+
+    #[test]
+    fn test_thread_rule_spreads_to_replies() {
+        let rules = ThreadRules::default();
+        rules
+            .write()
+            .unwrap()
+            .insert("a@example.com".to_string(), ThreadRule::Muted);
+
+        /* A reply whose root isn't in the mailbox gets the rule through insert. */
+        let envelopes = Envelopes::default();
+        let mut threads = Threads::new(1);
+        threads.set_rules(rules.clone());
+        let reply = add(
+            &envelopes,
+            envelope(
+                "b@example.com",
+                "11:00:00",
+                "In-Reply-To: <a@example.com>\r\n",
+            ),
+        );
+        threads.insert(&envelopes, reply);
+        let thread = threads.thread_ref(thread_of(&threads, &envelopes, reply));
+        assert!(thread.is_muted());
+        assert_eq!(thread.unseen(), 0);
+
+        /* A root inserted before the rule was shared, and a reply from another mailbox. */
+        let envelopes = Envelopes::default();
+        let mut threads = Threads::new(2);
+        let root = add(&envelopes, envelope("a@example.com", "10:00:00", ""));
+        threads.insert(&envelopes, root);
+        threads.set_rules(rules);
+        assert_eq!(
+            threads
+                .thread_ref(thread_of(&threads, &envelopes, root))
+                .rule(),
+            None
+        );
+        let reply = add(
+            &envelopes,
+            envelope(
+                "c@example.com",
+                "12:00:00",
+                "In-Reply-To: <a@example.com>\r\nReferences: <a@example.com>\r\n",
+            ),
+        );
+        assert!(threads.insert_reply(&envelopes, reply));
+        assert_eq!(
+            thread_of(&threads, &envelopes, reply),
+            thread_of(&threads, &envelopes, root)
+        );
+        assert!(threads
+            .thread_ref(thread_of(&threads, &envelopes, root))
+            .is_muted());
+    }
+
+    #[test]
+    fn test_thread_rule_survives_reparenting() {
+        let envelopes = Envelopes::default();
+        let mut threads = Threads::new(3);
+        let parent = add(&envelopes, envelope("p@example.com", "09:00:00", ""));
+        threads.insert(&envelopes, parent);
+        let root = add(&envelopes, envelope("a@example.com", "10:00:00", ""));
+        threads.insert(&envelopes, root);
+        let root_thread = thread_of(&threads, &envelopes, root);
+        assert_ne!(root_thread, thread_of(&threads, &envelopes, parent));
+        assert_eq!(
+            threads
+                .root_message_id(root_thread)
+                .as_ref()
+                .map(String::as_str),
+            Some("a@example.com")
+        );
+        threads.set_rule("a@example.com", Some(ThreadRule::Watched));
+        assert!(threads.thread_ref(root_thread).is_watched());
+
+        /* The reply's References make the watched root a reply to the other thread. */
+        let reply = add(
+            &envelopes,
+            envelope(
+                "c@example.com",
+                "11:00:00",
+                "In-Reply-To: <a@example.com>\r\nReferences: <p@example.com> <a@example.com>\r\n",
+            ),
+        );
+        threads.insert(&envelopes, reply);
+        let thread = thread_of(&threads, &envelopes, root);
+        assert_eq!(thread, thread_of(&threads, &envelopes, parent));
+        assert_eq!(thread, thread_of(&threads, &envelopes, reply));
+        assert_eq!(threads.thread_ref(thread).len(), 3);
+        assert!(threads.thread_ref(thread).is_watched());
+    }
+
+    #[test]
+    fn test_muted_thread_unseen() {
+        let envelopes = Envelopes::default();
+        let mut threads = Threads::new(2);
+        let root = add(&envelopes, envelope("a@example.com", "10:00:00", ""));
+        threads.insert(&envelopes, root);
+        let reply = add(
+            &envelopes,
+            envelope(
+                "b@example.com",
+                "11:00:00",
+                "In-Reply-To: <a@example.com>\r\n",
+            ),
+        );
+        threads.insert(&envelopes, reply);
+        let thread = thread_of(&threads, &envelopes, root);
+        assert_eq!(threads.thread_ref(thread).unseen(), 2);
+        threads.set_rule("a@example.com", Some(ThreadRule::Muted));
+        assert_eq!(threads.thread_ref(thread).unseen(), 0);
+        assert_eq!(threads.thread_ref(thread).unseen, 2);
+        threads.set_rule("a@example.com", None);
+        assert_eq!(threads.thread_ref(thread).unseen(), 2);
+    }
+}
@@ -0,0 +1,415 @@
+/*
+ * meli - autocrypt peer state
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Per-account [Autocrypt Level 1](https://autocrypt.org/level1.html) peer state, kept in an
+ * sqlite3 database next to the search index.
+ *
+ * The state of each peer is updated from the `Autocrypt` header of every message they send, and
+ * from `Autocrypt-Gossip` headers found inside encrypted messages. It is then used to recommend
+ * whether outgoing mail should be encrypted.
+ */
+
+use crate::components::{ComponentId, UIConfirmationDialog};
+use crate::conf::accounts::JobRequest;
+use crate::state::Context;
+use crate::types::{CallbackFn, StatusEvent, ThreadEvent, UIEvent};
+use melib::backends::AccountHash;
+use melib::datetime::{self, UnixTimestamp};
+use melib::email::autocrypt::{AutocryptHeader, PreferEncrypt};
+use melib::sqlite3::{
+    self as melib_sqlite3,
+    rusqlite::{self, params, OptionalExtension},
+    DatabaseDescription,
+};
+use melib::{MeliError, Result};
+
+const DB: DatabaseDescription = DatabaseDescription {
+    name: "autocrypt.db",
+    init_script: Some(
+        "CREATE TABLE IF NOT EXISTS peers (
+                    addr                 TEXT PRIMARY KEY NOT NULL,
+                    last_seen            INTEGER NOT NULL,
+                    autocrypt_timestamp  INTEGER NOT NULL DEFAULT 0,
+                    public_key           BLOB,
+                    prefer_encrypt       INTEGER NOT NULL DEFAULT 0,
+                    gossip_timestamp     INTEGER NOT NULL DEFAULT 0,
+                    gossip_key           BLOB
+                  );",
+    ),
+    version: 1,
+};
+
+/// Peers whose last `Autocrypt` header is older than this are considered stale.
+const STALE_INTERVAL: UnixTimestamp = 35 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PeerPreference {
+    NoPreference = 0,
+    Mutual = 1,
+    /// The peer sent mail without an `Autocrypt` header after having sent one.
+    Reset = 2,
+}
+
+impl From<i64> for PeerPreference {
+    fn from(val: i64) -> Self {
+        match val {
+            1 => PeerPreference::Mutual,
+            2 => PeerPreference::Reset,
+            _ => PeerPreference::NoPreference,
+        }
+    }
+}
+
+/// Autocrypt UI recommendation for a message, ordered from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Recommendation {
+    /// At least one recipient has no known key.
+    Disable,
+    /// Keys are known but might be out of date.
+    Discourage,
+    Available,
+    /// Every recipient and the sender prefer encryption.
+    Encrypt,
+}
+
+impl std::fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Recommendation::Disable => write!(f, "unavailable"),
+            Recommendation::Discourage => write!(f, "discouraged"),
+            Recommendation::Available => write!(f, "available"),
+            Recommendation::Encrypt => write!(f, "recommended"),
+        }
+    }
+}
+
+fn open(account_name: &str) -> Result<rusqlite::Connection> {
+    melib_sqlite3::open_or_create_db(&DB, Some(account_name))
+}
+
+/// Update the state of `from` with a message sent at `date`. `autocrypt` is the message's
+/// `Autocrypt` header if it had a valid one whose address matches `from`. Returns the keydata
+/// that should be imported in the keyring, if any.
+pub fn update_peer(
+    account_name: &str,
+    from: &str,
+    date: UnixTimestamp,
+    autocrypt: Option<&AutocryptHeader>,
+) -> Result<Option<Vec<u8>>> {
+    let date = std::cmp::min(date, datetime::now());
+    let from = from.to_lowercase();
+    let conn = open(account_name)?;
+    let peer: Option<(i64, i64, Option<Vec<u8>>)> = conn
+        .query_row(
+            "SELECT last_seen, autocrypt_timestamp, public_key FROM peers WHERE addr = ?",
+            params![&from],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    if let Some((last_seen, _, _)) = peer {
+        if date as i64 <= last_seen {
+            return Ok(None);
+        }
+    }
+    match (autocrypt, peer) {
+        (Some(header), peer) => {
+            let prefer_encrypt = if header.prefer_encrypt == PreferEncrypt::Mutual {
+                PeerPreference::Mutual
+            } else {
+                PeerPreference::NoPreference
+            };
+            conn.execute(
+                "INSERT INTO peers (addr, last_seen, autocrypt_timestamp, public_key, prefer_encrypt)
+                 VALUES (?1, ?2, ?2, ?3, ?4)
+                 ON CONFLICT(addr) DO UPDATE SET last_seen = ?2, autocrypt_timestamp = ?2,
+                 public_key = ?3, prefer_encrypt = ?4",
+                params![&from, date as i64, &header.keydata, prefer_encrypt as i64],
+            )?;
+            if peer.and_then(|(_, _, key)| key).as_ref() == Some(&header.keydata) {
+                Ok(None)
+            } else {
+                Ok(Some(header.keydata.clone()))
+            }
+        }
+        (None, Some((_, autocrypt_timestamp, _))) => {
+            if date as i64 > autocrypt_timestamp {
+                conn.execute(
+                    "UPDATE peers SET last_seen = ?2, prefer_encrypt = ?3 WHERE addr = ?1",
+                    params![&from, date as i64, PeerPreference::Reset as i64],
+                )?;
+            } else {
+                conn.execute(
+                    "UPDATE peers SET last_seen = ?2 WHERE addr = ?1",
+                    params![&from, date as i64],
+                )?;
+            }
+            Ok(None)
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// Update the gossiped key of a peer from an `Autocrypt-Gossip` header of a message sent at
+/// `date`. Returns the keydata that should be imported in the keyring, if any.
+pub fn update_gossip(
+    account_name: &str,
+    date: UnixTimestamp,
+    gossip: &AutocryptHeader,
+) -> Result<Option<Vec<u8>>> {
+    let date = std::cmp::min(date, datetime::now());
+    let conn = open(account_name)?;
+    let gossip_timestamp: Option<i64> = conn
+        .query_row(
+            "SELECT gossip_timestamp FROM peers WHERE addr = ?",
+            params![&gossip.addr],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if gossip_timestamp.map(|t| date as i64 <= t).unwrap_or(false) {
+        return Ok(None);
+    }
+    conn.execute(
+        "INSERT INTO peers (addr, last_seen, gossip_timestamp, gossip_key)
+         VALUES (?1, 0, ?2, ?3)
+         ON CONFLICT(addr) DO UPDATE SET gossip_timestamp = ?2, gossip_key = ?3",
+        params![&gossip.addr, date as i64, &gossip.keydata],
+    )?;
+    Ok(Some(gossip.keydata.clone()))
+}
+
+/// Compute the Autocrypt recommendation for sending to `recipients`. `prefer_encrypt` is the
+/// account's own preference.
+pub fn recommendation(
+    account_name: &str,
+    prefer_encrypt: bool,
+    recipients: &[String],
+) -> Result<Recommendation> {
+    if recipients.is_empty() {
+        return Ok(Recommendation::Disable);
+    }
+    let conn = open(account_name)?;
+    let mut ret = Recommendation::Encrypt;
+    for addr in recipients {
+        let peer: Option<(i64, i64, bool, i64, bool)> = conn
+            .query_row(
+                "SELECT last_seen, autocrypt_timestamp, public_key IS NOT NULL, prefer_encrypt,
+                 gossip_key IS NOT NULL FROM peers WHERE addr = ?",
+                params![addr.to_lowercase()],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let peer_recommendation = match peer {
+            None | Some((_, _, false, _, false)) => Recommendation::Disable,
+            Some((_, _, false, _, true)) => Recommendation::Discourage,
+            Some((last_seen, autocrypt_timestamp, true, preference, _)) => {
+                if PeerPreference::from(preference) == PeerPreference::Reset
+                    || (last_seen - autocrypt_timestamp) as UnixTimestamp > STALE_INTERVAL
+                {
+                    Recommendation::Discourage
+                } else if prefer_encrypt
+                    && PeerPreference::from(preference) == PeerPreference::Mutual
+                {
+                    Recommendation::Encrypt
+                } else {
+                    Recommendation::Available
+                }
+            }
+        };
+        if peer_recommendation < ret {
+            ret = peer_recommendation;
+        }
+    }
+    Ok(ret)
+}
+
+/// Values of the `name` header fields in the header section of `bytes`, in order.
+pub fn header_values(bytes: &[u8], name: &str) -> Vec<String> {
+    melib::email::parser::headers::headers(bytes)
+        .map(|(_, headers)| {
+            headers
+                .into_iter()
+                .filter(|(n, _)| n.eq_ignore_ascii_case(name.as_bytes()))
+                .map(|(_, v)| String::from_utf8_lossy(v).to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The Autocrypt related headers of a message.
+#[derive(Debug)]
+pub struct MessageHeaders {
+    pub from: String,
+    pub date: UnixTimestamp,
+    /// `To` and `Cc` addresses: gossip about anyone else is ignored.
+    pub recipients: Vec<String>,
+    /// `Autocrypt` header values, or `None` when only gossip should be processed, e.g. for the
+    /// decrypted part of a message whose outer headers were already seen.
+    pub autocrypt: Option<Vec<String>>,
+    /// `Autocrypt-Gossip` header values.
+    pub gossip: Vec<String>,
+}
+
+/// Update the peer state of `account_name` from the headers of a message. Returns the addresses
+/// and keydata of keys that weren't known before.
+fn update_state(account_name: &str, headers: &MessageHeaders) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut new_keys = vec![];
+    if let Some(ref autocrypt) = headers.autocrypt {
+        /* A message with more than one valid Autocrypt header is treated as having none. */
+        let mut valid = autocrypt
+            .iter()
+            .filter_map(|v| AutocryptHeader::parse(v.as_bytes()).ok())
+            .filter(|h| h.addr.eq_ignore_ascii_case(&headers.from))
+            .collect::<Vec<AutocryptHeader>>();
+        let header = if valid.len() == 1 { valid.pop() } else { None };
+        if let Some(keydata) =
+            update_peer(account_name, &headers.from, headers.date, header.as_ref())?
+        {
+            new_keys.push((headers.from.clone(), keydata));
+        }
+    }
+    for value in &headers.gossip {
+        if let Ok(gossip) = AutocryptHeader::parse(value.as_bytes()) {
+            if !headers
+                .recipients
+                .iter()
+                .any(|r| r.eq_ignore_ascii_case(&gossip.addr))
+            {
+                continue;
+            }
+            if let Some(keydata) = update_gossip(account_name, headers.date, &gossip)? {
+                new_keys.push((gossip.addr.clone(), keydata));
+            }
+        }
+    }
+    Ok(new_keys)
+}
+
+/// Update the peer state of `account_hash` from the headers of a message in a background job,
+/// and ask whether to import any new keys into the keyring.
+pub fn process_headers(context: &mut Context, account_hash: AccountHash, headers: MessageHeaders) {
+    if !*account_settings!(context[account_hash].pgp.autocrypt) {
+        return;
+    }
+    let account_name = context.accounts[&account_hash].name().to_string();
+    let sender = context.sender.clone();
+    let fut = async move {
+        let new_keys = update_state(&account_name, &headers)?;
+        if !new_keys.is_empty() {
+            sender
+                .send(ThreadEvent::UIEvent(UIEvent::Callback(CallbackFn(
+                    Box::new(move |context| ask_import(context, account_hash, new_keys)),
+                ))))
+                .map_err(|err| MeliError::new(err.to_string()))?;
+        }
+        Ok(())
+    };
+    let handle = context.job_executor.spawn_blocking(fut);
+    context.accounts[&account_hash].insert_job(
+        handle.job_id,
+        JobRequest::Generic {
+            name: "Autocrypt: updating peer state".into(),
+            handle,
+            on_finish: None,
+            logging_level: melib::LoggingLevel::TRACE,
+        },
+    );
+}
+
+/// Ask for confirmation before importing keys learned from Autocrypt headers into the keyring.
+fn ask_import(context: &mut Context, account_hash: AccountHash, keys: Vec<(String, Vec<u8>)>) {
+    let addresses = keys
+        .iter()
+        .map(|(addr, _)| addr.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+    let dialog = UIConfirmationDialog::new(
+        &format!("Import Autocrypt keys of {} into the keyring?", addresses),
+        vec![(true, "yes".to_string()), (false, "no".to_string())],
+        true,
+        Some(Box::new(move |_id: ComponentId, result: bool| {
+            Some(if result {
+                UIEvent::Callback(CallbackFn(Box::new(move |context| {
+                    import_keys(context, account_hash, keys)
+                })))
+            } else {
+                UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                    "Autocrypt keys were not imported.".to_string(),
+                ))
+            })
+        })),
+        context,
+    );
+    context
+        .replies
+        .push_back(UIEvent::GlobalUIDialog(Box::new(dialog)));
+}
+
+fn import_keys(context: &mut Context, account_hash: AccountHash, keys: Vec<(String, Vec<u8>)>) {
+    for (addr, keydata) in keys {
+        let fut = async move {
+            let mut ctx = melib::gpgme::Context::new()?;
+            let keydata = ctx.new_data_mem(&keydata)?;
+            ctx.import_key(keydata)?.await
+        };
+        let handle = context.job_executor.spawn_specialized(fut);
+        context.accounts[&account_hash].insert_job(
+            handle.job_id,
+            JobRequest::Generic {
+                name: format!("Autocrypt: importing key of {}", addr).into(),
+                handle,
+                on_finish: None,
+                logging_level: melib::LoggingLevel::INFO,
+            },
+        );
+    }
+}
+
+/// Build the `Autocrypt` header value for mail sent from `addr`, exporting the key matching
+/// `key_pattern` (or `addr`) from the keyring.
+pub async fn header(
+    addr: String,
+    key_pattern: Option<String>,
+    prefer_encrypt: bool,
+) -> Result<String> {
+    let mut ctx = melib::gpgme::Context::new()?;
+    ctx.set_flag(melib::gpgme::GpgmeFlag::AsciiArmor, false)?;
+    let keydata = ctx
+        .export_key(key_pattern.as_ref().unwrap_or(&addr))?
+        .await?;
+    Ok(AutocryptHeader::new(
+        addr,
+        if prefer_encrypt {
+            PreferEncrypt::Mutual
+        } else {
+            PreferEncrypt::NoPreference
+        },
+        keydata,
+    )
+    .to_string())
+}
@@ -74,8 +74,14 @@ use crate::conf::*;
 #[cfg(feature = "sqlite3")]
 pub mod sqlite3;
 
+#[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+pub mod autocrypt;
+
+pub mod control;
 pub mod jobs;
 pub mod mailcap;
+pub mod session;
+pub mod undo;
 
 use std::os::raw::c_int;
 
@@ -185,6 +191,17 @@ enum SubCommand {
         #[structopt(value_name = "INPUT", parse(from_os_str))]
         path: PathBuf,
     },
+
+    /// send a command or query to a running meli instance and print the reply.
+    #[structopt(display_order = 4)]
+    Ctl {
+        /// use specified control socket instead of $XDG_RUNTIME_DIR/meli/control.sock
+        #[structopt(long, value_name = "SOCKET_PATH", parse(from_os_str))]
+        socket: Option<PathBuf>,
+        /// a command such as `compose alice@example.com`, or one of the queries `unread`, `status` and `selection`.
+        #[structopt(value_name = "COMMAND", required = true)]
+        command: Vec<String>,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -312,6 +329,12 @@ fn run_app(opt: Opt) -> Result<()> {
                 )));
             }
         }
+        Some(SubCommand::Ctl {
+            ref socket,
+            ref command,
+        }) => {
+            return control::client(socket.as_ref().map(PathBuf::as_path), &command.join(" "));
+        }
         None => {}
     }
 
@@ -330,6 +353,7 @@ fn run_app(opt: Opt) -> Result<()> {
 
     /* Create the application State. */
     let mut state;
+    let mut _control_server = None;
 
     if let Some(SubCommand::View { path }) = opt.subcommand {
         let bytes = std::fs::read(&path)
@@ -341,14 +365,25 @@ fn run_app(opt: Opt) -> Result<()> {
             sender,
             receiver.clone(),
         )?;
-        state.register_component(Box::new(EnvelopeView::new(wrapper, None, None, 0)));
+        state.register_component(Box::new(EnvelopeView::new(wrapper, None, None, 0, None)));
     } else {
         state = State::new(None, sender, receiver.clone())?;
         #[cfg(feature = "svgscreenshot")]
         state.register_component(Box::new(components::svg::SVGScreenshotFilter::new()));
+        let session = session::Session::load().unwrap_or_else(|err| {
+            log(
+                format!("Could not load the previous session: {}", err),
+                melib::LoggingLevel::WARN,
+            );
+            None
+        });
+        let mut listing = listing::Listing::new(&mut state.context);
+        if let Some(ref session) = session {
+            listing.restore_session(session, &mut state.context);
+        }
         let window = Box::new(Tabbed::new(
             vec![
-                Box::new(listing::Listing::new(&mut state.context)),
+                Box::new(listing),
                 Box::new(ContactList::new(&state.context)),
             ],
             &state.context,
@@ -367,6 +402,20 @@ fn run_app(opt: Opt) -> Result<()> {
         state.register_component(Box::new(
             components::notifications::NotificationCommand::new(),
         ));
+        state.register_component(Box::new(
+            components::notifications::NewMailNotifications::new(&state.context),
+        ));
+        state.start_session(session);
+        for cmd in state.context.settings.commands.on_startup.clone() {
+            state.rcv_event(UIEvent::Command(cmd));
+        }
+        match control::ControlServer::new(state.sender()) {
+            Ok(server) => _control_server = Some(server),
+            Err(err) => log(
+                format!("Could not start the control socket: {}", err),
+                melib::LoggingLevel::WARN,
+            ),
+        }
     }
     let enter_command_mode: Key = state
         .context
@@ -424,6 +473,7 @@ fn run_app(opt: Opt) -> Result<()> {
                                     match k {
                                         _ if k == quit_key => {
                                             if state.can_quit_cleanly() {
+                                                state.save_session();
                                                 drop(state);
                                                 break 'main;
                                             } else {
@@ -502,6 +552,10 @@ fn run_app(opt: Opt) -> Result<()> {
                             }
                             //state.new_thread(id, name);
                         },
+                        ThreadEvent::Control(request) => {
+                            state.control_request(request);
+                            state.redraw();
+                        },
                     }
                 },
                 recv(signal_recvr) -> sig => {
@@ -21,13 +21,15 @@
 
 /*! A parser module for user commands passed through Command mode.
 */
+use crate::components::SplitDirection;
+use crate::conf::ListingLayout;
 use crate::melib::parser::BytesExt;
 use melib::nom::{
     self,
     branch::alt,
     bytes::complete::{is_a, is_not, tag, take_until},
     character::complete::{digit1, not_line_ending},
-    combinator::{map, map_res},
+    combinator::{map, map_res, opt},
     multi::separated_list,
     sequence::{pair, preceded, separated_pair},
     IResult,
@@ -261,6 +263,39 @@ define_commands!([
                        }
                    )
                  },
+                 { tags: ["archive"],
+                   desc: "move message to the account's Archive mailbox",
+                   tokens: &[One(Literal("archive"))],
+                   parser: (
+                       fn archive_message<'a>(input: &'a [u8]) -> IResult<&'a [u8], Action> {
+                           let (input, ret) = map(preceded(tag("archive"), eof), |_| Listing(Archive))(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, ret))
+                       }
+                   )
+                 },
+                 { tags: ["filter-dry-run"],
+                   desc: "show which of the account's filters would apply to each message of the current mailbox",
+                   tokens: &[One(Literal("filter-dry-run"))],
+                   parser: (
+                       fn filter_dry_run<'a>(input: &'a [u8]) -> IResult<&'a [u8], Action> {
+                           let (input, ret) = map(preceded(tag("filter-dry-run"), eof), |_| Listing(FilterDryRun))(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, ret))
+                       }
+                   )
+                 },
+                 { tags: ["undo", "redo"],
+                   desc: "undo/redo the last action on messages of the current account",
+                   tokens: &[One(Alternatives(&[to_stream!(One(Literal("undo"))), to_stream!(One(Literal("redo")))]))],
+                   parser: (
+                       fn undo_redo<'a>(input: &'a [u8]) -> IResult<&'a [u8], Action> {
+                           let (input, ret) = alt((map(tag("undo"), |_| Listing(Undo)), map(tag("redo"), |_| Listing(Redo))))(input.trim())?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, ret))
+                       }
+                   )
+                 },
                  { tags: ["copyto", "moveto"],
                    desc: "copy/move message",
                    tokens: &[One(Alternatives(&[to_stream!(One(Literal("copyto"))), to_stream!(One(Literal("moveto")))])), ZeroOrOne(AccountName), One(MailboxPath)],
@@ -342,6 +377,70 @@ define_commands!([
                        }
                    )
                  },
+                 { tags: ["go-to-mailbox "],
+                   desc: "go-to-mailbox ACCOUNT MAILBOX_PATH, open a mailbox by its path",
+                   tokens: &[One(Literal("go-to-mailbox")), One(AccountName), One(MailboxPath)],
+                   parser: (
+                       fn go_to_mailbox(input: &[u8]) -> IResult<&[u8], Action> {
+                           let (input, _) = tag("go-to-mailbox")(input.trim())?;
+                           let (input, _) = is_a(" ")(input)?;
+                           let (input, account) = quoted_argument(input)?;
+                           let (input, _) = is_a(" ")(input)?;
+                           let (input, path) = quoted_argument(input)?;
+                           let (input, _) = eof(input)?;
+                           Ok((input, Action::GoToMailbox(account.to_string(), path.to_string())))
+                       }
+                   )
+                 },
+                 { tags: ["refresh"],
+                   desc: "refresh, check the current mailbox for new mail",
+                   tokens: &[One(Literal("refresh"))],
+                   parser: (
+                       fn refresh_mailbox(input: &[u8]) -> IResult<&[u8], Action> {
+                           let (input, _) = tag("refresh")(input.trim())?;
+                           let (input, _) = eof(input.trim())?;
+                           Ok((input, Action::RefreshMailbox))
+                       }
+                   )
+                 },
+                 { tags: ["compose"],
+                   desc: "compose [ADDRESS], open a new draft, optionally addressed to ADDRESS or a mailto: URI",
+                   tokens: &[One(Literal("compose")), ZeroOrOne(QuotedStringValue)],
+                   parser: (
+                       fn compose(input: &[u8]) -> IResult<&[u8], Action> {
+                           let (input, _) = tag("compose")(input.trim())?;
+                           let (input, address) = opt(preceded(is_a(" "), quoted_argument))(input)?;
+                           let (input, _) = eof(input.trim())?;
+                           Ok((input, Action::NewDraft(address.map(String::from))))
+                       }
+                   )
+                 },
+                 { tags: ["split", "vsplit"],
+                   desc: "split/vsplit contacts/compose, show the contact list or a new draft below or beside the current tab",
+                   tokens: &[One(Alternatives(&[to_stream!(One(Literal("split"))), to_stream!(One(Literal("vsplit")))])), One(Alternatives(&[to_stream!(One(Literal("contacts"))), to_stream!(One(Literal("compose")))]))],
+                   parser: (
+                       fn split(input: &[u8]) -> IResult<&[u8], Action> {
+                           let (input, direction) = alt((map(tag("split"), |_| SplitDirection::Horizontal), map(tag("vsplit"), |_| SplitDirection::Vertical)))(input.trim())?;
+                           let (input, _) = is_a(" ")(input)?;
+                           let (input, pane) = alt((tag("contacts"), tag("compose")))(input)?;
+                           let (input, _) = eof(input.trim())?;
+                           Ok((input, Action::Split(direction, String::from_utf8_lossy(pane).into_owned())))
+                       }
+                   )
+                 },
+                 { tags: ["layout"],
+                   desc: "layout [default/three_columns/stacked], changes where the mail listing previews the message under the cursor",
+                   tokens: &[One(Literal("layout")), One(Alternatives(&[to_stream!(One(Literal("default"))), to_stream!(One(Literal("three_columns"))), to_stream!(One(Literal("stacked")))]))],
+                   parser: (
+                       fn layout(input: &[u8]) -> IResult<&[u8], Action> {
+                           let (input, _) = tag("layout")(input.trim())?;
+                           let (input, _) = is_a(" ")(input)?;
+                           let (input, layout) = alt((map(tag("default"), |_| ListingLayout::Default), map(tag("three_columns"), |_| ListingLayout::ThreeColumns), map(tag("stacked"), |_| ListingLayout::Stacked)))(input)?;
+                           let (input, _) = eof(input.trim())?;
+                           Ok((input, Listing(SetLayout(layout))))
+                       }
+                   )
+                 },
                  { tags: ["subsort"],
                    desc: "subsort [date/subject] [asc/desc], sorts first level replies in threads.",
                    tokens: &[One(Literal("subsort")), One(Alternatives(&[to_stream!(One(Literal("date"))), to_stream!(One(Literal("subject")))])), One(Alternatives(&[to_stream!(One(Literal("asc"))), to_stream!(One(Literal("desc")))])) ],
@@ -393,6 +492,31 @@ define_commands!([
                       }
                   )
                 },
+                { tags: ["toggle thread_mute", "toggle thread_watch"],
+                  desc: "mute or watch the thread under cursor, or clear its mute or watch rule",
+                  tokens: &[One(Literal("toggle")), One(Alternatives(&[to_stream!(One(Literal("thread_mute"))), to_stream!(One(Literal("thread_watch")))]))],
+                  parser: (
+                      fn toggle_thread_rule(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("toggle")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, ret) = alt((map(tag("thread_mute"), |_| Listing(ToggleThreadMute)), map(tag("thread_watch"), |_| Listing(ToggleThreadWatch))))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, ret))
+                      }
+                  )
+                },
+                { tags: ["snooze"],
+                  desc: "snooze [DATE], moves the thread under cursor to the snooze mailbox until DATE, such as `tomorrow`, `monday 9:00` or `in 2 hours`. Asks for the date if it is omitted",
+                  tokens: &[One(Literal("snooze")), ZeroOrOne(RestOfStringValue)],
+                  parser: (
+                      fn snooze(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("snooze")(input.trim())?;
+                          let (input, date) = opt(preceded(is_a(" "), map_res(not_line_ending, std::str::from_utf8)))(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Listing(Snooze(date.map(|d| d.trim().to_string()).filter(|d| !d.is_empty())))))
+                      }
+                  )
+                },
                 { tags: ["search"],
                   desc: "search <TERM>, searches list with given term",
                   tokens: &[One(Literal("search")), One(RestOfStringValue)],
@@ -675,6 +799,19 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["manage-sieve-scripts "],
+                  desc: "manage-sieve-scripts ACCOUNT, open the account's server-side Sieve filters in a new tab",
+                  tokens: &[One(Literal("manage-sieve-scripts")), One(AccountName)],
+                  parser:(
+                      fn manage_sieve_scripts(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("manage-sieve-scripts")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, account) = quoted_argument(input)?;
+                          let (input, _) = eof(input)?;
+                          Ok( (input, AccountAction(account.to_string(), ManageSieveScripts)))
+                      }
+                  )
+                },
                 { tags: ["open-in-tab"],
                   desc: "opens envelope view in new tab",
                   tokens: &[One(Literal("open-in-tab"))],
@@ -714,6 +851,62 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["rsvp", "rsvp accept", "rsvp decline", "rsvp tentative"],
+                  desc: "rsvp [accept/decline/tentative], reply to the calendar invitation of the opened message",
+                  tokens: &[One(Literal("rsvp")), One(Alternatives(&[to_stream!(One(Literal("accept"))), to_stream!(One(Literal("decline"))), to_stream!(One(Literal("tentative")))]))],
+                  parser:(
+                      fn rsvp(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("rsvp")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, partstat) = alt((
+                              map(tag("accept"), |_| melib::icalendar::PartStat::Accepted),
+                              map(tag("decline"), |_| melib::icalendar::PartStat::Declined),
+                              map(tag("tentative"), |_| melib::icalendar::PartStat::Tentative),
+                          ))(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(Rsvp(partstat))))
+                      }
+                  )
+                },
+                { tags: ["export-ics "],
+                  desc: "export-ics PATH, save the calendar attachment of the opened message",
+                  tokens: &[One(Literal("export-ics")), One(Filepath)],
+                  parser:(
+                      fn export_ics(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("export-ics")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, path) = quoted_argument(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(ExportCalendar(path.to_string()))))
+                      }
+                  )
+                },
+                { tags: ["view-as-charset "],
+                  desc: "view-as-charset auto/CHARSET, decode the text of the opened message as CHARSET instead of its declared charset",
+                  tokens: &[One(Literal("view-as-charset")), One(Alternatives(&[to_stream!(One(Literal("auto"))), to_stream!(One(AlphanumericStringValue))]))],
+                  parser:(
+                      fn view_as_charset(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("view-as-charset")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, charset) = alt((
+                              map(tag("auto"), |_| None),
+                              map_res(quoted_argument, |c: &str| {
+                                  let charset = melib::email::attachment_types::Charset::from(c.as_bytes());
+                                  if charset == melib::email::attachment_types::Charset::Ascii
+                                      && !c.eq_ignore_ascii_case("ascii")
+                                      && !c.eq_ignore_ascii_case("us-ascii")
+                                  {
+                                      Err(MeliError::new(format!("Unknown charset {}", c)))
+                                  } else {
+                                      Ok(Some(charset))
+                                  }
+                              }),
+                          ))(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, View(ViewAsCharset(charset))))
+                      }
+                  )
+                },
                 { tags: ["tag", "tag add", "tag remove"],
                    desc: "tag [add/remove], edits message's tags.",
                    tokens: &[One(Literal("tag")), One(Alternatives(&[to_stream!(One(Literal("add"))), to_stream!(One(Literal("remove")))]))],
@@ -791,6 +984,19 @@ Alternatives(&[to_stream!(One(Literal("add-attachment")), One(Filepath)), to_str
                       }
                   )
                 },
+                { tags: ["source "],
+                  desc: "source FILEPATH, execute the commands in a file, one per line",
+                  tokens: &[One(Literal("source")), One(Filepath)],
+                  parser:(
+                      fn source(input: &[u8]) -> IResult<&[u8], Action> {
+                          let (input, _) = tag("source")(input.trim())?;
+                          let (input, _) = is_a(" ")(input)?;
+                          let (input, path) = quoted_argument(input.trim())?;
+                          let (input, _) = eof(input)?;
+                          Ok((input, Action::Source(path.to_string())))
+                      }
+                  )
+                },
                 { tags: ["reload-config"],
                   desc: "reload configuration file",
                   tokens: &[One(Literal("reload-config"))],
@@ -846,13 +1052,19 @@ fn listing_action(input: &[u8]) -> IResult<&[u8], Action> {
         toggle,
         seen_flag,
         delete_message,
+        archive_message,
+        filter_dry_run,
+        undo_redo,
         copymove,
         import,
         search,
         select,
         toggle_thread_snooze,
+        toggle_thread_rule,
+        snooze,
         open_in_new_tab,
         _tag,
+        layout,
     ))(input)
 }
 
@@ -867,16 +1079,27 @@ fn compose_action(input: &[u8]) -> IResult<&[u8], Action> {
 }
 
 fn account_action(input: &[u8]) -> IResult<&[u8], Action> {
-    alt((reindex, print_account_setting))(input)
+    alt((reindex, print_account_setting, manage_sieve_scripts))(input)
 }
 
 fn view(input: &[u8]) -> IResult<&[u8], Action> {
-    alt((pipe, save_attachment, export_mail))(input)
+    alt((
+        pipe,
+        save_attachment,
+        export_mail,
+        rsvp,
+        export_ics,
+        view_as_charset,
+    ))(input)
+}
+
+fn navigation(input: &[u8]) -> IResult<&[u8], Action> {
+    alt((goto, go_to_mailbox, refresh_mailbox, compose, split))(input)
 }
 
 pub fn parse_command(input: &[u8]) -> Result<Action, MeliError> {
     alt((
-        goto,
+        navigation,
         listing_action,
         sort,
         subsort,
@@ -895,6 +1118,7 @@ pub fn parse_command(input: &[u8]) -> Result<Action, MeliError> {
         print_setting,
         toggle_mouse,
         reload_config,
+        source,
         quit,
     ))(input)
     .map(|(_, v)| v)
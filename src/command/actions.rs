@@ -23,7 +23,8 @@
  * User actions that need to be handled by the UI
  */
 
-use crate::components::Component;
+use crate::components::{Component, SplitDirection};
+use crate::conf::ListingLayout;
 pub use melib::thread::{SortField, SortOrder};
 use std::path::PathBuf;
 
@@ -42,6 +43,7 @@ pub enum ListingAction {
     SetThreaded,
     SetCompact,
     SetConversations,
+    SetLayout(ListingLayout),
     Search(String),
     Select(String),
     SetSeen,
@@ -52,9 +54,16 @@ pub enum ListingAction {
     MoveToOtherAccount(AccountName, MailboxPath),
     Import(PathBuf, MailboxPath),
     Delete,
+    Archive,
+    FilterDryRun,
     OpenInNewTab,
     Tag(TagAction),
     ToggleThreadSnooze,
+    ToggleThreadMute,
+    ToggleThreadWatch,
+    Snooze(Option<String>),
+    Undo,
+    Redo,
 }
 
 #[derive(Debug)]
@@ -62,6 +71,10 @@ pub enum TabAction {
     Close,
     Kill(Uuid),
     New(Option<Box<dyn Component>>),
+    /// Adds a tab of the previous session without switching to it.
+    Restore(Option<Box<dyn Component>>),
+    /// Shows a component in a new pane of the current tab.
+    SplitWith(SplitDirection, Option<Box<dyn Component>>),
 }
 
 #[derive(Debug)]
@@ -76,6 +89,9 @@ pub enum ViewAction {
     Pipe(String, Vec<String>),
     SaveAttachment(usize, String),
     ExportMail(String),
+    Rsvp(melib::icalendar::PartStat),
+    ExportCalendar(String),
+    ViewAsCharset(Option<melib::email::attachment_types::Charset>),
 }
 
 #[derive(Debug)]
@@ -93,6 +109,7 @@ pub enum ComposeAction {
 pub enum AccountAction {
     ReIndex,
     PrintAccountSetting(String),
+    ManageSieveScripts,
 }
 
 #[derive(Debug)]
@@ -110,6 +127,11 @@ pub enum MailboxOperation {
 pub enum Action {
     Listing(ListingAction),
     ViewMailbox(usize),
+    GoToMailbox(AccountName, MailboxPath),
+    RefreshMailbox,
+    NewDraft(Option<String>),
+    /// Splits the current tab to show `contacts` or a new draft (`compose`).
+    Split(SplitDirection, String),
     Sort(SortField, SortOrder),
     SubSort(SortField, SortOrder),
     Tab(TabAction),
@@ -122,6 +144,7 @@ pub enum Action {
     AccountAction(AccountName, AccountAction),
     PrintSetting(String),
     ReloadConfiguration,
+    Source(String),
     ToggleMouse,
     Quit,
 }
@@ -131,6 +154,10 @@ impl Action {
         match self {
             Action::Listing(_) => false,
             Action::ViewMailbox(_) => false,
+            Action::GoToMailbox(_, _) => false,
+            Action::RefreshMailbox => false,
+            Action::NewDraft(_) => false,
+            Action::Split(_, _) => false,
             Action::Sort(_, _) => false,
             Action::SubSort(_, _) => false,
             Action::Tab(_) => false,
@@ -145,6 +172,7 @@ impl Action {
             Action::ToggleMouse => false,
             Action::Quit => true,
             Action::ReloadConfiguration => false,
+            Action::Source(_) => false,
         }
     }
 }
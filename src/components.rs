@@ -91,4 +91,7 @@ pub trait Component: Display + Debug + Send + Sync {
     fn get_status(&self, _context: &Context) -> String {
         String::new()
     }
+
+    /// Adds what should be restored of this component on the next start to `session`.
+    fn save_session(&self, _session: &mut crate::session::Session, _context: &Context) {}
 }
@@ -924,4 +924,10 @@ impl Component for ContactList {
             context.accounts[self.account_pos].address_book.len()
         )
     }
+
+    fn save_session(&self, session: &mut crate::session::Session, _context: &Context) {
+        session.tabs.push(crate::session::TabSession::Contacts {
+            account: self.accounts[self.account_pos].name.clone(),
+        });
+    }
 }
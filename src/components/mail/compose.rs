@@ -22,6 +22,7 @@
 use super::*;
 use melib::email::attachment_types::{ContentType, MultipartType};
 use melib::list_management;
+use melib::text_processing::format_flowed;
 use melib::Draft;
 
 use crate::conf::accounts::JobRequest;
@@ -31,6 +32,7 @@ use indexmap::IndexSet;
 use nix::sys::wait::WaitStatus;
 use std::convert::TryInto;
 use std::future::Future;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
@@ -92,9 +94,18 @@ pub struct Composer {
     embed: Option<EmbedStatus>,
     #[cfg(feature = "gpgme")]
     gpg_state: gpg::GpgComposeState,
+    /// Recipients the Autocrypt recommendation was last computed for, and the recommendation.
+    #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+    autocrypt_recommendation: Option<(String, crate::autocrypt::Recommendation)>,
+    /// Recipients and job of an Autocrypt recommendation that is being computed.
+    #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+    autocrypt_recommendation_job:
+        Option<(String, JoinHandle<Result<crate::autocrypt::Recommendation>>)>,
     dirty: bool,
     has_changes: bool,
     initialized: bool,
+    /// File the draft is auto-saved to for the next session.
+    autosave: Option<PathBuf>,
     id: ComponentId,
 }
 
@@ -160,11 +171,16 @@ impl Composer {
             mode: ViewMode::Edit,
             #[cfg(feature = "gpgme")]
             gpg_state: gpg::GpgComposeState::new(),
+            #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+            autocrypt_recommendation: None,
+            #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+            autocrypt_recommendation_job: None,
             dirty: true,
             has_changes: false,
             embed_area: ((0, 0), (0, 0)),
             embed: None,
             initialized: false,
+            autosave: None,
             id: ComponentId::new_v4(),
         }
     }
@@ -401,6 +417,37 @@ impl Composer {
         self.update_form();
     }
 
+    /// Restores a compose tab of the previous session from its auto-saved draft.
+    pub fn restore(
+        account_hash: AccountHash,
+        path: Option<PathBuf>,
+        context: &Context,
+    ) -> Result<Self> {
+        let mut ret = Composer::with_account(account_hash, context);
+        if let Some(path) = path {
+            ret.set_draft(crate::session::load_draft(&path)?);
+            ret.has_changes = true;
+            ret.autosave = Some(path);
+        }
+        Ok(ret)
+    }
+
+    fn autosave_path(&self) -> Result<PathBuf> {
+        if let Some(ref path) = self.autosave {
+            return Ok(path.clone());
+        }
+        crate::session::draft_path(self.id)
+    }
+
+    /// Removes the auto-saved draft once the tab is closed.
+    fn remove_autosave(&self) {
+        if let Some(path) = self.autosave_path().ok().filter(|path| path.exists()) {
+            if let Err(err) = std::fs::remove_file(&path) {
+                debug!("Could not remove {}: {}", path.display(), err);
+            }
+        }
+    }
+
     fn update_draft(&mut self) {
         let header_values = self.form.values_mut();
         let draft_header_map = self.draft.headers_mut();
@@ -482,6 +529,50 @@ impl Composer {
         }
     }
 
+    #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+    fn update_autocrypt_recommendation(&mut self, context: &Context) {
+        if !*account_settings!(context[self.account_hash].pgp.autocrypt) {
+            self.autocrypt_recommendation = None;
+            return;
+        }
+        let recipients_s = ["To", "Cc", "Bcc"]
+            .iter()
+            .filter_map(|h| self.draft.headers().get(h))
+            .filter(|v| !v.trim().is_empty())
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ");
+        if self
+            .autocrypt_recommendation
+            .as_ref()
+            .map(|(r, _)| *r == recipients_s)
+            .unwrap_or(false)
+            || self
+                .autocrypt_recommendation_job
+                .as_ref()
+                .map(|(r, _)| *r == recipients_s)
+                .unwrap_or(false)
+        {
+            return;
+        }
+        let recipients =
+            melib::email::parser::address::rfc2822address_list(recipients_s.as_bytes())
+                .map(|(_, v)| {
+                    v.into_iter()
+                        .map(|a| a.get_email())
+                        .collect::<Vec<String>>()
+                })
+                .unwrap_or_default();
+        let account_name = context.accounts[&self.account_hash].name().to_string();
+        let prefer_encrypt =
+            *account_settings!(context[self.account_hash].pgp.autocrypt_prefer_encrypt);
+        /* The peer state database isn't opened while drawing. */
+        let handle = context.job_executor.spawn_blocking(async move {
+            crate::autocrypt::recommendation(&account_name, prefer_encrypt, &recipients)
+        });
+        self.autocrypt_recommendation_job = Some((recipients_s, handle));
+    }
+
     fn draw_attachments(&self, grid: &mut CellBuffer, area: Area, context: &Context) {
         let attachments_no = self.draft.attachments().len();
         let theme_default = crate::conf::value(context, "theme_default");
@@ -495,15 +586,21 @@ impl Composer {
                 .map(|k| k.fingerprint())
                 .collect::<Vec<_>>()
                 .join(", ");
+            let smime_sign_key =
+                account_settings!(context[self.account_hash].pgp.smime_sign_key).as_ref();
             write_string_to_grid(
-                &format!(
-                    "☑ sign with {}",
-                    if self.gpg_state.sign_keys.is_empty() {
-                        "default key"
-                    } else {
-                        key_list.as_str()
-                    }
-                ),
+                &if let Some(cert) = smime_sign_key {
+                    format!("☑ sign with S/MIME certificate {}", cert)
+                } else {
+                    format!(
+                        "☑ sign with {}",
+                        if self.gpg_state.sign_keys.is_empty() {
+                            "default key"
+                        } else {
+                            key_list.as_str()
+                        }
+                    )
+                },
                 grid,
                 theme_default.fg,
                 if self.cursor == Cursor::Sign {
@@ -566,8 +663,19 @@ impl Composer {
                 None,
             );
         } else {
+            #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+            let autocrypt_hint = match self.autocrypt_recommendation {
+                Some((_, recommendation))
+                    if recommendation != crate::autocrypt::Recommendation::Disable =>
+                {
+                    format!(" (Autocrypt: encryption {})", recommendation)
+                }
+                _ => String::new(),
+            };
+            #[cfg(not(all(feature = "sqlite3", feature = "gpgme")))]
+            let autocrypt_hint = String::new();
             write_string_to_grid(
-                "☐ don't encrypt",
+                &format!("☐ don't encrypt{}", autocrypt_hint),
                 grid,
                 theme_default.fg,
                 if self.cursor == Cursor::Encrypt {
@@ -918,6 +1026,8 @@ impl Component for Composer {
             }
         }
         if !self.mode.is_edit_attachments() {
+            #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+            self.update_autocrypt_recommendation(context);
             self.draw_attachments(grid, attachment_area, context);
         }
         self.dirty = false;
@@ -926,6 +1036,23 @@ impl Component for Composer {
 
     fn process_event(&mut self, mut event: &mut UIEvent, context: &mut Context) -> bool {
         let shortcuts = self.get_shortcuts(context);
+        #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+        {
+            if let UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id)) = *event {
+                match self.autocrypt_recommendation_job.take() {
+                    Some((recipients_s, mut handle)) if handle.job_id == *job_id => {
+                        let recommendation = match handle.chan.try_recv() {
+                            Ok(Some(Ok(recommendation))) => recommendation,
+                            _ => crate::autocrypt::Recommendation::Disable,
+                        };
+                        self.autocrypt_recommendation = Some((recipients_s, recommendation));
+                        self.set_dirty(true);
+                        return true;
+                    }
+                    job => self.autocrypt_recommendation_job = job,
+                }
+            }
+        }
         match (&mut self.mode, &mut event) {
             (ViewMode::Edit, _) => {
                 if self.pager.process_event(event, context) {
@@ -1056,6 +1183,7 @@ impl Component for Composer {
                 if let Some(key) = result.downcast_mut::<char>() {
                     match key {
                         'x' => {
+                            self.remove_autosave();
                             context.replies.push_back(UIEvent::Action(Tab(Kill(*u))));
                             return true;
                         }
@@ -1068,6 +1196,7 @@ impl Component for Composer {
                                 Flag::SEEN | Flag::DRAFT,
                                 self.account_hash,
                             );
+                            self.remove_autosave();
                             context.replies.push_back(UIEvent::Action(Tab(Kill(*u))));
                             return true;
                         }
@@ -1090,6 +1219,7 @@ impl Component for Composer {
                 if let Some(key) = result.downcast_mut::<char>() {
                     match key {
                         'c' => {
+                            self.remove_autosave();
                             context
                                 .replies
                                 .push_back(UIEvent::Action(Tab(Kill(self.id))));
@@ -1131,6 +1261,7 @@ impl Component for Composer {
                         self.set_dirty(true);
                     }
                     Ok(None) | Ok(Some(Ok(()))) => {
+                        self.remove_autosave();
                         context
                             .replies
                             .push_back(UIEvent::Action(Tab(Kill(self.id))));
@@ -1878,6 +2009,7 @@ impl Component for Composer {
         }
 
         if !self.has_changes {
+            self.remove_autosave();
             context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
             return;
         }
@@ -1924,6 +2056,37 @@ impl Component for Composer {
         self.id = id;
     }
 
+    fn save_session(&self, session: &mut crate::session::Session, context: &Context) {
+        let account = context.accounts[&self.account_hash].name().to_string();
+        if !self.has_changes {
+            session.tabs.push(crate::session::TabSession::Compose {
+                account,
+                draft: None,
+            });
+            return;
+        }
+        let mut draft = self.draft.clone();
+        let header_values = self.form.values();
+        for (k, v) in draft.headers_mut().iter_mut() {
+            if let Some(ref vn) = header_values.get(k.as_str()) {
+                *v = vn.as_str().to_string();
+            }
+        }
+        match self.autosave_path().and_then(|path| {
+            crate::session::save_draft(&path, &draft)?;
+            Ok(path)
+        }) {
+            Ok(path) => session.tabs.push(crate::session::TabSession::Compose {
+                account,
+                draft: Some(path),
+            }),
+            Err(err) => log(
+                format!("Could not auto-save draft: {}", err.to_string()),
+                ERROR,
+            ),
+        }
+    }
+
     fn can_quit_cleanly(&mut self, context: &Context) -> bool {
         if !self.has_changes {
             return true;
@@ -2043,6 +2206,7 @@ pub fn send_draft(
             {
                 parameters.push((b"format".to_vec(), b"flowed".to_vec()));
             }
+            draft.body = format_flowed::encode(&draft.body, format_flowed::MAX_LINE_LENGTH);
 
             let body: AttachmentBuilder = Attachment::new(
                 content_type,
@@ -2117,9 +2281,17 @@ pub fn send_draft_async(
     > = vec![];
     #[cfg(feature = "gpgme")]
     if gpg_state.sign_mail.is_true() && !gpg_state.encrypt_mail.is_true() {
-        filters_stack.push(Box::new(crate::components::mail::pgp::sign_filter(
-            gpg_state.sign_keys.clone(),
-        )?));
+        if let Some(smime_sign_key) =
+            account_settings!(context[account_hash].pgp.smime_sign_key).as_ref()
+        {
+            filters_stack.push(Box::new(crate::components::mail::pgp::smime_sign_filter(
+                Some(smime_sign_key.to_string()),
+            )?));
+        } else {
+            filters_stack.push(Box::new(crate::components::mail::pgp::sign_filter(
+                gpg_state.sign_keys.clone(),
+            )?));
+        }
     } else if gpg_state.encrypt_mail.is_true() {
         filters_stack.push(Box::new(crate::components::mail::pgp::encrypt_filter(
             if gpg_state.sign_mail.is_true() {
@@ -2140,6 +2312,7 @@ pub fn send_draft_async(
         {
             parameters.push((b"format".to_vec(), b"flowed".to_vec()));
         }
+        draft.body = format_flowed::encode(&draft.body, format_flowed::MAX_LINE_LENGTH);
     }
     let mut body: AttachmentBuilder = Attachment::new(
         content_type,
@@ -2162,12 +2335,41 @@ pub fn send_draft_async(
         )
         .into();
     }
+    #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+    let autocrypt_header = if *account_settings!(context[account_hash].pgp.autocrypt) {
+        draft
+            .headers()
+            .get("From")
+            .and_then(|from| melib::email::parser::address::mailbox(from.as_bytes()).ok())
+            .map(|(_, addr)| {
+                crate::autocrypt::header(
+                    addr.get_email(),
+                    account_settings!(context[account_hash].pgp.sign_key).clone(),
+                    *account_settings!(context[account_hash].pgp.autocrypt_prefer_encrypt),
+                )
+            })
+    } else {
+        None
+    };
     Ok(Box::pin(async move {
         #[cfg(feature = "gpgme")]
         for f in filters_stack {
             body = f(body).await?;
         }
 
+        #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+        if let Some(autocrypt_header) = autocrypt_header {
+            match autocrypt_header.await {
+                Ok(value) => {
+                    draft.set_header("Autocrypt", value);
+                }
+                Err(err) => log(
+                    format!("Could not generate Autocrypt header: {}", err),
+                    ERROR,
+                ),
+            }
+        }
+
         draft.attachments.insert(0, body);
         let message = Arc::new(draft.finalise()?);
         let ret = send_cb(message.clone()).await;
@@ -20,8 +20,11 @@
  */
 
 use super::*;
-use crate::conf::accounts::JobRequest;
+use crate::conf::accounts::{DeleteAction, JobRequest};
+use crate::conf::index_format::{IndexField, IndexFormat};
+use crate::session::{ListingLayoutSession, MailboxPosition, Session};
 use crate::types::segment_tree::SegmentTree;
+use crate::undo::{EnvelopeOperation, FlagOp};
 use melib::backends::EnvelopeHashBatch;
 use smallvec::SmallVec;
 use std::collections::{HashMap, HashSet};
@@ -83,6 +86,8 @@ struct ColorCache {
     attachment_flag: ThemeAttribute,
     thread_snooze_flag: ThemeAttribute,
     tag_default: ThemeAttribute,
+    /// Attributes of `listing.index_format` fields, indexed by `IndexField as usize`.
+    fields: [ThemeAttribute; 13],
 
     /* Conversations */
     subject: ThemeAttribute,
@@ -99,6 +104,8 @@ pub(super) struct EntryStrings {
     pub(super) flag: FlagString,
     pub(super) from: FromString,
     pub(super) tags: TagString,
+    /// The row rendered from `listing.index_format`, one segment list per template line.
+    pub(super) formatted: Option<Vec<Vec<(Option<IndexField>, String)>>>,
 }
 
 #[macro_export]
@@ -145,6 +152,230 @@ column_str!(struct SubjectString(String));
 column_str!(struct FlagString(String));
 column_str!(struct TagString(String, SmallVec<[Option<Color>; 8]>));
 
+impl ColorCache {
+    fn index_format_fields(context: &Context) -> [ThemeAttribute; 13] {
+        let mut ret = [ThemeAttribute::default(); 13];
+        for &field in IndexField::ALL.iter() {
+            ret[field as usize] = crate::conf::value(context, field.theme_key());
+        }
+        ret
+    }
+}
+
+/// Values of `listing.index_format` fields that are not in a row's `EntryStrings`.
+pub(super) struct RowValues {
+    pub(super) index: usize,
+    pub(super) date: UnixTimestamp,
+    pub(super) thread_count: usize,
+}
+
+/// Renders a row with `index_format`.
+fn format_row(
+    index_format: &IndexFormat,
+    context: &Context,
+    coordinates: (AccountHash, MailboxHash),
+    envelope: &Envelope,
+    strings: &EntryStrings,
+    values: RowValues,
+) -> Vec<Vec<(Option<IndexField>, String)>> {
+    let mailbox = &context.accounts[&coordinates.0][&coordinates.1];
+    let is_outgoing = match mailbox.ref_mailbox.special_usage() {
+        SpecialUsageMailbox::Sent | SpecialUsageMailbox::Drafts => true,
+        _ => false,
+    };
+    let value = |field: IndexField, argument: Option<&str>| -> String {
+        match field {
+            IndexField::Index => values.index.to_string(),
+            IndexField::Date => match argument {
+                Some(fmt) => melib::datetime::timestamp_to_string(values.date, Some(fmt)),
+                None => strings.date.to_string(),
+            },
+            IndexField::From => strings.from.to_string(),
+            IndexField::To => address_list!((envelope.to()) as comma_sep_list),
+            IndexField::Correspondent if is_outgoing => {
+                address_list!((envelope.to()) as comma_sep_list)
+            }
+            IndexField::Correspondent => strings.from.to_string(),
+            IndexField::Subject => envelope.subject().to_string(),
+            IndexField::Size => envelope
+                .size()
+                .map(|size| melib::Bytes(size).to_string())
+                .unwrap_or_default(),
+            IndexField::Attachments => match envelope.attachment_count() {
+                Some(0) | None => String::new(),
+                Some(count) => count.to_string(),
+            },
+            IndexField::Tags => strings
+                .tags
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" "),
+            IndexField::ThreadCount => values.thread_count.to_string(),
+            IndexField::ListId => envelope
+                .other_headers()
+                .get("List-Id")
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default(),
+            IndexField::Mailbox => mailbox.name().to_string(),
+            IndexField::Flags => strings.flag.to_string(),
+        }
+    };
+    (0..index_format.lines())
+        .map(|line| index_format.render(line, value))
+        .collect()
+}
+
+impl EntryStrings {
+    /// Renders the row with `listing.index_format`, if it is set.
+    pub(super) fn format(
+        &mut self,
+        index_format: Option<&IndexFormat>,
+        context: &Context,
+        coordinates: (AccountHash, MailboxHash),
+        envelope: &Envelope,
+        values: RowValues,
+    ) {
+        self.formatted = index_format.map(|index_format| {
+            format_row(index_format, context, coordinates, envelope, self, values)
+        });
+    }
+
+    /// Width of line `line` of the formatted row.
+    pub(super) fn formatted_width(&self, line: usize) -> usize {
+        self.formatted
+            .as_ref()
+            .and_then(|formatted| formatted.get(line))
+            .map(|segments| segments.iter().map(|(_, s)| s.grapheme_width()).sum())
+            .unwrap_or(0)
+    }
+}
+
+/// Writes line `line` of a row rendered from `listing.index_format` at `(x, y)` of `grid`. Fields
+/// with `Default` theme colors or attributes take those of `row_attr`, and keep their own
+/// otherwise. Returns the x coordinate after the line.
+pub(super) fn write_formatted_line(
+    grid: &mut CellBuffer,
+    strings: &EntryStrings,
+    line: usize,
+    (x, y): Pos,
+    row_attr: ThemeAttribute,
+    color_cache: &ColorCache,
+) -> usize {
+    fn write(
+        grid: &mut CellBuffer,
+        s: &str,
+        (x, y): Pos,
+        attr: ThemeAttribute,
+        keep: (bool, bool, bool),
+    ) -> usize {
+        let end = std::cmp::min(grid.size().0, x + s.grapheme_width());
+        if end > x {
+            write_string_to_grid(
+                s,
+                grid,
+                attr.fg,
+                attr.bg,
+                attr.attrs,
+                ((x, y), (end - 1, y)),
+                None,
+            );
+            for c in grid.row_iter(x..end, y) {
+                grid[c]
+                    .set_keep_fg(keep.0)
+                    .set_keep_bg(keep.1)
+                    .set_keep_attrs(keep.2);
+            }
+        }
+        end
+    }
+
+    let segments = match strings.formatted.as_ref().and_then(|f| f.get(line)) {
+        Some(segments) => segments,
+        None => return x,
+    };
+    let mut x = x;
+    for (field, text) in segments {
+        let field_attr = field
+            .map(|f| color_cache.fields[f as usize])
+            .unwrap_or(row_attr);
+        let keep = (
+            field_attr.fg != Color::Default && field_attr.fg != row_attr.fg,
+            field_attr.bg != Color::Default && field_attr.bg != row_attr.bg,
+            field_attr.attrs != Attr::DEFAULT && field_attr.attrs != row_attr.attrs,
+        );
+        let attr = ThemeAttribute {
+            fg: if keep.0 { field_attr.fg } else { row_attr.fg },
+            bg: if keep.1 { field_attr.bg } else { row_attr.bg },
+            attrs: if keep.2 {
+                field_attr.attrs
+            } else {
+                row_attr.attrs
+            },
+        };
+        match field {
+            Some(IndexField::Tags) => {
+                /* Draw each tag with its color */
+                let mut colors = strings.tags.1.iter();
+                let mut rest = text.as_str();
+                while !rest.is_empty() {
+                    let word_start = rest.find(|c: char| c != ' ').unwrap_or_else(|| rest.len());
+                    x = write(grid, &rest[..word_start], (x, y), attr, keep);
+                    rest = &rest[word_start..];
+                    let word_end = rest.find(' ').unwrap_or_else(|| rest.len());
+                    if word_end > 0 {
+                        let tag_attr = ThemeAttribute {
+                            bg: colors
+                                .next()
+                                .and_then(|c| *c)
+                                .unwrap_or(color_cache.tag_default.bg),
+                            ..color_cache.tag_default
+                        };
+                        x = write(
+                            grid,
+                            &rest[..word_end],
+                            (x, y),
+                            tag_attr,
+                            (true, true, true),
+                        );
+                    }
+                    rest = &rest[word_end..];
+                }
+            }
+            Some(IndexField::Flags) => {
+                for g in text.split_graphemes() {
+                    x = match g {
+                        "📎" => write(
+                            grid,
+                            g,
+                            (x, y),
+                            ThemeAttribute {
+                                fg: color_cache.attachment_flag.fg,
+                                ..attr
+                            },
+                            (true, keep.1, keep.2),
+                        ),
+                        "💤" => write(
+                            grid,
+                            g,
+                            (x, y),
+                            ThemeAttribute {
+                                fg: color_cache.thread_snooze_flag.fg,
+                                ..attr
+                            },
+                            (true, keep.1, keep.2),
+                        ),
+                        _ => write(grid, g, (x, y), attr, keep),
+                    };
+                }
+            }
+            _ => {
+                x = write(grid, text, (x, y), attr, keep);
+            }
+        }
+    }
+    x
+}
+
 #[derive(Debug)]
 struct AccountMenuEntry {
     name: String,
@@ -178,102 +409,171 @@ pub trait MailListingTrait: ListingTrait {
         }
         let env_hashes = EnvelopeHashBatch::try_from(envs_to_set.as_slice()).unwrap();
         match a {
-            ListingAction::SetSeen => {
-                let job = account.backend.write().unwrap().set_flags(
-                    env_hashes.clone(),
-                    mailbox_hash,
-                    smallvec::smallvec![(Ok(Flag::SEEN), true)],
-                );
-                match job {
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(err.to_string()),
-                        ));
-                    }
-                    Ok(fut) => {
-                        let handle = account.job_executor.spawn_specialized(fut);
-                        account
-                            .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
-                    }
+            ListingAction::SetSeen
+            | ListingAction::SetUnseen
+            | ListingAction::Tag(Remove(_))
+            | ListingAction::Tag(Add(_)) => {
+                let (description, flags): (String, SmallVec<[FlagOp; 8]>) = match a {
+                    ListingAction::SetSeen => (
+                        "set seen".to_string(),
+                        smallvec::smallvec![(Ok(Flag::SEEN), true)],
+                    ),
+                    ListingAction::SetUnseen => (
+                        "set unseen".to_string(),
+                        smallvec::smallvec![(Ok(Flag::SEEN), false)],
+                    ),
+                    ListingAction::Tag(Remove(ref tag_str)) => (
+                        format!("remove tag {}", tag_str),
+                        smallvec::smallvec![(Err(tag_str.to_string()), false)],
+                    ),
+                    ListingAction::Tag(Add(ref tag_str)) => (
+                        format!("add tag {}", tag_str),
+                        smallvec::smallvec![(Err(tag_str.to_string()), true)],
+                    ),
+                    _ => unreachable!(),
+                };
+                if let Err(err) =
+                    account.set_flags_and_record(env_hashes, mailbox_hash, description, flags)
+                {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            err.to_string(),
+                        )));
                 }
             }
-            ListingAction::SetUnseen => {
-                let job = account.backend.write().unwrap().set_flags(
-                    env_hashes.clone(),
-                    mailbox_hash,
-                    smallvec::smallvec![(Ok(Flag::SEEN), false)],
-                );
-                match job {
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(err.to_string()),
-                        ));
+            ListingAction::Delete => {
+                match account.delete_action(mailbox_hash) {
+                    DeleteAction::SetTrashed => {
+                        if let Err(err) = account.set_flags_and_record(
+                            env_hashes,
+                            mailbox_hash,
+                            "delete".to_string(),
+                            smallvec::smallvec![(Ok(Flag::TRASHED), true)],
+                        ) {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(err.to_string()),
+                            ));
+                        }
                     }
-                    Ok(fut) => {
-                        let handle = account.job_executor.spawn_specialized(fut);
-                        account
-                            .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                    DeleteAction::ConfirmExpunge => {
+                        /* Without a Trash mailbox, fall back to deleting permanently, but ask
+                         * first. */
+                        let title = format!(
+                            "Account {} has no Trash mailbox. Delete {} message{} permanently?",
+                            account.name(),
+                            env_hashes.len(),
+                            if env_hashes.len() == 1 { "" } else { "s" }
+                        );
+                        context.replies.push_back(UIEvent::GlobalUIDialog(Box::new(
+                            UIConfirmationDialog::new(
+                                &title,
+                                vec![(true, "yes".to_string()), (false, "no".to_string())],
+                                true,
+                                Some(Box::new(move |_id: ComponentId, result: bool| {
+                                    Some(if result {
+                                        UIEvent::Callback(CallbackFn(Box::new(
+                                            move |context: &mut Context| {
+                                                if let Err(err) = context.accounts[&account_hash]
+                                                    .expunge_messages(env_hashes, mailbox_hash)
+                                                {
+                                                    context.replies.push_back(
+                                                        UIEvent::StatusEvent(
+                                                            StatusEvent::DisplayMessage(
+                                                                err.to_string(),
+                                                            ),
+                                                        ),
+                                                    );
+                                                }
+                                            },
+                                        )))
+                                    } else {
+                                        UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                                            "Nothing was deleted.".to_string(),
+                                        ))
+                                    })
+                                })),
+                                context,
+                            ),
+                        )));
                     }
-                }
-            }
-            ListingAction::Tag(Remove(ref tag_str)) => {
-                let job = account.backend.write().unwrap().set_flags(
-                    env_hashes.clone(),
-                    mailbox_hash,
-                    smallvec::smallvec![(Err(tag_str.to_string()), false)],
-                );
-                match job {
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(err.to_string()),
-                        ));
+                    DeleteAction::MoveToTrash(trash_mailbox_hash) => {
+                        let operation = EnvelopeOperation::Move {
+                            source: mailbox_hash,
+                            destination: trash_mailbox_hash,
+                            message_ids: account.message_ids(&env_hashes),
+                        };
+                        let job = account.backend.write().unwrap().copy_messages(
+                            env_hashes,
+                            mailbox_hash,
+                            trash_mailbox_hash,
+                            /* move? */ true,
+                        );
+                        match job {
+                            Err(err) => {
+                                context.replies.push_back(UIEvent::StatusEvent(
+                                    StatusEvent::DisplayMessage(err.to_string()),
+                                ));
+                            }
+                            Ok(fut) => {
+                                let handle = account.job_executor.spawn_specialized(fut);
+                                account.insert_job(
+                                    handle.job_id,
+                                    JobRequest::Generic {
+                                        name: "moving messages to trash".into(),
+                                        handle,
+                                        on_finish: None,
+                                        logging_level: melib::LoggingLevel::INFO,
+                                    },
+                                );
+                                let inverse = operation.inverse().into_iter().collect();
+                                account.record_operation(
+                                    "delete".to_string(),
+                                    vec![operation],
+                                    inverse,
+                                );
+                            }
+                        }
                     }
-                    Ok(fut) => {
-                        let handle = account.job_executor.spawn_specialized(fut);
-                        account
-                            .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+                    DeleteAction::Expunge => {
+                        if let Err(err) = account.expunge_messages(env_hashes, mailbox_hash) {
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(err.to_string()),
+                            ));
+                        }
                     }
                 }
             }
-            ListingAction::Tag(Add(ref tag_str)) => {
-                let job = account.backend.write().unwrap().set_flags(
-                    env_hashes.clone(),
-                    mailbox_hash,
-                    smallvec::smallvec![(Err(tag_str.to_string()), true)],
-                );
-                match job {
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(err.to_string()),
-                        ));
-                    }
-                    Ok(fut) => {
-                        let handle = account.job_executor.spawn_specialized(fut);
-                        account
-                            .insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
-                    }
+            ListingAction::Archive => {
+                if let Err(err) = account.archive(&env_hashes, mailbox_hash) {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                            err.to_string(),
+                        )));
                 }
             }
-            ListingAction::Delete => {
-                let job = account
-                    .backend
-                    .write()
-                    .unwrap()
-                    .delete_messages(env_hashes.clone(), mailbox_hash);
-                match job {
-                    Err(err) => {
-                        context.replies.push_back(UIEvent::StatusEvent(
-                            StatusEvent::DisplayMessage(err.to_string()),
-                        ));
-                    }
-                    Ok(fut) => {
-                        let handle = account.job_executor.spawn_specialized(fut);
-                        account.insert_job(
-                            handle.job_id,
-                            JobRequest::DeleteMessages { env_hashes, handle },
-                        );
-                    }
-                }
+            ListingAction::Snooze(Some(ref date)) => {
+                let result = melib::datetime::natural_to_timestamp(date, melib::datetime::now())
+                    .and_then(|until| {
+                        account
+                            .snooze(&env_hashes, mailbox_hash, until)
+                            .map(|()| until)
+                    });
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        match result {
+                            Ok(until) => format!(
+                                "Snoozed until {}.",
+                                melib::datetime::timestamp_to_string(
+                                    until,
+                                    Some("%a, %d %b %Y %H:%M")
+                                )
+                            ),
+                            Err(err) => err.to_string(),
+                        },
+                    )));
             }
             ListingAction::CopyTo(ref mailbox_path) => {
                 match account
@@ -313,22 +613,26 @@ pub trait MailListingTrait: ListingTrait {
                     )));
             }
             ListingAction::MoveTo(ref mailbox_path) => {
+                let message_ids = account.message_ids(&env_hashes);
                 match account
                     .mailbox_by_path(mailbox_path)
                     .and_then(|destination_mailbox_hash| {
-                        account.backend.write().unwrap().copy_messages(
-                            env_hashes,
-                            mailbox_hash,
+                        Ok((
                             destination_mailbox_hash,
-                            /* move? */ true,
-                        )
+                            account.backend.write().unwrap().copy_messages(
+                                env_hashes,
+                                mailbox_hash,
+                                destination_mailbox_hash,
+                                /* move? */ true,
+                            )?,
+                        ))
                     }) {
                     Err(err) => {
                         context.replies.push_back(UIEvent::StatusEvent(
                             StatusEvent::DisplayMessage(err.to_string()),
                         ));
                     }
-                    Ok(fut) => {
+                    Ok((destination_mailbox_hash, fut)) => {
                         let handle = account.job_executor.spawn_specialized(fut);
                         account.insert_job(
                             handle.job_id,
@@ -339,6 +643,17 @@ pub trait MailListingTrait: ListingTrait {
                                 logging_level: melib::LoggingLevel::INFO,
                             },
                         );
+                        let operation = EnvelopeOperation::Move {
+                            source: mailbox_hash,
+                            destination: destination_mailbox_hash,
+                            message_ids,
+                        };
+                        let inverse = operation.inverse().into_iter().collect();
+                        account.record_operation(
+                            format!("move to {}", mailbox_path),
+                            vec![operation],
+                            inverse,
+                        );
                     }
                 }
             }
@@ -393,6 +708,31 @@ pub trait ListingTrait: Component {
         None
     }
     fn set_movement(&mut self, mvm: PageMovement);
+    /// Index of the entry under the cursor.
+    fn cursor(&self) -> usize {
+        0
+    }
+    /// The message under the cursor, shown in the preview of the `three_columns` and `stacked`
+    /// layouts.
+    fn preview_env(&self, _context: &Context) -> Option<EnvelopeHash> {
+        None
+    }
+}
+
+/// The most recent message of a thread, which is what its preview shows.
+fn latest_message(
+    account_hash: AccountHash,
+    mailbox_hash: MailboxHash,
+    thread_hash: ThreadHash,
+    context: &Context,
+) -> Option<EnvelopeHash> {
+    let account = &context.accounts[&account_hash];
+    let threads = account.collection.get_threads(mailbox_hash);
+    threads
+        .thread_group_iter(thread_hash)
+        .filter_map(|(_, h)| threads.thread_nodes()[&h].message())
+        .filter(|env_hash| account.contains_key(*env_hash))
+        .max_by_key(|env_hash| account.collection.get_env(*env_hash).date())
 }
 
 #[derive(Debug)]
@@ -506,6 +846,20 @@ pub struct Listing {
     ratio: usize, // right/(container width) * 100
     menu_width: WidgetWidth,
     focus: ListingFocus,
+    /// Path of the mailbox last shown in each account, and its cursor position.
+    positions: HashMap<AccountHash, (String, usize)>,
+    /// Position of the previous session, restored once its account is ready.
+    pending_position: Option<MailboxPosition>,
+    /// Where the message under the cursor is previewed, if anywhere.
+    layout: ListingLayout,
+    /// Width or height of the preview as a percentage of the listing.
+    preview_ratio: usize,
+    preview: Option<MailView>,
+    /// Divider between the list and the preview when last drawn, to drag it with the mouse.
+    preview_divider: Option<Area>,
+    preview_drag: bool,
+    /// Area of the list and the preview when last drawn.
+    component_area: Area,
 }
 
 impl fmt::Display for Listing {
@@ -583,7 +937,7 @@ impl Component for Listing {
             if let Some(s) = self.status.as_mut() {
                 s.draw(grid, area, context);
             } else {
-                self.component.draw(grid, area, context);
+                self.draw_component(grid, area, context);
             }
         } else if right_component_width == 0 {
             self.draw_menu(grid, area, context);
@@ -604,14 +958,17 @@ impl Component for Listing {
             if let Some(s) = self.status.as_mut() {
                 s.draw(grid, (set_x(upper_left, mid + 1), bottom_right), context);
             } else {
-                self.component
-                    .draw(grid, (set_x(upper_left, mid + 1), bottom_right), context);
+                self.draw_component(grid, (set_x(upper_left, mid + 1), bottom_right), context);
             }
         }
         self.dirty = false;
     }
 
     fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        if let UIEvent::Input(_) = event {
+            /* Don't move away from a mailbox the user has started using. */
+            self.pending_position = None;
+        }
         match event {
             UIEvent::ConfigReload { old_settings: _ } => {
                 self.theme_default = crate::conf::value(context, "theme_default");
@@ -619,6 +976,9 @@ impl Component for Listing {
                 self.sidebar_divider =
                     *account_settings!(context[account_hash].listing.sidebar_divider);
                 self.sidebar_divider_theme = conf::value(context, "mail.sidebar_divider");
+                self.layout = *account_settings!(context[account_hash].listing.layout);
+                self.preview_ratio =
+                    *account_settings!(context[account_hash].listing.preview_ratio);
                 self.menu_content = CellBuffer::new_with_context(0, 0, None, context);
                 self.set_dirty(true);
             }
@@ -672,6 +1032,7 @@ impl Component for Listing {
                             self.get_status(context),
                         )));
                 }
+                self.restore_position(context);
                 return true;
             }
             UIEvent::MailboxDelete((account_hash, mailbox_hash))
@@ -723,6 +1084,142 @@ impl Component for Listing {
             UIEvent::Resize => {
                 self.set_dirty(true);
             }
+            UIEvent::ControlQuery(ref request) if request.request == "selection" => {
+                let (account_hash, mailbox_hash) = self.component.coordinates();
+                let account = match context.accounts.get(&account_hash) {
+                    Some(account)
+                        if self.status.is_none()
+                            && account.mailbox_entries.contains_key(&mailbox_hash) =>
+                    {
+                        account
+                    }
+                    _ => {
+                        request.reply(Ok(serde_json::Value::Null));
+                        return true;
+                    }
+                };
+                /* The listing has no cursor in an empty mailbox. */
+                let is_empty = account
+                    .collection
+                    .mailboxes
+                    .read()
+                    .unwrap()
+                    .get(&mailbox_hash)
+                    .map(|envelopes| envelopes.is_empty())
+                    .unwrap_or(true);
+                let mut threads_json = vec![];
+                if !is_empty {
+                    let threads = account.collection.get_threads(mailbox_hash);
+                    for thread_hash in self.component.get_focused_items(context) {
+                        let thread = threads.thread_ref(thread_hash);
+                        let env_hash = match threads.thread_nodes()[&thread.root()].message() {
+                            Some(env_hash) => env_hash,
+                            None => continue,
+                        };
+                        let envelope = account.collection.get_env(env_hash);
+                        threads_json.push(serde_json::json!({
+                            "subject": envelope.subject().to_string(),
+                            "from": envelope.field_from_to_string(),
+                            "date": envelope.date_as_str(),
+                            "message_id": envelope.message_id_display().to_string(),
+                            "messages": thread.len(),
+                            "unseen": thread.unseen(),
+                        }));
+                    }
+                }
+                request.reply(Ok(serde_json::json!({
+                    "account": account.name(),
+                    "mailbox": account[&mailbox_hash].ref_mailbox.path(),
+                    "threads": threads_json,
+                })));
+                return true;
+            }
+            UIEvent::Action(Action::GoToMailbox(ref account_name, ref path)) => {
+                let target = context
+                    .accounts
+                    .values()
+                    .position(|a| a.name() == account_name)
+                    .ok_or_else(|| {
+                        MeliError::new(format!("Account {} was not found.", account_name))
+                    })
+                    .and_then(|account_index| {
+                        let mailbox_hash = context.accounts[account_index].mailbox_by_path(path)?;
+                        self.accounts[account_index]
+                            .entries
+                            .iter()
+                            .position(|(_, _, _, h)| *h == mailbox_hash)
+                            .map(|idx| (account_index, idx))
+                            .ok_or_else(|| {
+                                MeliError::new(format!("Mailbox {} is not subscribed.", path))
+                            })
+                    });
+                match target {
+                    Ok((account_index, idx)) => {
+                        self.cursor_pos = (account_index, MenuEntryCursor::Mailbox(idx));
+                        self.change_account(context);
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(format!("Could not open {}", path)),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return true;
+            }
+            UIEvent::Action(Action::RefreshMailbox) => {
+                let (account_hash, mailbox_hash) = self.component.coordinates();
+                if let Some(account) = context.accounts.get_mut(&account_hash) {
+                    if let Err(err) = account.refresh(mailbox_hash) {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not refresh.".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return true;
+            }
+            UIEvent::Action(Action::NewDraft(ref address)) => {
+                let account_hash = context.accounts[self.cursor_pos.0].hash();
+                let mut composer = Composer::with_account(account_hash, context);
+                if let Some(address) = address {
+                    let mailto = if address.starts_with("mailto:") {
+                        address.to_string()
+                    } else {
+                        format!("mailto:{}", address)
+                    };
+                    match Mailto::try_from(mailto.as_bytes()) {
+                        Ok(mailto) => composer.set_draft(mailto.into()),
+                        Err(err) => {
+                            context.replies.push_back(UIEvent::Notification(
+                                Some(format!("Could not parse {}", address)),
+                                err,
+                                Some(NotificationType::Error(ErrorKind::None)),
+                            ));
+                            return true;
+                        }
+                    }
+                }
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+                return true;
+            }
+            UIEvent::Action(Action::Split(direction, ref pane)) => {
+                let pane: Box<dyn Component> = match pane.as_str() {
+                    "contacts" => Box::new(ContactList::for_account(self.cursor_pos.0, context)),
+                    _ => {
+                        let account_hash = context.accounts[self.cursor_pos.0].hash();
+                        Box::new(Composer::with_account(account_hash, context))
+                    }
+                };
+                context
+                    .replies
+                    .push_back(UIEvent::Action(Tab(SplitWith(*direction, Some(pane)))));
+                return true;
+            }
             UIEvent::Action(Action::ViewMailbox(ref idx)) => {
                 if let Some((_, _, _, mailbox_hash)) =
                     self.accounts[self.cursor_pos.0].entries.get(*idx)
@@ -753,10 +1250,61 @@ impl Component for Listing {
         {
             return true;
         }
+        if let Some(preview) = self.preview.as_mut() {
+            match event {
+                UIEvent::Input(_) | UIEvent::CmdInput(_) | UIEvent::EmbedInput(_) => {}
+                _ => {
+                    if preview.process_event(event, context) {
+                        return true;
+                    }
+                }
+            }
+        }
 
-        let shortcuts = self.get_shortcuts(context);
+        let mut shortcuts = self.get_shortcuts(context);
+        shortcuts.insert("general", context.settings.shortcuts.general.key_values());
         if self.focus == ListingFocus::Mailbox {
             match *event {
+                UIEvent::Input(Key::Mouse(MouseEvent::Press(MouseButton::Left, x, y)))
+                    if self.preview_divider.map_or(false, |divider| {
+                        let (x, y) = (
+                            usize::from(x).saturating_sub(1),
+                            usize::from(y).saturating_sub(1),
+                        );
+                        (get_x(upper_left!(divider))..=get_x(bottom_right!(divider))).contains(&x)
+                            && (get_y(upper_left!(divider))..=get_y(bottom_right!(divider)))
+                                .contains(&y)
+                    }) =>
+                {
+                    self.preview_drag = true;
+                    return true;
+                }
+                UIEvent::Input(Key::Mouse(MouseEvent::Hold(x, y))) if self.preview_drag => {
+                    self.move_preview_divider(
+                        usize::from(x).saturating_sub(1),
+                        usize::from(y).saturating_sub(1),
+                    );
+                    return true;
+                }
+                UIEvent::Input(Key::Mouse(MouseEvent::Release(_, _))) if self.preview_drag => {
+                    self.preview_drag = false;
+                    return true;
+                }
+                UIEvent::Input(ref k)
+                    if self.preview.is_some()
+                        && (shortcut!(k == shortcuts["general"]["grow_pane"])
+                            || shortcut!(k == shortcuts["general"]["shrink_pane"])) =>
+                {
+                    /* The list is the focused pane, so growing it shrinks the preview. */
+                    self.preview_ratio = if shortcut!(k == shortcuts["general"]["grow_pane"]) {
+                        self.preview_ratio.saturating_sub(5)
+                    } else {
+                        self.preview_ratio + 5
+                    };
+                    self.preview_ratio = std::cmp::min(95, std::cmp::max(5, self.preview_ratio));
+                    self.set_dirty(true);
+                    return true;
+                }
                 UIEvent::Input(Key::Mouse(MouseEvent::Press(MouseButton::Left, x, _y)))
                     if self.menu_visibility =>
                 {
@@ -900,7 +1448,12 @@ impl Component for Listing {
                         }
                         _ => return false,
                     }
+                    let (entry, cursor) = self.last_position(self.cursor_pos.0, context);
+                    self.cursor_pos.1 = entry;
                     self.change_account(context);
+                    if cursor > 0 {
+                        self.component.set_movement(PageMovement::Down(cursor));
+                    }
 
                     return true;
                 }
@@ -934,6 +1487,12 @@ impl Component for Listing {
                             self.component.set_style(IndexStyle::Conversations);
                             return true;
                         }
+                        Action::Listing(ListingAction::SetLayout(layout)) => {
+                            self.layout = *layout;
+                            self.preview = None;
+                            self.set_dirty(true);
+                            return true;
+                        }
                         Action::Listing(ListingAction::Import(file_path, mailbox_path)) => {
                             let account = &mut context.accounts[self.cursor_pos.0];
                             if let Err(err) = account
@@ -956,9 +1515,86 @@ impl Component for Listing {
                             }
                             return true;
                         }
+                        Action::Listing(ListingAction::FilterDryRun) => {
+                            let (account_hash, mailbox_hash) = self.component.coordinates();
+                            let report =
+                                context.accounts[&account_hash].filters_dry_run(mailbox_hash);
+                            let colors = crate::conf::value(context, "theme_default");
+                            let pager =
+                                Pager::from_string(report, Some(context), None, None, colors);
+                            context
+                                .replies
+                                .push_back(UIEvent::Action(Tab(New(Some(Box::new(pager))))));
+                            return true;
+                        }
+                        Action::Listing(ListingAction::Undo)
+                        | Action::Listing(ListingAction::Redo) => {
+                            let account = &mut context.accounts[self.cursor_pos.0];
+                            let result = if let Action::Listing(ListingAction::Undo) = action {
+                                account.undo().map(|d| format!("Undid {}.", d))
+                            } else {
+                                account.redo().map(|d| format!("Redid {}.", d))
+                            };
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(match result {
+                                    Ok(msg) => msg,
+                                    Err(err) => err.to_string(),
+                                }),
+                            ));
+                            return true;
+                        }
+                        Action::Listing(ListingAction::Snooze(None)) => {
+                            context.replies.push_back(UIEvent::GlobalUIDialog(Box::new(
+                                DateTimeDialog::new(
+                                    "Snooze until",
+                                    Box::new(move |id: ComponentId, until| {
+                                        let until = melib::datetime::timestamp_to_string(
+                                            until,
+                                            Some("%Y-%m-%d %H:%M"),
+                                        );
+                                        Some(UIEvent::FinishedUIDialog(
+                                            id,
+                                            Box::new(Some(Action::Listing(ListingAction::Snooze(
+                                                Some(until),
+                                            )))),
+                                        ))
+                                    }),
+                                    context,
+                                ),
+                            )));
+                            return true;
+                        }
+                        Action::Listing(ListingAction::ToggleThreadMute)
+                        | Action::Listing(ListingAction::ToggleThreadWatch) => {
+                            let (rule, description) =
+                                if let Action::Listing(ListingAction::ToggleThreadMute) = action {
+                                    (ThreadRule::Muted, "muted")
+                                } else {
+                                    (ThreadRule::Watched, "watched")
+                                };
+                            let (account_hash, mailbox_hash) = self.component.coordinates();
+                            let threads = self.component.get_focused_items(context);
+                            let result = context.accounts[&account_hash].toggle_thread_rule(
+                                mailbox_hash,
+                                &threads,
+                                rule,
+                            );
+                            context.replies.push_back(UIEvent::StatusEvent(
+                                StatusEvent::DisplayMessage(match result {
+                                    Ok(true) => format!("Thread {}.", description),
+                                    Ok(false) => format!("Thread no longer {}.", description),
+                                    Err(err) => err.to_string(),
+                                }),
+                            ));
+                            self.component.row_updates().extend(threads.into_iter());
+                            self.component.set_dirty(true);
+                            return true;
+                        }
                         Action::Listing(a @ ListingAction::SetSeen)
                         | Action::Listing(a @ ListingAction::SetUnseen)
                         | Action::Listing(a @ ListingAction::Delete)
+                        | Action::Listing(a @ ListingAction::Archive)
+                        | Action::Listing(a @ ListingAction::Snooze(Some(_)))
                         | Action::Listing(a @ ListingAction::CopyTo(_))
                         | Action::Listing(a @ ListingAction::MoveTo(_))
                         | Action::Listing(a @ ListingAction::CopyToOtherAccount(_, _))
@@ -1095,6 +1731,30 @@ impl Component for Listing {
                             return true;
                         }
                     }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Listing::DESCRIPTION]["archive"]) =>
+                    {
+                        let mut event = UIEvent::Action(Action::Listing(ListingAction::Archive));
+                        if self.process_event(&mut event, context) {
+                            return true;
+                        }
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Listing::DESCRIPTION]["undo"]) =>
+                    {
+                        let mut event = UIEvent::Action(Action::Listing(ListingAction::Undo));
+                        if self.process_event(&mut event, context) {
+                            return true;
+                        }
+                    }
+                    UIEvent::Input(ref key)
+                        if shortcut!(key == shortcuts[Listing::DESCRIPTION]["redo"]) =>
+                    {
+                        let mut event = UIEvent::Action(Action::Listing(ListingAction::Redo));
+                        if self.process_event(&mut event, context) {
+                            return true;
+                        }
+                    }
                     UIEvent::Input(ref key)
                         if shortcut!(key == shortcuts[Listing::DESCRIPTION]["refresh"]) =>
                     {
@@ -1459,7 +2119,10 @@ impl Component for Listing {
                 .status
                 .as_ref()
                 .map(Component::is_dirty)
-                .unwrap_or_else(|| self.component.is_dirty())
+                .unwrap_or_else(|| {
+                    self.component.is_dirty()
+                        || self.preview.as_ref().map_or(false, Component::is_dirty)
+                })
     }
     fn set_dirty(&mut self, value: bool) {
         self.dirty = value;
@@ -1467,6 +2130,9 @@ impl Component for Listing {
             s.set_dirty(value);
         } else {
             self.component.set_dirty(value);
+            if let Some(p) = self.preview.as_mut() {
+                p.set_dirty(value);
+            }
         }
     }
 
@@ -1532,6 +2198,39 @@ impl Component for Listing {
             MailboxStatus::Failed(_) | MailboxStatus::None => account[&mailbox_hash].status(),
         }
     }
+
+    fn save_session(&self, session: &mut Session, context: &Context) {
+        let (account_hash, mailbox_hash) = self.component.coordinates();
+        session.listing = if self.pending_position.is_some() {
+            self.pending_position.clone()
+        } else {
+            context
+                .accounts
+                .get(&account_hash)
+                .and_then(|account| account.mailbox_entries.get(&mailbox_hash))
+                .map(|entry| MailboxPosition {
+                    account: context.accounts[&account_hash].name().to_string(),
+                    mailbox: entry.ref_mailbox.path().to_string(),
+                    cursor: self.component.cursor(),
+                })
+        };
+        session.accounts = self
+            .positions
+            .iter()
+            .filter(|(h, _)| **h != account_hash)
+            .filter_map(|(h, (path, cursor))| {
+                Some(MailboxPosition {
+                    account: context.accounts.get(h)?.name().to_string(),
+                    mailbox: path.to_string(),
+                    cursor: *cursor,
+                })
+            })
+            .collect();
+        session.listing_layout = Some(ListingLayoutSession {
+            layout: self.layout,
+            preview_ratio: self.preview_ratio,
+        });
+    }
 }
 
 impl Listing {
@@ -1584,11 +2283,179 @@ impl Listing {
             menu_width: WidgetWidth::Unset,
             focus: ListingFocus::Mailbox,
             cmd_buf: String::with_capacity(4),
+            positions: HashMap::default(),
+            pending_position: None,
+            layout: *account_settings!(context[first_account_hash].listing.layout),
+            preview_ratio: *account_settings!(context[first_account_hash].listing.preview_ratio),
+            preview: None,
+            preview_divider: None,
+            preview_drag: false,
+            component_area: ((0, 0), (0, 0)),
         };
         ret.change_account(context);
         ret
     }
 
+    /// Restores the listing positions of the previous session.
+    pub fn restore_session(&mut self, session: &Session, context: &mut Context) {
+        for position in session.accounts.iter() {
+            if let Some(account) = context
+                .accounts
+                .values()
+                .find(|a| a.name() == position.account)
+            {
+                self.positions
+                    .insert(account.hash(), (position.mailbox.clone(), position.cursor));
+            }
+        }
+        self.pending_position = session.listing.clone();
+        self.restore_position(context);
+        if let Some(ref listing_layout) = session.listing_layout {
+            self.layout = listing_layout.layout;
+            self.preview_ratio = std::cmp::min(95, std::cmp::max(5, listing_layout.preview_ratio));
+            self.set_dirty(true);
+        }
+    }
+
+    /// Draws the mailbox listing in `area`, beside or above the message under the cursor if the
+    /// layout has a preview.
+    fn draw_component(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        self.component_area = area;
+        let direction = match self.layout {
+            ListingLayout::Default => None,
+            ListingLayout::ThreeColumns => Some(SplitDirection::Vertical),
+            ListingLayout::Stacked => Some(SplitDirection::Horizontal),
+        };
+        let direction = match direction {
+            Some(direction) if !self.component.unfocused() => direction,
+            _ => {
+                self.preview = None;
+                self.preview_divider = None;
+                self.component.draw(grid, area, context);
+                return;
+            }
+        };
+        let areas = split_area(
+            area,
+            direction,
+            &[100 - self.preview_ratio, self.preview_ratio],
+        );
+        let (list_area, preview_area) = (areas[0], areas[1]);
+        let divider = match direction {
+            SplitDirection::Horizontal => {
+                let y = get_y(bottom_right!(list_area)) + 1;
+                (set_y(upper_left!(area), y), set_y(bottom_right!(area), y))
+            }
+            SplitDirection::Vertical => {
+                let x = get_x(bottom_right!(list_area)) + 1;
+                (set_x(upper_left!(area), x), set_x(bottom_right!(area), x))
+            }
+        };
+        if !is_valid_area!(preview_area) {
+            self.preview_divider = None;
+            self.component.draw(grid, area, context);
+            return;
+        }
+        if self.dirty || self.preview_divider != Some(divider) {
+            let ch = match direction {
+                SplitDirection::Horizontal => HORZ_BOUNDARY,
+                SplitDirection::Vertical => self.sidebar_divider,
+            };
+            for row in grid.bounds_iter(divider) {
+                for c in row {
+                    grid[c]
+                        .set_ch(ch)
+                        .set_fg(self.sidebar_divider_theme.fg)
+                        .set_bg(self.sidebar_divider_theme.bg)
+                        .set_attrs(self.sidebar_divider_theme.attrs);
+                }
+            }
+            context.dirty_areas.push_back(divider);
+            self.preview_divider = Some(divider);
+        }
+        self.component.draw(grid, list_area, context);
+        /* The cursor is only up to date once the list has been drawn. */
+        if let Some(env_hash) = self.component.preview_env(context) {
+            let (account_hash, mailbox_hash) = self.component.coordinates();
+            let coordinates = (account_hash, mailbox_hash, env_hash);
+            if let Some(ref mut v) = self.preview {
+                v.update(coordinates, context);
+            } else {
+                self.preview = Some(MailView::new(coordinates, None, None, context));
+            }
+            if let Some(v) = self.preview.as_mut() {
+                v.draw(grid, preview_area, context);
+            }
+        } else if self.preview.take().is_some() || self.dirty {
+            clear_area(grid, preview_area, self.theme_default);
+            context.dirty_areas.push_back(preview_area);
+        }
+    }
+
+    /// Moves the divider between the list and the preview to `(x, y)`.
+    fn move_preview_divider(&mut self, x: usize, y: usize) {
+        let area = self.component_area;
+        let (start, end, pos) = match self.layout {
+            ListingLayout::Stacked => (get_y(upper_left!(area)), get_y(bottom_right!(area)), y),
+            _ => (get_x(upper_left!(area)), get_x(bottom_right!(area)), x),
+        };
+        if end <= start {
+            return;
+        }
+        let list_ratio = pos.saturating_sub(start) * 100 / (end - start);
+        self.preview_ratio =
+            std::cmp::min(95, std::cmp::max(5, 100usize.saturating_sub(list_ratio)));
+        self.set_dirty(true);
+    }
+
+    /// Opens the mailbox of the previous session if its account is ready.
+    fn restore_position(&mut self, context: &mut Context) {
+        let position = match self.pending_position.take() {
+            Some(position) => position,
+            None => return,
+        };
+        let target = context
+            .accounts
+            .values()
+            .position(|a| a.name() == position.account)
+            .and_then(|account_index| {
+                let mailbox_hash = context.accounts[account_index]
+                    .mailbox_by_path(&position.mailbox)
+                    .ok()?;
+                self.accounts[account_index]
+                    .entries
+                    .iter()
+                    .position(|(_, _, _, h)| *h == mailbox_hash)
+                    .map(|idx| (account_index, idx))
+            });
+        if let Some((account_index, idx)) = target {
+            self.cursor_pos = (account_index, MenuEntryCursor::Mailbox(idx));
+            self.change_account(context);
+            if position.cursor > 0 {
+                self.component
+                    .set_movement(PageMovement::Down(position.cursor));
+            }
+        } else {
+            self.pending_position = Some(position);
+        }
+    }
+
+    /// The menu entry of the mailbox last shown in account `account_index`, and its cursor
+    /// position.
+    fn last_position(&self, account_index: usize, context: &Context) -> (MenuEntryCursor, usize) {
+        self.positions
+            .get(&self.accounts[account_index].hash)
+            .and_then(|(path, cursor)| {
+                let mailbox_hash = context.accounts[account_index].mailbox_by_path(path).ok()?;
+                self.accounts[account_index]
+                    .entries
+                    .iter()
+                    .position(|(_, _, _, h)| *h == mailbox_hash)
+                    .map(|idx| (MenuEntryCursor::Mailbox(idx), *cursor))
+            })
+            .unwrap_or((MenuEntryCursor::Mailbox(0), 0))
+    }
+
     fn draw_menu(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
         clear_area(grid, area, self.theme_default);
         let total_height: usize = 3 * (self.accounts.len())
@@ -1952,6 +2819,20 @@ impl Listing {
 
     fn change_account(&mut self, context: &mut Context) {
         let account_hash = context.accounts[self.cursor_pos.0].hash();
+        let (prev_account_hash, prev_mailbox_hash) = self.component.coordinates();
+        if let Some(entry) = context
+            .accounts
+            .get(&prev_account_hash)
+            .and_then(|account| account.mailbox_entries.get(&prev_mailbox_hash))
+        {
+            self.positions.insert(
+                prev_account_hash,
+                (
+                    entry.ref_mailbox.path().to_string(),
+                    self.component.cursor(),
+                ),
+            );
+        }
         self.accounts[self.cursor_pos.0].entries = context.accounts[self.cursor_pos.0]
             .list_mailboxes()
             .into_iter()
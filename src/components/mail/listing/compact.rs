@@ -149,6 +149,7 @@ pub struct CompactListing {
     view: ThreadView,
     row_updates: SmallVec<[ThreadHash; 8]>,
     color_cache: ColorCache,
+    index_format: Option<IndexFormat>,
 
     movement: Option<PageMovement>,
     modifier_active: bool,
@@ -212,6 +213,7 @@ impl MailListingTrait for CompactListing {
             attachment_flag: crate::conf::value(context, "mail.listing.attachment_flag"),
             thread_snooze_flag: crate::conf::value(context, "mail.listing.thread_snooze_flag"),
             tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+            fields: ColorCache::index_format_fields(context),
             theme_default: crate::conf::value(context, "theme_default"),
             ..self.color_cache
         };
@@ -221,6 +223,12 @@ impl MailListingTrait for CompactListing {
             self.color_cache.even_highlighted.attrs |= Attr::REVERSE;
             self.color_cache.odd_highlighted.attrs |= Attr::REVERSE;
         }
+        self.index_format = mailbox_settings!(
+            context[self.cursor_pos.0][&self.cursor_pos.1]
+                .listing
+                .index_format
+        )
+        .clone();
 
         // Get mailbox as a reference.
         //
@@ -281,6 +289,7 @@ impl MailListingTrait for CompactListing {
         self.length = 0;
         let mut rows = Vec::with_capacity(1024);
         let mut min_width = (0, 0, 0, 0, 0);
+        let mut formatted_width = 0;
         let mut row_widths: (
             SmallVec<[u8; 1024]>,
             SmallVec<[u8; 1024]>,
@@ -341,7 +350,20 @@ impl MailListingTrait for CompactListing {
                 }
             }
 
-            let entry_strings = self.make_entry_string(&root_envelope, context, &threads, thread);
+            let mut entry_strings =
+                self.make_entry_string(&root_envelope, context, &threads, thread);
+            entry_strings.format(
+                self.index_format.as_ref(),
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &root_envelope,
+                RowValues {
+                    index: self.length,
+                    date: threads.thread_ref(thread).date(),
+                    thread_count: threads.thread_ref(thread).len(),
+                },
+            );
+            formatted_width = cmp::max(formatted_width, entry_strings.formatted_width(0));
             row_widths.1.push(
                 entry_strings
                     .date
@@ -384,6 +406,10 @@ impl MailListingTrait for CompactListing {
         }
 
         min_width.0 = self.length.saturating_sub(1).to_string().len();
+        if self.index_format.is_some() {
+            /* Rows are drawn from `listing.index_format` in the first column. */
+            min_width = (formatted_width, 0, 0, 0, 0);
+        }
 
         /* index column */
         self.data_columns.columns[0] =
@@ -646,6 +672,10 @@ impl ListingTrait for CompactListing {
                 );
             }
         }
+        if self.index_format.is_some() {
+            self.data_columns.widths = Default::default();
+            self.data_columns.widths[0] = self.data_columns.columns[0].size().0;
+        }
         clear_area(grid, area, self.color_cache.theme_default);
         /* Page_no has changed, so draw new page */
         let mut x = get_x(upper_left);
@@ -845,6 +875,22 @@ impl ListingTrait for CompactListing {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn cursor(&self) -> usize {
+        self.cursor_pos.2
+    }
+
+    fn preview_env(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0 || self.unfocused {
+            return None;
+        }
+        latest_message(
+            self.cursor_pos.0,
+            self.cursor_pos.1,
+            self.get_thread_under_cursor(self.cursor_pos.2),
+            context,
+        )
+    }
 }
 
 impl fmt::Display for CompactListing {
@@ -879,6 +925,7 @@ impl CompactListing {
             unfocused: false,
             view: ThreadView::default(),
             color_cache: ColorCache::default(),
+            index_format: None,
             movement: None,
             modifier_active: false,
             modifier_command: None,
@@ -943,6 +990,7 @@ impl CompactListing {
                 )),
                 from: FromString(address_list!((e.from()) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                formatted: None,
             }
         } else {
             EntryStrings {
@@ -955,6 +1003,7 @@ impl CompactListing {
                 )),
                 from: FromString(address_list!((e.from()) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                formatted: None,
             }
         }
     }
@@ -995,9 +1044,38 @@ impl CompactListing {
                 false,
             );
             let envelope: EnvelopeRef = account.collection.get_env(env_hash);
-            let strings = self.make_entry_string(&envelope, context, &threads, thread_hash);
+            let mut strings = self.make_entry_string(&envelope, context, &threads, thread_hash);
+            strings.format(
+                self.index_format.as_ref(),
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &envelope,
+                RowValues {
+                    index: idx,
+                    date: thread.date(),
+                    thread_count: thread.len(),
+                },
+            );
             drop(envelope);
             let columns = &mut self.data_columns.columns;
+            if strings.formatted.is_some() {
+                let width = columns[0].size().0;
+                let x = write_formatted_line(
+                    &mut columns[0],
+                    &strings,
+                    0,
+                    (0, idx),
+                    row_attr,
+                    &self.color_cache,
+                );
+                for c in columns[0].row_iter(x..width, idx) {
+                    columns[0][c].set_ch(' ');
+                    columns[0][c].set_bg(row_attr.bg);
+                }
+                *self.rows.get_mut(idx).unwrap() = ((idx, (thread_hash, env_hash)), strings);
+                self.rows_drawn.update(idx, 1);
+                return;
+            }
             let min_width = (
                 columns[0].size().0,
                 columns[1].size().0,
@@ -1163,6 +1241,22 @@ impl CompactListing {
                 self.cursor_pos.2 == idx,
                 self.selection[thread_hash]
             );
+            if strings.formatted.is_some() {
+                let x = write_formatted_line(
+                    &mut self.data_columns.columns[0],
+                    strings,
+                    0,
+                    (0, idx),
+                    row_attr,
+                    &self.color_cache,
+                );
+                for x in x..min_width.0 {
+                    self.data_columns.columns[0][(x, idx)]
+                        .set_bg(row_attr.bg)
+                        .set_attrs(row_attr.attrs);
+                }
+                continue;
+            }
             let (x, _) = write_string_to_grid(
                 &idx.to_string(),
                 &mut self.data_columns.columns[0],
@@ -1716,6 +1810,7 @@ impl Component for CompactListing {
                         "mail.listing.thread_snooze_flag",
                     ),
                     tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+                    fields: ColorCache::index_format_fields(context),
                     theme_default: crate::conf::value(context, "theme_default"),
                     ..self.color_cache
                 };
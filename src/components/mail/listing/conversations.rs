@@ -117,6 +117,7 @@ pub struct ConversationsListing {
     view: ThreadView,
     row_updates: SmallVec<[ThreadHash; 8]>,
     color_cache: ColorCache,
+    index_format: Option<IndexFormat>,
 
     movement: Option<PageMovement>,
     modifier_active: bool,
@@ -183,6 +184,7 @@ impl MailListingTrait for ConversationsListing {
             attachment_flag: crate::conf::value(context, "mail.listing.attachment_flag"),
             thread_snooze_flag: crate::conf::value(context, "mail.listing.thread_snooze_flag"),
             tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+            fields: ColorCache::index_format_fields(context),
             ..self.color_cache
         };
 
@@ -190,6 +192,12 @@ impl MailListingTrait for ConversationsListing {
             self.color_cache.highlighted.attrs |= Attr::REVERSE;
             self.color_cache.tag_default.attrs |= Attr::REVERSE;
         }
+        self.index_format = mailbox_settings!(
+            context[self.cursor_pos.0][&self.cursor_pos.1]
+                .listing
+                .index_format
+        )
+        .clone();
         // Get mailbox as a reference.
         //
         match context.accounts[&self.cursor_pos.0].load(self.cursor_pos.1) {
@@ -320,13 +328,24 @@ impl MailListingTrait for ConversationsListing {
                 }
             }
 
-            let strings = self.make_entry_string(
+            let mut strings = self.make_entry_string(
                 root_envelope,
                 context,
                 &from_address_list,
                 &threads,
                 thread,
             );
+            strings.format(
+                self.index_format.as_ref(),
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                root_envelope,
+                RowValues {
+                    index: self.length,
+                    date: threads.thread_ref(thread).date(),
+                    thread_count: threads.thread_ref(thread).len(),
+                },
+            );
             max_entry_columns = std::cmp::max(
                 max_entry_columns,
                 strings.flag.len()
@@ -339,6 +358,10 @@ impl MailListingTrait for ConversationsListing {
                 max_entry_columns,
                 strings.date.len() + 1 + strings.from.grapheme_width(),
             );
+            max_entry_columns = std::cmp::max(
+                max_entry_columns,
+                std::cmp::max(strings.formatted_width(0), strings.formatted_width(1)),
+            );
             rows.push(((self.length, (thread, root_env_hash)), strings));
             self.all_threads.insert(thread);
 
@@ -470,6 +493,9 @@ impl MailListingTrait for ConversationsListing {
                     .set_fg(row_attr.fg)
                     .set_bg(row_attr.bg);
             }
+            if strings.formatted.is_some() {
+                self.draw_formatted_entry(&strings, idx, row_attr);
+            }
             for x in 0..width {
                 self.content[(x, 3 * idx + 2)]
                     .set_ch('▓')
@@ -860,6 +886,22 @@ impl ListingTrait for ConversationsListing {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn cursor(&self) -> usize {
+        self.cursor_pos.2
+    }
+
+    fn preview_env(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0 || self.unfocused {
+            return None;
+        }
+        latest_message(
+            self.cursor_pos.0,
+            self.cursor_pos.1,
+            self.get_thread_under_cursor(self.cursor_pos.2),
+            context,
+        )
+    }
 }
 
 impl fmt::Display for ConversationsListing {
@@ -891,6 +933,7 @@ impl ConversationsListing {
             unfocused: false,
             view: ThreadView::default(),
             color_cache: ColorCache::default(),
+            index_format: None,
             movement: None,
             modifier_active: false,
             modifier_command: None,
@@ -956,6 +999,7 @@ impl ConversationsListing {
                 )),
                 from: FromString(address_list!((from) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                formatted: None,
             }
         } else {
             EntryStrings {
@@ -968,6 +1012,7 @@ impl ConversationsListing {
                 )),
                 from: FromString(address_list!((from) as comma_sep_list)),
                 tags: TagString(tags, colors),
+                formatted: None,
             }
         }
     }
@@ -1006,6 +1051,42 @@ impl ConversationsListing {
         }
     }
 
+    /// Draws the entry at `idx` from `listing.index_format` over its default first row, and over
+    /// its second row if the template has a second line.
+    fn draw_formatted_entry(
+        &mut self,
+        strings: &EntryStrings,
+        idx: usize,
+        row_attr: ThemeAttribute,
+    ) {
+        let width = self.content.size().0;
+        let lines = strings.formatted.as_ref().map(Vec::len).unwrap_or(0);
+        for &(line, y, fill) in &[(0, 3 * idx, ' '), (1, 3 * idx + 1, '▁')] {
+            if line >= lines {
+                break;
+            }
+            /* `set_ch` also resets the keep flags of the default row. */
+            for c in self.content.row_iter(0..width, y) {
+                self.content[c]
+                    .set_ch(' ')
+                    .set_fg(row_attr.fg)
+                    .set_bg(row_attr.bg)
+                    .set_attrs(row_attr.attrs);
+            }
+            let x = write_formatted_line(
+                &mut self.content,
+                strings,
+                line,
+                (0, y),
+                row_attr,
+                &self.color_cache,
+            );
+            for c in self.content.row_iter(x..width, y) {
+                self.content[c].set_ch(fill);
+            }
+        }
+    }
+
     fn get_thread_under_cursor(&self, cursor: usize) -> ThreadHash {
         if self.filter_term.is_empty() {
             *self
@@ -1066,13 +1147,24 @@ impl ConversationsListing {
             }
         }
         let envelope: EnvelopeRef = account.collection.get_env(env_hash);
-        let strings = self.make_entry_string(
+        let mut strings = self.make_entry_string(
             &envelope,
             context,
             &from_address_list,
             &threads,
             thread_hash,
         );
+        strings.format(
+            self.index_format.as_ref(),
+            context,
+            (self.cursor_pos.0, self.cursor_pos.1),
+            &envelope,
+            RowValues {
+                index: idx,
+                date: thread.date(),
+                thread_count: thread.len(),
+            },
+        );
         drop(envelope);
         /* draw flags */
         let (x, _) = write_string_to_grid(
@@ -1180,6 +1272,9 @@ impl ConversationsListing {
                 .set_fg(row_attr.fg)
                 .set_bg(row_attr.bg);
         }
+        if strings.formatted.is_some() {
+            self.draw_formatted_entry(&strings, idx, row_attr);
+        }
         for c in self.content.row_iter(0..width, 3 * idx + 2) {
             self.content[c]
                 .set_ch('▓')
@@ -1640,6 +1735,7 @@ impl Component for ConversationsListing {
                         "mail.listing.thread_snooze_flag",
                     ),
                     tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+                    fields: ColorCache::index_format_fields(context),
                     ..self.color_cache
                 };
 
@@ -150,6 +150,7 @@ pub struct PlainListing {
     row_updates: SmallVec<[EnvelopeHash; 8]>,
     _row_updates: SmallVec<[ThreadHash; 8]>,
     color_cache: ColorCache,
+    index_format: Option<IndexFormat>,
 
     active_jobs: HashMap<JobId, JoinHandle<Result<()>>>,
     movement: Option<PageMovement>,
@@ -209,6 +210,7 @@ impl MailListingTrait for PlainListing {
             attachment_flag: crate::conf::value(context, "mail.listing.attachment_flag"),
             thread_snooze_flag: crate::conf::value(context, "mail.listing.thread_snooze_flag"),
             tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+            fields: ColorCache::index_format_fields(context),
             theme_default: crate::conf::value(context, "theme_default"),
             ..self.color_cache
         };
@@ -218,6 +220,12 @@ impl MailListingTrait for PlainListing {
             self.color_cache.even_highlighted.attrs |= Attr::REVERSE;
             self.color_cache.odd_highlighted.attrs |= Attr::REVERSE;
         }
+        self.index_format = mailbox_settings!(
+            context[self.cursor_pos.0][&self.cursor_pos.1]
+                .listing
+                .index_format
+        )
+        .clone();
 
         // Get mailbox as a reference.
         //
@@ -510,6 +518,10 @@ impl ListingTrait for PlainListing {
                 self.data_columns.widths[2] = min_col_width;
             }
         }
+        if self.index_format.is_some() {
+            self.data_columns.widths = Default::default();
+            self.data_columns.widths[0] = self.data_columns.columns[0].size().0;
+        }
         clear_area(grid, area, self.color_cache.theme_default);
         /* Page_no has changed, so draw new page */
         let mut x = get_x(upper_left);
@@ -543,7 +555,21 @@ impl ListingTrait for PlainListing {
             }
         }
         for r in 0..cmp::min(self.length - top_idx, rows) {
-            let (fg_color, bg_color) = {
+            let (fg_color, bg_color) = if self.index_format.is_some() {
+                /* The first cell may have the colors of a field */
+                let env_hash = self.get_env_under_cursor(r + top_idx, context);
+                let row_attr = row_attr!(
+                    self.color_cache,
+                    (r + top_idx) % 2 == 0,
+                    !context.accounts[&self.cursor_pos.0]
+                        .collection
+                        .get_env(env_hash)
+                        .is_seen(),
+                    self.cursor_pos.2 == (r + top_idx),
+                    self.selection[&env_hash]
+                );
+                (row_attr.fg, row_attr.bg)
+            } else {
                 let c = &self.data_columns.columns[0][(0, r + top_idx)];
                 (c.fg(), c.bg())
             };
@@ -687,6 +713,17 @@ impl ListingTrait for PlainListing {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn cursor(&self) -> usize {
+        self.cursor_pos.2
+    }
+
+    fn preview_env(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0 || self.unfocused {
+            return None;
+        }
+        Some(self.get_env_under_cursor(self.cursor_pos.2, context))
+    }
 }
 
 impl fmt::Display for PlainListing {
@@ -722,6 +759,7 @@ impl PlainListing {
             unfocused: false,
             view: MailView::default(),
             color_cache: ColorCache::default(),
+            index_format: None,
             active_jobs: HashMap::default(),
 
             movement: None,
@@ -775,6 +813,7 @@ impl PlainListing {
             flag: FlagString(format!("{}", if e.has_attachments() { "📎" } else { "" },)),
             from: FromString(address_list!((e.from()) as comma_sep_list)),
             tags: TagString(tags, colors),
+            formatted: None,
         }
     }
 
@@ -787,6 +826,8 @@ impl PlainListing {
         self.length = 0;
         let mut rows = Vec::with_capacity(1024);
         let mut min_width = (0, 0, 0, 0, 0);
+        let mut formatted_width = 0;
+        let threads = account.collection.get_threads(self.cursor_pos.1);
 
         for i in iter {
             if !context.accounts[&self.cursor_pos.0].contains_key(i) {
@@ -814,7 +855,25 @@ impl PlainListing {
                 }
             }
 
-            let entry_strings = self.make_entry_string(envelope, context);
+            let date = envelope.date();
+            let thread_count = threads
+                .thread_nodes()
+                .get(&envelope.thread())
+                .map(|node| threads.thread_ref(threads.find_group(node.group)).len())
+                .unwrap_or(1);
+            let mut entry_strings = self.make_entry_string(envelope, context);
+            entry_strings.format(
+                self.index_format.as_ref(),
+                context,
+                (self.cursor_pos.0, self.cursor_pos.1),
+                &context.accounts[&self.cursor_pos.0].collection.get_env(i),
+                RowValues {
+                    index: self.length,
+                    date,
+                    thread_count,
+                },
+            );
+            formatted_width = cmp::max(formatted_width, entry_strings.formatted_width(0));
             min_width.1 = cmp::max(min_width.1, entry_strings.date.grapheme_width()); /* date */
             min_width.2 = cmp::max(min_width.2, entry_strings.from.grapheme_width()); /* from */
             min_width.3 = cmp::max(min_width.3, entry_strings.flag.grapheme_width()); /* flags */
@@ -830,6 +889,10 @@ impl PlainListing {
         }
 
         min_width.0 = self.length.saturating_sub(1).to_string().len();
+        if self.index_format.is_some() {
+            /* Rows are drawn from `listing.index_format` in the first column. */
+            min_width = (formatted_width, 0, 0, 0, 0);
+        }
 
         /* index column */
         self.data_columns.columns[0] =
@@ -878,6 +941,20 @@ impl PlainListing {
                 false
             );
 
+            if strings.formatted.is_some() {
+                let x = write_formatted_line(
+                    &mut columns[0],
+                    &strings,
+                    0,
+                    (0, idx),
+                    row_attr,
+                    &self.color_cache,
+                );
+                for c in columns[0].row_iter(x..min_width.0, idx) {
+                    columns[0][c].set_bg(row_attr.bg).set_attrs(row_attr.attrs);
+                }
+                continue;
+            }
             let (x, _) = write_string_to_grid(
                 &idx.to_string(),
                 &mut columns[0],
@@ -1237,6 +1314,7 @@ impl Component for PlainListing {
                         "mail.listing.thread_snooze_flag",
                     ),
                     tag_default: crate::conf::value(context, "mail.listing.tag_default"),
+                    fields: ColorCache::index_format_fields(context),
                     theme_default: crate::conf::value(context, "theme_default"),
                     ..self.color_cache
                 };
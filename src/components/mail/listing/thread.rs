@@ -728,6 +728,17 @@ impl ListingTrait for ThreadListing {
         self.movement = Some(mvm);
         self.set_dirty(true);
     }
+
+    fn cursor(&self) -> usize {
+        self.cursor_pos.2
+    }
+
+    fn preview_env(&self, context: &Context) -> Option<EnvelopeHash> {
+        if self.length == 0 || self.unfocused {
+            return None;
+        }
+        Some(self.get_env_under_cursor(self.cursor_pos.2, context))
+    }
 }
 
 impl fmt::Display for ThreadListing {
@@ -882,6 +893,7 @@ impl ThreadListing {
             flag: FlagString(format!("{}", if e.has_attachments() { "📎" } else { "" },)),
             from: FromString(address_list!((e.from()) as comma_sep_list)),
             tags: TagString(tags, colors),
+            formatted: None,
         }
     }
 
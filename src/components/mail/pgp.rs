@@ -21,6 +21,7 @@
 
 use melib::email::{
     attachment_types::{ContentDisposition, ContentType, MultipartType},
+    attachments::decode,
     pgp as melib_pgp, Attachment, AttachmentBuilder,
 };
 use melib::error::*;
@@ -35,11 +36,24 @@ pub async fn decrypt(raw: Vec<u8>) -> Result<(melib_pgp::DecryptionMetadata, Vec
     ctx.decrypt(cipher)?.await
 }
 
+/// Decrypt the (transfer-decoded) body of an `application/pkcs7-mime` enveloped-data part.
+pub async fn decrypt_smime(raw: Vec<u8>) -> Result<(melib_pgp::DecryptionMetadata, Vec<u8>)> {
+    let mut ctx = Context::new()?;
+    ctx.set_protocol(Protocol::CMS)?;
+    let cipher = ctx.new_data_mem(&raw)?;
+    ctx.decrypt(cipher)?.await
+}
+
 pub async fn verify(a: Attachment) -> Result<()> {
     let (data, sig) =
         melib_pgp::verify_signature(&a).chain_err_summary(|| "Could not verify signature.")?;
     let mut ctx = Context::new()?;
-    let sig = ctx.new_data_mem(&sig.body().trim())?;
+    let sig = if sig.content_type == ContentType::CMSSignature {
+        ctx.set_protocol(Protocol::CMS)?;
+        ctx.new_data_mem(&decode(sig, None))?
+    } else {
+        ctx.new_data_mem(&sig.body().trim())?
+    };
     let data = ctx.new_data_mem(&data)?;
     ctx.verify(sig, data)?.await
 }
@@ -81,6 +95,63 @@ pub fn sign_filter(
     )
 }
 
+/// Like [`sign_filter`] but produces an S/MIME `multipart/signed` entity, signed with the
+/// certificate matching `sign_key` (or the first usable secret certificate if `None`).
+pub fn smime_sign_filter(
+    sign_key: Option<String>,
+) -> Result<
+    impl FnOnce(AttachmentBuilder) -> Pin<Box<dyn Future<Output = Result<AttachmentBuilder>> + Send>>
+        + Send,
+> {
+    Ok(
+        move |a: AttachmentBuilder| -> Pin<Box<dyn Future<Output = Result<AttachmentBuilder>>+Send>> {
+            Box::pin(async move {
+                let a: Attachment = a.into();
+                let mut ctx = Context::new()?;
+                ctx.set_protocol(Protocol::CMS)?;
+                ctx.set_flag(GpgmeFlag::AsciiArmor, false)?;
+                let sign_keys = ctx
+                    .keylist(true, sign_key.clone())?
+                    .await?
+                    .into_iter()
+                    .filter(|k| k.can_sign() && !k.revoked() && !k.expired() && !k.invalid())
+                    .take(1)
+                    .collect::<Vec<Key>>();
+                if sign_keys.is_empty() {
+                    return Err(MeliError::new(format!(
+                        "No usable S/MIME signing certificate found{}.",
+                        sign_key
+                            .as_ref()
+                            .map(|k| format!(" for `{}`", k))
+                            .unwrap_or_default()
+                    )));
+                }
+                let data = ctx.new_data_mem(&melib_pgp::convert_attachment_to_rfc_spec(
+                                a.into_raw().as_bytes(),
+                ))?;
+                let sig_attachment = Attachment::new(
+                    ContentType::CMSSignature,
+                    Default::default(),
+                    ctx.sign(sign_keys, data)?.await?,
+                );
+                let a: AttachmentBuilder = a.into();
+                let parts = vec![a, sig_attachment.into()];
+                let boundary = ContentType::make_boundary(&parts);
+                Ok(Attachment::new(
+                    ContentType::Multipart {
+                        boundary: boundary.into_bytes(),
+                        kind: MultipartType::Signed,
+                        parts: parts.into_iter().map(|a| a.into()).collect::<Vec<_>>(),
+                    },
+                    Default::default(),
+                    Vec::new(),
+                )
+                .into())
+            })
+        },
+    )
+}
+
 pub fn encrypt_filter(
     sign_keys: Option<Vec<Key>>,
     encrypt_keys: Vec<Key>,
@@ -126,3 +197,100 @@ pub fn encrypt_filter(
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use melib::email::Draft;
+    use std::os::unix::fs::DirBuilderExt;
+    use std::process::Command;
+
+    /// Run `gpgsm` with `args` in the keyring of `GNUPGHOME`, returning its stdout.
+    fn gpgsm(args: &[&str]) -> Option<Vec<u8>> {
+        let output = Command::new("gpgsm")
+            .args(&["--batch", "--pinentry-mode", "loopback", "--passphrase", ""])
+            .args(args)
+            .output()
+            .ok()?;
+        if output.status.success() {
+            Some(output.stdout)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_smime_sign() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("meli-smime-{}", uuid::Uuid::new_v4()));
+        std::fs::DirBuilder::new().mode(0o700).create(&dir).unwrap();
+        std::env::set_var("GNUPGHOME", &dir);
+        std::fs::write(dir.join("gpg-agent.conf"), "allow-loopback-pinentry\n").unwrap();
+        std::fs::write(dir.join("gpgsm.conf"), "disable-crl-checks\n").unwrap();
+        std::fs::write(
+            dir.join("params"),
+            "Key-Type: RSA\nKey-Length: 2048\nKey-Usage: sign\nSerial: random\n\
+Name-DN: CN=Jane,O=meli\nName-Email: jane@example.com\n",
+        )
+        .unwrap();
+        let cert = dir.join("cert.pem");
+        let fingerprint = gpgsm(&[
+            "--gen-key",
+            "--armor",
+            "--output",
+            cert.to_str().unwrap(),
+            dir.join("params").to_str().unwrap(),
+        ])
+        .and_then(|_| gpgsm(&["--import", cert.to_str().unwrap()]))
+        .and_then(|_| gpgsm(&["--with-colons", "--list-secret-keys"]))
+        .and_then(|out| {
+            String::from_utf8_lossy(&out)
+                .lines()
+                .find(|l| l.starts_with("fpr:"))
+                .and_then(|l| l.split(':').nth(9))
+                .map(str::to_string)
+        });
+        let fingerprint = if let Some(fingerprint) = fingerprint {
+            fingerprint
+        } else {
+            eprintln!("Skipping test_smime_sign: could not create a certificate with gpgsm.");
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        };
+        /* A self-signed certificate is only usable if it's trusted. */
+        std::fs::write(
+            dir.join("trustlist.txt"),
+            format!("{} S relax\n", fingerprint),
+        )
+        .unwrap();
+        let _ = Command::new("gpgconf")
+            .args(&["--reload", "gpg-agent"])
+            .status();
+
+        let mut text = AttachmentBuilder::default();
+        text.set_raw(b"Hello".to_vec());
+        let filter = smime_sign_filter(Some("jane@example.com".to_string())).unwrap();
+        let signed = futures::executor::block_on(filter(text)).unwrap();
+        let mut draft = Draft::default();
+        draft
+            .set_header("From", "Jane <jane@example.com>".to_string())
+            .set_header("To", "John <john@example.com>".to_string());
+        draft.attachments_mut().push(signed);
+        let raw = draft.finalise().unwrap();
+        assert!(raw.contains("; micalg=sha-256; protocol=\"application/pkcs7-signature\""));
+        assert!(raw.contains("Content-Type: application/pkcs7-signature; name=\"smime.p7s\""));
+
+        let a = AttachmentBuilder::new(raw.as_bytes()).build();
+        let (data, sig) = melib_pgp::verify_signature(&a).unwrap();
+        assert_eq!(data.as_slice(), &b"\r\nHello\r\n"[..]);
+        assert_eq!(sig.content_type, ContentType::CMSSignature);
+        /* DER encoded signatures start with a SEQUENCE tag. */
+        assert_eq!(decode(sig, None).first(), Some(&0x30));
+        futures::executor::block_on(verify(a)).unwrap();
+
+        let _ = Command::new("gpgconf")
+            .args(&["--kill", "gpg-agent"])
+            .status();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,688 @@
+/*
+ * meli - sieve script management tab module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Management of an account's server-side Sieve scripts over ManageSieve.
+ */
+use super::*;
+use crate::jobs::JoinHandle;
+use futures::lock::Mutex as FutureMutex;
+use melib::backends::imap::managesieve::{ManageSieveConnection, Vacation};
+use std::future::Future;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Name of the script uploaded by the vacation responder form.
+const VACATION_SCRIPT: &str = "vacation";
+
+#[derive(Debug)]
+enum SieveReply {
+    Scripts(Vec<(String, bool)>),
+    /// A script was fetched to be edited.
+    Script(String, Vec<u8>),
+    /// The server refused an uploaded script.
+    Rejected(String, Vec<u8>, MeliError),
+    Message(String),
+    /// An operation that changed the script list succeeded.
+    Done(String),
+}
+
+#[derive(Debug)]
+enum ViewMode {
+    List,
+    NewScript(FormWidget<bool>),
+    Vacation(FormWidget<bool>),
+    Delete(String, UIConfirmationDialog),
+}
+
+#[derive(Debug)]
+pub struct SieveScripts {
+    account_hash: AccountHash,
+    connection: Arc<FutureMutex<ManageSieveConnection>>,
+    scripts: Vec<(String, bool)>,
+    cursor_pos: usize,
+    /// The last script refused by the server, kept so that it can be corrected.
+    rejected: Option<(String, Vec<u8>)>,
+    message: String,
+    job: Option<JoinHandle<Result<SieveReply>>>,
+    mode: ViewMode,
+    theme_default: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for SieveScripts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", SieveScripts::DESCRIPTION)
+    }
+}
+
+impl SieveScripts {
+    const DESCRIPTION: &'static str = "sieve scripts";
+
+    pub fn new(account_hash: AccountHash, context: &Context) -> Result<Self> {
+        let account = &context.accounts[&account_hash];
+        let connection = ManageSieveConnection::new(
+            account_hash,
+            account.name().to_string(),
+            account.settings.account(),
+            melib::backends::BackendEventConsumer::new(Arc::new(|_, _| {})),
+        )?;
+        let mut ret = SieveScripts {
+            account_hash,
+            connection: Arc::new(FutureMutex::new(connection)),
+            scripts: vec![],
+            cursor_pos: 0,
+            rejected: None,
+            message: String::new(),
+            job: None,
+            mode: ViewMode::List,
+            theme_default: crate::conf::value(context, "theme_default"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        };
+        ret.refresh(context);
+        Ok(ret)
+    }
+
+    fn spawn<F>(&mut self, message: String, job: F, context: &Context)
+    where
+        F: Future<Output = Result<SieveReply>> + Send + 'static,
+    {
+        self.job = Some(context.job_executor.spawn_specialized(job));
+        self.message = message;
+        self.set_dirty(true);
+    }
+
+    fn refresh(&mut self, context: &Context) {
+        let connection = self.connection.clone();
+        self.spawn(
+            "Loading scripts...".to_string(),
+            async move {
+                let mut conn = connection.lock().await;
+                conn.connect().await?;
+                Ok(SieveReply::Scripts(conn.listscripts().await?))
+            },
+            context,
+        );
+    }
+
+    fn fetch(&mut self, name: String, context: &Context) {
+        let connection = self.connection.clone();
+        self.spawn(
+            format!("Fetching {}...", name),
+            async move {
+                let mut conn = connection.lock().await;
+                conn.connect().await?;
+                let script = conn.getscript(&name).await?;
+                Ok(SieveReply::Script(name, script))
+            },
+            context,
+        );
+    }
+
+    fn check(&mut self, name: String, context: &Context) {
+        let connection = self.connection.clone();
+        self.spawn(
+            format!("Checking {}...", name),
+            async move {
+                let mut conn = connection.lock().await;
+                conn.connect().await?;
+                let script = conn.getscript(&name).await?;
+                Ok(SieveReply::Message(
+                    match conn.checkscript(&script).await? {
+                        Some(warnings) => format!("{} is valid, with warnings: {}", name, warnings),
+                        None => format!("{} is valid.", name),
+                    },
+                ))
+            },
+            context,
+        );
+    }
+
+    fn upload(&mut self, name: String, script: Vec<u8>, activate: bool, context: &Context) {
+        let connection = self.connection.clone();
+        self.spawn(
+            format!("Uploading {}...", name),
+            async move {
+                let mut conn = connection.lock().await;
+                conn.connect().await?;
+                let warnings = match conn.putscript(&name, &script).await {
+                    Ok(warnings) => warnings,
+                    Err(err) => return Ok(SieveReply::Rejected(name, script, err)),
+                };
+                if activate {
+                    conn.setactive(&name).await?;
+                }
+                Ok(SieveReply::Done(match warnings {
+                    Some(warnings) => format!("Saved {}, with warnings: {}", name, warnings),
+                    None => format!("Saved {}.", name),
+                }))
+            },
+            context,
+        );
+    }
+
+    fn set_active(&mut self, name: String, active: bool, context: &Context) {
+        let connection = self.connection.clone();
+        self.spawn(
+            format!("Updating {}...", name),
+            async move {
+                let mut conn = connection.lock().await;
+                conn.connect().await?;
+                if active {
+                    conn.setactive(&name).await?;
+                    Ok(SieveReply::Done(format!("{} is now active.", name)))
+                } else {
+                    conn.setactive("").await?;
+                    Ok(SieveReply::Done(format!("{} is no longer active.", name)))
+                }
+            },
+            context,
+        );
+    }
+
+    fn delete(&mut self, name: String, context: &Context) {
+        let connection = self.connection.clone();
+        self.spawn(
+            format!("Deleting {}...", name),
+            async move {
+                let mut conn = connection.lock().await;
+                conn.connect().await?;
+                conn.deletescript(&name).await?;
+                Ok(SieveReply::Done(format!("Deleted {}.", name)))
+            },
+            context,
+        );
+    }
+
+    /// Opens `script` in the user's editor and uploads the result if it was changed.
+    fn edit(&mut self, name: String, script: Vec<u8>, context: &mut Context) {
+        let editor = if let Some(editor_command) =
+            account_settings!(context[self.account_hash].composing.editor_command).as_ref()
+        {
+            editor_command.to_string()
+        } else {
+            match std::env::var("EDITOR") {
+                Err(err) => {
+                    context.replies.push_back(UIEvent::Notification(
+                        Some(err.to_string()),
+                        "$EDITOR is not set. You can change an envvar's value with setenv or set composing.editor_command setting in your configuration.".to_string(),
+                        Some(NotificationType::Error(melib::error::ErrorKind::None)),
+                    ));
+                    return;
+                }
+                Ok(v) => v,
+            }
+        };
+        let f = create_temp_file(&script, None, None, true);
+        /* Kill input thread so that spawned command can be sole receiver of stdin */
+        {
+            context.input_kill();
+        }
+        let editor_command = format!("{} {}", editor, f.path().display());
+        match Command::new("sh")
+            .args(&["-c", &editor_command])
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .spawn()
+        {
+            Ok(mut child) => {
+                let _ = child.wait();
+            }
+            Err(err) => {
+                context.replies.push_back(UIEvent::Notification(
+                    Some(format!("Failed to execute {}: {}", editor, err)),
+                    err.to_string(),
+                    Some(NotificationType::Error(melib::error::ErrorKind::External)),
+                ));
+                context.replies.push_back(UIEvent::Fork(ForkType::Finished));
+                context.restore_input();
+                return;
+            }
+        }
+        context.replies.push_back(UIEvent::Fork(ForkType::Finished));
+        let new_script = f.read_to_string().into_bytes();
+        if new_script == script && self.scripts.iter().any(|(n, _)| *n == name) {
+            self.message = format!("{} was not changed.", name);
+            self.set_dirty(true);
+            return;
+        }
+        self.upload(name, new_script, false, context);
+    }
+
+    fn vacation_form(context: &Context, account_hash: AccountHash) -> FormWidget<bool> {
+        let mut form = FormWidget::new(("Save and activate".into(), true));
+        form.add_button(("Cancel(Esc)".into(), false));
+        form.push(("DAYS".into(), "7".to_string()));
+        form.push(("SUBJECT".into(), "Out of office".to_string()));
+        form.push(("FROM".into(), String::new()));
+        form.push((
+            "ADDRESSES".into(),
+            context.accounts[&account_hash]
+                .settings
+                .account()
+                .identity
+                .to_string(),
+        ));
+        form.push(("MESSAGE".into(), String::new()));
+        form
+    }
+
+    fn draw_list(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        let upper_left = upper_left!(area);
+        let bottom_right = bottom_right!(area);
+        clear_area(grid, area, self.theme_default);
+        let header_attrs = crate::conf::value(context, "widgets.list.header");
+        write_string_to_grid(
+            &format!(
+                "Sieve scripts of {}",
+                context.accounts[&self.account_hash].name()
+            ),
+            grid,
+            header_attrs.fg,
+            header_attrs.bg,
+            header_attrs.attrs,
+            (upper_left, set_y(bottom_right, get_y(upper_left))),
+            None,
+        );
+        let rows = height!(area).saturating_sub(4);
+        if rows == 0 {
+            return;
+        }
+        let top_idx = (self.cursor_pos / rows) * rows;
+        let highlighted = crate::conf::value(context, "widgets.options.highlighted");
+        for (i, (name, active)) in self.scripts.iter().enumerate().skip(top_idx).take(rows) {
+            let y = get_y(upper_left) + 2 + i - top_idx;
+            let attrs = if i == self.cursor_pos {
+                highlighted
+            } else {
+                self.theme_default
+            };
+            let line_area = (set_y(upper_left, y), set_y(bottom_right, y));
+            if i == self.cursor_pos {
+                clear_area(grid, line_area, highlighted);
+            }
+            write_string_to_grid(
+                &format!("{} {}", if *active { "[active]" } else { "        " }, name),
+                grid,
+                attrs.fg,
+                attrs.bg,
+                attrs.attrs,
+                line_area,
+                None,
+            );
+        }
+        if self.scripts.is_empty() && self.job.is_none() {
+            write_string_to_grid(
+                "No scripts.",
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs,
+                (
+                    pos_inc(upper_left, (0, 2)),
+                    set_y(bottom_right, get_y(upper_left) + 2),
+                ),
+                None,
+            );
+        }
+        write_string_to_grid(
+            &self.message,
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs,
+            (set_y(upper_left, get_y(bottom_right)), bottom_right),
+            None,
+        );
+    }
+}
+
+impl Component for SieveScripts {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        match self.mode {
+            ViewMode::NewScript(ref mut form) | ViewMode::Vacation(ref mut form) => {
+                form.draw(grid, area, context);
+            }
+            ViewMode::List | ViewMode::Delete(_, _) => {
+                if self.dirty {
+                    self.draw_list(grid, area, context);
+                    self.dirty = false;
+                    context.dirty_areas.push_back(area);
+                }
+                if let ViewMode::Delete(_, ref mut dialog) = self.mode {
+                    dialog.draw(grid, area, context);
+                }
+            }
+        }
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match (&mut self.mode, &mut *event) {
+            (_, UIEvent::ConfigReload { old_settings: _ }) => {
+                self.theme_default = crate::conf::value(context, "theme_default");
+                self.set_dirty(true);
+            }
+            (_, UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id)))
+                if self
+                    .job
+                    .as_ref()
+                    .map(|handle| handle.job_id == *job_id)
+                    .unwrap_or(false) =>
+            {
+                let mut handle = self.job.take().unwrap();
+                match handle
+                    .chan
+                    .try_recv()
+                    .map_err(|_: futures::channel::oneshot::Canceled| {
+                        MeliError::new("Job was canceled")
+                    }) {
+                    Ok(None) => {}
+                    Err(err) | Ok(Some(Err(err))) => {
+                        self.message = err.to_string();
+                    }
+                    Ok(Some(Ok(SieveReply::Scripts(scripts)))) => {
+                        self.message = format!("{} scripts", scripts.len());
+                        self.scripts = scripts;
+                        self.cursor_pos =
+                            std::cmp::min(self.cursor_pos, self.scripts.len().saturating_sub(1));
+                    }
+                    Ok(Some(Ok(SieveReply::Script(name, script)))) => {
+                        self.message.clear();
+                        self.edit(name, script, context);
+                    }
+                    Ok(Some(Ok(SieveReply::Rejected(name, script, err)))) => {
+                        self.message = format!(
+                            "{} was not saved: {} Edit it again to correct it.",
+                            name, err
+                        );
+                        if !self.scripts.iter().any(|(n, _)| *n == name) {
+                            self.scripts.push((name.clone(), false));
+                            self.cursor_pos = self.scripts.len() - 1;
+                        }
+                        self.rejected = Some((name, script));
+                    }
+                    Ok(Some(Ok(SieveReply::Message(message)))) => {
+                        self.message = message;
+                    }
+                    Ok(Some(Ok(SieveReply::Done(message)))) => {
+                        self.rejected = None;
+                        self.refresh(context);
+                        self.message = message;
+                    }
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::Delete(ref name, ref dialog), UIEvent::FinishedUIDialog(id, result))
+                if dialog.id() == *id =>
+            {
+                let name = name.to_string();
+                if let Some(true) = result.downcast_ref::<bool>() {
+                    self.delete(name, context);
+                }
+                self.mode = ViewMode::List;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::Delete(_, ref dialog), UIEvent::ComponentKill(ref id))
+                if *id == dialog.id() =>
+            {
+                self.mode = ViewMode::List;
+                self.set_dirty(true);
+            }
+            (ViewMode::Delete(_, ref mut dialog), _) => {
+                if dialog.process_event(event, context) {
+                    return true;
+                }
+            }
+            (ViewMode::NewScript(_), UIEvent::Input(Key::Esc))
+            | (ViewMode::Vacation(_), UIEvent::Input(Key::Esc)) => {
+                self.mode = ViewMode::List;
+                self.set_dirty(true);
+                return true;
+            }
+            (ViewMode::NewScript(ref mut form), _) => {
+                if form.process_event(event, context) {
+                    match form.buttons_result() {
+                        None => {}
+                        Some(true) => {
+                            let name = form.values()["NAME"].as_str().trim().to_string();
+                            self.mode = ViewMode::List;
+                            if !name.is_empty() {
+                                self.edit(name, b"require [\"fileinto\"];\n\n".to_vec(), context);
+                            }
+                        }
+                        Some(false) => {
+                            self.mode = ViewMode::List;
+                        }
+                    }
+                    self.set_dirty(true);
+                    return true;
+                }
+            }
+            (ViewMode::Vacation(ref mut form), _) => {
+                if form.process_event(event, context) {
+                    match form.buttons_result() {
+                        None => {}
+                        Some(true) => {
+                            let values = form.values();
+                            let field = |name: &str| {
+                                Some(values[name].as_str().trim().to_string())
+                                    .filter(|v| !v.is_empty())
+                            };
+                            let vacation = Vacation {
+                                days: field("DAYS").and_then(|d| d.parse::<u64>().ok()),
+                                subject: field("SUBJECT"),
+                                from: field("FROM"),
+                                addresses: field("ADDRESSES")
+                                    .map(|a| {
+                                        a.split(',')
+                                            .map(|a| a.trim().to_string())
+                                            .filter(|a| !a.is_empty())
+                                            .collect()
+                                    })
+                                    .unwrap_or_default(),
+                                reason: field("MESSAGE").unwrap_or_default(),
+                            };
+                            if vacation.reason.is_empty() {
+                                self.message = "The vacation message is empty.".to_string();
+                            } else {
+                                self.mode = ViewMode::List;
+                                self.upload(
+                                    VACATION_SCRIPT.to_string(),
+                                    vacation.to_script().into_bytes(),
+                                    true,
+                                    context,
+                                );
+                            }
+                        }
+                        Some(false) => {
+                            self.mode = ViewMode::List;
+                        }
+                    }
+                    self.set_dirty(true);
+                    return true;
+                }
+            }
+            (ViewMode::List, _) => {}
+        }
+        match self.mode {
+            ViewMode::List => {}
+            _ => return false,
+        }
+
+        let shortcuts = self.get_shortcuts(context);
+        match *event {
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_up"]) =>
+            {
+                self.cursor_pos = self.cursor_pos.saturating_sub(1);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["scroll_down"]) =>
+            {
+                if self.cursor_pos + 1 < self.scripts.len() {
+                    self.cursor_pos += 1;
+                }
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if self.job.is_some()
+                    && shortcuts[Self::DESCRIPTION].values().any(|k| k == key) =>
+            {
+                /* Commands of the ManageSieve session are sent one at a time */
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        "Waiting for the ManageSieve server.".to_string(),
+                    )));
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["refresh"]) =>
+            {
+                self.refresh(context);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["edit_script"])
+                    && !self.scripts.is_empty() =>
+            {
+                let name = self.scripts[self.cursor_pos].0.clone();
+                match self.rejected.take() {
+                    Some((rejected, script)) if rejected == name => {
+                        self.edit(name, script, context);
+                    }
+                    rejected => {
+                        self.rejected = rejected;
+                        self.fetch(name, context);
+                    }
+                }
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["new_script"]) =>
+            {
+                let mut form = FormWidget::new(("Create".into(), true));
+                form.add_button(("Cancel(Esc)".into(), false));
+                form.push(("NAME".into(), String::new()));
+                self.mode = ViewMode::NewScript(form);
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["check_script"])
+                    && !self.scripts.is_empty() =>
+            {
+                let name = self.scripts[self.cursor_pos].0.clone();
+                self.check(name, context);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["toggle_active"])
+                    && !self.scripts.is_empty() =>
+            {
+                let (name, active) = self.scripts[self.cursor_pos].clone();
+                self.set_active(name, !active, context);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["delete_script"])
+                    && !self.scripts.is_empty() =>
+            {
+                let name = self.scripts[self.cursor_pos].0.clone();
+                self.mode = ViewMode::Delete(
+                    name.clone(),
+                    UIConfirmationDialog::new(
+                        &format!("delete {}?", name),
+                        vec![(true, "yes".to_string()), (false, "no".to_string())],
+                        true,
+                        Some(Box::new(move |id: ComponentId, result: bool| {
+                            Some(UIEvent::FinishedUIDialog(id, Box::new(result)))
+                        })),
+                        context,
+                    ),
+                );
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Input(ref key)
+                if shortcut!(key == shortcuts[Self::DESCRIPTION]["vacation"]) =>
+            {
+                self.mode = ViewMode::Vacation(Self::vacation_form(context, self.account_hash));
+                self.set_dirty(true);
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+            || match self.mode {
+                ViewMode::NewScript(ref form) | ViewMode::Vacation(ref form) => form.is_dirty(),
+                ViewMode::Delete(_, ref dialog) => dialog.is_dirty(),
+                ViewMode::List => false,
+            }
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+        match self.mode {
+            ViewMode::NewScript(ref mut form) | ViewMode::Vacation(ref mut form) => {
+                form.set_dirty(value)
+            }
+            ViewMode::Delete(_, ref mut dialog) => dialog.set_dirty(value),
+            ViewMode::List => {}
+        }
+    }
+
+    fn kill(&mut self, uuid: Uuid, context: &mut Context) {
+        debug_assert!(uuid == self.id);
+        context.replies.push_back(UIEvent::Action(Tab(Kill(uuid))));
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        let mut map = ShortcutMaps::default();
+        map.insert(
+            Self::DESCRIPTION,
+            context.settings.shortcuts.sieve.key_values(),
+        );
+        map
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn get_status(&self, _context: &Context) -> String {
+        format!("{} scripts", self.scripts.len())
+    }
+}
@@ -22,7 +22,7 @@
 use super::*;
 use crate::conf::accounts::JobRequest;
 use crate::jobs::{JobId, JoinHandle};
-use melib::email::attachment_types::ContentType;
+use melib::email::attachment_types::{Charset, ContentType};
 use melib::list_management;
 use melib::parser::BytesExt;
 use smallvec::SmallVec;
@@ -35,12 +35,19 @@ use std::process::{Command, Stdio};
 
 mod html;
 pub use self::html::*;
+mod image;
+pub use self::image::*;
 mod thread;
 pub use self::thread::*;
 
 mod envelope;
 pub use self::envelope::*;
 
+#[cfg(feature = "dkim")]
+mod authentication;
+#[cfg(feature = "dkim")]
+use self::authentication::*;
+
 use linkify::LinkFinder;
 use xdg_utils::query_default_app;
 
@@ -105,6 +112,14 @@ pub enum AttachmentDisplay {
     InlineOther {
         inner: Attachment,
     },
+    /// An `image/*` part shown in the body, e.g. a part of a `multipart/related` body. The
+    /// body text reserves `INLINE_IMAGE_ROWS` empty lines below its description, and the image is
+    /// drawn over them.
+    InlineImage {
+        inner: Attachment,
+        image: Result<RgbaImage>,
+        kitty_id: u32,
+    },
     Attachment {
         inner: Attachment,
     },
@@ -157,12 +172,21 @@ pub struct MailView {
     expand_headers: bool,
     attachment_tree: String,
     attachment_paths: Vec<Vec<usize>>,
+    /// Indices in the attachment tree of the text parts the body doesn't show, searched along
+    /// with it.
+    hidden_text_parts: Vec<usize>,
     headers_no: usize,
     headers_cursor: usize,
     force_draw_headers: bool,
+    /// Charset to decode text parts with, overriding their declared charset.
+    force_charset: Option<Charset>,
     theme_default: ThemeAttribute,
     active_jobs: HashSet<JobId>,
     state: MailViewState,
+    /// Search of the parts of the message that the body view doesn't show.
+    search: Option<PartsSearch>,
+    #[cfg(feature = "dkim")]
+    authentication: AuthenticationState,
 
     cmd_buf: String,
     id: ComponentId,
@@ -209,6 +233,16 @@ struct Link {
     kind: LinkKind,
 }
 
+#[derive(Debug, Clone)]
+struct PartsSearch {
+    pattern: String,
+    /// Attachment indices of the hidden text parts with matches, and their number of matches.
+    parts: Vec<(usize, usize)>,
+    /// The part just switched to still has to be searched, starting from its last match if
+    /// `true`.
+    pending: Option<bool>,
+}
+
 impl Default for MailViewState {
     fn default() -> Self {
         MailViewState::Init {
@@ -226,8 +260,12 @@ impl Clone for MailView {
             mode: ViewMode::Normal,
             attachment_tree: self.attachment_tree.clone(),
             attachment_paths: self.attachment_paths.clone(),
+            hidden_text_parts: self.hidden_text_parts.clone(),
             state: MailViewState::default(),
             active_jobs: self.active_jobs.clone(),
+            search: None,
+            #[cfg(feature = "dkim")]
+            authentication: AuthenticationState::default(),
             ..*self
         }
     }
@@ -257,14 +295,19 @@ impl MailView {
             expand_headers: false,
             attachment_tree: String::new(),
             attachment_paths: vec![],
+            hidden_text_parts: vec![],
 
             headers_no: 5,
             headers_cursor: 0,
             force_draw_headers: false,
+            force_charset: None,
 
             theme_default: crate::conf::value(context, "mail.view.body"),
             active_jobs: Default::default(),
             state: MailViewState::default(),
+            search: None,
+            #[cfg(feature = "dkim")]
+            authentication: AuthenticationState::default(),
 
             cmd_buf: String::with_capacity(4),
             id: ComponentId::new_v4(),
@@ -310,26 +353,14 @@ impl MailView {
                                             .get_env_mut(self.coordinates.2)
                                             .populate_headers(&bytes);
                                     }
-                                    let body = AttachmentBuilder::new(&bytes).build();
-                                    let display = Self::attachment_to(
-                                        &body,
-                                        context,
+                                    #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+                                    Self::update_autocrypt_state(
                                         self.coordinates,
-                                        &mut self.active_jobs,
+                                        &bytes,
+                                        false,
+                                        context,
                                     );
-                                    let (paths, attachment_tree_s) =
-                                        self.attachment_displays_to_tree(&display);
-                                    self.attachment_tree = attachment_tree_s;
-                                    self.attachment_paths = paths;
-                                    let body_text =
-                                        self.attachment_displays_to_text(&display, context, true);
-                                    self.state = MailViewState::Loaded {
-                                        display,
-                                        body,
-                                        bytes,
-                                        body_text,
-                                        links: vec![],
-                                    };
+                                    self.load_body(bytes, context);
                                 }
                                 Err(err) => {
                                     self.state = MailViewState::Error { err };
@@ -478,6 +509,28 @@ impl MailView {
                         acc.push_str("\n\n");
                     }
                 }
+                InlineImage {
+                    inner,
+                    image,
+                    kitty_id: _,
+                } => {
+                    if !acc.ends_with("\n\n") {
+                        acc.push_str("\n\n");
+                    }
+                    acc.push_str(&inner.to_string());
+                    acc.push('\n');
+                    match image {
+                        Ok(_) => {
+                            for _ in 0..INLINE_IMAGE_ROWS {
+                                acc.push('\n');
+                            }
+                        }
+                        Err(err) => {
+                            acc.push_str(&err.to_string());
+                            acc.push_str("\n\n");
+                        }
+                    }
+                }
                 Attachment { inner: _ } => {}
                 SignedPending {
                     inner: _,
@@ -562,20 +615,96 @@ impl MailView {
         acc
     }
 
+    /// Draw `InlineImage` displays over the rows `attachment_displays_to_text` reserved for them
+    /// below their description, if that row is on screen.
+    fn draw_inline_images(&self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        fn collect<'a>(
+            displays: &'a [AttachmentDisplay],
+            acc: &mut Vec<(&'a Attachment, &'a RgbaImage, u32)>,
+        ) {
+            use AttachmentDisplay::*;
+            for d in displays {
+                match d {
+                    Alternative {
+                        inner: _,
+                        shown_display,
+                        display,
+                    } => collect(&display[*shown_display..(*shown_display + 1)], acc),
+                    InlineImage {
+                        inner,
+                        image: Ok(image),
+                        kitty_id,
+                    } => acc.push((inner, image, *kitty_id)),
+                    SignedPending { display, .. }
+                    | SignedUnverified { display, .. }
+                    | SignedFailed { display, .. }
+                    | SignedVerified { display, .. }
+                    | EncryptedSuccess {
+                        plaintext_display: display,
+                        ..
+                    } => collect(display, acc),
+                    _ => {}
+                }
+            }
+        }
+        let display = if let MailViewState::Loaded { ref display, .. } = self.state {
+            display
+        } else {
+            return;
+        };
+        let mut images = vec![];
+        collect(display, &mut images);
+        if images.is_empty() {
+            return;
+        }
+        let protocol = context.settings.terminal.image_protocol.resolve();
+        let (upper_left, bottom_right) = area;
+        let cursor = self.pager.cursor_pos();
+        let lines = self.pager.text_lines();
+        let mut line_idx = 0;
+        for (inner, image, kitty_id) in images {
+            let description = inner.to_string();
+            let top = match lines
+                .iter()
+                .skip(line_idx)
+                .position(|l| l.trim_end() == description)
+            {
+                Some(pos) => line_idx + pos + 1,
+                None => return,
+            };
+            line_idx = top + INLINE_IMAGE_ROWS;
+            if top < cursor || top - cursor > height!(area) {
+                continue;
+            }
+            let y = get_y(upper_left) + top - cursor;
+            let image_area = (
+                set_y(upper_left, y),
+                set_y(
+                    bottom_right,
+                    std::cmp::min(get_y(bottom_right), y + INLINE_IMAGE_ROWS - 1),
+                ),
+            );
+            draw_image(image, protocol, kitty_id, grid, image_area, context);
+        }
+    }
+
+    /// Returns the path of each entry of the attachment tree, the tree's text and the indices of
+    /// the text parts that aren't shown inline in the body: attachments and the alternatives that
+    /// aren't displayed by default.
     fn attachment_displays_to_tree(
-        &self,
         displays: &[AttachmentDisplay],
-    ) -> (Vec<Vec<usize>>, String) {
+    ) -> (Vec<Vec<usize>>, String, Vec<usize>) {
         let mut acc = String::new();
         let mut branches = SmallVec::new();
         let mut paths = Vec::with_capacity(displays.len());
+        let mut hidden_text_parts = vec![];
         let mut cur_path = vec![];
         let mut idx = 0;
 
         fn append_entry(
-            (idx, (depth, att_display)): (&mut usize, (usize, &AttachmentDisplay)),
+            (idx, (depth, shown, att_display)): (&mut usize, (usize, bool, &AttachmentDisplay)),
             branches: &mut SmallVec<[bool; 8]>,
-            paths: &mut Vec<Vec<usize>>,
+            (paths, hidden_text_parts): (&mut Vec<Vec<usize>>, &mut Vec<usize>),
             cur_path: &mut Vec<usize>,
             has_sibling: bool,
             s: &mut String,
@@ -597,6 +726,11 @@ impl MailView {
                     comment: _,
                 }
                 | InlineOther { inner }
+                | InlineImage {
+                    inner,
+                    image: _,
+                    kitty_id: _,
+                }
                 | Attachment { inner }
                 | EncryptedPending { inner, handle: _ }
                 | EncryptedFailed { inner, error: _ } => (inner, &[][..]),
@@ -647,6 +781,11 @@ impl MailView {
 
             s.extend(att.to_string().chars());
             paths.push(cur_path.clone());
+            let inline = if let InlineText { .. } = att_display {
+                shown
+            } else {
+                false
+            };
             match att.content_type {
                 ContentType::Multipart { .. } => {
                     let mut iter = (0..sub_att_display_vec.len()).peekable();
@@ -659,9 +798,16 @@ impl MailView {
                         *idx += 1;
                         cur_path.push(i);
                         append_entry(
-                            (idx, (depth + 1, &sub_att_display_vec[i])),
+                            (
+                                idx,
+                                (
+                                    depth + 1,
+                                    shown && default_alternative.map(|d| d == i).unwrap_or(true),
+                                    &sub_att_display_vec[i],
+                                ),
+                            ),
                             branches,
-                            paths,
+                            (paths, hidden_text_parts),
                             cur_path,
                             iter.peek() != None,
                             s,
@@ -673,6 +819,9 @@ impl MailView {
                     }
                     branches.pop();
                 }
+                _ if !inline && att.is_text() && !att.is_html() => {
+                    hidden_text_parts.push(*idx);
+                }
                 _ => {}
             }
         }
@@ -680,9 +829,9 @@ impl MailView {
         for (i, d) in displays.iter().enumerate() {
             cur_path.push(i);
             append_entry(
-                (&mut idx, (0, d)),
+                (&mut idx, (0, true, d)),
                 &mut branches,
-                &mut paths,
+                (&mut paths, &mut hidden_text_parts),
                 &mut cur_path,
                 i + 1 < displays.len(),
                 &mut acc,
@@ -690,7 +839,117 @@ impl MailView {
             cur_path.pop();
             idx += 1;
         }
-        (paths, acc)
+        (paths, acc, hidden_text_parts)
+    }
+
+    /// Searches the parts of the message that the body view doesn't show along with the pager,
+    /// and continues the search in them with `n` and `N` once the pager runs out of matches.
+    fn process_search_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match self.mode {
+            ViewMode::Normal | ViewMode::Attachment(_) => {}
+            _ => return false,
+        }
+        match event {
+            UIEvent::Action(Listing(Search(pattern))) => {
+                self.pager.set_search(pattern.to_string(), false, context);
+                let mut parts = vec![];
+                for lidx in self.hidden_text_parts.clone() {
+                    let matches = self
+                        .open_attachment(lidx, context)
+                        .map(|a| self.pager.count_matches(&a.text()))
+                        .unwrap_or(0);
+                    if matches > 0 {
+                        parts.push((lidx, matches));
+                    }
+                }
+                if !parts.is_empty() {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(format!(
+                            "Other parts of the message also match: {}. Press n/N past the last match to go through them.",
+                            parts
+                                .iter()
+                                .map(|(lidx, matches)| format!("[{}] {}", lidx, matches))
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        ))));
+                }
+                self.search = Some(PartsSearch {
+                    pattern: pattern.to_string(),
+                    parts,
+                    pending: None,
+                });
+                true
+            }
+            UIEvent::Input(Key::Char(c)) if *c == 'n' || *c == 'N' => {
+                let forward = *c == 'n';
+                let search = match self.search {
+                    Some(ref mut search) if !search.parts.is_empty() => search,
+                    _ => return false,
+                };
+                let (cursor, total) = match self.pager.search_position() {
+                    Some(position) => position,
+                    None => return false,
+                };
+                if total > 0 && ((forward && cursor + 1 < total) || (!forward && cursor > 0)) {
+                    return false;
+                }
+                let current = if let ViewMode::Attachment(lidx) = self.mode {
+                    Some(lidx)
+                } else {
+                    None
+                };
+                /* The body comes first, then the hidden parts in tree order. */
+                let sequence = std::iter::once(None)
+                    .chain(search.parts.iter().map(|(lidx, _)| Some(*lidx)))
+                    .collect::<Vec<Option<usize>>>();
+                let pos = sequence.iter().position(|p| *p == current).unwrap_or(0);
+                let next = if forward {
+                    (pos + 1) % sequence.len()
+                } else {
+                    (pos + sequence.len() - 1) % sequence.len()
+                };
+                search.pending = Some(!forward);
+                self.mode = match sequence[next] {
+                    Some(lidx) => ViewMode::Attachment(lidx),
+                    None => ViewMode::Normal,
+                };
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::DisplayMessage(
+                        match sequence[next] {
+                            Some(lidx) => format!("Continuing search in attachment [{}].", lidx),
+                            None => "Continuing search in message body.".to_string(),
+                        },
+                    )));
+                self.initialised = false;
+                self.set_dirty(true);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Parse `bytes`, with text parts decoded as `force_charset` if it is set, and show them.
+    fn load_body(&mut self, bytes: Vec<u8>, context: &mut Context) {
+        let mut body = AttachmentBuilder::new(&bytes).build();
+        if let Some(charset) = self.force_charset {
+            body.force_charset(charset);
+        }
+        let display = Self::attachment_to(&body, context, self.coordinates, &mut self.active_jobs);
+        let (paths, attachment_tree_s, hidden_text_parts) =
+            Self::attachment_displays_to_tree(&display);
+        self.attachment_tree = attachment_tree_s;
+        self.attachment_paths = paths;
+        self.hidden_text_parts = hidden_text_parts;
+        let body_text = self.attachment_displays_to_text(&display, context, true);
+        self.state = MailViewState::Loaded {
+            display,
+            body,
+            bytes,
+            body_text,
+            links: vec![],
+        };
     }
 
     fn attachment_to(
@@ -707,7 +966,40 @@ impl MailView {
             acc: &mut Vec<AttachmentDisplay>,
             active_jobs: &mut HashSet<JobId>,
         ) {
-            if a.content_disposition.kind.is_attachment() || a.content_type == "message/rfc822" {
+            if melib::pgp::smime_type(a) == Some(melib::pgp::SMimeType::EnvelopedData) {
+                #[cfg(not(feature = "gpgme"))]
+                {
+                    acc.push(AttachmentDisplay::EncryptedFailed {
+                        inner: a.clone(),
+                        error: MeliError::new(
+                            "Cannot decrypt: meli must be compiled with libgpgme support.",
+                        ),
+                    });
+                }
+                #[cfg(feature = "gpgme")]
+                {
+                    if *mailbox_settings!(context[coordinates.0][&coordinates.1].pgp.auto_decrypt) {
+                        let decrypt_fut =
+                            crate::components::mail::pgp::decrypt_smime(decode(a, None));
+                        let handle = context.job_executor.spawn_specialized(decrypt_fut);
+                        active_jobs.insert(handle.job_id);
+                        context
+                            .replies
+                            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+                        acc.push(AttachmentDisplay::EncryptedPending {
+                            inner: a.clone(),
+                            handle,
+                        });
+                    } else {
+                        acc.push(AttachmentDisplay::EncryptedFailed {
+                            inner: a.clone(),
+                            error: MeliError::new("Undecrypted."),
+                        });
+                    }
+                }
+            } else if a.content_disposition.kind.is_attachment()
+                || a.content_type == "message/rfc822"
+            {
                 acc.push(AttachmentDisplay::Attachment { inner: a.clone() });
             } else if a.content_type().is_text_html() {
                 let bytes = decode(a, None);
@@ -764,12 +1056,63 @@ impl MailView {
                         });
                     }
                 }
+            } else if a.is_image() {
+                /* Inline images, e.g. parts of a multipart/related body */
+                let protocol = context.settings.terminal.image_protocol.resolve();
+                if protocol == ImageProtocol::None {
+                    acc.push(AttachmentDisplay::InlineOther { inner: a.clone() });
+                } else {
+                    acc.push(AttachmentDisplay::InlineImage {
+                        inner: a.clone(),
+                        image: RgbaImage::decode(&decode(a, None)),
+                        kitty_id: next_kitty_image_id(),
+                    });
+                }
+            } else if a.is_calendar() {
+                let bytes = decode(a, None);
+                let text = String::from_utf8_lossy(&bytes).to_string();
+                match melib::icalendar::Calendar::parse(&text) {
+                    Ok(calendar) if !calendar.events().is_empty() => {
+                        let method = calendar.method();
+                        let mut text = String::new();
+                        for event in calendar.events() {
+                            text.push_str(&event.to_string());
+                            text.push('\n');
+                        }
+                        let comment = match method.as_ref().map(String::as_str) {
+                            Some("REQUEST") => "Invitation. Reply with `rsvp accept`, `rsvp decline` or `rsvp tentative`, save with `export-ics PATH`.",
+                            Some("CANCEL") => "Cancelled event.",
+                            Some("REPLY") => "Reply to invitation.",
+                            _ => "Calendar event. Save with `export-ics PATH`.",
+                        };
+                        acc.push(AttachmentDisplay::InlineText {
+                            inner: a.clone(),
+                            comment: Some(comment.to_string()),
+                            text,
+                        });
+                    }
+                    _ => {
+                        acc.push(AttachmentDisplay::InlineText {
+                            inner: a.clone(),
+                            comment: None,
+                            text,
+                        });
+                    }
+                }
             } else if a.is_text() {
                 let bytes = decode(a, None);
+                let mut text = String::from_utf8_lossy(&bytes).to_string();
+                if let Some(delsp) = a.format_flowed() {
+                    if *mailbox_settings!(
+                        context[coordinates.0][&coordinates.1].pager.format_flowed
+                    ) {
+                        text = melib::email::attachments::interpret_format_flowed(&text, delsp);
+                    }
+                }
                 acc.push(AttachmentDisplay::InlineText {
                     inner: a.clone(),
                     comment: None,
-                    text: String::from_utf8_lossy(&bytes).to_string(),
+                    text,
                 });
             } else if let ContentType::Multipart {
                 ref kind,
@@ -920,12 +1263,165 @@ impl MailView {
         if self.coordinates != new_coordinates {
             self.coordinates = new_coordinates;
             self.mode = ViewMode::Normal;
+            self.force_charset = None;
+            self.search = None;
+            #[cfg(feature = "dkim")]
+            {
+                self.authentication = AuthenticationState::default();
+            }
             self.initialised = false;
             self.init_futures(context);
             self.set_dirty(true);
         }
     }
 
+    #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+    /// Feed the `Autocrypt` header of the viewed message, or the `Autocrypt-Gossip` headers of
+    /// its decrypted part if `gossip` is true, to the account's Autocrypt peer state. This is
+    /// only done with the full message bytes: backends don't fetch these headers with envelopes.
+    fn update_autocrypt_state(
+        coordinates: (AccountHash, MailboxHash, EnvelopeHash),
+        bytes: &[u8],
+        gossip: bool,
+        context: &mut Context,
+    ) {
+        let (from, date, recipients) = {
+            let envelope = context.accounts[&coordinates.0]
+                .collection
+                .get_env(coordinates.2);
+            let from = match envelope.from().get(0) {
+                Some(addr) => addr.get_email(),
+                None => return,
+            };
+            let mut recipients = envelope
+                .to()
+                .iter()
+                .map(|addr| addr.get_email())
+                .collect::<Vec<String>>();
+            if let Some(Ok((_, cc))) = envelope
+                .other_headers()
+                .get("Cc")
+                .map(|cc| melib::email::parser::address::rfc2822address_list(cc.as_bytes()))
+            {
+                recipients.extend(cc.into_iter().map(|addr| addr.get_email()));
+            }
+            (from, envelope.date(), recipients)
+        };
+        let (autocrypt, gossip) = if gossip {
+            (
+                None,
+                crate::autocrypt::header_values(bytes, "Autocrypt-Gossip"),
+            )
+        } else {
+            (
+                Some(crate::autocrypt::header_values(bytes, "Autocrypt")),
+                vec![],
+            )
+        };
+        crate::autocrypt::process_headers(
+            context,
+            coordinates.0,
+            crate::autocrypt::MessageHeaders {
+                from,
+                date,
+                recipients,
+                autocrypt,
+                gossip,
+            },
+        );
+    }
+
+    /// Returns the first calendar attachment of the loaded message and the calendar it contains.
+    fn calendar(&self) -> Result<(Attachment, melib::icalendar::Calendar)> {
+        let body = if let MailViewState::Loaded { ref body, .. } = self.state {
+            body
+        } else {
+            return Err(MeliError::new("Message is not loaded yet."));
+        };
+        let attachment = body
+            .attachments()
+            .into_iter()
+            .find(Attachment::is_calendar)
+            .ok_or_else(|| MeliError::new("Message has no calendar attachment."))?;
+        let calendar = melib::icalendar::Calendar::parse(&String::from_utf8_lossy(&decode(
+            &attachment,
+            None,
+        )))?;
+        Ok((attachment, calendar))
+    }
+
+    /// Open a composer with an iTIP reply to the loaded message's invitation.
+    fn rsvp(
+        &self,
+        partstat: melib::icalendar::PartStat,
+        context: &mut Context,
+    ) -> Result<Composer> {
+        use melib::email::attachment_types::{Charset, Text};
+        use melib::icalendar::PartStat;
+
+        let (_, calendar) = self.calendar()?;
+        if calendar.method().as_ref().map(String::as_str) != Some("REQUEST") {
+            return Err(MeliError::new("Calendar attachment is not an invitation."));
+        }
+        let event = calendar
+            .events()
+            .into_iter()
+            .next()
+            .ok_or_else(|| MeliError::new("Invitation has no event."))?;
+        let organizer = event
+            .organizer
+            .as_ref()
+            .ok_or_else(|| MeliError::new("Invitation has no organizer."))?;
+        let account = &context.accounts[&self.coordinates.0];
+        let identity = account.settings.account().identity();
+        let address = event
+            .attendees
+            .iter()
+            .find(|a| a.address.eq_ignore_ascii_case(identity))
+            .map(|a| a.address.clone())
+            .unwrap_or_else(|| identity.to_string());
+        let reply = event.reply(&address, partstat.clone());
+
+        let mut draft = Draft::default();
+        draft.set_header("To", organizer.mailbox());
+        draft.set_header(
+            "Subject",
+            format!(
+                "{}: {}",
+                match partstat {
+                    PartStat::Accepted => "Accepted",
+                    PartStat::Declined => "Declined",
+                    PartStat::Tentative => "Tentative",
+                    _ => "Reply",
+                },
+                event.summary.as_ref().map(String::as_str).unwrap_or("")
+            ),
+        );
+        if account.contains_key(self.coordinates.2) {
+            let envelope: EnvelopeRef = account.collection.get_env(self.coordinates.2);
+            draft.set_header("In-Reply-To", envelope.message_id_display().into());
+            draft.set_header("References", envelope.message_id_display().into());
+        }
+        draft.set_body(format!("{} has {} this invitation.\n", address, partstat));
+        draft.attachments_mut().push(
+            Attachment::new(
+                ContentType::Text {
+                    kind: Text::Other {
+                        tag: b"calendar".to_vec(),
+                    },
+                    parameters: vec![(b"method".to_vec(), b"REPLY".to_vec())],
+                    charset: Charset::UTF8,
+                },
+                Default::default(),
+                reply.to_string().into_bytes(),
+            )
+            .into(),
+        );
+        let mut composer = Composer::with_account(self.coordinates.0, context);
+        composer.set_draft(draft);
+        Ok(composer)
+    }
+
     fn open_attachment(
         &'_ self,
         lidx: usize,
@@ -961,6 +1457,11 @@ impl MailView {
                     comment: _,
                 }
                 | InlineOther { inner }
+                | InlineImage {
+                    inner,
+                    image: _,
+                    kitty_id: _,
+                }
                 | Attachment { inner }
                 | SignedPending {
                     inner,
@@ -1041,6 +1542,17 @@ impl Component for MailView {
         let upper_left = upper_left!(area);
         let bottom_right = bottom_right!(area);
 
+        #[cfg(feature = "dkim")]
+        {
+            if let MailViewState::Loaded { ref bytes, .. } = self.state {
+                self.authentication.start_if_enabled(
+                    bytes,
+                    Some((self.coordinates.0, self.coordinates.1)),
+                    context,
+                );
+            }
+        }
+
         let y: usize = {
             let account = &context.accounts[&self.coordinates.0];
             if !account.contains_key(self.coordinates.2) {
@@ -1111,11 +1623,35 @@ impl Component for MailView {
                         })+
                     };
                 }
-                print_header!(
-                    ("Date:", envelope.date_as_str()),
-                    ("From:", envelope.field_from_to_string()),
-                    ("To:", envelope.field_to_to_string()),
-                );
+                print_header!(("Date:", envelope.date_as_str()));
+                #[cfg(feature = "dkim")]
+                let from_y = y;
+                print_header!(("From:", envelope.field_from_to_string()));
+                #[cfg(feature = "dkim")]
+                {
+                    /* Show the indicator after the address, if the From: line was drawn and there
+                     * is room for it on the same line. */
+                    let (indicator, color) = self.authentication.indicator();
+                    let x = get_x(upper_left)
+                        + "From: ".len()
+                        + envelope.field_from_to_string().grapheme_width()
+                        + 1;
+                    if !indicator.is_empty()
+                        && y == from_y + 1
+                        && x + indicator.grapheme_width() <= get_x(bottom_right)
+                    {
+                        write_string_to_grid(
+                            &indicator,
+                            grid,
+                            color,
+                            headers_area.bg,
+                            Attr::BOLD,
+                            ((x, y - 1), bottom_right),
+                            None,
+                        );
+                    }
+                }
+                print_header!(("To:", envelope.field_to_to_string()));
                 if envelope.other_headers().contains_key("Cc")
                     && !envelope.other_headers()["Cc"].is_empty()
                 {
@@ -1340,6 +1876,9 @@ impl Component for MailView {
                         if attachment.is_html() {
                             self.subview = Some(Box::new(HtmlView::new(&attachment, context)));
                             self.mode = ViewMode::Subview;
+                        } else if attachment.is_image() {
+                            self.subview = Some(Box::new(ImageView::new(&attachment, context)));
+                            self.mode = ViewMode::Subview;
                         } else {
                             text.push_str(&attachment.text());
                             let colors = crate::conf::value(context, "mail.view.body");
@@ -1502,6 +2041,14 @@ impl Component for MailView {
                 }
             };
         }
+        if let Some(from_end) = self.search.as_mut().and_then(|s| s.pending.take()) {
+            let pattern = self
+                .search
+                .as_ref()
+                .map(|s| s.pattern.clone())
+                .unwrap_or_default();
+            self.pager.set_search(pattern, from_end, context);
+        }
         match self.mode {
             ViewMode::Subview if self.subview.is_some() => {
                 if let Some(s) = self.subview.as_mut() {
@@ -1515,6 +2062,10 @@ impl Component for MailView {
             _ => {
                 self.pager
                     .draw(grid, (set_y(upper_left, y), bottom_right), context);
+                /* Redrawing headers or the pager may have removed images from the screen */
+                if self.mode == ViewMode::Normal || self.mode == ViewMode::Url {
+                    self.draw_inline_images(grid, (set_y(upper_left, y), bottom_right), context);
+                }
             }
         }
         if let ViewMode::ContactSelector(ref mut s) = self.mode {
@@ -1526,6 +2077,9 @@ impl Component for MailView {
         if self.coordinates.0 == 0 || self.coordinates.1 == 0 {
             return false;
         }
+        if self.process_search_event(event, context) {
+            return true;
+        }
         let shortcuts = self.get_shortcuts(context);
         match (&mut self.mode, &mut event) {
             /*(ViewMode::Ansi(ref mut buf), _) => {
@@ -1599,6 +2153,15 @@ impl Component for MailView {
                     self.pager.set_dirty(true);
                     return true;
                 }
+                #[cfg(feature = "dkim")]
+                UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                    if self.authentication.job_id() == Some(*job_id) =>
+                {
+                    self.authentication.finish();
+                    self.force_draw_headers = true;
+                    self.set_dirty(true);
+                    return true;
+                }
                 UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
                     if self.active_jobs.contains(job_id) =>
                 {
@@ -1623,26 +2186,14 @@ impl Component for MailView {
                                             .get_env_mut(self.coordinates.2)
                                             .populate_headers(&bytes);
                                     }
-                                    let body = AttachmentBuilder::new(&bytes).build();
-                                    let display = Self::attachment_to(
-                                        &body,
-                                        context,
+                                    #[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+                                    Self::update_autocrypt_state(
                                         self.coordinates,
-                                        &mut self.active_jobs,
+                                        &bytes,
+                                        false,
+                                        context,
                                     );
-                                    let (paths, attachment_tree_s) =
-                                        self.attachment_displays_to_tree(&display);
-                                    self.attachment_tree = attachment_tree_s;
-                                    self.attachment_paths = paths;
-                                    let body_text =
-                                        self.attachment_displays_to_text(&display, context, true);
-                                    self.state = MailViewState::Loaded {
-                                        bytes,
-                                        body,
-                                        display,
-                                        links: vec![],
-                                        body_text,
-                                    };
+                                    self.load_body(bytes, context);
                                 }
                                 Ok(Some(Err(err))) => {
                                     self.state = MailViewState::Error { err };
@@ -1702,6 +2253,16 @@ impl Component for MailView {
                                             Ok(None) => { /* something happened, perhaps a worker thread panicked */
                                             }
                                             Ok(Some(Ok((metadata, decrypted_bytes)))) => {
+                                                #[cfg(all(
+                                                    feature = "sqlite3",
+                                                    feature = "gpgme"
+                                                ))]
+                                                Self::update_autocrypt_state(
+                                                    self.coordinates,
+                                                    &decrypted_bytes,
+                                                    true,
+                                                    context,
+                                                );
                                                 let plaintext =
                                                     AttachmentBuilder::new(&decrypted_bytes)
                                                         .build();
@@ -1740,10 +2301,11 @@ impl Component for MailView {
                                 if let MailViewState::Loaded { ref display, .. } = self.state {
                                     new_body_text =
                                         self.attachment_displays_to_text(&display, context, true);
-                                    let (paths, attachment_tree_s) =
-                                        self.attachment_displays_to_tree(&display);
+                                    let (paths, attachment_tree_s, hidden_text_parts) =
+                                        Self::attachment_displays_to_tree(&display);
                                     self.attachment_tree = attachment_tree_s;
                                     self.attachment_paths = paths;
+                                    self.hidden_text_parts = hidden_text_parts;
                                 }
                                 if let MailViewState::Loaded {
                                     ref mut body_text,
@@ -2009,6 +2571,7 @@ impl Component for MailView {
                                                     None,
                                                     None,
                                                     self.coordinates.0,
+                                                    Some(self.coordinates.1),
                                                 ))),
                                             ))));
                                         }
@@ -2035,6 +2598,15 @@ impl Component for MailView {
                                         ),
                                     ));
                                 }
+                                ContentType::Other { .. }
+                                    if attachment.is_image()
+                                        && context.settings.terminal.image_protocol.resolve()
+                                            != ImageProtocol::None =>
+                                {
+                                    self.mode = ViewMode::Attachment(lidx);
+                                    self.initialised = false;
+                                    self.dirty = true;
+                                }
                                 ContentType::Other { .. } => {
                                     let attachment_type = attachment.mime_type();
                                     let filename = attachment.filename();
@@ -2249,6 +2821,85 @@ impl Component for MailView {
 
                 return true;
             }
+            UIEvent::Action(View(ViewAction::ViewAsCharset(charset))) => {
+                self.force_charset = charset;
+                if let MailViewState::Loaded { ref bytes, .. } = self.state {
+                    let bytes = bytes.clone();
+                    self.load_body(bytes, context);
+                    self.initialised = false;
+                    self.set_dirty(true);
+                }
+                return true;
+            }
+            UIEvent::Action(View(ViewAction::Rsvp(ref partstat))) => {
+                match self.rsvp(partstat.clone(), context) {
+                    Ok(composer) => {
+                        context
+                            .replies
+                            .push_back(UIEvent::Action(Tab(New(Some(Box::new(composer))))));
+                    }
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not reply to invitation".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+                return true;
+            }
+            UIEvent::Action(View(ViewAction::ExportCalendar(ref path))) => {
+                let (attachment, calendar) = match self.calendar() {
+                    Ok(v) => v,
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some("Could not export calendar".to_string()),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                        return true;
+                    }
+                };
+                let mut path = std::path::Path::new(path).to_path_buf();
+                if path.is_dir() {
+                    path.push(attachment.filename().unwrap_or_else(|| {
+                        format!(
+                            "{}.ics",
+                            calendar
+                                .events()
+                                .into_iter()
+                                .find_map(|e| e.uid)
+                                .unwrap_or_else(|| Uuid::new_v4().to_hyphenated().to_string())
+                                .replace(std::path::is_separator, "_")
+                        )
+                    }));
+                }
+                match save_attachment(&path, &decode(&attachment, None)) {
+                    Err(err) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            Some(format!("Failed to create file at {}", path.display())),
+                            err.to_string(),
+                            Some(NotificationType::Error(melib::ErrorKind::External)),
+                        ));
+                        log(
+                            format!(
+                                "Failed to create file at {}: {}",
+                                path.display(),
+                                err.to_string()
+                            ),
+                            ERROR,
+                        );
+                    }
+                    Ok(()) => {
+                        context.replies.push_back(UIEvent::Notification(
+                            None,
+                            format!("Saved at {}", path.display()),
+                            Some(NotificationType::Info),
+                        ));
+                    }
+                }
+                return true;
+            }
             UIEvent::Action(View(ViewAction::SaveAttachment(a_i, ref path))) => {
                 {
                     let account = &context.accounts[&self.coordinates.0];
@@ -2566,6 +3217,39 @@ impl Component for MailView {
                 .push_back(UIEvent::Action(Tab(Kill(self.id))));
         }
     }
+
+    fn save_session(&self, session: &mut crate::session::Session, context: &Context) {
+        if let Some((account, mailbox, message_id)) = session_coordinates(self.coordinates, context)
+        {
+            session.tabs.push(crate::session::TabSession::Envelope {
+                account,
+                mailbox,
+                message_id,
+            });
+        }
+    }
+}
+
+/// Returns the account name, mailbox path and Message-ID of an envelope, if it still exists.
+fn session_coordinates(
+    (account_hash, mailbox_hash, env_hash): (AccountHash, MailboxHash, EnvelopeHash),
+    context: &Context,
+) -> Option<(String, String, String)> {
+    let account = context.accounts.get(&account_hash)?;
+    let mailbox = account.mailbox_entries.get(&mailbox_hash)?;
+    if !account.collection.contains_key(&env_hash) {
+        return None;
+    }
+    let message_id = account
+        .collection
+        .get_env(env_hash)
+        .message_id_display()
+        .to_string();
+    Some((
+        account.name().to_string(),
+        mailbox.ref_mailbox.path().to_string(),
+        message_id,
+    ))
 }
 
 fn save_attachment(path: &std::path::Path, bytes: &[u8]) -> Result<()> {
@@ -2624,3 +3308,59 @@ fn test_desktop_exec() {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_displays_to_tree() {
+        let attachment = |raw: &[u8]| AttachmentBuilder::new(raw).build();
+        let alternative = attachment(
+            b"Content-Type: multipart/alternative; boundary=\"b\"\r\n\r\n\
+--b\r\nContent-Type: text/plain\r\n\r\nHello\r\n\
+--b\r\nContent-Type: text/html\r\n\r\n<p>Hello</p>\r\n\
+--b--\r\n",
+        );
+        let (plain, html) = match alternative.content_type {
+            ContentType::Multipart { ref parts, .. } => (parts[0].clone(), parts[1].clone()),
+            _ => panic!("not a multipart"),
+        };
+        let displays = vec![
+            AttachmentDisplay::Alternative {
+                inner: alternative,
+                shown_display: 1,
+                display: vec![
+                    AttachmentDisplay::InlineText {
+                        inner: plain,
+                        comment: None,
+                        text: "Hello".to_string(),
+                    },
+                    AttachmentDisplay::InlineText {
+                        inner: html,
+                        comment: None,
+                        text: "Hello".to_string(),
+                    },
+                ],
+            },
+            AttachmentDisplay::Attachment {
+                inner: attachment(b"Content-Type: text/plain; name=notes.txt\r\n\r\nNotes\r\n"),
+            },
+            AttachmentDisplay::Attachment {
+                inner: attachment(b"Content-Type: image/png; name=a.png\r\n\r\n"),
+            },
+        ];
+        let (paths, tree, hidden_text_parts) = MailView::attachment_displays_to_tree(&displays);
+        assert_eq!(
+            paths,
+            vec![vec![0], vec![0, 0], vec![0, 1], vec![1], vec![2]]
+        );
+        assert!(tree.contains("[2]"));
+        assert!(tree.contains("(displayed by default)"));
+        /* The plain text alternative isn't displayed and the text attachment isn't inline. */
+        assert_eq!(hidden_text_parts, vec![1, 3]);
+
+        let (_, _, hidden_text_parts) = MailView::attachment_displays_to_tree(&displays[1..]);
+        assert_eq!(hidden_text_parts, vec![0]);
+    }
+}
@@ -0,0 +1,305 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! DKIM, ARC and SPF results of a viewed message, shown beside its `From:` header. */
+
+use super::*;
+use melib::email::dkim::{self, KeyResolver, VerificationResult};
+
+/// Sender authentication results of a message.
+#[derive(Debug, Clone)]
+pub struct Authentication {
+    dkim: Vec<dkim::SignatureVerification>,
+    /// Domain of the `From:` address, which a passing signature's `d=` must align with.
+    from_domain: Option<String>,
+    arc: Option<VerificationResult>,
+    /// SPF result of the topmost `Authentication-Results` header of the configured authserv-id,
+    /// added by the receiving server.
+    spf: Option<String>,
+}
+
+impl Authentication {
+    fn new(bytes: &[u8], resolver: &dyn KeyResolver, authserv_id: Option<&str>) -> Self {
+        let headers = melib::email::parser::headers::headers(bytes)
+            .map(|(_, headers)| headers)
+            .unwrap_or_default();
+        let from_domain = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(b"From"))
+            .and_then(|(_, value)| {
+                melib::email::parser::address::rfc2822address_list(value)
+                    .ok()
+                    .and_then(|(_, addresses)| addresses.first().and_then(Address::get_fqdn))
+            });
+        let spf = authserv_id.and_then(|authserv_id| {
+            headers
+                .into_iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case(b"Authentication-Results"))
+                .filter_map(|(_, value)| {
+                    dkim::AuthenticationResults::parse(&String::from_utf8_lossy(value))
+                })
+                .find(|results| results.authserv_id.eq_ignore_ascii_case(authserv_id))
+                .and_then(|results| results.get("spf").map(|spf| spf.result.clone()))
+        });
+        Authentication {
+            dkim: dkim::verify(bytes, resolver),
+            from_domain,
+            arc: dkim::verify_arc(bytes, resolver),
+            spf,
+        }
+    }
+
+    fn indicator(&self) -> (String, Color) {
+        let aligned = |s: &&dkim::SignatureVerification| {
+            self.from_domain
+                .as_ref()
+                .map(|from| is_aligned(&s.domain, from))
+                .unwrap_or(false)
+        };
+        let mut passing = self.dkim.iter().filter(|s| s.result.is_pass());
+        let (mut ret, color) = if let Some(pass) = passing.clone().find(aligned) {
+            (format!("DKIM: verified ({})", pass.domain), Color::Green)
+        } else if let Some(pass) = passing.next() {
+            /* Anyone can sign a message with their own domain, so a signature only vouches for
+             * the sender if its domain is the `From:` domain. */
+            (
+                format!("DKIM: verified, not aligned ({})", pass.domain),
+                Color::Default,
+            )
+        } else if let Some(failed) = self.dkim.first() {
+            (
+                format!("DKIM: failed ({}: {})", failed.domain, failed.result),
+                Color::Red,
+            )
+        } else {
+            ("DKIM: none".to_string(), Color::Default)
+        };
+        if let Some(ref arc) = self.arc {
+            ret.push_str(&format!(" ARC: {}", arc));
+        }
+        if let Some(ref spf) = self.spf {
+            ret.push_str(&format!(" SPF: {}", spf));
+        }
+        (ret, color)
+    }
+}
+
+/// Relaxed alignment of a signing domain with the `From:` domain: either domain is the other or
+/// one of its subdomains. Without a public suffix list, sibling subdomains of the same
+/// organizational domain aren't recognised as aligned.
+fn is_aligned(signing_domain: &str, from_domain: &str) -> bool {
+    let d = signing_domain.trim_end_matches('.').to_ascii_lowercase();
+    let f = from_domain.trim_end_matches('.').to_ascii_lowercase();
+    if d.is_empty() || f.is_empty() {
+        return false;
+    }
+    let is_subdomain = |sub: &str, parent: &str| {
+        parent.contains('.')
+            && sub.ends_with(parent)
+            && sub[..sub.len() - parent.len()].ends_with('.')
+    };
+    d == f || is_subdomain(&f, &d) || is_subdomain(&d, &f)
+}
+
+#[derive(Debug)]
+pub enum AuthenticationState {
+    Init,
+    Pending(JoinHandle<Authentication>),
+    Done(Authentication),
+    /// The verification job was canceled or didn't return a result.
+    Unknown,
+}
+
+impl Default for AuthenticationState {
+    fn default() -> Self {
+        AuthenticationState::Init
+    }
+}
+
+impl AuthenticationState {
+    /// Start verifying `bytes` if it hasn't started yet and `pager.verify_dkim` is set, with the
+    /// settings of the message's mailbox if `coordinates` is given or else the global ones.
+    pub fn start_if_enabled(
+        &mut self,
+        bytes: &[u8],
+        coordinates: Option<(AccountHash, MailboxHash)>,
+        context: &mut Context,
+    ) {
+        if let AuthenticationState::Init = self {
+        } else {
+            return;
+        }
+        let (verify_dkim, key_file, authserv_id) = match coordinates {
+            Some((account, mailbox)) => (
+                *mailbox_settings!(context[account][&mailbox].pager.verify_dkim),
+                mailbox_settings!(context[account][&mailbox].pager.dkim_key_file).clone(),
+                mailbox_settings!(context[account][&mailbox].pager.authserv_id).clone(),
+            ),
+            None => (
+                context.settings.pager.verify_dkim,
+                context.settings.pager.dkim_key_file.clone(),
+                context.settings.pager.authserv_id.clone(),
+            ),
+        };
+        if verify_dkim {
+            *self = Self::start(bytes.to_vec(), key_file, authserv_id, context);
+        }
+    }
+
+    /// Verify `bytes` in a background job, with the keys of `key_file` if it's set or else with
+    /// DNS lookups. The SPF result is only taken from `Authentication-Results` headers of
+    /// `authserv_id`.
+    pub fn start(
+        bytes: Vec<u8>,
+        key_file: Option<String>,
+        authserv_id: Option<String>,
+        context: &mut Context,
+    ) -> Self {
+        let fut = async move {
+            let resolver: Box<dyn KeyResolver> = match key_file {
+                Some(path) => match dkim::StaticKeys::from_file(&path) {
+                    Ok(keys) => Box::new(keys),
+                    Err(err) => {
+                        return Authentication {
+                            dkim: vec![dkim::SignatureVerification {
+                                domain: String::new(),
+                                selector: String::new(),
+                                result: VerificationResult::TempError(err.to_string()),
+                            }],
+                            from_domain: None,
+                            arc: None,
+                            spf: None,
+                        }
+                    }
+                },
+                None => Box::new(dkim::DnsResolver::default()),
+            };
+            Authentication::new(
+                &bytes,
+                resolver.as_ref(),
+                authserv_id.as_ref().map(String::as_str),
+            )
+        };
+        let handle = context.job_executor.spawn_blocking(fut);
+        context
+            .replies
+            .push_back(UIEvent::StatusEvent(StatusEvent::NewJob(handle.job_id)));
+        AuthenticationState::Pending(handle)
+    }
+
+    pub fn job_id(&self) -> Option<JobId> {
+        match self {
+            AuthenticationState::Pending(ref handle) => Some(handle.job_id),
+            _ => None,
+        }
+    }
+
+    /// Collect the result of the verification job once it has finished.
+    pub fn finish(&mut self) {
+        if let AuthenticationState::Pending(ref mut handle) = self {
+            match handle.chan.try_recv() {
+                Err(_) => {
+                    /* Job was canceled */
+                    *self = AuthenticationState::Unknown;
+                }
+                Ok(None) => {
+                    /* something happened, perhaps a worker thread panicked */
+                    *self = AuthenticationState::Unknown;
+                }
+                Ok(Some(authentication)) => {
+                    *self = AuthenticationState::Done(authentication);
+                }
+            }
+        }
+    }
+
+    /// The indicator shown beside `From:`, empty if verification hasn't started.
+    pub fn indicator(&self) -> (String, Color) {
+        match self {
+            AuthenticationState::Init => (String::new(), Color::Default),
+            AuthenticationState::Pending(_) => ("DKIM: verifying…".to_string(), Color::Default),
+            AuthenticationState::Done(ref authentication) => authentication.indicator(),
+            AuthenticationState::Unknown => ("DKIM: unknown".to_string(), Color::Default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dkim_alignment() {
+        assert!(is_aligned("example.com", "example.com"));
+        assert!(is_aligned("Example.COM.", "example.com"));
+        assert!(is_aligned("example.com", "mail.example.com"));
+        assert!(is_aligned("mail.example.com", "example.com"));
+        assert!(!is_aligned("example.net", "example.com"));
+        assert!(!is_aligned("notexample.com", "example.com"));
+        assert!(!is_aligned("com", "example.com"));
+        assert!(!is_aligned("", "example.com"));
+
+        let verification = |domain: &str, result| dkim::SignatureVerification {
+            domain: domain.to_string(),
+            selector: "s".to_string(),
+            result,
+        };
+        let mut authentication = Authentication {
+            dkim: vec![
+                verification("attacker.example", VerificationResult::Pass),
+                verification("bank.example", VerificationResult::Pass),
+            ],
+            from_domain: Some("bank.example".to_string()),
+            arc: None,
+            spf: None,
+        };
+        assert_eq!(
+            authentication.indicator(),
+            ("DKIM: verified (bank.example)".to_string(), Color::Green)
+        );
+        authentication.dkim.remove(1);
+        assert_eq!(
+            authentication.indicator(),
+            (
+                "DKIM: verified, not aligned (attacker.example)".to_string(),
+                Color::Default
+            )
+        );
+    }
+
+    #[test]
+    fn test_spf_authserv_id() {
+        let bytes = b"Authentication-Results: forged.example; spf=pass\r\n\
+Authentication-Results: mx.example.com; spf=fail smtp.mailfrom=bank.example\r\n\
+From: Bank <support@bank.example>\r\n\
+Subject: Hello\r\n\r\nHello\r\n";
+        let keys = dkim::StaticKeys::default();
+        assert_eq!(Authentication::new(bytes, &keys, None).spf, None);
+        assert_eq!(
+            Authentication::new(bytes, &keys, Some("MX.example.com")).spf,
+            Some("fail".to_string())
+        );
+        assert_eq!(
+            Authentication::new(bytes, &keys, Some("other.example")).spf,
+            None
+        );
+    }
+}
@@ -54,6 +54,10 @@ pub struct EnvelopeView {
     mail: Mail,
 
     account_hash: AccountHash,
+    /// Mailbox of the message this was opened from, if any, for per-mailbox settings.
+    mailbox_hash: Option<MailboxHash>,
+    #[cfg(feature = "dkim")]
+    authentication: AuthenticationState,
     cmd_buf: String,
     id: ComponentId,
 }
@@ -70,6 +74,7 @@ impl EnvelopeView {
         pager: Option<Pager>,
         subview: Option<Box<dyn Component>>,
         account_hash: AccountHash,
+        mailbox_hash: Option<MailboxHash>,
     ) -> Self {
         EnvelopeView {
             pager,
@@ -78,6 +83,9 @@ impl EnvelopeView {
             mode: ViewMode::Normal,
             mail,
             account_hash,
+            mailbox_hash,
+            #[cfg(feature = "dkim")]
+            authentication: AuthenticationState::Init,
             cmd_buf: String::with_capacity(4),
             id: ComponentId::new_v4(),
         }
@@ -251,6 +259,31 @@ impl Component for EnvelopeView {
                     (set_y(upper_left, y + 1), bottom_right),
                     Some(get_x(upper_left)),
                 );
+                #[cfg(feature = "dkim")]
+                let (x, y) = {
+                    let account_hash = self.account_hash;
+                    self.authentication.start_if_enabled(
+                        &self.mail.bytes,
+                        self.mailbox_hash
+                            .map(|mailbox_hash| (account_hash, mailbox_hash)),
+                        context,
+                    );
+                    let (indicator, color) = self.authentication.indicator();
+                    if indicator.is_empty() || x + 1 > get_x(bottom_right) {
+                        (x, y)
+                    } else {
+                        grid[(x, y)].set_ch(' ');
+                        write_string_to_grid(
+                            &indicator,
+                            grid,
+                            color,
+                            Color::Default,
+                            Attr::BOLD,
+                            ((x + 1, y), bottom_right),
+                            None,
+                        )
+                    }
+                };
                 for x in x..=get_x(bottom_right) {
                     grid[(x, y)].set_ch(' ');
                     grid[(x, y)].set_bg(Color::Default);
@@ -365,6 +398,14 @@ impl Component for EnvelopeView {
             }
         }
         match *event {
+            #[cfg(feature = "dkim")]
+            UIEvent::StatusEvent(StatusEvent::JobFinished(ref job_id))
+                if self.authentication.job_id() == Some(*job_id) =>
+            {
+                self.authentication.finish();
+                self.dirty = true;
+                return true;
+            }
             UIEvent::Input(Key::Esc) | UIEvent::Input(Key::Alt('')) if !self.cmd_buf.is_empty() => {
                 self.cmd_buf.clear();
                 context
@@ -0,0 +1,179 @@
+/*
+ * meli
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Image ids for the kitty graphics protocol. They only need to be unique per meli instance.
+static KITTY_IMAGE_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Rows reserved in the body text for each image shown inline.
+pub const INLINE_IMAGE_ROWS: usize = 12;
+
+pub fn next_kitty_image_id() -> u32 {
+    KITTY_IMAGE_ID.fetch_add(1, Ordering::SeqCst)
+}
+
+/// Draw `img` scaled down to fit in `area`, with `protocol`. Kitty and sixel images are queued in
+/// `Context::pending_graphics`, half blocks are drawn to `grid`.
+pub fn draw_image(
+    img: &RgbaImage,
+    protocol: ImageProtocol,
+    kitty_id: u32,
+    grid: &mut CellBuffer,
+    area: Area,
+    context: &mut Context,
+) {
+    let cols = width!(area) + 1;
+    let rows = height!(area) + 1;
+    match protocol {
+        ImageProtocol::Kitty | ImageProtocol::Sixel => {
+            let (cell_width, cell_height) = cell_size();
+            let img = img.fit(cols * cell_width, rows * cell_height);
+            let used_cols = (img.width + cell_width - 1) / cell_width;
+            let used_rows = (img.height + cell_height - 1) / cell_height;
+            if used_cols > 0 && used_rows > 0 {
+                let sequence = if protocol == ImageProtocol::Kitty {
+                    kitty_sequence(&img, kitty_id)
+                } else {
+                    sixel_sequence(&img)
+                };
+                context.pending_graphics.push(GraphicsPlacement {
+                    area: (
+                        upper_left!(area),
+                        pos_inc(upper_left!(area), (used_cols - 1, used_rows - 1)),
+                    ),
+                    protocol,
+                    id: kitty_id,
+                    sequence,
+                });
+            }
+        }
+        _ => {
+            draw_half_blocks(&img.fit(cols, 2 * rows), grid, area);
+        }
+    }
+}
+
+/// Displays an `image/*` attachment inside the mail view, using the protocol configured in
+/// `terminal.image_protocol`.
+#[derive(Debug)]
+pub struct ImageView {
+    image: Result<RgbaImage>,
+    description: String,
+    protocol: ImageProtocol,
+    kitty_id: u32,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl ImageView {
+    pub fn new(attachment: &Attachment, context: &Context) -> Self {
+        let protocol = context.settings.terminal.image_protocol.resolve();
+        let image = if protocol == ImageProtocol::None {
+            Err(MeliError::new(
+                "Inline images are disabled (terminal.image_protocol is set to none).",
+            ))
+        } else {
+            RgbaImage::decode(&decode(attachment, None))
+        };
+        ImageView {
+            image,
+            description: attachment.to_string(),
+            protocol,
+            kitty_id: next_kitty_image_id(),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        }
+    }
+}
+
+impl fmt::Display for ImageView {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "view")
+    }
+}
+
+impl Component for ImageView {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !self.is_dirty() {
+            return;
+        }
+        let theme_default = crate::conf::value(context, "mail.view.body");
+        clear_area(grid, area, theme_default);
+        write_string_to_grid(
+            &format!("Viewing {}. Press `r` to return", self.description),
+            grid,
+            theme_default.fg,
+            theme_default.bg,
+            theme_default.attrs,
+            area,
+            None,
+        );
+        let image_area = (pos_inc(upper_left!(area), (0, 2)), bottom_right!(area));
+        if !is_valid_area!(image_area) {
+            context.dirty_areas.push_back(area);
+            self.dirty = false;
+            return;
+        }
+        match self.image {
+            Err(ref err) => {
+                write_string_to_grid(
+                    &err.to_string(),
+                    grid,
+                    theme_default.fg,
+                    theme_default.bg,
+                    theme_default.attrs,
+                    image_area,
+                    Some(get_x(upper_left!(image_area))),
+                );
+            }
+            Ok(ref img) => {
+                draw_image(img, self.protocol, self.kitty_id, grid, image_area, context);
+            }
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, _context: &mut Context) -> bool {
+        if let UIEvent::Resize = event {
+            self.set_dirty(true);
+        }
+        false
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
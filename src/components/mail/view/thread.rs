@@ -1192,4 +1192,20 @@ impl Component for ThreadView {
             .replies
             .push_back(UIEvent::Action(Tab(Kill(self.id))));
     }
+
+    fn save_session(&self, session: &mut crate::session::Session, context: &Context) {
+        let env_hash = match self.entries.get(self.expanded_pos) {
+            Some(entry) => entry.msg_hash,
+            None => return,
+        };
+        if let Some((account, mailbox, message_id)) =
+            session_coordinates((self.coordinates.0, self.coordinates.1, env_hash), context)
+        {
+            session.tabs.push(crate::session::TabSession::Thread {
+                account,
+                mailbox,
+                message_id,
+            });
+        }
+    }
 }
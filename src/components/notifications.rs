@@ -23,8 +23,10 @@
 Notification handling components.
 */
 use std::process::{Command, Stdio};
+use std::time::Duration;
 
 use super::*;
+use crate::conf::notifications::{NotificationRule, NotificationsSettings, Urgency};
 
 #[cfg(all(target_os = "linux", feature = "dbus-notifications"))]
 pub use dbus::*;
@@ -58,71 +60,21 @@ mod dbus {
         fn draw(&mut self, _grid: &mut CellBuffer, _area: Area, _context: &mut Context) {}
 
         fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
-            if !context.settings.notifications.enable {
-                return false;
-            }
-
             if let UIEvent::Notification(ref title, ref body, ref kind) = event {
-                if !self.rate_limit.tick() {
+                if !context.settings.notifications.enable
+                    && *kind != Some(NotificationType::WatchedThread)
+                {
                     return false;
                 }
-
-                let settings = &context.settings.notifications;
-                let mut notification = notify_rust::Notification::new();
-                notification
-                    .appname("meli")
-                    .summary(title.as_ref().map(String::as_str).unwrap_or("meli"))
-                    .body(&escape_str(body));
-                match *kind {
-                    Some(NotificationType::NewMail) => {
-                        notification.hint(notify_rust::Hint::Category("email".to_owned()));
-                        notification.icon("mail-message-new");
-                        notification.sound_name("message-new-email");
-                    }
-                    Some(NotificationType::SentMail) => {
-                        notification.hint(notify_rust::Hint::Category("email".to_owned()));
-                        notification.icon("mail-send");
-                        notification.sound_name("message-sent-email");
-                    }
-                    Some(NotificationType::Saved) => {
-                        notification.icon("document-save");
-                    }
-                    Some(NotificationType::Info) => {
-                        notification.icon("dialog-information");
-                    }
-                    Some(NotificationType::Error(melib::ErrorKind::Authentication)) => {
-                        notification.icon("dialog-password");
-                    }
-                    Some(NotificationType::Error(melib::ErrorKind::Bug)) => {
-                        notification.icon("face-embarrassed");
-                    }
-                    Some(NotificationType::Error(melib::ErrorKind::None))
-                    | Some(NotificationType::Error(melib::ErrorKind::External)) => {
-                        notification.icon("dialog-error");
-                    }
-                    Some(NotificationType::Error(melib::ErrorKind::Network)) => {
-                        notification.icon("network-error");
-                    }
-                    Some(NotificationType::Error(melib::ErrorKind::Timeout)) => {
-                        notification.icon("network-offline");
-                    }
-                    _ => {}
-                }
-                if settings.play_sound.is_true() {
-                    if let Some(ref sound_path) = settings.sound_file {
-                        notification.hint(notify_rust::Hint::SoundFile(sound_path.to_owned()));
-                    }
-                } else {
-                    notification.hint(notify_rust::Hint::SuppressSound(true));
-                }
-
-                if let Err(err) = notification.show() {
-                    debug!("Could not show dbus notification: {:?}", &err);
-                    melib::log(
-                        format!("Could not show dbus notification: {}", err),
-                        melib::ERROR,
-                    );
+                if !self.rate_limit.tick() {
+                    return false;
                 }
+                show(
+                    title.as_ref().map(String::as_str),
+                    body,
+                    *kind,
+                    &Delivery::new(&context.settings.notifications),
+                );
             }
             false
         }
@@ -140,6 +92,74 @@ mod dbus {
         fn set_id(&mut self, _id: ComponentId) {}
     }
 
+    pub(super) fn show(
+        title: Option<&str>,
+        body: &str,
+        kind: Option<NotificationType>,
+        delivery: &Delivery,
+    ) {
+        let mut notification = notify_rust::Notification::new();
+        notification
+            .appname("meli")
+            .summary(title.unwrap_or("meli"))
+            .body(&escape_str(body))
+            .urgency(match delivery.urgency {
+                Urgency::Low => notify_rust::Urgency::Low,
+                Urgency::Normal => notify_rust::Urgency::Normal,
+                Urgency::Critical => notify_rust::Urgency::Critical,
+            });
+        match kind {
+            Some(NotificationType::NewMail) | Some(NotificationType::WatchedThread) => {
+                notification.hint(notify_rust::Hint::Category("email".to_owned()));
+                notification.icon("mail-message-new");
+                notification.sound_name("message-new-email");
+            }
+            Some(NotificationType::SentMail) => {
+                notification.hint(notify_rust::Hint::Category("email".to_owned()));
+                notification.icon("mail-send");
+                notification.sound_name("message-sent-email");
+            }
+            Some(NotificationType::Saved) => {
+                notification.icon("document-save");
+            }
+            Some(NotificationType::Info) => {
+                notification.icon("dialog-information");
+            }
+            Some(NotificationType::Error(melib::ErrorKind::Authentication)) => {
+                notification.icon("dialog-password");
+            }
+            Some(NotificationType::Error(melib::ErrorKind::Bug)) => {
+                notification.icon("face-embarrassed");
+            }
+            Some(NotificationType::Error(melib::ErrorKind::None))
+            | Some(NotificationType::Error(melib::ErrorKind::External)) => {
+                notification.icon("dialog-error");
+            }
+            Some(NotificationType::Error(melib::ErrorKind::Network)) => {
+                notification.icon("network-error");
+            }
+            Some(NotificationType::Error(melib::ErrorKind::Timeout)) => {
+                notification.icon("network-offline");
+            }
+            _ => {}
+        }
+        if delivery.play_sound {
+            if let Some(ref sound_path) = delivery.sound_file {
+                notification.hint(notify_rust::Hint::SoundFile(sound_path.to_owned()));
+            }
+        } else {
+            notification.hint(notify_rust::Hint::SuppressSound(true));
+        }
+
+        if let Err(err) = notification.show() {
+            debug!("Could not show dbus notification: {:?}", &err);
+            melib::log(
+                format!("Could not show dbus notification: {}", err),
+                melib::ERROR,
+            );
+        }
+    }
+
     fn escape_str(s: &str) -> String {
         let mut ret: String = String::with_capacity(s.len());
         for c in s.chars() {
@@ -188,34 +208,23 @@ impl Component for NotificationCommand {
     fn draw(&mut self, _grid: &mut CellBuffer, _area: Area, _context: &mut Context) {}
 
     fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
-        if !context.settings.notifications.enable {
-            return false;
-        }
-
         if let UIEvent::Notification(ref title, ref body, ref kind) = event {
-            if let Some(ref bin) = context.settings.notifications.script {
-                match Command::new(bin)
-                    .arg(&kind.map(|k| k.to_string()).unwrap_or_default())
-                    .arg(title.as_ref().map(String::as_str).unwrap_or("meli"))
-                    .arg(body)
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .spawn()
-                {
-                    Ok(child) => {
-                        context.children.push(child);
-                    }
-                    Err(err) => {
-                        log(
-                            format!("Could not run notification script: {}.", err.to_string()),
-                            ERROR,
-                        );
-                        debug!("Could not run notification script: {:?}", err);
-                    }
-                }
+            if !context.settings.notifications.enable
+                && *kind != Some(NotificationType::WatchedThread)
+            {
+                return false;
             }
+            run_script(
+                title.as_ref().map(String::as_str),
+                body,
+                *kind,
+                &Delivery::new(&context.settings.notifications),
+                context,
+            );
 
-            if *kind == Some(NotificationType::NewMail) {
+            if *kind == Some(NotificationType::NewMail)
+                || *kind == Some(NotificationType::WatchedThread)
+            {
                 if let Some(ref path) = context.settings.notifications.xbiff_file_path {
                     if let Err(err) = update_xbiff(path) {
                         debug!("Could not update xbiff file: {:?}", &err);
@@ -248,3 +257,293 @@ fn update_xbiff(path: &str) -> Result<()> {
     }
     Ok(())
 }
+
+fn run_script(
+    title: Option<&str>,
+    body: &str,
+    kind: Option<NotificationType>,
+    delivery: &Delivery,
+    context: &mut Context,
+) {
+    if let Some(ref bin) = delivery.script {
+        match Command::new(bin)
+            .arg(&kind.map(|k| k.to_string()).unwrap_or_default())
+            .arg(title.unwrap_or("meli"))
+            .arg(body)
+            .arg(delivery.urgency.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => {
+                context.children.push(child);
+            }
+            Err(err) => {
+                log(
+                    format!("Could not run notification script: {}.", err.to_string()),
+                    ERROR,
+                );
+                debug!("Could not run notification script: {:?}", err);
+            }
+        }
+    }
+}
+
+/// How a notification is shown: with the `notifications` settings, or the overrides of the
+/// [`NotificationRule`] a new message matched.
+#[derive(Debug, Clone)]
+struct Delivery {
+    script: Option<String>,
+    play_sound: bool,
+    sound_file: Option<String>,
+    urgency: Urgency,
+}
+
+impl Delivery {
+    fn new(settings: &NotificationsSettings) -> Self {
+        Delivery {
+            script: settings.script.clone(),
+            play_sound: settings.play_sound.is_true(),
+            sound_file: settings.sound_file.clone(),
+            urgency: Urgency::Normal,
+        }
+    }
+
+    fn with_rule(mut self, rule: &NotificationRule) -> Self {
+        if rule.script.is_some() {
+            self.script = rule.script.clone();
+        }
+        if let Some(play_sound) = rule.play_sound {
+            self.play_sound = play_sound;
+        }
+        if rule.sound_file.is_some() {
+            self.sound_file = rule.sound_file.clone();
+        }
+        self.urgency = rule.urgency;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct PendingMail {
+    mailbox: String,
+    title: String,
+    body: String,
+    kind: NotificationType,
+    delivery: Delivery,
+    xbiff_file_path: Option<String>,
+}
+
+/// Notifies about new mail according to the `notifications` rules, do not disturb times and
+/// batching settings of its account.
+#[derive(Debug)]
+pub struct NewMailNotifications {
+    pending: Vec<PendingMail>,
+    batch_timer: Option<crate::jobs::Timer>,
+    rate_limit: crate::types::RateLimit,
+}
+
+impl fmt::Display for NewMailNotifications {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "")
+    }
+}
+
+impl NewMailNotifications {
+    pub fn new(context: &Context) -> Self {
+        NewMailNotifications {
+            pending: vec![],
+            batch_timer: None,
+            rate_limit: crate::types::RateLimit::new(1000, 1000, context.job_executor.clone()),
+        }
+    }
+
+    fn new_mail(
+        &mut self,
+        account_hash: AccountHash,
+        mailbox_hash: MailboxHash,
+        env_hash: EnvelopeHash,
+        kind: NotificationType,
+        context: &mut Context,
+    ) {
+        let account = &context.accounts[&account_hash];
+        let mailbox = &account.mailbox_entries[&mailbox_hash];
+        let envelope = account.collection.get_env(env_hash);
+        let rule = account_settings!(context[account_hash].notifications.rules)
+            .iter()
+            .find(|r| r.is_match(&envelope, mailbox.ref_mailbox.path()));
+        let enabled = match rule {
+            _ if kind == NotificationType::WatchedThread => true,
+            Some(rule) => rule.enable,
+            None => *account_settings!(context[account_hash].notifications.enable),
+        };
+        if !enabled {
+            return;
+        }
+        let mut delivery = Delivery::new(&context.settings.notifications);
+        delivery.script = account_settings!(context[account_hash].notifications.script).clone();
+        delivery.play_sound =
+            account_settings!(context[account_hash].notifications.play_sound).is_true();
+        delivery.sound_file =
+            account_settings!(context[account_hash].notifications.sound_file).clone();
+        if let Some(rule) = rule {
+            delivery = delivery.with_rule(rule);
+        }
+        if delivery.urgency != Urgency::Critical
+            && is_do_not_disturb(account_settings!(
+                context[account_hash].notifications.do_not_disturb
+            ))
+        {
+            return;
+        }
+        let mail = PendingMail {
+            mailbox: format!("{} {}", account.name(), mailbox.name()),
+            title: format!("new e-mail from: {}", envelope.field_from_to_string()),
+            body: format!(
+                "{}\n{} {}",
+                envelope.subject(),
+                account.name(),
+                mailbox.name()
+            ),
+            kind,
+            delivery,
+            xbiff_file_path: account_settings!(context[account_hash].notifications.xbiff_file_path)
+                .clone(),
+        };
+        drop(envelope);
+        let batch_delay = *account_settings!(context[account_hash].notifications.batch_delay);
+        if batch_delay == 0 {
+            self.notify(mail, context);
+            return;
+        }
+        if self.pending.is_empty() {
+            if let Some(timer) = self.batch_timer.take() {
+                timer.disable();
+            }
+            self.batch_timer = Some(
+                context
+                    .job_executor
+                    .clone()
+                    .create_timer(Duration::from_secs(0), Duration::from_secs(batch_delay)),
+            );
+        }
+        self.pending.push(mail);
+    }
+
+    /// Notifies about batched mail at once, eg. "12 new messages in 3 mailboxes".
+    fn flush(&mut self, context: &mut Context) {
+        if self.pending.len() < 2 {
+            if let Some(mail) = self.pending.pop() {
+                self.notify(mail, context);
+            }
+            return;
+        }
+        let mut mailboxes: Vec<(&str, usize)> = vec![];
+        for mail in &self.pending {
+            if let Some(entry) = mailboxes.iter_mut().find(|(m, _)| *m == mail.mailbox) {
+                entry.1 += 1;
+            } else {
+                mailboxes.push((&mail.mailbox, 1));
+            }
+        }
+        let title = format!(
+            "{} new messages in {} mailbox{}",
+            self.pending.len(),
+            mailboxes.len(),
+            if mailboxes.len() == 1 { "" } else { "es" }
+        );
+        let body = mailboxes
+            .iter()
+            .map(|(m, count)| format!("{}: {}", m, count))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let mut pending = std::mem::replace(&mut self.pending, vec![]);
+        /* Use the most urgent delivery among the batch. */
+        pending.sort_by_key(|m| m.delivery.urgency);
+        let kind = if pending
+            .iter()
+            .any(|m| m.kind == NotificationType::WatchedThread)
+        {
+            NotificationType::WatchedThread
+        } else {
+            NotificationType::NewMail
+        };
+        let mut mail = pending.pop().unwrap();
+        mail.title = title;
+        mail.body = body;
+        mail.kind = kind;
+        self.notify(mail, context);
+    }
+
+    fn notify(&mut self, mail: PendingMail, context: &mut Context) {
+        if !self.rate_limit.tick() {
+            return;
+        }
+        #[cfg(all(target_os = "linux", feature = "dbus-notifications"))]
+        dbus::show(
+            Some(&mail.title),
+            &mail.body,
+            Some(mail.kind),
+            &mail.delivery,
+        );
+        run_script(
+            Some(&mail.title),
+            &mail.body,
+            Some(mail.kind),
+            &mail.delivery,
+            context,
+        );
+        if let Some(ref path) = mail.xbiff_file_path {
+            if let Err(err) = update_xbiff(path) {
+                debug!("Could not update xbiff file: {:?}", &err);
+                melib::log(format!("Could not update xbiff file: {}.", err), ERROR);
+            }
+        }
+    }
+}
+
+impl Component for NewMailNotifications {
+    fn draw(&mut self, _grid: &mut CellBuffer, _area: Area, _context: &mut Context) {}
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        match *event {
+            UIEvent::NewMail(account_hash, mailbox_hash, env_hash, kind) => {
+                self.new_mail(account_hash, mailbox_hash, env_hash, kind, context);
+                true
+            }
+            UIEvent::Timer(id) if self.batch_timer.as_ref().map(|t| t.id()) == Some(id) => {
+                self.batch_timer = None;
+                self.flush(context);
+                true
+            }
+            _ => false,
+        }
+    }
+    fn id(&self) -> ComponentId {
+        ComponentId::nil()
+    }
+    fn is_dirty(&self) -> bool {
+        false
+    }
+    fn set_dirty(&mut self, _value: bool) {}
+    fn set_id(&mut self, _id: ComponentId) {}
+}
+
+fn is_do_not_disturb(windows: &[crate::conf::notifications::TimeWindow]) -> bool {
+    if windows.is_empty() {
+        return false;
+    }
+    let now = melib::datetime::timestamp_to_string(melib::datetime::now(), Some("%u %H %M"));
+    let mut fields = now
+        .split_whitespace()
+        .map(|f| f.parse::<u32>().unwrap_or(0));
+    let (weekday, hour, minute) = (
+        fields.next().unwrap_or(1),
+        fields.next().unwrap_or(0),
+        fields.next().unwrap_or(0),
+    );
+    let weekday = crate::conf::notifications::WEEKDAYS[(weekday.max(1).min(7) - 1) as usize];
+    windows
+        .iter()
+        .any(|w| w.contains(weekday, hour * 60 + minute))
+}
@@ -36,7 +36,11 @@ pub use self::layouts::*;
 mod dialogs;
 pub use self::dialogs::*;
 
-use crate::jobs::JobId;
+use crate::conf::keymap::{
+    expand_count, key_sequence_to_string, push_count_digit, KeyBinding, Keymap,
+    COUNT_ARGUMENT_SHORTCUTS,
+};
+use crate::jobs::{JobId, Timer};
 use std::collections::HashSet;
 
 #[derive(Default, Debug, Clone)]
@@ -67,6 +71,8 @@ pub struct StatusBar {
 
     auto_complete: AutoComplete,
     cmd_history: Vec<String>,
+    /// Pattern of the `search` command being typed, last sent as `UIEvent::IncrementalSearch`.
+    incremental_search: Option<String>,
 }
 
 impl fmt::Display for StatusBar {
@@ -105,6 +111,23 @@ impl StatusBar {
             in_progress_jobs: HashSet::default(),
             done_jobs: HashSet::default(),
             cmd_history: crate::command::history::old_cmd_history(),
+            incremental_search: None,
+        }
+    }
+
+    /// Lets the pager highlight the matches of a `search` command while it is typed.
+    fn update_incremental_search(&mut self, context: &mut Context) {
+        let cmd = self.ex_buffer.as_str().trim_start();
+        let pattern = if cmd.starts_with("search ") {
+            Some(cmd["search ".len()..].to_string())
+        } else {
+            None
+        };
+        if pattern != self.incremental_search {
+            self.incremental_search = pattern.clone();
+            context
+                .replies
+                .push_back(UIEvent::IncrementalSearch(pattern));
         }
     }
 
@@ -487,12 +510,20 @@ impl Component for StatusBar {
                 match m {
                     UIMode::Normal => {
                         self.height = 1;
+                        if self.incremental_search.take().is_some() {
+                            context.replies.push_back(UIEvent::IncrementalSearch(None));
+                        }
                         if !self.ex_buffer.is_empty() {
                             context
                                 .replies
                                 .push_back(UIEvent::Command(self.ex_buffer.as_str().to_string()));
                         }
-                        if parse_command(&self.ex_buffer.as_str().as_bytes()).is_ok()
+                        if (parse_command(&self.ex_buffer.as_str().as_bytes()).is_ok()
+                            || context
+                                .settings
+                                .commands
+                                .alias_name(self.ex_buffer.as_str())
+                                .is_some())
                             && self.cmd_history.last().map(String::as_str)
                                 != Some(self.ex_buffer.as_str())
                         {
@@ -527,24 +558,28 @@ impl Component for StatusBar {
                     self.container.set_dirty(true);
                     self.set_dirty(true);
                     self.ex_buffer = Field::Text(utext, None);
+                    self.update_incremental_search(context);
                 }
             }
             UIEvent::CmdInput(Key::Char(c)) => {
                 self.dirty = true;
                 self.ex_buffer
                     .process_event(&mut UIEvent::InsertInput(Key::Char(*c)), context);
+                self.update_incremental_search(context);
                 return true;
             }
             UIEvent::CmdInput(Key::Paste(s)) => {
                 self.dirty = true;
                 self.ex_buffer
                     .process_event(&mut UIEvent::InsertInput(Key::Paste(s.clone())), context);
+                self.update_incremental_search(context);
                 return true;
             }
             UIEvent::CmdInput(Key::Ctrl('u')) => {
                 self.dirty = true;
                 self.ex_buffer.clear();
                 self.ex_buffer_cmd_history_pos.take();
+                self.update_incremental_search(context);
                 return true;
             }
             UIEvent::CmdInput(Key::Up) => {
@@ -593,6 +628,7 @@ impl Component for StatusBar {
                     self.ex_buffer = Field::Text(utext, None);
                     self.ex_buffer_cmd_history_pos = pos;
                     self.dirty = true;
+                    self.update_incremental_search(context);
                 }
 
                 return true;
@@ -605,6 +641,7 @@ impl Component for StatusBar {
                     self.ex_buffer_cmd_history_pos = None;
                     self.ex_buffer.clear();
                     self.dirty = true;
+                    self.update_incremental_search(context);
                 } else if self.ex_buffer_cmd_history_pos.is_some() {
                     let pos = self.ex_buffer_cmd_history_pos.map(|p| p - 1);
                     let mut utext = UText::new(
@@ -618,6 +655,7 @@ impl Component for StatusBar {
                     self.ex_buffer = Field::Text(utext, None);
                     self.ex_buffer_cmd_history_pos = pos;
                     self.dirty = true;
+                    self.update_incremental_search(context);
                 }
 
                 return true;
@@ -626,10 +664,12 @@ impl Component for StatusBar {
                 self.dirty = true;
                 self.ex_buffer
                     .process_event(&mut UIEvent::InsertInput(k.clone()), context);
+                self.update_incremental_search(context);
                 return true;
             }
             UIEvent::CmdInput(Key::Esc) => {
                 self.ex_buffer.clear();
+                self.update_incremental_search(context);
                 context
                     .replies
                     .push_back(UIEvent::ChangeMode(UIMode::Normal));
@@ -739,6 +779,10 @@ impl Component for StatusBar {
     fn can_quit_cleanly(&mut self, context: &Context) -> bool {
         self.container.can_quit_cleanly(context)
     }
+
+    fn save_session(&self, session: &mut crate::session::Session, context: &Context) {
+        self.container.save_session(session, context)
+    }
 }
 
 #[derive(Debug)]
@@ -754,6 +798,12 @@ pub struct Tabbed {
     help_search: Option<SearchPattern>,
     theme_default: ThemeAttribute,
 
+    /// Keys of an unfinished key sequence and its repeat count.
+    pending_keys: Vec<Key>,
+    pending_count: Option<usize>,
+    keymap_timer: Option<Timer>,
+    replaying_keys: bool,
+
     dirty: bool,
     id: ComponentId,
 }
@@ -774,6 +824,10 @@ impl Tabbed {
             children,
             cursor_pos: 0,
             show_shortcuts: false,
+            pending_keys: vec![],
+            pending_count: None,
+            keymap_timer: None,
+            replaying_keys: false,
             dirty: true,
             id: ComponentId::new_v4(),
         };
@@ -860,6 +914,245 @@ impl Tabbed {
     pub fn add_component(&mut self, new: Box<dyn Component>) {
         self.children.push(new);
     }
+
+    /// Returns the keymaps of the focused tab, most specific first.
+    fn active_keymaps<'a>(&self, context: &'a Context) -> Vec<&'a Keymap> {
+        self.children[self.cursor_pos]
+            .get_shortcuts(context)
+            .keys()
+            .chain(std::iter::once(&"general"))
+            .filter_map(|section| context.settings.shortcuts.keymap(section))
+            .filter(|keymap| !keymap.is_empty())
+            .collect()
+    }
+
+    /// Feeds `key` to the keymaps of the focused tab, or repeats it if a count was typed before
+    /// it. Returns false if `key` must be processed as usual.
+    fn process_keymap(&mut self, key: &Key, context: &mut Context) -> bool {
+        if self.pending_keys.is_empty() {
+            if let Key::Char(c) = *key {
+                if let Some(count) = push_count_digit(self.pending_count, c) {
+                    self.pending_count = Some(count);
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::BufSet(count.to_string())));
+                    self.arm_keymap_timer(context);
+                    return true;
+                }
+            }
+            if *key == Key::Esc && self.pending_count.take().is_some() {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                return true;
+            }
+        }
+        let mut sequence = self.pending_keys.clone();
+        sequence.push(key.clone());
+        let (is_prefix, binding) = {
+            let keymaps = self.active_keymaps(context);
+            (
+                keymaps.iter().any(|keymap| keymap.is_prefix(&sequence)),
+                keymaps
+                    .iter()
+                    .find_map(|keymap| keymap.get(&sequence))
+                    .cloned(),
+            )
+        };
+        if is_prefix {
+            /* Wait for the next key, even if `sequence` is bound too. */
+            self.pending_keys = sequence;
+            self.arm_keymap_timer(context);
+            self.dirty = true;
+            return true;
+        }
+        if let Some(binding) = binding {
+            self.run_binding(binding, context);
+            return true;
+        }
+        if self.pending_keys.is_empty() {
+            /* A built-in shortcut */
+            return match self.pending_count.take() {
+                None => false,
+                Some(count) => {
+                    context
+                        .replies
+                        .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                    let keys = expand_count(count, &sequence, self.count_is_argument(key, context));
+                    self.replay_keys(keys, context);
+                    true
+                }
+            };
+        }
+        /* `key` doesn't continue the pending sequence: resolve it and start over. */
+        self.resolve_pending_keys(context);
+        self.process_keymap(key, context)
+    }
+
+    /// Returns whether `key` is a shortcut of the focused tab that reads the count typed before
+    /// it as an argument.
+    fn count_is_argument(&self, key: &Key, context: &Context) -> bool {
+        self.children[self.cursor_pos]
+            .get_shortcuts(context)
+            .values()
+            .any(|map| {
+                COUNT_ARGUMENT_SHORTCUTS
+                    .iter()
+                    .any(|name| map.get(name) == Some(key))
+            })
+    }
+
+    /// Runs the binding of the pending sequence, or processes the pending keys as usual if it
+    /// has none.
+    fn resolve_pending_keys(&mut self, context: &mut Context) {
+        let binding = self
+            .active_keymaps(context)
+            .iter()
+            .find_map(|keymap| keymap.get(&self.pending_keys))
+            .cloned();
+        if let Some(binding) = binding {
+            self.run_binding(binding, context);
+            return;
+        }
+        let keys = std::mem::replace(&mut self.pending_keys, vec![]);
+        if !keys.is_empty() {
+            self.set_dirty(true);
+        }
+        let keys = match self.pending_count.take() {
+            None => keys,
+            Some(count) => {
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::BufClear));
+                /* A count on its own is passed on, for the views that read it themselves. */
+                let count_is_argument =
+                    keys.is_empty() || self.count_is_argument(&keys[0], context);
+                expand_count(count, &keys, count_is_argument)
+            }
+        };
+        self.replay_keys(keys, context);
+    }
+
+    fn run_binding(&mut self, binding: KeyBinding, context: &mut Context) {
+        let count = self.pending_count.take().unwrap_or(1);
+        if !self.pending_keys.is_empty() {
+            self.pending_keys.clear();
+            /* Clear the completions box */
+            self.set_dirty(true);
+        }
+        match binding {
+            KeyBinding::Command(cmd) => {
+                for _ in 0..count {
+                    context.replies.push_back(UIEvent::Command(cmd.clone()));
+                }
+            }
+            KeyBinding::Keys { keys } => {
+                let len = keys.len() * count;
+                self.replay_keys(keys.into_iter().cycle().take(len), context);
+            }
+        }
+    }
+
+    /// Processes `keys` as if they were typed, without looking them up in keymaps.
+    fn replay_keys<I: IntoIterator<Item = Key>>(&mut self, keys: I, context: &mut Context) {
+        self.replaying_keys = true;
+        for key in keys {
+            self.process_event(&mut UIEvent::Input(key), context);
+        }
+        self.replaying_keys = false;
+    }
+
+    fn arm_keymap_timer(&mut self, context: &Context) {
+        if context.settings.shortcuts.sequence_timeout == 0 {
+            return;
+        }
+        if let Some(ref timer) = self.keymap_timer {
+            timer.rearm();
+        } else {
+            self.keymap_timer = Some(context.job_executor.clone().create_timer(
+                std::time::Duration::from_secs(0),
+                std::time::Duration::from_millis(context.settings.shortcuts.sequence_timeout),
+            ));
+        }
+    }
+
+    /// Draws the keys that complete the pending sequence and what they are bound to.
+    fn draw_pending_keys(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        let mut completions: Vec<(String, String)> = vec![];
+        for keymap in self.active_keymaps(context) {
+            for (keys, binding) in keymap.completions(&self.pending_keys) {
+                let keys = key_sequence_to_string(&keys);
+                /* Bindings of more specific keymaps shadow the rest */
+                if !completions.iter().any(|(k, _)| *k == keys) {
+                    completions.push((keys, binding.to_string()));
+                }
+            }
+        }
+        let title = format!(
+            "{}{}",
+            self.pending_count
+                .map(|count| count.to_string() + " ")
+                .unwrap_or_default(),
+            key_sequence_to_string(&self.pending_keys)
+        );
+        let keys_width = completions.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        let width = completions
+            .iter()
+            .map(|(_, b)| keys_width + b.len() + 6)
+            .max()
+            .unwrap_or(0)
+            .max(title.len() + 6);
+        let dialog_area = align_area(
+            area,
+            (width, completions.len() + 2),
+            /* vertical */
+            Alignment::End,
+            /* horizontal */
+            Alignment::Start,
+        );
+        context.dirty_areas.push_back(dialog_area);
+        clear_area(grid, dialog_area, self.theme_default);
+        let inner_area = create_box(grid, dialog_area);
+        write_string_to_grid(
+            &title,
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (
+                pos_inc(upper_left!(dialog_area), (2, 0)),
+                bottom_right!(dialog_area),
+            ),
+            None,
+        );
+        for (idx, (keys, binding)) in completions.iter().enumerate() {
+            let y = get_y(upper_left!(inner_area)) + idx;
+            if y > get_y(bottom_right!(inner_area)) {
+                break;
+            }
+            let (x, _) = write_string_to_grid(
+                &format!("{:1$}", keys, keys_width),
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs | Attr::BOLD,
+                (
+                    (get_x(upper_left!(inner_area)) + 1, y),
+                    bottom_right!(inner_area),
+                ),
+                None,
+            );
+            write_string_to_grid(
+                binding,
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs,
+                ((x + 2, y), bottom_right!(inner_area)),
+                None,
+            );
+        }
+    }
 }
 
 impl fmt::Display for Tabbed {
@@ -910,6 +1203,10 @@ impl Component for Tabbed {
             self.children[self.cursor_pos].draw(grid, area, context);
         }
 
+        if !self.pending_keys.is_empty() && !self.show_shortcuts {
+            self.draw_pending_keys(grid, area, context);
+        }
+
         if (self.show_shortcuts && self.dirty) || must_redraw_shortcuts {
             let mut children_maps = self.children[self.cursor_pos].get_shortcuts(context);
             let our_map = self.get_shortcuts(context);
@@ -1011,6 +1308,38 @@ impl Component for Tabbed {
                     ),
                 );
             }
+            let keymaps: Vec<(String, Vec<(String, String)>)> = children_maps
+                .keys()
+                .filter_map(|desc| {
+                    context
+                        .settings
+                        .shortcuts
+                        .keymap(desc)
+                        .filter(|keymap| !keymap.is_empty())
+                        .map(|keymap| {
+                            (
+                                format!("{} key sequences", desc),
+                                keymap
+                                    .bindings()
+                                    .into_iter()
+                                    .map(|(keys, binding)| {
+                                        (key_sequence_to_string(&keys), binding.to_string())
+                                    })
+                                    .collect(),
+                            )
+                        })
+                })
+                .collect();
+            for (desc, bindings) in keymaps.iter() {
+                max_length += bindings.len() + 3;
+                max_width = std::cmp::max(
+                    max_width,
+                    std::cmp::max(
+                        desc.len(),
+                        bindings.iter().map(|(k, _)| k.len() + 5).max().unwrap_or(0),
+                    ),
+                );
+            }
             self.help_content =
                 CellBuffer::new_with_context(max_width, max_length + 2, None, context);
             self.help_content.set_growable(true);
@@ -1058,6 +1387,40 @@ impl Component for Tabbed {
                 }
                 idx += 1;
             }
+            for (desc, bindings) in keymaps.iter() {
+                write_string_to_grid(
+                    desc,
+                    &mut self.help_content,
+                    self.theme_default.fg,
+                    self.theme_default.bg,
+                    self.theme_default.attrs,
+                    ((2, 2 + idx), (max_width.saturating_sub(2), max_length - 1)),
+                    None,
+                );
+                idx += 2;
+                for (keys, binding) in bindings {
+                    let (x, y) = write_string_to_grid(
+                        &format!("{:1$}", keys, max_width),
+                        &mut self.help_content,
+                        self.theme_default.fg,
+                        self.theme_default.bg,
+                        self.theme_default.attrs | Attr::BOLD,
+                        ((2, 2 + idx), (max_width.saturating_sub(2), max_length - 1)),
+                        None,
+                    );
+                    write_string_to_grid(
+                        binding,
+                        &mut self.help_content,
+                        self.theme_default.fg,
+                        self.theme_default.bg,
+                        self.theme_default.attrs,
+                        ((x + 2, y), (max_width.saturating_sub(2), max_length - 1)),
+                        None,
+                    );
+                    idx += 1;
+                }
+                idx += 1;
+            }
             self.help_curr_views = children_maps;
             let dialog_area = align_area(
                 area,
@@ -1211,10 +1574,41 @@ impl Component for Tabbed {
         self.dirty = false;
     }
     fn process_event(&mut self, mut event: &mut UIEvent, context: &mut Context) -> bool {
+        match *event {
+            UIEvent::Input(ref key) if !self.show_shortcuts && !self.replaying_keys => {
+                let key = key.clone();
+                if self.process_keymap(&key, context) {
+                    return true;
+                }
+            }
+            UIEvent::Timer(id)
+                if self
+                    .keymap_timer
+                    .as_ref()
+                    .map(|timer| timer.id() == id)
+                    .unwrap_or(false) =>
+            {
+                if !self.pending_keys.is_empty() || self.pending_count.is_some() {
+                    self.resolve_pending_keys(context);
+                }
+                return true;
+            }
+            UIEvent::ChangeMode(_) => {
+                self.pending_count = None;
+                if !self.pending_keys.is_empty() {
+                    self.pending_keys.clear();
+                    self.set_dirty(true);
+                }
+            }
+            _ => {}
+        }
         let shortcuts = &self.help_curr_views;
         match &mut event {
             UIEvent::ConfigReload { old_settings: _ } => {
                 self.theme_default = crate::conf::value(context, "theme_default");
+                if let Some(timer) = self.keymap_timer.take() {
+                    timer.disable();
+                }
                 self.set_dirty(true);
             }
             UIEvent::Input(Key::Alt(no)) if *no >= '1' && *no <= '9' => {
@@ -1264,6 +1658,11 @@ impl Component for Tabbed {
                 self.help_curr_views = children_maps;
                 return true;
             }
+            UIEvent::Action(Tab(Restore(ref mut e))) if e.is_some() => {
+                self.add_component(e.take().unwrap());
+                self.set_dirty(true);
+                return true;
+            }
             UIEvent::Action(Tab(Close)) => {
                 if self.pinned > self.cursor_pos {
                     return true;
@@ -1277,10 +1676,10 @@ impl Component for Tabbed {
                 return true;
             }
             UIEvent::Action(Tab(Kill(id))) => {
-                if self.pinned > self.cursor_pos {
-                    return true;
-                }
                 if let Some(c_idx) = self.children.iter().position(|x| x.id() == *id) {
+                    if self.pinned > c_idx {
+                        return true;
+                    }
                     self.children.remove(c_idx);
                     self.cursor_pos = 0;
                     self.set_dirty(true);
@@ -1288,12 +1687,33 @@ impl Component for Tabbed {
                     children_maps.extend(self.get_shortcuts(context));
                     self.help_curr_views = children_maps;
                     return true;
-                } else {
-                    debug!(
-                        "DEBUG: Child component with id {:?} not found.\nList: {:?}",
-                        id, self.children
-                    );
                 }
+                /* Not a tab; it may be a pane of a split tab, which removes it. */
+                debug!(
+                    "DEBUG: Child component with id {:?} not found.\nList: {:?}",
+                    id, self.children
+                );
+            }
+            UIEvent::Action(Tab(SplitWith(_, _))) => {
+                let c = self.cursor_pos;
+                /* A split tab adds the pane next to its focused pane. */
+                if self.children[c].process_event(event, context) {
+                    return true;
+                }
+                if let UIEvent::Action(Tab(SplitWith(direction, pane))) = event {
+                    if let Some(pane) = pane.take() {
+                        let old = self.children.remove(c);
+                        self.children.insert(
+                            c,
+                            Box::new(Panes::new(*direction, vec![old, pane], context)),
+                        );
+                        self.set_dirty(true);
+                        let mut children_maps = self.children[c].get_shortcuts(context);
+                        children_maps.extend(self.get_shortcuts(context));
+                        self.help_curr_views = children_maps;
+                    }
+                }
+                return true;
             }
             UIEvent::Action(Action::Listing(ListingAction::Search(pattern)))
                 if self.show_shortcuts =>
@@ -1421,6 +1841,18 @@ impl Component for Tabbed {
         }
         true
     }
+    fn save_session(&self, session: &mut crate::session::Session, context: &Context) {
+        for (i, c) in self.children.iter().enumerate() {
+            let len = session.tabs.len();
+            c.save_session(session, context);
+            /* The pinned contact list is opened on startup anyway. */
+            if i < self.pinned && session.tabs.len() == len + 1 {
+                if let Some(crate::session::TabSession::Contacts { .. }) = session.tabs.last() {
+                    session.tabs.pop();
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -962,3 +962,162 @@ impl UIConfirmationDialog {
         })
     }
 }
+
+/// Called with the date and time entered in a `DateTimeDialog`.
+pub type DateTimeDialogFn =
+    Box<dyn FnOnce(ComponentId, melib::UnixTimestamp) -> Option<UIEvent> + Send + Sync>;
+
+/// Asks for a date and time with a form. The date can also be written as in
+/// `melib::datetime::natural_to_timestamp`, eg. `monday` or `in 3 days`.
+pub struct DateTimeDialog {
+    title: String,
+    form: FormWidget<bool>,
+    /// Why the last entered date was refused.
+    message: String,
+    done_fn: Option<DateTimeDialogFn>,
+    theme_default: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Debug for DateTimeDialog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt("DateTimeDialog", f)
+    }
+}
+
+impl fmt::Display for DateTimeDialog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.title, f)
+    }
+}
+
+impl DateTimeDialog {
+    pub fn new(title: &str, done_fn: DateTimeDialogFn, context: &Context) -> Self {
+        let tomorrow = melib::datetime::now() + 24 * 60 * 60;
+        DateTimeDialog {
+            title: title.to_string(),
+            form: Self::form(
+                melib::datetime::timestamp_to_string(tomorrow, Some("%Y-%m-%d")),
+                "09:00".to_string(),
+            ),
+            message: String::new(),
+            done_fn: Some(done_fn),
+            theme_default: crate::conf::value(context, "theme_default"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    fn form(date: String, time: String) -> FormWidget<bool> {
+        let mut form = FormWidget::new(("OK".into(), true));
+        form.add_button(("Cancel(Esc)".into(), false));
+        form.push(("DATE".into(), date));
+        form.push(("TIME".into(), time));
+        form
+    }
+
+    fn finish(&mut self, event: Option<UIEvent>, context: &mut Context) {
+        if let Some(event) = event {
+            context.replies.push_back(event);
+        }
+        context.replies.push_back(UIEvent::ComponentKill(self.id));
+    }
+}
+
+impl Component for DateTimeDialog {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        /* Overlays are drawn over a fresh copy of the screen every time */
+        clear_area(grid, area, self.theme_default);
+        let inner_area = create_box(grid, area);
+        write_string_to_grid(
+            &self.title,
+            grid,
+            self.theme_default.fg,
+            self.theme_default.bg,
+            self.theme_default.attrs | Attr::BOLD,
+            (pos_inc(upper_left!(area), (2, 0)), bottom_right!(area)),
+            None,
+        );
+        let form_area = (
+            pos_inc(upper_left!(inner_area), (1, 1)),
+            bottom_right!(inner_area),
+        );
+        self.form.set_dirty(true);
+        self.form.draw(grid, form_area, context);
+        if !self.message.is_empty() && height!(form_area) > self.form.len() + 5 {
+            write_string_to_grid(
+                &self.message,
+                grid,
+                self.theme_default.fg,
+                self.theme_default.bg,
+                self.theme_default.attrs,
+                (
+                    pos_inc(upper_left!(form_area), (1, self.form.len() + 5)),
+                    bottom_right!(form_area),
+                ),
+                None,
+            );
+        }
+        context.dirty_areas.push_back(area);
+        self.dirty = false;
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        if let UIEvent::Input(Key::Esc) = event {
+            self.finish(None, context);
+            return true;
+        }
+        if self.form.process_event(event, context) {
+            match self.form.buttons_result() {
+                None => {}
+                Some(true) => {
+                    let values = self.form.values();
+                    let date = values["DATE"].as_str().trim().to_string();
+                    let time = values["TIME"].as_str().trim().to_string();
+                    match melib::datetime::natural_to_timestamp(
+                        &format!("{} {}", date, time),
+                        melib::datetime::now(),
+                    ) {
+                        Ok(timestamp) => {
+                            let event = self
+                                .done_fn
+                                .take()
+                                .and_then(|done_fn| done_fn(self.id, timestamp));
+                            self.finish(event, context);
+                        }
+                        Err(err) => {
+                            self.message = err.to_string();
+                            self.form = Self::form(date, time);
+                        }
+                    }
+                }
+                Some(false) => self.finish(None, context),
+            }
+            self.set_dirty(true);
+            return true;
+        }
+        /* Don't let keys through to the components under the dialog */
+        match event {
+            UIEvent::Input(_) | UIEvent::InsertInput(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.form.is_dirty()
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+        self.form.set_dirty(value);
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+}
@@ -254,3 +254,464 @@ impl Component for VSplit {
         self.id = id;
     }
 }
+
+/// The orientation of [`Panes`], named as in `HSplit` and `VSplit`: horizontally split panes are
+/// stacked, vertically split panes are side by side.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+/// The smallest size of a pane, as a percentage of the whole.
+const MIN_PANE_SIZE: usize = 5;
+
+/// Divides `area` among panes of `sizes` percent of it, with a column or row between each pane
+/// for the divider. The last pane takes what is left.
+pub fn split_area(area: Area, direction: SplitDirection, sizes: &[usize]) -> Vec<Area> {
+    let (upper_left, bottom_right) = area;
+    let (start, end) = match direction {
+        SplitDirection::Horizontal => (get_y(upper_left), get_y(bottom_right)),
+        SplitDirection::Vertical => (get_x(upper_left), get_x(bottom_right)),
+    };
+    let total = end + 1 - start;
+    let mut ret = Vec::with_capacity(sizes.len());
+    let mut pos = start;
+    for (i, size) in sizes.iter().enumerate() {
+        let last = if i + 1 == sizes.len() {
+            end
+        } else {
+            std::cmp::min(pos + std::cmp::max(1, size * total / 100), end + 1) - 1
+        };
+        ret.push(match direction {
+            SplitDirection::Horizontal => (set_y(upper_left, pos), set_y(bottom_right, last)),
+            SplitDirection::Vertical => (set_x(upper_left, pos), set_x(bottom_right, last)),
+        });
+        /* Skip the divider. An area past the end is invalid and is not drawn. */
+        pos = last + 2;
+    }
+    ret
+}
+
+/// Grows pane `idx` by `delta` percent, taking it from or giving it to the next pane, or the
+/// previous one if `idx` is the last.
+fn resize_pane(sizes: &mut [usize], idx: usize, delta: isize) {
+    if sizes.len() < 2 || idx >= sizes.len() {
+        return;
+    }
+    let other = if idx + 1 == sizes.len() {
+        idx - 1
+    } else {
+        idx + 1
+    };
+    let total = sizes[idx] + sizes[other];
+    let new_size = (sizes[idx] as isize + delta).max(MIN_PANE_SIZE as isize) as usize;
+    let new_size = std::cmp::min(new_size, total.saturating_sub(MIN_PANE_SIZE));
+    sizes[idx] = new_size;
+    sizes[other] = total - new_size;
+}
+
+fn equal_sizes(n: usize) -> Vec<usize> {
+    let mut ret = vec![100 / std::cmp::max(n, 1); n];
+    if let Some(last) = ret.last_mut() {
+        *last += 100 % n;
+    }
+    ret
+}
+
+/// Any number of components in panes side by side or stacked. The panes can be resized by
+/// dragging the dividers between them with the mouse, or with the `grow_pane` and `shrink_pane`
+/// shortcuts. Input goes to the focused pane, which is changed with `next_pane` or a mouse click.
+#[derive(Debug)]
+pub struct Panes {
+    direction: SplitDirection,
+    panes: Vec<Box<dyn Component>>,
+    /// Percentage of the area of each pane.
+    sizes: Vec<usize>,
+    focus: usize,
+    /// Areas of the panes when last drawn, to find what the mouse points at.
+    areas: Vec<Area>,
+    /// The divider being dragged with the mouse, which is after the pane with this index.
+    drag: Option<usize>,
+    theme_default: ThemeAttribute,
+    dirty: bool,
+    id: ComponentId,
+}
+
+impl fmt::Display for Panes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.panes[self.focus], f)
+    }
+}
+
+impl Panes {
+    pub fn new(
+        direction: SplitDirection,
+        panes: Vec<Box<dyn Component>>,
+        context: &Context,
+    ) -> Self {
+        debug_assert!(!panes.is_empty());
+        Panes {
+            direction,
+            sizes: equal_sizes(panes.len()),
+            panes,
+            focus: 0,
+            areas: vec![],
+            drag: None,
+            theme_default: crate::conf::value(context, "theme_default"),
+            dirty: true,
+            id: ComponentId::new_v4(),
+        }
+    }
+
+    /// Sets the sizes of the panes, as percentages that are scaled to add up to 100.
+    pub fn set_sizes(&mut self, sizes: &[usize]) {
+        let sum: usize = sizes.iter().sum();
+        if sizes.len() != self.panes.len() || sum == 0 {
+            return;
+        }
+        self.sizes = sizes
+            .iter()
+            .map(|s| std::cmp::max(MIN_PANE_SIZE, s * 100 / sum))
+            .collect();
+        let sum: usize = self.sizes.iter().sum();
+        if let Some(last) = self.sizes.last_mut() {
+            *last = (*last + 100).saturating_sub(sum).max(MIN_PANE_SIZE);
+        }
+        self.set_dirty(true);
+    }
+
+    /// Adds and focuses a pane after the focused one, taking half of its size.
+    pub fn split(&mut self, pane: Box<dyn Component>) {
+        let half = self.sizes[self.focus] / 2;
+        self.sizes[self.focus] -= half;
+        self.focus += 1;
+        self.panes.insert(self.focus, pane);
+        self.sizes.insert(self.focus, half);
+        if half < MIN_PANE_SIZE {
+            self.sizes = equal_sizes(self.panes.len());
+        }
+        self.set_dirty(true);
+    }
+
+    fn remove(&mut self, idx: usize) {
+        self.panes.remove(idx);
+        let size = self.sizes.remove(idx);
+        let neighbour = idx.saturating_sub(1);
+        self.sizes[neighbour] += size;
+        if self.focus >= idx && self.focus > 0 {
+            self.focus -= 1;
+        }
+        self.set_dirty(true);
+    }
+
+    /// Returns the index of the pane before the divider at `(x, y)`.
+    fn divider_at(&self, x: usize, y: usize) -> Option<usize> {
+        self.areas
+            .iter()
+            .take(self.areas.len().saturating_sub(1))
+            .position(|&(upper_left, bottom_right)| match self.direction {
+                SplitDirection::Horizontal => {
+                    y == get_y(bottom_right) + 1
+                        && x >= get_x(upper_left)
+                        && x <= get_x(bottom_right)
+                }
+                SplitDirection::Vertical => {
+                    x == get_x(bottom_right) + 1
+                        && y >= get_y(upper_left)
+                        && y <= get_y(bottom_right)
+                }
+            })
+    }
+
+    /// Moves the divider after pane `idx` to `(x, y)`.
+    fn move_divider(&mut self, idx: usize, x: usize, y: usize) {
+        let (start, _) = self.areas[idx];
+        let (_, end) = self.areas[idx + 1];
+        let (start, end, pos) = match self.direction {
+            SplitDirection::Horizontal => (get_y(start), get_y(end), y),
+            SplitDirection::Vertical => (get_x(start), get_x(end), x),
+        };
+        if end <= start {
+            return;
+        }
+        let total = self.sizes[idx] + self.sizes[idx + 1];
+        let new_size = pos.saturating_sub(start) * total / (end - start);
+        let delta = new_size as isize - self.sizes[idx] as isize;
+        resize_pane(&mut self.sizes, idx, delta);
+    }
+}
+
+impl Component for Panes {
+    fn draw(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
+        if !is_valid_area!(area) {
+            return;
+        }
+        self.areas = split_area(area, self.direction, &self.sizes);
+        if self.dirty {
+            for &(upper_left, bottom_right) in
+                self.areas.iter().take(self.areas.len().saturating_sub(1))
+            {
+                let (divider, ch) = match self.direction {
+                    SplitDirection::Horizontal => {
+                        let y = get_y(bottom_right) + 1;
+                        (
+                            (set_y(upper_left, y), set_y(bottom_right, y)),
+                            HORZ_BOUNDARY,
+                        )
+                    }
+                    SplitDirection::Vertical => {
+                        let x = get_x(bottom_right) + 1;
+                        (
+                            (set_x(upper_left, x), set_x(bottom_right, x)),
+                            VERT_BOUNDARY,
+                        )
+                    }
+                };
+                if !is_valid_area!(divider)
+                    || get_x(upper_left!(divider)) > get_x(bottom_right!(area))
+                    || get_y(upper_left!(divider)) > get_y(bottom_right!(area))
+                {
+                    continue;
+                }
+                for row in grid.bounds_iter(divider) {
+                    for c in row {
+                        grid[c]
+                            .set_ch(ch)
+                            .set_fg(self.theme_default.fg)
+                            .set_bg(self.theme_default.bg)
+                            .set_attrs(self.theme_default.attrs);
+                    }
+                }
+                context.dirty_areas.push_back(divider);
+            }
+            self.dirty = false;
+        }
+        for (pane, &pane_area) in self.panes.iter_mut().zip(self.areas.iter()) {
+            if is_valid_area!(pane_area) {
+                pane.draw(grid, pane_area, context);
+            }
+        }
+    }
+
+    fn process_event(&mut self, event: &mut UIEvent, context: &mut Context) -> bool {
+        let mut shortcuts = ShortcutMaps::default();
+        shortcuts.insert("general", context.settings.shortcuts.general.key_values());
+        match *event {
+            UIEvent::Input(Key::Mouse(MouseEvent::Press(MouseButton::Left, x, y))) => {
+                let (x, y) = (
+                    usize::from(x).saturating_sub(1),
+                    usize::from(y).saturating_sub(1),
+                );
+                if let Some(idx) = self.divider_at(x, y) {
+                    self.drag = Some(idx);
+                    return true;
+                }
+                if let Some(idx) = self.areas.iter().position(|&(upper_left, bottom_right)| {
+                    (get_x(upper_left)..=get_x(bottom_right)).contains(&x)
+                        && (get_y(upper_left)..=get_y(bottom_right)).contains(&y)
+                }) {
+                    if idx < self.panes.len() && idx != self.focus {
+                        self.focus = idx;
+                        self.set_dirty(true);
+                    }
+                }
+            }
+            UIEvent::Input(Key::Mouse(MouseEvent::Hold(x, y))) => {
+                if let Some(idx) = self.drag {
+                    self.move_divider(
+                        idx,
+                        usize::from(x).saturating_sub(1),
+                        usize::from(y).saturating_sub(1),
+                    );
+                    self.set_dirty(true);
+                    return true;
+                }
+            }
+            UIEvent::Input(Key::Mouse(MouseEvent::Release(_, _))) => {
+                if self.drag.take().is_some() {
+                    return true;
+                }
+            }
+            UIEvent::Input(ref k)
+                if self.panes.len() > 1 && shortcut!(k == shortcuts["general"]["next_pane"]) =>
+            {
+                self.focus = (self.focus + 1) % self.panes.len();
+                context
+                    .replies
+                    .push_back(UIEvent::StatusEvent(StatusEvent::UpdateStatus(
+                        self.panes[self.focus].get_status(context),
+                    )));
+                self.set_dirty(true);
+                return true;
+            }
+            UIEvent::Action(Tab(Kill(id))) => {
+                if let Some(idx) = self.panes.iter().position(|p| p.id() == id) {
+                    if self.panes.len() > 1 {
+                        self.remove(idx);
+                    } else {
+                        context
+                            .replies
+                            .push_back(UIEvent::Action(Tab(Kill(self.id))));
+                    }
+                    return true;
+                }
+            }
+            _ => {}
+        }
+        let focus = self.focus;
+        if let UIEvent::Input(_) | UIEvent::CmdInput(_) | UIEvent::EmbedInput(_) = event {
+            if self.panes[focus].process_event(event, context) {
+                return true;
+            }
+            if let UIEvent::Input(ref k) = event {
+                let delta = if shortcut!(k == shortcuts["general"]["grow_pane"]) {
+                    5
+                } else if shortcut!(k == shortcuts["general"]["shrink_pane"]) {
+                    -5
+                } else {
+                    return false;
+                };
+                resize_pane(&mut self.sizes, focus, delta);
+                self.set_dirty(true);
+                return true;
+            }
+            false
+        } else {
+            if self.panes[focus].process_event(event, context) {
+                return true;
+            }
+            if let UIEvent::Action(Tab(SplitWith(direction, pane))) = event {
+                if let Some(pane) = pane.take() {
+                    if *direction == self.direction {
+                        self.split(pane);
+                    } else {
+                        let old = self.panes.remove(focus);
+                        self.panes.insert(
+                            focus,
+                            Box::new(Panes::new(*direction, vec![old, pane], context)),
+                        );
+                        self.set_dirty(true);
+                    }
+                }
+                return true;
+            }
+            self.panes.iter_mut().enumerate().any(|(idx, pane)| {
+                if idx == focus {
+                    return false;
+                }
+                pane.process_event(event, context)
+            })
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty || self.panes.iter().any(|p| p.is_dirty())
+    }
+
+    fn set_dirty(&mut self, value: bool) {
+        self.dirty = value;
+        for p in self.panes.iter_mut() {
+            p.set_dirty(value);
+        }
+    }
+
+    fn kill(&mut self, id: ComponentId, context: &mut Context) {
+        if id == self.id {
+            let focused = self.panes[self.focus].id();
+            self.panes[self.focus].kill(focused, context);
+        }
+    }
+
+    fn can_quit_cleanly(&mut self, context: &Context) -> bool {
+        for (i, p) in self.panes.iter_mut().enumerate() {
+            if !p.can_quit_cleanly(context) {
+                self.focus = i;
+                self.set_dirty(true);
+                return false;
+            }
+        }
+        true
+    }
+
+    fn get_shortcuts(&self, context: &Context) -> ShortcutMaps {
+        self.panes[self.focus].get_shortcuts(context)
+    }
+
+    fn get_status(&self, context: &Context) -> String {
+        self.panes[self.focus].get_status(context)
+    }
+
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn set_id(&mut self, id: ComponentId) {
+        self.id = id;
+    }
+
+    fn save_session(&self, session: &mut crate::session::Session, context: &Context) {
+        let mut panes = vec![];
+        let mut sizes = vec![];
+        for (p, size) in self.panes.iter().zip(self.sizes.iter()) {
+            let mut pane_session = crate::session::Session::default();
+            p.save_session(&mut pane_session, context);
+            /* Panes that aren't restored on their own, eg the listing, keep their state in the
+             * session but are left out of the layout. */
+            session.listing = session.listing.take().or(pane_session.listing);
+            session.accounts.extend(pane_session.accounts);
+            session.listing_layout = session
+                .listing_layout
+                .take()
+                .or(pane_session.listing_layout);
+            for tab in pane_session.tabs {
+                panes.push(tab);
+                sizes.push(*size);
+            }
+        }
+        if panes.len() > 1 {
+            session.tabs.push(crate::session::TabSession::Split {
+                direction: self.direction,
+                sizes,
+                panes,
+            });
+        } else {
+            session.tabs.extend(panes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_area() {
+        let area = ((0, 0), (99, 9));
+        assert_eq!(
+            split_area(area, SplitDirection::Vertical, &[20, 30, 50]),
+            vec![((0, 0), (19, 9)), ((21, 0), (50, 9)), ((52, 0), (99, 9))]
+        );
+        assert_eq!(
+            split_area(area, SplitDirection::Horizontal, &[50, 50]),
+            vec![((0, 0), (99, 4)), ((0, 6), (99, 9))]
+        );
+        /* Panes that don't fit are invalid areas */
+        let areas = split_area(((0, 0), (1, 1)), SplitDirection::Vertical, &[50, 25, 25]);
+        assert_eq!(areas[0], ((0, 0), (0, 1)));
+        assert!(!is_valid_area!(areas[2]));
+    }
+
+    #[test]
+    fn test_resize_pane() {
+        let mut sizes = vec![30, 30, 40];
+        resize_pane(&mut sizes, 0, 10);
+        assert_eq!(sizes, vec![40, 20, 40]);
+        resize_pane(&mut sizes, 2, 50);
+        assert_eq!(sizes, vec![40, 5, 55]);
+        resize_pane(&mut sizes, 0, -100);
+        assert_eq!(sizes, vec![5, 40, 55]);
+        assert_eq!(equal_sizes(3), vec![33, 33, 34]);
+    }
+}
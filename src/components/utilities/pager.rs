@@ -20,8 +20,140 @@
  */
 
 use super::*;
+use crate::conf::PagerSearchMode;
 use melib::text_processing::LineBreakText;
 
+/// A search in the text of a [`Pager`].
+#[derive(Debug, Clone)]
+struct PagerSearch {
+    pattern: String,
+    mode: PagerSearchMode,
+    /// The pattern as it is matched, lowercase for case insensitive searches.
+    needle: String,
+    #[cfg(feature = "regexp")]
+    regexp: Option<crate::conf::RegexpWrapper>,
+    /// Why nothing can match, eg. an invalid regular expression.
+    error: Option<String>,
+    /// Whether the pattern is still being typed in the `search` command.
+    incremental: bool,
+    /// Line, column and width of each match.
+    positions: Vec<(usize, usize, usize)>,
+    cursor: usize,
+    movement: Option<PageMovement>,
+}
+
+impl PagerSearch {
+    fn new(pattern: String, mode: PagerSearchMode, incremental: bool) -> Self {
+        let needle = if mode == PagerSearchMode::CaseInsensitive {
+            pattern.to_lowercase()
+        } else {
+            pattern.clone()
+        };
+        let mut ret = PagerSearch {
+            pattern,
+            mode,
+            needle,
+            #[cfg(feature = "regexp")]
+            regexp: None,
+            error: None,
+            incremental,
+            positions: vec![],
+            cursor: 0,
+            movement: Some(PageMovement::Home),
+        };
+        if mode.is_regex() && !ret.pattern.is_empty() {
+            #[cfg(feature = "regexp")]
+            {
+                match crate::conf::RegexpWrapper::new(
+                    &ret.pattern,
+                    mode == PagerSearchMode::RegexCaseInsensitive,
+                    false,
+                    false,
+                    false,
+                    true,
+                    true,
+                ) {
+                    Ok(regexp) => ret.regexp = Some(regexp),
+                    Err(err) => ret.error = Some(err.to_string()),
+                }
+            }
+            #[cfg(not(feature = "regexp"))]
+            {
+                ret.error = Some("regex search needs the `regexp` feature".to_string());
+            }
+        }
+        ret
+    }
+
+    /// Returns the column and width of each match in `line`.
+    fn find(&self, line: &str) -> Vec<(usize, usize)> {
+        use melib::text_processing::search::KMP;
+        if self.needle.is_empty() || self.error.is_some() {
+            return vec![];
+        }
+        let kmp = |line: &str| -> Vec<(usize, usize)> {
+            let width = self.needle.grapheme_width();
+            line.kmp_search(&self.needle)
+                .into_iter()
+                .map(|offset| (line[..offset].grapheme_width(), width))
+                .collect()
+        };
+        match self.mode {
+            PagerSearchMode::CaseSensitive => kmp(line),
+            PagerSearchMode::CaseInsensitive => kmp(&line.to_lowercase()),
+            PagerSearchMode::Regex | PagerSearchMode::RegexCaseInsensitive => {
+                #[cfg(feature = "regexp")]
+                {
+                    if let Some(ref regexp) = self.regexp {
+                        /* Match byte offsets are converted to columns like the KMP offsets. */
+                        return regexp
+                            .0
+                            .find_iter(line.as_bytes())
+                            .filter_map(|m| m.ok())
+                            .filter(|m| m.end() > m.start())
+                            .map(|m| {
+                                (
+                                    line[..m.start()].grapheme_width(),
+                                    line[m.start()..m.end()].grapheme_width(),
+                                )
+                            })
+                            .collect();
+                    }
+                }
+                vec![]
+            }
+        }
+    }
+
+    /// Move to the next match, wrapping around to the first one.
+    fn next_match(&mut self) {
+        self.movement = Some(PageMovement::Down(1));
+        self.cursor = if self.cursor + 1 < self.positions.len() {
+            self.cursor + 1
+        } else {
+            0
+        };
+    }
+
+    /// Move to the previous match, wrapping around to the last one.
+    fn previous_match(&mut self) {
+        self.movement = Some(PageMovement::Up(1));
+        self.cursor = if self.cursor > 0 {
+            self.cursor - 1
+        } else {
+            self.positions.len().saturating_sub(1)
+        };
+    }
+
+    fn extend_positions<'l>(&mut self, lines: impl Iterator<Item = (usize, &'l String)>) {
+        for (y, l) in lines {
+            let matches = self.find(l);
+            self.positions
+                .extend(matches.into_iter().map(|(x, width)| (y, x, width)));
+        }
+    }
+}
+
 /// A pager for text.
 /// `Pager` holds its own content in its own `CellBuffer` and when `draw` is called, it draws the
 /// current view of the text. It is responsible for scrolling etc.
@@ -33,7 +165,7 @@ pub struct Pager {
     height: usize,
     width: usize,
     minimum_width: usize,
-    search: Option<SearchPattern>,
+    search: Option<PagerSearch>,
     dirty: bool,
 
     colors: ThemeAttribute,
@@ -214,6 +346,38 @@ impl Pager {
         (self.width, self.height)
     }
 
+    /// Lines of text reflowed so far.
+    pub fn text_lines(&self) -> &[String] {
+        &self.text_lines
+    }
+
+    /// Searches for `pattern`, starting from the last match if `from_end` is set.
+    pub fn set_search(&mut self, pattern: String, from_end: bool, context: &Context) {
+        let mut search = PagerSearch::new(pattern, context.settings.pager.search_mode, false);
+        if from_end {
+            search.cursor = usize::max_value();
+            search.movement = Some(PageMovement::End);
+        }
+        self.search = Some(search);
+        self.initialised = false;
+        self.dirty = true;
+    }
+
+    /// Index of the current match and number of matches of the search, if there is one.
+    pub fn search_position(&self) -> Option<(usize, usize)> {
+        self.search
+            .as_ref()
+            .map(|search| (search.cursor, search.positions.len()))
+    }
+
+    /// Number of matches of the current search in `text`.
+    pub fn count_matches(&self, text: &str) -> usize {
+        self.search
+            .as_ref()
+            .map(|search| text.lines().map(|l| search.find(l).len()).sum())
+            .unwrap_or(0)
+    }
+
     pub fn initialise(&mut self, grid: &mut CellBuffer, area: Area, context: &mut Context) {
         let mut width = width!(area);
         if width < self.minimum_width {
@@ -232,27 +396,24 @@ impl Pager {
         self.height = self.text_lines.len();
         self.width = width;
         if let Some(ref mut search) = self.search {
-            use melib::text_processing::search::KMP;
             search.positions.clear();
-            for (y, l) in self.text_lines.iter().enumerate() {
-                search.positions.extend(
-                    l.kmp_search(&search.pattern)
-                        .into_iter()
-                        .map(|offset| (y, offset)),
-                );
-            }
+            search.extend_positions(self.text_lines.iter().enumerate());
+        }
+        /* Matches are counted in the whole text. */
+        let up_to = if self.search.is_some() {
+            0
+        } else {
+            self.cursor.1 + Self::PAGES_AHEAD_TO_RENDER_NO * height!(area)
+        };
+        self.draw_lines_up_to(grid, area, context, up_to);
+        if let Some(ref mut search) = self.search {
+            search.cursor = std::cmp::min(search.positions.len().saturating_sub(1), search.cursor);
             if let Some(pos) = search.positions.get(search.cursor) {
                 if self.cursor.1 > pos.0 || self.cursor.1 + height!(area) < pos.0 {
                     self.cursor.1 = pos.0.saturating_sub(3);
                 }
             }
         }
-        self.draw_lines_up_to(
-            grid,
-            area,
-            context,
-            self.cursor.1 + Self::PAGES_AHEAD_TO_RENDER_NO * height!(area),
-        );
         self.draw_page(grid, area, context);
         self.initialised = true;
     }
@@ -280,14 +441,7 @@ impl Pager {
         };
         let new_lines_no = self.text_lines.len() - old_lines_no;
         if let Some(ref mut search) = self.search {
-            use melib::text_processing::search::KMP;
-            for (y, l) in self.text_lines.iter().enumerate().skip(old_lines_no) {
-                search.positions.extend(
-                    l.kmp_search(&search.pattern)
-                        .into_iter()
-                        .map(|offset| (y, offset)),
-                );
-            }
+            search.extend_positions(self.text_lines.iter().enumerate().skip(old_lines_no));
         }
         self.height += new_lines_no;
     }
@@ -352,19 +506,16 @@ impl Pager {
             let results_current_attr =
                 crate::conf::value(context, "pager.highlight_search_current");
             search.cursor = std::cmp::min(search.positions.len().saturating_sub(1), search.cursor);
-            for (i, (y, x)) in search
+            for (i, (y, x, width)) in search
                 .positions
                 .iter()
                 .enumerate()
-                .filter(|(_, (y, _))| *y >= cursor_line)
-                .take(height!(area) + 1)
+                .skip_while(|(_, (y, _, _))| *y < cursor_line)
+                .take_while(|(_, (y, _, _))| *y <= cursor_line + height!(area))
             {
                 let x = *x + get_x(upper_left);
                 let y = *y - cursor_line;
-                for c in grid.row_iter(
-                    x..x + search.pattern.grapheme_width(),
-                    y + get_y(upper_left),
-                ) {
+                for c in grid.row_iter(x..x + *width, y + get_y(upper_left)) {
                     if i == search.cursor {
                         grid[c]
                             .set_fg(results_current_attr.fg)
@@ -554,22 +705,31 @@ impl Component for Pager {
                 String::new()
             };
             let search_results = if let Some(ref search) = self.search {
-                format!(
-                    "{results_str}{search_pattern}: {current_pos}/{total_results}{has_more_lines}",
-                    results_str = RESULTS_STR,
-                    search_pattern = &search.pattern,
-                    current_pos = if search.positions.is_empty() {
-                        0
-                    } else {
-                        search.cursor + 1
-                    },
-                    total_results = search.positions.len(),
-                    has_more_lines = if self.line_breaker.is_finished() {
-                        ""
-                    } else {
-                        "(+)"
-                    }
-                )
+                if let Some(ref err) = search.error {
+                    format!(
+                        "{results_str}{search_pattern}: {err}",
+                        results_str = RESULTS_STR,
+                        search_pattern = &search.pattern,
+                        err = err
+                    )
+                } else {
+                    format!(
+                        "{results_str}{search_pattern}: {current_pos}/{total_results}{has_more_lines}",
+                        results_str = RESULTS_STR,
+                        search_pattern = &search.pattern,
+                        current_pos = if search.positions.is_empty() {
+                            0
+                        } else {
+                            search.cursor + 1
+                        },
+                        total_results = search.positions.len(),
+                        has_more_lines = if self.line_breaker.is_finished() {
+                            ""
+                        } else {
+                            "(+)"
+                        }
+                    )
+                }
             } else {
                 String::new()
             };
@@ -700,20 +860,30 @@ impl Component for Pager {
                 return true;
             }
             UIEvent::Action(Action::Listing(ListingAction::Search(pattern))) => {
-                self.search = Some(SearchPattern {
-                    pattern: pattern.to_string(),
-                    positions: vec![],
-                    cursor: 0,
-                    movement: Some(PageMovement::Home),
-                });
+                self.set_search(pattern.to_string(), false, context);
+                return true;
+            }
+            UIEvent::IncrementalSearch(Some(pattern)) => {
+                self.search = Some(PagerSearch::new(
+                    pattern.to_string(),
+                    context.settings.pager.search_mode,
+                    true,
+                ));
+                self.initialised = false;
+                self.dirty = true;
+                return true;
+            }
+            UIEvent::IncrementalSearch(None)
+                if self.search.as_ref().map_or(false, |s| s.incremental) =>
+            {
+                self.search = None;
                 self.initialised = false;
                 self.dirty = true;
                 return true;
             }
             UIEvent::Input(Key::Char('n')) if self.search.is_some() => {
                 if let Some(ref mut search) = self.search {
-                    search.movement = Some(PageMovement::Down(1));
-                    search.cursor += 1;
+                    search.next_match();
                 } else {
                     unsafe {
                         std::hint::unreachable_unchecked();
@@ -725,8 +895,7 @@ impl Component for Pager {
             }
             UIEvent::Input(Key::Char('N')) if self.search.is_some() => {
                 if let Some(ref mut search) = self.search {
-                    search.movement = Some(PageMovement::Up(1));
-                    search.cursor = search.cursor.saturating_sub(1);
+                    search.previous_match();
                 } else {
                     unsafe {
                         std::hint::unreachable_unchecked();
@@ -774,3 +943,73 @@ impl Component for Pager {
         self.id = id;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pager_search_find() {
+        let line = "Ελληνικά and English, english";
+        let search = PagerSearch::new("English".to_string(), PagerSearchMode::CaseSensitive, false);
+        assert_eq!(search.find(line), vec![(13, 7)]);
+        let search = PagerSearch::new(
+            "english".to_string(),
+            PagerSearchMode::CaseInsensitive,
+            false,
+        );
+        assert_eq!(search.find(line), vec![(13, 7), (22, 7)]);
+        let search = PagerSearch::new(String::new(), PagerSearchMode::CaseSensitive, false);
+        assert!(search.find(line).is_empty());
+
+        /* Columns, not byte or char offsets, of matches after wide characters. */
+        let search = PagerSearch::new("日本".to_string(), PagerSearchMode::CaseSensitive, false);
+        assert_eq!(search.find("日本語 日本"), vec![(0, 4), (7, 4)]);
+    }
+
+    #[cfg(feature = "regexp")]
+    #[test]
+    fn test_pager_search_find_regex() {
+        let line = "Ελληνικά and English, english";
+        let search = PagerSearch::new("[Ee]ngl[a-z]+".to_string(), PagerSearchMode::Regex, false);
+        assert_eq!(search.find(line), vec![(13, 7), (22, 7)]);
+        let search = PagerSearch::new("english".to_string(), PagerSearchMode::Regex, false);
+        assert_eq!(search.find(line), vec![(22, 7)]);
+        let search = PagerSearch::new(
+            "english".to_string(),
+            PagerSearchMode::RegexCaseInsensitive,
+            false,
+        );
+        assert_eq!(search.find(line), vec![(13, 7), (22, 7)]);
+        let search = PagerSearch::new("本+".to_string(), PagerSearchMode::Regex, false);
+        assert_eq!(search.find("日本語 日本本"), vec![(2, 2), (9, 4)]);
+        /* Empty matches are skipped. */
+        let search = PagerSearch::new("x*".to_string(), PagerSearchMode::Regex, false);
+        assert_eq!(search.find("abxxc"), vec![(2, 2)]);
+        let search = PagerSearch::new("(".to_string(), PagerSearchMode::Regex, false);
+        assert!(search.error.is_some());
+        assert!(search.find("(").is_empty());
+    }
+
+    #[test]
+    fn test_pager_search_wrap_around() {
+        let mut search = PagerSearch::new("a".to_string(), PagerSearchMode::CaseSensitive, false);
+        search.previous_match();
+        assert_eq!(search.cursor, 0);
+        search.next_match();
+        assert_eq!(search.cursor, 0);
+
+        let lines = vec!["a b".to_string(), "b".to_string(), "a a".to_string()];
+        search.extend_positions(lines.iter().enumerate());
+        assert_eq!(search.positions, vec![(0, 0, 1), (2, 0, 1), (2, 2, 1)]);
+        search.next_match();
+        search.next_match();
+        assert_eq!(search.cursor, 2);
+        search.next_match();
+        assert_eq!(search.cursor, 0);
+        search.previous_match();
+        assert_eq!(search.cursor, 2);
+        search.previous_match();
+        assert_eq!(search.cursor, 1);
+    }
+}
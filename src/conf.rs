@@ -32,7 +32,11 @@ use melib::search::Query;
 use std::collections::HashSet;
 mod overrides;
 pub use overrides::*;
+pub mod commands;
 pub mod composing;
+pub mod filters;
+pub mod index_format;
+pub mod keymap;
 pub mod notifications;
 pub mod pager;
 pub mod pgp;
@@ -46,14 +50,16 @@ pub use themes::*;
 
 pub mod accounts;
 pub use self::accounts::Account;
+pub use self::commands::*;
 pub use self::composing::*;
 pub use self::pgp::*;
 pub use self::shortcuts::*;
 pub use self::tags::*;
 
 use self::default_vals::*;
+use self::index_format::IndexFormat;
 use self::listing::ListingSettings;
-use self::notifications::NotificationsSettings;
+use self::notifications::{NotificationRule, NotificationsSettings, TimeWindow};
 use self::terminal::TerminalSettings;
 use crate::pager::PagerSettings;
 use melib::conf::{AccountSettings, MailboxConf, ToggleFlag};
@@ -170,6 +176,18 @@ pub struct FileAccount {
     pub manual_refresh: bool,
     #[serde(default = "none")]
     pub refresh_command: Option<String>,
+    #[serde(default)]
+    pub delete_policy: DeletePolicy,
+    #[serde(default = "none")]
+    pub empty_trash_after_days: Option<u64>,
+    #[serde(default)]
+    pub archive_hierarchy: ArchiveHierarchy,
+    #[serde(default = "snooze_mailbox_val")]
+    pub snooze_mailbox: String,
+    #[serde(default = "undo_limit_val")]
+    pub undo_limit: usize,
+    #[serde(default)]
+    pub filters: Vec<filters::FilterRule>,
     #[serde(flatten)]
     pub conf_override: MailUIConf,
     #[serde(flatten)]
@@ -189,6 +207,10 @@ impl FileAccount {
     pub fn search_backend(&self) -> &SearchBackend {
         &self.search_backend
     }
+
+    pub fn delete_policy(&self) -> DeletePolicy {
+        self.delete_policy
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -212,6 +234,8 @@ pub struct FileSettings {
     pub terminal: TerminalSettings,
     #[serde(default)]
     pub log: LogSettings,
+    #[serde(default)]
+    pub commands: CommandsSettings,
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
@@ -379,6 +403,7 @@ impl FileSettings {
         }
 
         s.terminal.themes.validate()?;
+        s.notifications.validate()?;
         for (name, acc) in &s.accounts {
             let FileAccount {
                 root_mailbox,
@@ -392,9 +417,58 @@ impl FileSettings {
                 manual_refresh,
                 refresh_command: _,
                 search_backend: _,
-                conf_override: _,
+                delete_policy: _,
+                empty_trash_after_days: _,
+                archive_hierarchy: _,
+                snooze_mailbox: _,
+                undo_limit: _,
+                filters,
+                conf_override,
             } = acc.clone();
 
+            notifications::validate(
+                conf_override
+                    .notifications
+                    .rules
+                    .as_ref()
+                    .map(Vec::as_slice)
+                    .unwrap_or(&[]),
+                conf_override
+                    .notifications
+                    .do_not_disturb
+                    .as_ref()
+                    .map(Vec::as_slice)
+                    .unwrap_or(&[]),
+            )
+            .map_err(|err| MeliError::new(format!("Account `{}`: {}", name, err)))?;
+
+            for rule in &filters {
+                filters::check_envelope_query(&rule.query).map_err(|err| {
+                    MeliError::new(format!(
+                        "Account `{}`, filter `{}`: {}",
+                        name,
+                        rule.name(),
+                        err
+                    ))
+                })?;
+                for action in &rule.actions {
+                    if let filters::FilterAction::SetFlags(flags)
+                    | filters::FilterAction::UnsetFlags(flags) = action
+                    {
+                        for flag in flags {
+                            flag.parse::<melib::Flag>().map_err(|err| {
+                                MeliError::new(format!(
+                                    "Account `{}`, filter `{}`: {}",
+                                    name,
+                                    rule.name(),
+                                    err
+                                ))
+                            })?;
+                        }
+                    }
+                }
+            }
+
             let lowercase_format = format.to_lowercase();
             let s = AccountSettings {
                 name: name.to_string(),
@@ -430,6 +504,7 @@ pub struct Settings {
     pub pgp: PGPSettings,
     pub terminal: TerminalSettings,
     pub log: LogSettings,
+    pub commands: CommandsSettings,
 }
 
 impl Settings {
@@ -462,6 +537,7 @@ impl Settings {
             pgp: fs.pgp,
             terminal: fs.terminal,
             log: fs.log,
+            commands: fs.commands,
         })
     }
 
@@ -485,6 +561,7 @@ impl Settings {
             pgp: fs.pgp,
             terminal: fs.terminal,
             log: fs.log,
+            commands: fs.commands,
         })
     }
 }
@@ -503,6 +580,50 @@ impl Default for IndexStyle {
     }
 }
 
+/// Where the mail listing previews the message under the cursor.
+#[derive(Copy, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListingLayout {
+    /// No preview, messages are opened in place of the list.
+    Default,
+    /// Sidebar, list and preview side by side.
+    ThreeColumns,
+    /// Preview below the list.
+    Stacked,
+}
+
+impl Default for ListingLayout {
+    fn default() -> Self {
+        ListingLayout::Default
+    }
+}
+
+/// How the pager matches the pattern of the `search` command.
+#[derive(Copy, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PagerSearchMode {
+    CaseSensitive,
+    CaseInsensitive,
+    /// PCRE2 regular expressions, if built with the `regexp` feature.
+    Regex,
+    RegexCaseInsensitive,
+}
+
+impl PagerSearchMode {
+    pub fn is_regex(self) -> bool {
+        match self {
+            PagerSearchMode::Regex | PagerSearchMode::RegexCaseInsensitive => true,
+            PagerSearchMode::CaseSensitive | PagerSearchMode::CaseInsensitive => false,
+        }
+    }
+}
+
+impl Default for PagerSearchMode {
+    fn default() -> Self {
+        PagerSearchMode::CaseSensitive
+    }
+}
+
 /*
  * Deserialize default functions
  */
@@ -528,6 +649,14 @@ mod default_vals {
         None
     }
 
+    pub(in crate::conf) fn snooze_mailbox_val() -> String {
+        "Snoozed".to_string()
+    }
+
+    pub(in crate::conf) fn undo_limit_val() -> usize {
+        crate::undo::UndoJournal::DEFAULT_LIMIT
+    }
+
     pub(in crate::conf) fn internal_value_false<T: std::convert::From<super::ToggleFlag>>() -> T {
         super::ToggleFlag::InternalVal(false).into()
     }
@@ -664,6 +793,53 @@ impl Serialize for SearchBackend {
     }
 }
 
+/// What the `delete` action does to messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeletePolicy {
+    /// Move messages to the account's `Trash` mailbox, and expunge them if they are already in it.
+    Trash,
+    /// Only set the `TRASHED` flag.
+    Flag,
+    /// Remove messages permanently.
+    Expunge,
+}
+
+impl Default for DeletePolicy {
+    fn default() -> Self {
+        DeletePolicy::Trash
+    }
+}
+
+/// Sub-mailboxes of the Archive mailbox that archived messages are sorted into, by date.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveHierarchy {
+    /// Move messages to the Archive mailbox itself.
+    None,
+    /// `Archive/2020`
+    Year,
+    /// `Archive/2020/09`
+    YearMonth,
+}
+
+impl Default for ArchiveHierarchy {
+    fn default() -> Self {
+        ArchiveHierarchy::None
+    }
+}
+
+impl ArchiveHierarchy {
+    /// `strftime` format of the sub-mailbox path for a message's date.
+    pub fn date_format(self) -> Option<&'static str> {
+        match self {
+            ArchiveHierarchy::None => None,
+            ArchiveHierarchy::Year => Some("%Y"),
+            ArchiveHierarchy::YearMonth => Some("%Y/%m"),
+        }
+    }
+}
+
 pub fn create_config_file(p: &Path) -> Result<()> {
     let mut file = OpenOptions::new()
         .write(true)
@@ -838,6 +1014,8 @@ mod dotaddressable {
     impl DotAddressable for String {}
     impl DotAddressable for char {}
     impl DotAddressable for IndexStyle {}
+    impl DotAddressable for ListingLayout {}
+    impl DotAddressable for PagerSearchMode {}
     impl DotAddressable for u64 {}
     impl DotAddressable for crate::terminal::Color {}
     impl DotAddressable for crate::terminal::Attr {}
@@ -848,6 +1026,8 @@ mod dotaddressable {
     impl DotAddressable for PathBuf {}
     impl DotAddressable for ToggleFlag {}
     impl DotAddressable for SearchBackend {}
+    impl DotAddressable for DeletePolicy {}
+    impl DotAddressable for ArchiveHierarchy {}
     impl DotAddressable for melib::SpecialUsageMailbox {}
     impl<T: DotAddressable> DotAddressable for Option<T> {}
     impl<T: DotAddressable> DotAddressable for Vec<T> {}
@@ -897,6 +1077,7 @@ mod dotaddressable {
                         "pgp" => Err(MeliError::new("unimplemented")),
                         "terminal" => self.terminal.lookup(field, tail),
                         "log" => self.log.lookup(field, tail),
+                        "commands" => self.commands.lookup(field, tail),
 
                         other => Err(MeliError::new(format!(
                             "{} has no field named {}",
@@ -992,6 +1173,12 @@ mod dotaddressable {
                         "search_backend" => self.search_backend.lookup(field, tail),
                         "manual_refresh" => self.manual_refresh.lookup(field, tail),
                         "refresh_command" => self.refresh_command.lookup(field, tail),
+                        "delete_policy" => self.delete_policy.lookup(field, tail),
+                        "empty_trash_after_days" => self.empty_trash_after_days.lookup(field, tail),
+                        "archive_hierarchy" => self.archive_hierarchy.lookup(field, tail),
+                        "snooze_mailbox" => self.snooze_mailbox.lookup(field, tail),
+                        "undo_limit" => self.undo_limit.lookup(field, tail),
+                        "filters" => self.filters.lookup(field, tail),
                         "conf_override" => self.conf_override.lookup(field, tail),
                         "extra" => self.extra.lookup(field, tail),
                         other => Err(MeliError::new(format!(
@@ -23,14 +23,16 @@
  * Account management from user configuration.
  */
 
+use super::filters::{self, FilterRule};
 use super::{AccountConf, FileMailboxConf};
 use crate::jobs::{JobExecutor, JobId, JoinHandle};
+use crate::undo::{EnvelopeOperation, FlagOp, JournalEntry, UndoJournal};
 use indexmap::IndexMap;
 use melib::backends::*;
 use melib::email::*;
 use melib::error::{MeliError, Result};
 use melib::text_processing::GlobMatch;
-use melib::thread::{SortField, SortOrder, Threads};
+use melib::thread::{SortField, SortOrder, ThreadHash, ThreadRule};
 use melib::AddressBook;
 use melib::Collection;
 use smallvec::SmallVec;
@@ -55,6 +57,87 @@ use std::pin::Pin;
 use std::result;
 use std::sync::{Arc, RwLock};
 
+/// What the `delete` action does to messages, depending on the account's `delete_policy` and
+/// Trash mailbox.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeleteAction {
+    /// Set the `TRASHED` flag.
+    SetTrashed,
+    MoveToTrash(MailboxHash),
+    /// There is no Trash mailbox to move messages to: ask before expunging them.
+    ConfirmExpunge,
+    Expunge,
+}
+
+fn delete_action(
+    policy: super::DeletePolicy,
+    mailbox_hash: MailboxHash,
+    trash_mailbox_hash: Option<MailboxHash>,
+) -> DeleteAction {
+    use super::DeletePolicy;
+    match (policy, trash_mailbox_hash) {
+        (DeletePolicy::Flag, _) => DeleteAction::SetTrashed,
+        (DeletePolicy::Trash, None) => DeleteAction::ConfirmExpunge,
+        (DeletePolicy::Trash, Some(trash_mailbox_hash)) if trash_mailbox_hash != mailbox_hash => {
+            DeleteAction::MoveToTrash(trash_mailbox_hash)
+        }
+        /* Expunge, or delete from within the Trash mailbox */
+        (DeletePolicy::Trash, Some(_)) | (DeletePolicy::Expunge, _) => DeleteAction::Expunge,
+    }
+}
+
+/// Returns the messages of `in_trash` that have been in Trash for more than `days` days at `now`,
+/// and the new arrival times: messages seen for the first time arrive `now`, and messages no
+/// longer in Trash are forgotten.
+fn expired_trash<T>(
+    arrivals: &HashMap<String, u64>,
+    in_trash: impl IntoIterator<Item = (T, String)>,
+    now: u64,
+    days: u64,
+) -> (HashMap<String, u64>, Vec<T>) {
+    let cutoff = now.saturating_sub(days.saturating_mul(24 * 60 * 60));
+    let mut new_arrivals = HashMap::default();
+    let mut expired = vec![];
+    for (h, message_id) in in_trash {
+        let arrival = arrivals.get(&message_id).cloned().unwrap_or(now);
+        if arrival <= cutoff {
+            expired.push(h);
+        }
+        new_arrivals.insert(message_id, arrival);
+    }
+    (new_arrivals, expired)
+}
+
+/// Path of the mailbox a message dated `date` is archived to, under the Archive mailbox at
+/// `archive_path`.
+fn archive_mailbox_path(
+    hierarchy: super::ArchiveHierarchy,
+    archive_path: &str,
+    date: melib::UnixTimestamp,
+) -> String {
+    if let Some(fmt) = hierarchy.date_format() {
+        format!(
+            "{}/{}",
+            archive_path,
+            melib::datetime::timestamp_to_string(date, Some(fmt))
+        )
+    } else {
+        archive_path.to_string()
+    }
+}
+
+/// The first mailbox on the way to `path` that doesn't exist, i.e. the one to create first.
+fn first_missing_mailbox<'a>(path: &'a str, exists: impl Fn(&str) -> bool) -> &'a str {
+    let mut missing = path;
+    while let Some(pos) = missing.rfind('/') {
+        if exists(&missing[..pos]) {
+            break;
+        }
+        missing = &missing[..pos];
+    }
+    missing
+}
+
 #[macro_export]
 macro_rules! try_recv_timeout {
     ($oneshot:expr) => {{
@@ -156,8 +239,39 @@ pub struct Account {
     sender: Sender<ThreadEvent>,
     event_queue: VecDeque<(MailboxHash, RefreshEvent)>,
     pub backend_capabilities: MailBackendCapabilities,
+    pub undo_journal: UndoJournal,
+    /// When each message in the Trash mailbox was first seen there, as seconds since the epoch,
+    /// keyed by Message-ID. Used by `empty_trash_after_days`.
+    trash_arrivals: HashMap<String, u64>,
+    /// Moves waiting for their destination mailbox to be created: description of the action,
+    /// destination path, source mailbox and Message-IDs.
+    pending_moves: Vec<(&'static str, String, MailboxHash, Vec<Vec<u8>>)>,
+    /// Messages in the `snooze_mailbox`, keyed by Message-ID.
+    snoozed: HashMap<String, SnoozedMessage>,
+    /// Fires when the earliest snoozed message is due.
+    snooze_timer: Option<crate::jobs::Timer>,
+    /// Fires every `EMPTY_TRASH_INTERVAL` if `empty_trash_after_days` is set.
+    trash_timer: Option<crate::jobs::Timer>,
+    /// Message-IDs of snoozed messages being moved back, to be marked as unread when they arrive.
+    waking: HashSet<String>,
+    /// Message-IDs of messages moved by `execute_envelope_operation`, keyed by destination
+    /// mailbox. They aren't new arrivals so filters are not applied to them.
+    moved_in: HashSet<(MailboxHash, String)>,
+}
+
+/// Where a snoozed message came from and when it's moved back there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnoozedMessage {
+    pub until: melib::UnixTimestamp,
+    pub mailbox: String,
 }
 
+/// How often waking snoozed messages is retried while the snooze mailbox isn't loaded.
+const SNOOZE_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often the Trash mailbox is checked for messages older than `empty_trash_after_days`.
+const EMPTY_TRASH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 pub enum JobRequest {
     Mailboxes {
         handle: JoinHandle<Result<HashMap<MailboxHash, Mailbox>>>,
@@ -470,6 +584,38 @@ impl Account {
             }
         };
 
+        let mut trash_arrivals = HashMap::default();
+        if settings.conf.empty_trash_after_days.is_some() {
+            if let Ok(data) = data_dir.place_data_file("trash") {
+                if data.exists() {
+                    let reader = io::BufReader::new(fs::File::open(data).unwrap());
+                    if let Ok(data_t) = serde_json::from_reader(reader) {
+                        trash_arrivals = data_t;
+                    }
+                }
+            }
+        }
+
+        let collection = Collection::default();
+        if let Ok(data) = data_dir.place_data_file("threads") {
+            if data.exists() {
+                let reader = io::BufReader::new(fs::File::open(data).unwrap());
+                if let Ok(rules) = serde_json::from_reader(reader) {
+                    *collection.thread_rules.write().unwrap() = rules;
+                }
+            }
+        }
+
+        let mut snoozed = HashMap::default();
+        if let Ok(data) = data_dir.place_data_file("snoozed") {
+            if data.exists() {
+                let reader = io::BufReader::new(fs::File::open(data).unwrap());
+                if let Ok(data_t) = serde_json::from_reader(reader) {
+                    snoozed = data_t;
+                }
+            }
+        }
+
         if settings.conf.search_backend == crate::conf::SearchBackend::Auto {
             if backend.capabilities().supports_search {
                 settings.conf.search_backend = crate::conf::SearchBackend::None;
@@ -504,7 +650,8 @@ impl Account {
                     .unwrap();
             }
         }
-        Ok(Account {
+        let undo_journal = UndoJournal::new(settings.conf.undo_limit);
+        let mut ret = Account {
             hash,
             name,
             is_online: if !backend.capabilities().is_remote {
@@ -517,7 +664,7 @@ impl Account {
             tree: Default::default(),
             address_book,
             sent_mailbox: Default::default(),
-            collection: Default::default(),
+            collection,
             settings,
             sender,
             job_executor,
@@ -526,7 +673,24 @@ impl Account {
             event_queue: VecDeque::with_capacity(8),
             backend_capabilities: backend.capabilities(),
             backend: Arc::new(RwLock::new(backend)),
-        })
+            undo_journal,
+            trash_arrivals,
+            pending_moves: Vec::new(),
+            snoozed,
+            snooze_timer: None,
+            trash_timer: None,
+            waking: HashSet::default(),
+            moved_in: HashSet::default(),
+        };
+        ret.arm_snooze_timer();
+        if ret.settings.conf.empty_trash_after_days.is_some() {
+            ret.trash_timer = Some(
+                ret.job_executor
+                    .clone()
+                    .create_timer(EMPTY_TRASH_INTERVAL, EMPTY_TRASH_INTERVAL),
+            );
+        }
+        Ok(ret)
     }
 
     fn init(&mut self, mut ref_mailboxes: HashMap<MailboxHash, Mailbox>) -> Result<()> {
@@ -871,12 +1035,6 @@ impl Account {
                     }
                     let (is_seen, is_draft) =
                         { (envelope.is_seen(), envelope.flags().contains(Flag::DRAFT)) };
-                    let (subject, from) = {
-                        (
-                            envelope.subject().into_owned(),
-                            envelope.field_from_to_string(),
-                        )
-                    };
                     #[cfg(feature = "sqlite3")]
                     if self.settings.conf.search_backend == crate::conf::SearchBackend::Sqlite3 {
                         let handle = self.job_executor.spawn_blocking(crate::sqlite3::insert(
@@ -904,47 +1062,74 @@ impl Account {
                         return None;
                     }
 
-                    if self.mailbox_entries[&mailbox_hash]
+                    let message_id = String::from_utf8_lossy(
+                        self.collection.get_env(env_hash).message_id().raw(),
+                    )
+                    .into_owned();
+                    let woken = self.waking.remove(&message_id);
+                    let moved = self.moved_in.remove(&(mailbox_hash, message_id));
+                    if !woken && !moved && self.apply_filters(env_hash, mailbox_hash) {
+                        return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
+                    }
+
+                    let (snoozed, rule) = {
+                        let thread_hash = self.collection.get_env(env_hash).thread();
+                        let threads = self.collection.get_threads(mailbox_hash);
+                        if threads.thread_nodes().contains_key(&thread_hash) {
+                            let thread = threads.thread_ref(threads[&thread_hash].group);
+                            (thread.snoozed(), thread.rule())
+                        } else {
+                            (false, None)
+                        }
+                    };
+                    if rule == Some(ThreadRule::Muted) {
+                        if !is_seen {
+                            if let Err(err) = self.set_seen(env_hash, mailbox_hash, true) {
+                                melib::log(
+                                    format!(
+                                        "Could not mark message of muted thread as read: {}",
+                                        err
+                                    ),
+                                    melib::ERROR,
+                                );
+                            }
+                        }
+                        return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
+                    }
+
+                    if woken && is_seen {
+                        /* A snoozed message is back */
+                        if let Err(err) = self.set_seen(env_hash, mailbox_hash, false) {
+                            melib::log(
+                                format!("Could not mark snoozed message as unread: {}", err),
+                                melib::ERROR,
+                            );
+                        }
+                    }
+                    let is_seen = is_seen && !woken;
+
+                    let notification_type = if rule == Some(ThreadRule::Watched) {
+                        crate::types::NotificationType::WatchedThread
+                    } else if self.mailbox_entries[&mailbox_hash]
                         .conf
                         .mailbox_conf
                         .ignore
                         .is_true()
+                        || snoozed
                     {
                         return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
-                    }
-
-                    let thread_hash = self.collection.get_env(env_hash).thread();
-                    if self
-                        .collection
-                        .get_threads(mailbox_hash)
-                        .thread_nodes()
-                        .contains_key(&thread_hash)
-                    {
-                        let thread = self.collection.get_threads(mailbox_hash).find_group(
-                            self.collection.get_threads(mailbox_hash)[&thread_hash].group,
-                        );
-                        if self
-                            .collection
-                            .get_threads(mailbox_hash)
-                            .thread_ref(thread)
-                            .snoozed()
-                        {
-                            return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
-                        }
-                    }
+                    } else {
+                        crate::types::NotificationType::NewMail
+                    };
                     if is_seen || is_draft {
                         return Some(UIEvent::MailboxUpdate((self.hash, mailbox_hash)));
                     }
 
-                    return Some(Notification(
-                        Some(format!("new e-mail from: {}", from)),
-                        format!(
-                            "{}\n{} {}",
-                            subject,
-                            self.name,
-                            self.mailbox_entries[&mailbox_hash].name()
-                        ),
-                        Some(crate::types::NotificationType::NewMail),
+                    return Some(UIEvent::NewMail(
+                        self.hash,
+                        mailbox_hash,
+                        env_hash,
+                        notification_type,
                     ));
                 }
                 RefreshEventKind::Remove(env_hash) => {
@@ -1495,6 +1680,152 @@ impl Account {
         }
     }
 
+    /// Mutes or watches `threads` of `mailbox_hash`, or clears their rule if they all already
+    /// have it. Returns whether the rule was set.
+    pub fn toggle_thread_rule(
+        &mut self,
+        mailbox_hash: MailboxHash,
+        threads: &[ThreadHash],
+        rule: ThreadRule,
+    ) -> Result<bool> {
+        let message_ids = {
+            let threads_lck = self.collection.get_threads(mailbox_hash);
+            threads
+                .iter()
+                .filter_map(|t| threads_lck.root_message_id(*t))
+                .collect::<Vec<String>>()
+        };
+        if message_ids.is_empty() {
+            return Err(MeliError::new("No thread selected."));
+        }
+        let set = {
+            let rules = self.collection.thread_rules.read().unwrap();
+            !message_ids.iter().all(|id| rules.get(id) == Some(&rule))
+        };
+        for message_id in message_ids {
+            self.collection
+                .set_thread_rule(message_id, if set { Some(rule) } else { None });
+        }
+        self.save_thread_rules()?;
+        Ok(set)
+    }
+
+    fn save_thread_rules(&self) -> Result<()> {
+        let data_dir = xdg::BaseDirectories::with_profile("meli", &self.name)
+            .map_err(|err| MeliError::new(err.to_string()))?;
+        let path = data_dir.place_data_file("threads")?;
+        let f = fs::File::create(path)?;
+        serde_json::to_writer(
+            io::BufWriter::new(f),
+            &*self.collection.thread_rules.read().unwrap(),
+        )
+        .map_err(|err| MeliError::new(err.to_string()))?;
+        Ok(())
+    }
+
+    /// Expunge messages that have been in the Trash mailbox for more than
+    /// `empty_trash_after_days` days. Backends don't report when a message was moved, so a
+    /// message's age is counted from the first time it was seen in Trash. Does nothing until the
+    /// Trash mailbox has been loaded.
+    pub fn empty_trash(&mut self) {
+        let days = match self.settings.conf.empty_trash_after_days {
+            Some(days) => days,
+            None => return,
+        };
+        let trash_mailbox_hash = match self.special_use_mailbox(SpecialUsageMailbox::Trash) {
+            Some(h) if self.mailbox_entries[&h].status.is_available() => h,
+            _ => return,
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let (arrivals, expired) = {
+            let envelopes = self.collection.envelopes.read().unwrap();
+            let in_trash = self
+                .collection
+                .get_mailbox(trash_mailbox_hash)
+                .iter()
+                .filter_map(|h| {
+                    envelopes.get(h).map(|env| {
+                        (
+                            *h,
+                            String::from_utf8_lossy(env.message_id().raw()).to_string(),
+                        )
+                    })
+                })
+                .collect::<Vec<(EnvelopeHash, String)>>();
+            expired_trash(&self.trash_arrivals, in_trash, now, days)
+        };
+        if arrivals != self.trash_arrivals {
+            self.trash_arrivals = arrivals;
+            self.save_trash_arrivals();
+        }
+        if let Ok(env_hashes) = EnvelopeHashBatch::try_from(expired.as_slice()) {
+            if let Err(err) = self.expunge_messages(env_hashes, trash_mailbox_hash) {
+                self.sender
+                    .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                        Some(format!("{}: could not empty trash", &self.name)),
+                        err.to_string(),
+                        Some(crate::types::NotificationType::Error(err.kind)),
+                    )))
+                    .expect("Could not send event on main channel");
+            }
+        }
+    }
+
+    /// Write the times messages were first seen in Trash to the account's data directory, where
+    /// they are read from on the next start.
+    fn save_trash_arrivals(&self) {
+        let result = xdg::BaseDirectories::with_profile("meli", &self.name)
+            .map_err(|err| MeliError::new(err.to_string()))
+            .and_then(|data_dir| Ok(data_dir.place_data_file("trash")?))
+            .and_then(|path| Ok(fs::File::create(path)?))
+            .and_then(|f| {
+                serde_json::to_writer(io::BufWriter::new(f), &self.trash_arrivals)
+                    .map_err(|err| MeliError::new(err.to_string()))
+            });
+        if let Err(err) = result {
+            melib::log(
+                format!("{}: could not save trash data: {}", &self.name, err),
+                melib::ERROR,
+            );
+        }
+    }
+
+    /// What the `delete` action does to messages of `mailbox_hash`.
+    pub fn delete_action(&self, mailbox_hash: MailboxHash) -> DeleteAction {
+        delete_action(
+            self.settings.conf.delete_policy(),
+            mailbox_hash,
+            self.special_use_mailbox(SpecialUsageMailbox::Trash),
+        )
+    }
+
+    /// Delete messages from `mailbox_hash` permanently.
+    pub fn expunge_messages(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+    ) -> Result<()> {
+        let job = self
+            .backend
+            .write()
+            .unwrap()
+            .delete_messages(env_hashes.clone(), mailbox_hash)?;
+        let handle = self.job_executor.spawn_specialized(job);
+        self.insert_job(
+            handle.job_id,
+            JobRequest::DeleteMessages { env_hashes, handle },
+        );
+        Ok(())
+    }
+
+    /// The timer that calls for `empty_trash`, if `empty_trash_after_days` is set.
+    pub fn trash_timer_id(&self) -> Option<uuid::Uuid> {
+        self.trash_timer.as_ref().map(|t| t.id())
+    }
+
     /* Call only in Context::is_online, since only Context can launch the watcher threads if an
      * account goes from offline to online. */
     pub fn is_online(&mut self) -> Result<()> {
@@ -1574,6 +1905,734 @@ impl Account {
         }
     }
 
+    pub fn message_ids(&self, env_hashes: &EnvelopeHashBatch) -> Vec<Vec<u8>> {
+        env_hashes
+            .iter()
+            .filter(|h| self.collection.contains_key(h))
+            .map(|h| self.collection.get_env(h).message_id().raw().to_vec())
+            .collect()
+    }
+
+    /// Find the envelopes of `mailbox_hash` with any of the given Message-IDs.
+    fn envelopes_by_message_id(
+        &self,
+        mailbox_hash: MailboxHash,
+        message_ids: &[Vec<u8>],
+    ) -> Vec<EnvelopeHash> {
+        let message_ids: HashSet<&[u8]> = message_ids.iter().map(Vec::as_slice).collect();
+        let envelopes = self.collection.envelopes.read().unwrap();
+        self.collection
+            .get_mailbox(mailbox_hash)
+            .iter()
+            .filter(|h| {
+                envelopes
+                    .get(*h)
+                    .map(|env| message_ids.contains(env.message_id().raw()))
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Operations that restore the current flags and tags of `env_hashes` after `flags` have
+    /// been applied to them.
+    pub fn flags_inverse(
+        &self,
+        env_hashes: &EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        flags: &[FlagOp],
+    ) -> Vec<EnvelopeOperation> {
+        let envelopes = self.collection.envelopes.read().unwrap();
+        flags
+            .iter()
+            .filter_map(|(flag, value)| {
+                let message_ids = env_hashes
+                    .iter()
+                    .filter_map(|h| envelopes.get(&h))
+                    .filter(|env| {
+                        let current = match flag {
+                            Ok(f) => env.flags().contains(*f),
+                            Err(tag) => env.labels().contains(&tag_hash!(tag)),
+                        };
+                        current != *value
+                    })
+                    .map(|env| env.message_id().raw().to_vec())
+                    .collect::<Vec<Vec<u8>>>();
+                if message_ids.is_empty() {
+                    None
+                } else {
+                    Some(EnvelopeOperation::SetFlags {
+                        mailbox_hash,
+                        message_ids,
+                        flags: smallvec::smallvec![(flag.clone(), !*value)],
+                    })
+                }
+            })
+            .collect()
+    }
+
+    pub fn execute_envelope_operation(&mut self, op: &EnvelopeOperation) -> Result<()> {
+        match op {
+            EnvelopeOperation::SetFlags {
+                mailbox_hash,
+                message_ids,
+                flags,
+            } => {
+                let env_hashes = EnvelopeHashBatch::try_from(
+                    self.envelopes_by_message_id(*mailbox_hash, message_ids)
+                        .as_slice(),
+                )
+                .map_err(|_| MeliError::new("Messages not found in mailbox."))?;
+                let fut = self.backend.write().unwrap().set_flags(
+                    env_hashes.clone(),
+                    *mailbox_hash,
+                    flags.clone(),
+                )?;
+                let handle = self.job_executor.spawn_specialized(fut);
+                self.insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+            }
+            EnvelopeOperation::Move {
+                source,
+                destination,
+                message_ids,
+            } => {
+                let env_hashes = EnvelopeHashBatch::try_from(
+                    self.envelopes_by_message_id(*source, message_ids)
+                        .as_slice(),
+                )
+                .map_err(|_| {
+                    MeliError::new(format!(
+                        "Messages not found in {}; it might not have been refreshed yet.",
+                        self.mailbox_entries
+                            .get(source)
+                            .map(|e| e.name())
+                            .unwrap_or("mailbox")
+                    ))
+                })?;
+                let fut = self.backend.write().unwrap().copy_messages(
+                    env_hashes,
+                    *source,
+                    *destination,
+                    /* move? */ true,
+                )?;
+                self.moved_in.extend(
+                    message_ids
+                        .iter()
+                        .map(|id| (*destination, String::from_utf8_lossy(id).into_owned())),
+                );
+                let handle = self.job_executor.spawn_specialized(fut);
+                self.insert_job(
+                    handle.job_id,
+                    JobRequest::Generic {
+                        name: "message moving".into(),
+                        handle,
+                        on_finish: None,
+                        logging_level: melib::LoggingLevel::INFO,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Take back the last recorded action. Returns its description.
+    pub fn undo(&mut self) -> Result<String> {
+        let mut entry = self
+            .undo_journal
+            .pop_undo()
+            .ok_or_else(|| MeliError::new("Nothing to undo."))?;
+        let failed = entry
+            .inverse
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(idx, op)| {
+                self.execute_envelope_operation(op)
+                    .err()
+                    .map(|err| (idx, err))
+            });
+        if let Some((idx, err)) = failed {
+            entry.undo_failed_at(idx);
+            self.undo_journal.push_undo(entry);
+            return Err(err);
+        }
+        let description = entry.description.clone();
+        self.undo_journal.push_redo(entry);
+        Ok(description)
+    }
+
+    /// Apply again the last undone action. Returns its description.
+    pub fn redo(&mut self) -> Result<String> {
+        let mut entry = self
+            .undo_journal
+            .pop_redo()
+            .ok_or_else(|| MeliError::new("Nothing to redo."))?;
+        let failed = entry.operations.iter().enumerate().find_map(|(idx, op)| {
+            self.execute_envelope_operation(op)
+                .err()
+                .map(|err| (idx, err))
+        });
+        if let Some((idx, err)) = failed {
+            entry.redo_failed_at(idx);
+            self.undo_journal.push_redo(entry);
+            return Err(err);
+        }
+        let description = entry.description.clone();
+        self.undo_journal.push_undo(entry);
+        Ok(description)
+    }
+
+    /// Run the account's filter rules on a message that just arrived in `mailbox_hash`. Returns
+    /// `true` if it was moved away or marked as read, so that it shouldn't be notified about.
+    fn apply_filters(&mut self, env_hash: EnvelopeHash, mailbox_hash: MailboxHash) -> bool {
+        use std::io::Write;
+        if self.settings.conf.filters.is_empty() {
+            return false;
+        }
+        let mailbox_path = self.mailbox_entries[&mailbox_hash]
+            .ref_mailbox
+            .path()
+            .to_string();
+        let is_inbox = self.special_use_mailbox(SpecialUsageMailbox::Inbox) == Some(mailbox_hash);
+        let rules = {
+            let envelope = self.collection.get_env(env_hash);
+            filters::matching_rules(
+                &self.settings.conf.filters,
+                &envelope,
+                &mailbox_path,
+                is_inbox,
+            )
+            .into_iter()
+            .cloned()
+            .collect::<Vec<FilterRule>>()
+        };
+        if rules.is_empty() {
+            return false;
+        }
+
+        let steps = filters::filter_steps(&rules);
+        let marked_read = steps.iter().any(|step| {
+            if let filters::FilterStep::SetFlags(flags) = step {
+                flags
+                    .iter()
+                    .any(|(f, value)| *value && f.as_ref().ok() == Some(&Flag::SEEN))
+            } else {
+                false
+            }
+        });
+
+        /* The steps run one after the other in a single job, so that e.g. the message isn't
+         * moved before its flags are set. */
+        let mut futures: Vec<Pin<Box<dyn Future<Output = Result<()>> + Send>>> = vec![];
+        let mut names = vec![];
+        let mut moved = false;
+        let mut result: Result<()> = Ok(());
+        for step in steps {
+            let move_ = if let filters::FilterStep::Move { .. } = step {
+                true
+            } else {
+                false
+            };
+            result = result.and_then(|()| {
+                match step {
+                    filters::FilterStep::SetFlags(flags) => {
+                        futures.push(self.backend.write().unwrap().set_flags(
+                            env_hash.into(),
+                            mailbox_hash,
+                            flags,
+                        )?);
+                        names.push("setting flags".to_string());
+                    }
+                    filters::FilterStep::Run { rule, command } => {
+                        let bytes_fut = self.operation(env_hash)?.as_bytes()?;
+                        names.push(format!("{}: running command", rule));
+                        futures.push(Box::pin(async move {
+                            let bytes = bytes_fut.await?;
+                            let mut child = std::process::Command::new("sh")
+                                .args(&["-c", &command])
+                                .stdin(std::process::Stdio::piped())
+                                .stdout(std::process::Stdio::null())
+                                .stderr(std::process::Stdio::piped())
+                                .spawn()?;
+                            child.stdin.take().unwrap().write_all(&bytes)?;
+                            let output = child.wait_with_output()?;
+                            if output.status.success() {
+                                Ok(())
+                            } else {
+                                Err(MeliError::new(format!(
+                                    "`{}` failed: {}",
+                                    command,
+                                    String::from_utf8_lossy(&output.stderr)
+                                )))
+                            }
+                        }));
+                    }
+                    filters::FilterStep::Copy { rule, path }
+                    | filters::FilterStep::Move { rule, path } => {
+                        let destination = self.mailbox_by_path(&path)?;
+                        if destination == mailbox_hash {
+                            return Ok(());
+                        }
+                        futures.push(self.backend.write().unwrap().copy_messages(
+                            env_hash.into(),
+                            mailbox_hash,
+                            destination,
+                            move_,
+                        )?);
+                        names.push(format!(
+                            "{}: {} message to {}",
+                            rule,
+                            if move_ { "moving" } else { "copying" },
+                            path
+                        ));
+                        moved |= move_;
+                    }
+                }
+                Ok(())
+            });
+        }
+        if !futures.is_empty() {
+            let handle = self.job_executor.spawn_blocking(async move {
+                for (fut, name) in futures.into_iter().zip(names.into_iter()) {
+                    fut.await
+                        .map_err(|err| MeliError::new(format!("{}: {}", name, err)))?;
+                }
+                Ok(())
+            });
+            self.insert_job(
+                handle.job_id,
+                JobRequest::Generic {
+                    name: "filters".into(),
+                    handle,
+                    on_finish: None,
+                    logging_level: melib::LoggingLevel::TRACE,
+                },
+            );
+        }
+        if let Err(err) = result {
+            self.sender
+                .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                    Some(format!("{}: could not apply filters", &self.name)),
+                    err.to_string(),
+                    Some(crate::types::NotificationType::Error(err.kind)),
+                )))
+                .expect("Could not send event on main channel");
+        }
+        moved || marked_read
+    }
+
+    /// Report which filter rules would apply to each message of `mailbox_hash`, without
+    /// applying them.
+    pub fn filters_dry_run(&self, mailbox_hash: MailboxHash) -> String {
+        let mut ret = String::new();
+        let mailbox_path = self.mailbox_entries[&mailbox_hash].ref_mailbox.path();
+        let is_inbox = self.special_use_mailbox(SpecialUsageMailbox::Inbox) == Some(mailbox_hash);
+        if self.settings.conf.filters.is_empty() {
+            return format!("Account {} has no filters.", self.name);
+        }
+        let envelopes = self.collection.envelopes.read().unwrap();
+        let mut count = 0;
+        for env_hash in self.collection.get_mailbox(mailbox_hash).iter() {
+            let envelope = match envelopes.get(env_hash) {
+                Some(e) => e,
+                None => continue,
+            };
+            let rules = filters::matching_rules(
+                &self.settings.conf.filters,
+                envelope,
+                mailbox_path,
+                is_inbox,
+            );
+            if rules.is_empty() {
+                continue;
+            }
+            count += 1;
+            ret.push_str(&format!(
+                "{} {}\n  From: {}\n",
+                envelope.message_id_display(),
+                envelope.subject(),
+                envelope.field_from_to_string()
+            ));
+            for rule in rules {
+                ret.push_str(&format!(
+                    "  {}: {}\n",
+                    rule.name(),
+                    rule.actions
+                        .iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ));
+            }
+        }
+        format!(
+            "Filters of account {} would apply to {} message{} of {}.\n\n{}",
+            self.name,
+            count,
+            if count == 1 { "" } else { "s" },
+            mailbox_path,
+            ret
+        )
+    }
+
+    /// Move `env_hashes` to the Archive mailbox, or to its sub-mailbox for each message's date
+    /// according to `archive_hierarchy`. Missing sub-mailboxes are created first.
+    pub fn archive(
+        &mut self,
+        env_hashes: &EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+    ) -> Result<()> {
+        let archive_mailbox_hash = self
+            .special_use_mailbox(SpecialUsageMailbox::Archive)
+            .ok_or_else(|| {
+                MeliError::new(format!(
+                    "Account {} has no Archive mailbox. Set a mailbox's `usage` to \"Archive\".",
+                    self.name
+                ))
+            })?;
+        let archive_path = self.mailbox_entries[&archive_mailbox_hash]
+            .ref_mailbox
+            .path()
+            .to_string();
+        let hierarchy = self.settings.conf.archive_hierarchy;
+        let mut destinations: IndexMap<String, Vec<Vec<u8>>> = IndexMap::default();
+        {
+            let envelopes = self.collection.envelopes.read().unwrap();
+            for h in env_hashes.iter() {
+                if let Some(env) = envelopes.get(&h) {
+                    destinations
+                        .entry(archive_mailbox_path(hierarchy, &archive_path, env.date()))
+                        .or_default()
+                        .push(env.message_id().raw().to_vec());
+                }
+            }
+        }
+        let mut operations = vec![];
+        for (path, message_ids) in destinations {
+            match self.mailbox_by_path(&path) {
+                Ok(destination) if destination == mailbox_hash => {}
+                Ok(destination) => {
+                    let operation = EnvelopeOperation::Move {
+                        source: mailbox_hash,
+                        destination,
+                        message_ids,
+                    };
+                    self.execute_envelope_operation(&operation)?;
+                    operations.push(operation);
+                }
+                Err(_) => {
+                    self.create_missing_mailbox(&path)?;
+                    self.pending_moves
+                        .push(("archive", path, mailbox_hash, message_ids));
+                }
+            }
+        }
+        let inverse = operations.iter().filter_map(|op| op.inverse()).collect();
+        self.record_operation("archive".to_string(), operations, inverse);
+        Ok(())
+    }
+
+    /// Create the first missing mailbox on the way to `path`, unless it's already being created.
+    fn create_missing_mailbox(&mut self, path: &str) -> Result<()> {
+        let missing = first_missing_mailbox(path, |p| self.mailbox_by_path(p).is_ok());
+        if self.active_jobs.values().any(|job| {
+            if let JobRequest::CreateMailbox { path, .. } = job {
+                path == missing
+            } else {
+                false
+            }
+        }) {
+            return Ok(());
+        }
+        self.mailbox_operation(crate::command::actions::MailboxOperation::Create(
+            missing.to_string(),
+        ))
+    }
+
+    /// Continue pending moves after `created_path` was created as `created_hash`.
+    fn continue_pending_moves(&mut self, created_path: &str, created_hash: MailboxHash) {
+        for (description, path, source, message_ids) in
+            std::mem::replace(&mut self.pending_moves, vec![])
+        {
+            /* The backend might have renamed the mailbox to use its own hierarchy separator */
+            let destination = if path == created_path {
+                Ok(created_hash)
+            } else {
+                self.mailbox_by_path(&path)
+            };
+            let result = match destination {
+                Ok(destination) => {
+                    let operation = EnvelopeOperation::Move {
+                        source,
+                        destination,
+                        message_ids,
+                    };
+                    self.execute_envelope_operation(&operation).map(|()| {
+                        let inverse = operation.inverse().into_iter().collect();
+                        self.record_operation(description.to_string(), vec![operation], inverse);
+                    })
+                }
+                Err(_) => self.create_missing_mailbox(&path).map(|()| {
+                    self.pending_moves
+                        .push((description, path.clone(), source, message_ids));
+                }),
+            };
+            if let Err(err) = result {
+                self.sender
+                    .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                        Some(format!(
+                            "{}: could not {} messages",
+                            &self.name, description
+                        )),
+                        err.to_string(),
+                        Some(crate::types::NotificationType::Error(err.kind)),
+                    )))
+                    .expect("Could not send event on main channel");
+            }
+        }
+    }
+
+    /// Move `env_hashes` to the `snooze_mailbox` until `until`, when they are moved back to
+    /// `mailbox_hash` and marked as unread. The snooze mailbox is created if it doesn't exist.
+    pub fn snooze(
+        &mut self,
+        env_hashes: &EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        until: melib::UnixTimestamp,
+    ) -> Result<()> {
+        if until <= melib::datetime::now() {
+            return Err(MeliError::new("That time is in the past."));
+        }
+        let path = self.settings.conf.snooze_mailbox.clone();
+        let message_ids = self.message_ids(env_hashes);
+        match self.mailbox_by_path(&path) {
+            Ok(destination) if destination == mailbox_hash => {
+                return Err(MeliError::new(format!(
+                    "Messages in {} are already snoozed.",
+                    path
+                )));
+            }
+            Ok(destination) => {
+                let operation = EnvelopeOperation::Move {
+                    source: mailbox_hash,
+                    destination,
+                    message_ids: message_ids.clone(),
+                };
+                self.execute_envelope_operation(&operation)?;
+                let inverse = operation.inverse().into_iter().collect();
+                self.record_operation("snooze".to_string(), vec![operation], inverse);
+            }
+            Err(_) => {
+                self.create_missing_mailbox(&path)?;
+                self.pending_moves
+                    .push(("snooze", path, mailbox_hash, message_ids.clone()));
+            }
+        }
+        let mailbox = self.mailbox_entries[&mailbox_hash]
+            .ref_mailbox
+            .path()
+            .to_string();
+        for message_id in message_ids {
+            self.snoozed.insert(
+                String::from_utf8_lossy(&message_id).into_owned(),
+                SnoozedMessage {
+                    until,
+                    mailbox: mailbox.clone(),
+                },
+            );
+        }
+        self.arm_snooze_timer();
+        self.save_snoozed()
+    }
+
+    /// Move the snoozed messages that are due back to the mailbox they were snoozed from. They
+    /// are marked as unread when they arrive there.
+    pub fn wake_snoozed(&mut self) {
+        let now = melib::datetime::now();
+        if self.snoozed.values().all(|s| s.until > now) {
+            return;
+        }
+        let snooze_mailbox_hash = match self.mailbox_by_path(&self.settings.conf.snooze_mailbox) {
+            Ok(mailbox_hash) => mailbox_hash,
+            /* Mailboxes haven't been fetched yet */
+            Err(_) if self.mailbox_entries.is_empty() => return,
+            Err(_) => {
+                /* The mailbox was removed along with the messages */
+                self.snoozed.retain(|_, s| s.until > now);
+                self.arm_snooze_timer();
+                if let Err(err) = self.save_snoozed() {
+                    melib::log(
+                        format!("Could not save snoozed messages: {}", err),
+                        melib::ERROR,
+                    );
+                }
+                return;
+            }
+        };
+        if self.load(snooze_mailbox_hash).is_err() {
+            /* Retried when the timer fires again */
+            return;
+        }
+        let mut destinations: IndexMap<String, Vec<Vec<u8>>> = IndexMap::default();
+        let due = self
+            .snoozed
+            .iter()
+            .filter(|(_, s)| s.until <= now)
+            .map(|(message_id, s)| (message_id.clone(), s.mailbox.clone()))
+            .collect::<Vec<(String, String)>>();
+        for (message_id, mailbox) in due {
+            self.snoozed.remove(&message_id);
+            destinations
+                .entry(mailbox)
+                .or_default()
+                .push(message_id.into_bytes());
+        }
+        for (path, message_ids) in destinations {
+            /* Messages moved out of the snooze mailbox in the meantime are left alone */
+            let env_hashes = match EnvelopeHashBatch::try_from(
+                self.envelopes_by_message_id(snooze_mailbox_hash, &message_ids)
+                    .as_slice(),
+            ) {
+                Ok(env_hashes) => env_hashes,
+                Err(_) => continue,
+            };
+            let message_ids = self.message_ids(&env_hashes);
+            let result = self
+                .mailbox_by_path(&path)
+                .or_else(|err| {
+                    self.special_use_mailbox(SpecialUsageMailbox::Inbox)
+                        .ok_or(err)
+                })
+                .and_then(|destination| {
+                    self.execute_envelope_operation(&EnvelopeOperation::Move {
+                        source: snooze_mailbox_hash,
+                        destination,
+                        message_ids: message_ids.clone(),
+                    })
+                });
+            match result {
+                Ok(()) => self.waking.extend(
+                    message_ids
+                        .iter()
+                        .map(|id| String::from_utf8_lossy(id).into_owned()),
+                ),
+                Err(err) => self
+                    .sender
+                    .send(ThreadEvent::UIEvent(UIEvent::Notification(
+                        Some(format!("{}: could not wake snoozed messages", &self.name)),
+                        err.to_string(),
+                        Some(crate::types::NotificationType::Error(err.kind)),
+                    )))
+                    .expect("Could not send event on main channel"),
+            }
+        }
+        self.arm_snooze_timer();
+        if let Err(err) = self.save_snoozed() {
+            melib::log(
+                format!("Could not save snoozed messages: {}", err),
+                melib::ERROR,
+            );
+        }
+    }
+
+    /// The timer that calls for `wake_snoozed`, if any messages are snoozed.
+    pub fn snooze_timer_id(&self) -> Option<uuid::Uuid> {
+        self.snooze_timer.as_ref().map(|t| t.id())
+    }
+
+    /// Set the snooze timer to fire when the earliest snoozed message is due.
+    fn arm_snooze_timer(&mut self) {
+        if let Some(timer) = self.snooze_timer.take() {
+            timer.disable();
+        }
+        if let Some(until) = self.snoozed.values().map(|s| s.until).min() {
+            let value = std::time::Duration::from_secs(std::cmp::max(
+                until.saturating_sub(melib::datetime::now()),
+                1,
+            ));
+            self.snooze_timer = Some(
+                self.job_executor
+                    .clone()
+                    .create_timer(SNOOZE_RETRY_INTERVAL, value),
+            );
+        }
+    }
+
+    fn save_snoozed(&self) -> Result<()> {
+        let data_dir = xdg::BaseDirectories::with_profile("meli", &self.name)
+            .map_err(|err| MeliError::new(err.to_string()))?;
+        let path = data_dir.place_data_file("snoozed")?;
+        let f = fs::File::create(path)?;
+        serde_json::to_writer(io::BufWriter::new(f), &self.snoozed)
+            .map_err(|err| MeliError::new(err.to_string()))?;
+        Ok(())
+    }
+
+    /// Mark `env_hash` as read or unread, without recording it in the undo journal.
+    fn set_seen(
+        &mut self,
+        env_hash: EnvelopeHash,
+        mailbox_hash: MailboxHash,
+        value: bool,
+    ) -> Result<()> {
+        let fut = self.backend.write().unwrap().set_flags(
+            env_hash.into(),
+            mailbox_hash,
+            smallvec::smallvec![(Ok(Flag::SEEN), value)],
+        )?;
+        let handle = self.job_executor.spawn_specialized(fut);
+        self.insert_job(
+            handle.job_id,
+            JobRequest::SetFlags {
+                env_hashes: env_hash.into(),
+                handle,
+            },
+        );
+        Ok(())
+    }
+
+    /// Apply `flags` to `env_hashes` and record the change in the undo journal.
+    pub fn set_flags_and_record(
+        &mut self,
+        env_hashes: EnvelopeHashBatch,
+        mailbox_hash: MailboxHash,
+        description: String,
+        flags: SmallVec<[FlagOp; 8]>,
+    ) -> Result<()> {
+        let inverse = self.flags_inverse(&env_hashes, mailbox_hash, &flags);
+        let message_ids = self.message_ids(&env_hashes);
+        let fut = self.backend.write().unwrap().set_flags(
+            env_hashes.clone(),
+            mailbox_hash,
+            flags.clone(),
+        )?;
+        let handle = self.job_executor.spawn_specialized(fut);
+        self.insert_job(handle.job_id, JobRequest::SetFlags { env_hashes, handle });
+        self.record_operation(
+            description,
+            vec![EnvelopeOperation::SetFlags {
+                mailbox_hash,
+                message_ids,
+                flags,
+            }],
+            inverse,
+        );
+        Ok(())
+    }
+
+    pub fn record_operation(
+        &mut self,
+        description: String,
+        operations: Vec<EnvelopeOperation>,
+        inverse: Vec<EnvelopeOperation>,
+    ) {
+        self.undo_journal.record(JournalEntry {
+            description,
+            operations,
+            inverse,
+        });
+    }
+
     pub fn process_event(&mut self, job_id: &JobId) -> bool {
         self.sender
             .send(ThreadEvent::UIEvent(UIEvent::StatusEvent(
@@ -1635,6 +2694,11 @@ impl Account {
                                 .and_modify(|entry| {
                                     entry.status = MailboxStatus::Available;
                                 });
+                            if self.special_use_mailbox(SpecialUsageMailbox::Trash)
+                                == Some(mailbox_hash)
+                            {
+                                self.empty_trash();
+                            }
                             self.sender
                                 .send(ThreadEvent::UIEvent(UIEvent::MailboxUpdate((
                                     self.hash,
@@ -1891,6 +2955,8 @@ impl Account {
                                         Some(crate::types::NotificationType::Error(err.kind)),
                                     )))
                                     .expect("Could not send event on main channel");
+                                self.pending_moves
+                                    .retain(|(_, p, _, _)| !p.starts_with(path.as_str()));
                             }
                             Ok((mailbox_hash, mut mailboxes)) => {
                                 self.sender
@@ -1942,7 +3008,7 @@ impl Account {
                                     .threads
                                     .write()
                                     .unwrap()
-                                    .insert(mailbox_hash, Threads::default());
+                                    .insert(mailbox_hash, self.collection.new_threads(0));
                                 self.collection
                                     .mailboxes
                                     .write()
@@ -1953,6 +3019,9 @@ impl Account {
                                     &self.mailbox_entries,
                                     &mut self.mailboxes_order,
                                 );
+                                if !self.pending_moves.is_empty() {
+                                    self.continue_pending_moves(path, mailbox_hash);
+                                }
                                 //Ok(format!("`{}` successfully created.", &path))
                             }
                         }
@@ -2318,3 +3387,102 @@ fn build_mailboxes_order(
         rec(node, &mailbox_entries, 0, 0, false);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::DeletePolicy;
+
+    #[test]
+    fn test_archive_mailbox_path() {
+        use crate::conf::ArchiveHierarchy;
+        /* 2020-09-13 12:26:40 UTC: the same month in every timezone */
+        let date = 1_600_000_000;
+        assert_eq!(
+            archive_mailbox_path(ArchiveHierarchy::None, "Archive", date),
+            "Archive"
+        );
+        assert_eq!(
+            archive_mailbox_path(ArchiveHierarchy::Year, "Archive", date),
+            "Archive/2020"
+        );
+        assert_eq!(
+            archive_mailbox_path(ArchiveHierarchy::YearMonth, "INBOX/Archive", date),
+            "INBOX/Archive/2020/09"
+        );
+    }
+
+    #[test]
+    fn test_first_missing_mailbox() {
+        let existing = ["Archive", "Archive/2019"];
+        let exists = |p: &str| existing.contains(&p);
+        assert_eq!(
+            first_missing_mailbox("Archive/2020/09", exists),
+            "Archive/2020"
+        );
+        assert_eq!(
+            first_missing_mailbox("Archive/2019/09", exists),
+            "Archive/2019/09"
+        );
+        assert_eq!(first_missing_mailbox("Other/2020", exists), "Other");
+    }
+
+    #[test]
+    fn test_delete_action() {
+        let (inbox, trash) = (1, 2);
+        assert_eq!(
+            delete_action(DeletePolicy::Trash, inbox, Some(trash)),
+            DeleteAction::MoveToTrash(trash)
+        );
+        assert_eq!(
+            delete_action(DeletePolicy::Trash, trash, Some(trash)),
+            DeleteAction::Expunge
+        );
+        assert_eq!(
+            delete_action(DeletePolicy::Trash, inbox, None),
+            DeleteAction::ConfirmExpunge
+        );
+        assert_eq!(
+            delete_action(DeletePolicy::Flag, trash, Some(trash)),
+            DeleteAction::SetTrashed
+        );
+        assert_eq!(
+            delete_action(DeletePolicy::Expunge, inbox, Some(trash)),
+            DeleteAction::Expunge
+        );
+        assert_eq!(
+            delete_action(DeletePolicy::Expunge, inbox, None),
+            DeleteAction::Expunge
+        );
+    }
+
+    #[test]
+    fn test_expired_trash() {
+        const DAY: u64 = 24 * 60 * 60;
+        let now = 100 * DAY;
+        let mut arrivals = HashMap::default();
+        arrivals.insert("old".to_string(), now - 31 * DAY);
+        arrivals.insert("cutoff".to_string(), now - 30 * DAY);
+        arrivals.insert("recent".to_string(), now - 29 * DAY);
+        arrivals.insert("gone".to_string(), now - 60 * DAY);
+        let in_trash = vec![
+            (1, "old".to_string()),
+            (2, "cutoff".to_string()),
+            (3, "recent".to_string()),
+            (4, "new".to_string()),
+        ];
+        let (new_arrivals, expired) = expired_trash(&arrivals, in_trash.clone(), now, 30);
+        assert_eq!(expired, vec![1, 2]);
+        /* Messages no longer in Trash are forgotten, new ones arrive now. */
+        assert!(!new_arrivals.contains_key("gone"));
+        assert_eq!(new_arrivals["new"], now);
+        assert_eq!(new_arrivals["recent"], arrivals["recent"]);
+        assert_eq!(new_arrivals.len(), 4);
+
+        /* With 0 days, everything in Trash is expired. */
+        let (_, expired) = expired_trash(&arrivals, in_trash.clone(), now, 0);
+        assert_eq!(expired, vec![1, 2, 3, 4]);
+        let (_, expired) = expired_trash(&arrivals, in_trash, now, u64::max_value());
+        assert!(expired.is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+/*
+ * meli - configuration module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! User defined command aliases, macros and startup commands.
+
+use super::DotAddressable;
+use indexmap::IndexMap;
+use melib::{MeliError, Result};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandsSettings {
+    /// Commands to execute after startup, in order.
+    /// Default: []
+    #[serde(default, alias = "on-startup")]
+    pub on_startup: Vec<String>,
+    /// Named commands that expand into one or more commands.
+    /// Default: empty
+    #[serde(default)]
+    pub aliases: IndexMap<String, CommandAlias>,
+}
+
+/// An alias expands into a single command and a macro into a sequence of commands.
+///
+/// `$1` to `$9` are replaced by the arguments the alias was called with and `$@` by all of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CommandAlias {
+    Alias(String),
+    Macro(Vec<String>),
+}
+
+impl CommandAlias {
+    pub fn commands(&self) -> &[String] {
+        match self {
+            CommandAlias::Alias(cmd) => std::slice::from_ref(cmd),
+            CommandAlias::Macro(cmds) => cmds,
+        }
+    }
+}
+
+impl CommandsSettings {
+    /// Returns the name of the alias `input` calls, if any.
+    pub fn alias_name<'a>(&self, input: &'a str) -> Option<&'a str> {
+        let name = input.trim().split_whitespace().next()?;
+        if self.aliases.contains_key(name) {
+            Some(name)
+        } else {
+            None
+        }
+    }
+
+    /// Expands `input` if it calls an alias.
+    pub fn expand(&self, input: &str) -> Option<Result<Vec<String>>> {
+        let name = self.alias_name(input)?;
+        let rest = input.trim()[name.len()..].trim();
+        let args = split_arguments(rest);
+        Some(
+            self.aliases[name]
+                .commands()
+                .iter()
+                .map(|cmd| substitute(name, cmd, &args, rest))
+                .collect(),
+        )
+    }
+}
+
+/// Splits `input` on whitespace, keeping double quoted arguments together.
+fn split_arguments(input: &str) -> Vec<String> {
+    let mut ret = vec![];
+    let mut arg = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                if let Some(c) = chars.next() {
+                    arg.push(c);
+                }
+            }
+            '"' => {
+                quoted = !quoted;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !arg.is_empty() {
+                    ret.push(std::mem::replace(&mut arg, String::new()));
+                }
+            }
+            c => arg.push(c),
+        }
+    }
+    if !arg.is_empty() {
+        ret.push(arg);
+    }
+    ret
+}
+
+fn substitute(name: &str, cmd: &str, args: &[String], rest: &str) -> Result<String> {
+    let mut ret = String::with_capacity(cmd.len());
+    let mut chars = cmd.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            ret.push(c);
+            continue;
+        }
+        match chars.peek().cloned() {
+            Some('@') => {
+                chars.next();
+                ret.push_str(rest);
+            }
+            Some('$') => {
+                chars.next();
+                ret.push('$');
+            }
+            Some(d @ '1'..='9') => {
+                chars.next();
+                let idx = d as usize - '1' as usize;
+                let arg = args.get(idx).ok_or_else(|| {
+                    MeliError::new(format!(
+                        "`{}` needs at least {} argument{}",
+                        name,
+                        idx + 1,
+                        if idx == 0 { "" } else { "s" }
+                    ))
+                })?;
+                /* Commands split their arguments on spaces, unless quoted. Quoted arguments
+                 * can't contain double quotes since `quoted_argument` doesn't unescape them. */
+                if arg.contains(char::is_whitespace) {
+                    if arg.contains('"') {
+                        return Err(MeliError::new(format!(
+                            "`{}` argument {} can't contain both spaces and double quotes",
+                            name,
+                            idx + 1
+                        )));
+                    }
+                    ret.push('"');
+                    ret.push_str(arg);
+                    ret.push('"');
+                } else {
+                    ret.push_str(arg);
+                }
+            }
+            _ => ret.push(c),
+        }
+    }
+    Ok(ret)
+}
+
+impl DotAddressable for CommandAlias {}
+
+impl DotAddressable for CommandsSettings {
+    fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
+        match path.first() {
+            Some(field) => {
+                let tail = &path[1..];
+                match *field {
+                    "on_startup" => self.on_startup.lookup(field, tail),
+                    "aliases" => self.aliases.lookup(field, tail),
+                    other => Err(MeliError::new(format!(
+                        "{} has no field named {}",
+                        parent_field, other
+                    ))),
+                }
+            }
+            None => Ok(toml::to_string(self).map_err(|err| err.to_string())?),
+        }
+    }
+}
+
+#[test]
+fn test_command_alias_expansion() {
+    let settings: CommandsSettings = toml::from_str(
+        r#"
+on_startup = ["go 1"]
+[aliases]
+mv = ["copyto $1", "delete"]
+s = "search $@"
+cost = "setenv PRICE $$$1"
+"#,
+    )
+    .unwrap();
+    assert_eq!(settings.on_startup, vec!["go 1".to_string()]);
+    assert_eq!(settings.alias_name("mv INBOX"), Some("mv"));
+    assert_eq!(settings.alias_name("move INBOX"), None);
+    assert_eq!(
+        settings.expand("mv \"Old Stuff\"").unwrap().unwrap(),
+        vec!["copyto \"Old Stuff\"".to_string(), "delete".to_string()]
+    );
+    assert_eq!(
+        settings
+            .expand("s from:alice and subject:\"a b\"")
+            .unwrap()
+            .unwrap(),
+        vec!["search from:alice and subject:\"a b\"".to_string()]
+    );
+    assert_eq!(
+        settings.expand("cost 5").unwrap().unwrap(),
+        vec!["setenv PRICE $5".to_string()]
+    );
+    assert_eq!(
+        settings.expand("mv").unwrap().unwrap_err().to_string(),
+        "`mv` needs at least 1 argument"
+    );
+    assert!(settings.expand("delete").is_none());
+}
+
+#[test]
+fn test_command_alias_arguments_parse_back() {
+    let settings: CommandsSettings = toml::from_str(
+        r#"
+[aliases]
+mv = "copyto $1"
+"#,
+    )
+    .unwrap();
+    for (line, arg) in &[
+        ("mv INBOX", "INBOX"),
+        ("mv \"Old Stuff\"", "Old Stuff"),
+        ("mv a\\\"b", "a\"b"),
+    ] {
+        let expanded = settings.expand(line).unwrap().unwrap();
+        let (_, parsed) =
+            crate::command::quoted_argument(expanded[0]["copyto ".len()..].as_bytes()).unwrap();
+        assert_eq!(parsed, *arg);
+    }
+    assert_eq!(
+        settings
+            .expand("mv \"a \\\"b\\\"\"")
+            .unwrap()
+            .unwrap_err()
+            .to_string(),
+        "`mv` argument 1 can't contain both spaces and double quotes"
+    );
+}
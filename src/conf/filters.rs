@@ -0,0 +1,346 @@
+/*
+ * meli - configuration module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Client-side filter rules, applied to new mail as it arrives in an account.
+
+use super::default_vals::{false_val, none};
+use super::DotAddressable;
+use crate::undo::FlagOp;
+use melib::email::{Address, Flag};
+use melib::search::{Query, QueryTrait};
+use melib::{Envelope, MeliError, Result};
+use smallvec::SmallVec;
+use std::fmt;
+
+/// A filter rule of an account, e.g.
+///
+/// ```toml
+/// [[accounts.personal.filters]]
+/// name = "lists"
+/// query = "from:rust-users"
+/// actions = [ { move_to = "INBOX/lists" }, "mark_read" ]
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterRule {
+    /// Shown in dry run reports and error notifications.
+    #[serde(default = "none")]
+    pub name: Option<String>,
+    /// Condition a new message must match.
+    pub query: Query,
+    /// Paths of the mailboxes the rule applies to. If empty, only the account's Inbox.
+    #[serde(default)]
+    pub mailboxes: Vec<String>,
+    pub actions: Vec<FilterAction>,
+    /// Don't try further rules on a message that matches this one.
+    #[serde(default = "false_val")]
+    pub stop: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    MoveTo(String),
+    CopyTo(String),
+    SetFlags(Vec<String>),
+    UnsetFlags(Vec<String>),
+    AddTags(Vec<String>),
+    RemoveTags(Vec<String>),
+    MarkRead,
+    /// Run a shell command with the message piped to its standard input.
+    Run(String),
+}
+
+impl fmt::Display for FilterAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterAction::MoveTo(path) => write!(f, "move to {}", path),
+            FilterAction::CopyTo(path) => write!(f, "copy to {}", path),
+            FilterAction::SetFlags(flags) => write!(f, "set flags {}", flags.join(", ")),
+            FilterAction::UnsetFlags(flags) => write!(f, "unset flags {}", flags.join(", ")),
+            FilterAction::AddTags(tags) => write!(f, "add tags {}", tags.join(", ")),
+            FilterAction::RemoveTags(tags) => write!(f, "remove tags {}", tags.join(", ")),
+            FilterAction::MarkRead => write!(f, "mark read"),
+            FilterAction::Run(command) => write!(f, "run `{}`", command),
+        }
+    }
+}
+
+impl FilterRule {
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{:?}", self.query))
+    }
+
+    /// Whether the rule applies to messages arriving in `mailbox_path`.
+    pub fn applies_to(&self, mailbox_path: &str, is_inbox: bool) -> bool {
+        if self.mailboxes.is_empty() {
+            is_inbox
+        } else {
+            self.mailboxes.iter().any(|m| m == mailbox_path)
+        }
+    }
+}
+
+/// Whether `envelope` matches `query`. Senders, recipients and subjects are matched against the
+/// envelope's parsed fields, since backends such as IMAP and JMAP don't fetch other headers.
+/// `query` must have passed `check_envelope_query`.
+pub fn envelope_matches(envelope: &Envelope, query: &Query) -> bool {
+    fn contains(addresses: &[Address], s: &str) -> bool {
+        addresses
+            .iter()
+            .any(|a| a.to_string().contains(s) || a.get_email().contains(s))
+    }
+    match query {
+        Query::From(s) => contains(envelope.from(), s),
+        Query::To(s) => contains(envelope.to(), s),
+        Query::Subject(s) => envelope.subject().contains(s.as_str()),
+        Query::AllAddresses(s) => {
+            contains(envelope.from(), s)
+                || contains(envelope.to(), s)
+                || envelope.is_match(&Query::Cc(s.clone()))
+                || envelope.is_match(&Query::Bcc(s.clone()))
+        }
+        Query::And(a, b) => envelope_matches(envelope, a) && envelope_matches(envelope, b),
+        Query::Or(a, b) => envelope_matches(envelope, a) || envelope_matches(envelope, b),
+        Query::Not(q) => !envelope_matches(envelope, q),
+        _ => envelope.is_match(query),
+    }
+}
+
+/// Reject queries that can't be evaluated on an envelope alone, i.e. body searches.
+pub fn check_envelope_query(query: &Query) -> Result<()> {
+    match query {
+        Query::Body(_) | Query::AllText(_) => Err(MeliError::new(
+            "body searches can't be used here, only headers, flags and dates are available.",
+        )),
+        Query::And(a, b) | Query::Or(a, b) => {
+            check_envelope_query(a)?;
+            check_envelope_query(b)
+        }
+        Query::Not(q) => check_envelope_query(q),
+        _ => Ok(()),
+    }
+}
+
+/// The rules of `rules` that match `envelope`, in order, up to and including the first matching
+/// rule with `stop` set.
+pub fn matching_rules<'r>(
+    rules: &'r [FilterRule],
+    envelope: &Envelope,
+    mailbox_path: &str,
+    is_inbox: bool,
+) -> Vec<&'r FilterRule> {
+    let mut ret = vec![];
+    for rule in rules {
+        if rule.applies_to(mailbox_path, is_inbox) && envelope_matches(envelope, &rule.query) {
+            ret.push(rule);
+            if rule.stop {
+                break;
+            }
+        }
+    }
+    ret
+}
+
+/// A step of applying filter rules to a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterStep {
+    SetFlags(SmallVec<[FlagOp; 8]>),
+    /// Pipe the message to `command`, for the rule named `rule`.
+    Run {
+        rule: String,
+        command: String,
+    },
+    Copy {
+        rule: String,
+        path: String,
+    },
+    Move {
+        rule: String,
+        path: String,
+    },
+}
+
+/// The steps that the matching `rules` take on a message, in the order they must run: flags and
+/// tags first, then commands and copies, and the move last since moving changes the message's
+/// hash. Only the first `move_to` action is kept.
+pub fn filter_steps(rules: &[FilterRule]) -> Vec<FilterStep> {
+    let mut flags: SmallVec<[FlagOp; 8]> = SmallVec::new();
+    let mut commands = vec![];
+    let mut copies = vec![];
+    let mut move_to = None;
+    for rule in rules {
+        for action in &rule.actions {
+            match action {
+                FilterAction::MoveTo(path) => {
+                    if move_to.is_none() {
+                        move_to = Some(FilterStep::Move {
+                            rule: rule.name(),
+                            path: path.clone(),
+                        });
+                    }
+                }
+                FilterAction::CopyTo(path) => copies.push(FilterStep::Copy {
+                    rule: rule.name(),
+                    path: path.clone(),
+                }),
+                FilterAction::SetFlags(v) | FilterAction::UnsetFlags(v) => {
+                    let value = if let FilterAction::SetFlags(_) = action {
+                        true
+                    } else {
+                        false
+                    };
+                    flags.extend(
+                        v.iter()
+                            .filter_map(|f| f.parse::<Flag>().ok())
+                            .map(|f| (Ok(f), value)),
+                    );
+                }
+                FilterAction::AddTags(v) => {
+                    flags.extend(v.iter().map(|t| (Err(t.clone()), true)));
+                }
+                FilterAction::RemoveTags(v) => {
+                    flags.extend(v.iter().map(|t| (Err(t.clone()), false)));
+                }
+                FilterAction::MarkRead => flags.push((Ok(Flag::SEEN), true)),
+                FilterAction::Run(command) => commands.push(FilterStep::Run {
+                    rule: rule.name(),
+                    command: command.clone(),
+                }),
+            }
+        }
+    }
+    let mut ret = vec![];
+    if !flags.is_empty() {
+        ret.push(FilterStep::SetFlags(flags));
+    }
+    ret.extend(commands);
+    ret.extend(copies);
+    ret.extend(move_to);
+    ret
+}
+
+impl DotAddressable for FilterRule {}
+impl DotAddressable for FilterAction {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn rule(query: &str, actions: Vec<FilterAction>, stop: bool) -> FilterRule {
+        FilterRule {
+            name: None,
+            query: Query::try_from(query).unwrap(),
+            mailboxes: vec![],
+            actions,
+            stop,
+        }
+    }
+
+    #[test]
+    fn test_filter_matching_rules() {
+        /* No other headers, as with envelopes fetched from IMAP or JMAP */
+        let mut envelope = Envelope::new(0);
+        let addresses = |s: &str| {
+            melib::email::parser::address::rfc2822address_list(s.as_bytes())
+                .unwrap()
+                .1
+        };
+        envelope.set_from(addresses("Rust Users <rust-users@example.com>"));
+        envelope.set_to(addresses("me@example.com"));
+        envelope.set_subject(b"[ANN] Release notes".to_vec());
+        assert!(envelope.other_headers().is_empty());
+
+        let rules = vec![
+            rule("from:rust-users", vec![FilterAction::MarkRead], false),
+            rule("subject:Release", vec![FilterAction::MarkRead], true),
+            rule("to:me@example.com", vec![FilterAction::MarkRead], false),
+        ];
+        assert_eq!(
+            matching_rules(&rules, &envelope, "INBOX", true)
+                .into_iter()
+                .map(|r| r.query.clone())
+                .collect::<Vec<Query>>(),
+            vec![rules[0].query.clone(), rules[1].query.clone()]
+        );
+        assert!(matching_rules(&rules, &envelope, "INBOX", false).is_empty());
+        assert!(envelope_matches(
+            &envelope,
+            &Query::try_from("to:me@example.com and not from:someone").unwrap()
+        ));
+        assert!(!envelope_matches(
+            &envelope,
+            &Query::try_from("from:someone").unwrap()
+        ));
+
+        assert!(check_envelope_query(&rules[0].query).is_ok());
+        assert!(check_envelope_query(&Query::try_from("from:a or words").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_filter_steps_order() {
+        let rules = vec![
+            rule(
+                "from:a",
+                vec![
+                    FilterAction::MoveTo("lists".to_string()),
+                    FilterAction::Run("notify".to_string()),
+                    FilterAction::CopyTo("archive".to_string()),
+                    FilterAction::AddTags(vec!["list".to_string()]),
+                ],
+                false,
+            ),
+            rule(
+                "from:a",
+                vec![
+                    FilterAction::MoveTo("other".to_string()),
+                    FilterAction::MarkRead,
+                ],
+                false,
+            ),
+        ];
+        let name = rules[0].name();
+        let mut flags: SmallVec<[FlagOp; 8]> = SmallVec::new();
+        flags.push((Err("list".to_string()), true));
+        flags.push((Ok(Flag::SEEN), true));
+        assert_eq!(
+            filter_steps(&rules),
+            vec![
+                FilterStep::SetFlags(flags),
+                FilterStep::Run {
+                    rule: name.clone(),
+                    command: "notify".to_string()
+                },
+                FilterStep::Copy {
+                    rule: name.clone(),
+                    path: "archive".to_string()
+                },
+                FilterStep::Move {
+                    rule: name,
+                    path: "lists".to_string()
+                },
+            ]
+        );
+    }
+}
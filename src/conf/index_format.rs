@@ -0,0 +1,434 @@
+/*
+ * meli - configuration module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Row templates of mail listings, set with `listing.index_format`.
+//!
+//! A template is literal text with fields in braces: `{field}`, `{field:spec}` or
+//! `{field:spec|argument}`. `spec` is `[<>^][min_width][.max_width]`, like Rust's format
+//! specifiers; values longer than `max_width` are truncated with `…`. Literal braces are written
+//! as `{{` and `}}`, and a newline starts the second line of conversations listing entries.
+
+use super::DotAddressable;
+use melib::text_processing::TextProcessing;
+use melib::{MeliError, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexField {
+    /// Row number.
+    Index,
+    /// Date of the latest message, formatted with the strftime(3) argument if there is one.
+    Date,
+    From,
+    To,
+    /// Recipients in sent and drafts mailboxes, senders everywhere else.
+    Correspondent,
+    Subject,
+    /// Size of the message.
+    Size,
+    /// Number of attachments.
+    Attachments,
+    Tags,
+    /// Number of messages in the thread.
+    ThreadCount,
+    /// `List-Id` header value.
+    ListId,
+    /// Name of the mailbox.
+    Mailbox,
+    /// Attachment and snooze flags.
+    Flags,
+}
+
+impl IndexField {
+    pub const ALL: [IndexField; 13] = [
+        IndexField::Index,
+        IndexField::Date,
+        IndexField::From,
+        IndexField::To,
+        IndexField::Correspondent,
+        IndexField::Subject,
+        IndexField::Size,
+        IndexField::Attachments,
+        IndexField::Tags,
+        IndexField::ThreadCount,
+        IndexField::ListId,
+        IndexField::Mailbox,
+        IndexField::Flags,
+    ];
+
+    /// Theme key of the field's colors.
+    pub fn theme_key(self) -> &'static str {
+        match self {
+            IndexField::Index => "mail.listing.field.index",
+            IndexField::Date => "mail.listing.field.date",
+            IndexField::From => "mail.listing.field.from",
+            IndexField::To => "mail.listing.field.to",
+            IndexField::Correspondent => "mail.listing.field.correspondent",
+            IndexField::Subject => "mail.listing.field.subject",
+            IndexField::Size => "mail.listing.field.size",
+            IndexField::Attachments => "mail.listing.field.attachments",
+            IndexField::Tags => "mail.listing.field.tags",
+            IndexField::ThreadCount => "mail.listing.field.thread_count",
+            IndexField::ListId => "mail.listing.field.list_id",
+            IndexField::Mailbox => "mail.listing.field.mailbox",
+            IndexField::Flags => "mail.listing.field.flags",
+        }
+    }
+
+    fn default_alignment(self) -> Alignment {
+        match self {
+            IndexField::Index | IndexField::Attachments | IndexField::ThreadCount => {
+                Alignment::Right
+            }
+            _ => Alignment::Left,
+        }
+    }
+}
+
+impl FromStr for IndexField {
+    type Err = MeliError;
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "index" => IndexField::Index,
+            "date" => IndexField::Date,
+            "from" => IndexField::From,
+            "to" => IndexField::To,
+            "correspondent" => IndexField::Correspondent,
+            "subject" => IndexField::Subject,
+            "size" => IndexField::Size,
+            "attachments" => IndexField::Attachments,
+            "tags" => IndexField::Tags,
+            "thread_count" => IndexField::ThreadCount,
+            "list_id" => IndexField::ListId,
+            "mailbox" => IndexField::Mailbox,
+            "flags" => IndexField::Flags,
+            other => {
+                return Err(MeliError::new(format!(
+                    "Unknown field `{}`. Valid fields are index, date, from, to, correspondent, \
+                     subject, size, attachments, tags, thread_count, list_id, mailbox and flags.",
+                    other
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSpec {
+    pub field: IndexField,
+    pub alignment: Alignment,
+    pub min_width: usize,
+    pub max_width: Option<usize>,
+    pub argument: Option<String>,
+}
+
+impl FieldSpec {
+    fn parse(input: &str) -> Result<Self> {
+        let (input, argument) = match input.find('|') {
+            Some(pos) => (&input[..pos], Some(input[pos + 1..].to_string())),
+            None => (input, None),
+        };
+        let (name, spec) = match input.find(':') {
+            Some(pos) => (&input[..pos], &input[pos + 1..]),
+            None => (input, ""),
+        };
+        let field = IndexField::from_str(name.trim())?;
+        let mut ret = FieldSpec {
+            field,
+            alignment: field.default_alignment(),
+            min_width: 0,
+            max_width: None,
+            argument,
+        };
+        let mut spec = spec.trim();
+        if spec.starts_with('<') {
+            ret.alignment = Alignment::Left;
+        } else if spec.starts_with('>') {
+            ret.alignment = Alignment::Right;
+        } else if spec.starts_with('^') {
+            ret.alignment = Alignment::Center;
+        }
+        if spec.starts_with(|c: char| c == '<' || c == '>' || c == '^') {
+            spec = &spec[1..];
+        }
+        let (min_width, max_width) = match spec.find('.') {
+            Some(pos) => (&spec[..pos], Some(&spec[pos + 1..])),
+            None => (spec, None),
+        };
+        let invalid = || {
+            MeliError::new(format!(
+                "Invalid width `{}` for field `{}`, expected `[<>^][min_width][.max_width]`.",
+                spec, name
+            ))
+        };
+        if !min_width.is_empty() {
+            ret.min_width = usize::from_str(min_width).map_err(|_| invalid())?;
+        }
+        if let Some(max_width) = max_width {
+            ret.max_width = Some(usize::from_str(max_width).map_err(|_| invalid())?);
+        }
+        Ok(ret)
+    }
+
+    /// Truncates and pads `value` to the field's width.
+    pub fn format(&self, value: &str) -> String {
+        let mut value = value.replace(|c: char| c == '\n' || c == '\r', " ");
+        if let Some(max_width) = self.max_width {
+            if value.grapheme_width() > max_width {
+                let mut truncated = String::with_capacity(value.len());
+                let mut width = 0;
+                for g in value.split_graphemes() {
+                    let w = g.grapheme_width();
+                    if width + w + 1 > max_width {
+                        break;
+                    }
+                    truncated.push_str(g);
+                    width += w;
+                }
+                if max_width > 0 {
+                    truncated.push('…');
+                }
+                value = truncated;
+            }
+        }
+        let width = value.grapheme_width();
+        if width >= self.min_width {
+            return value;
+        }
+        let padding = self.min_width - width;
+        let (left, right) = match self.alignment {
+            Alignment::Left => (0, padding),
+            Alignment::Right => (padding, 0),
+            Alignment::Center => (padding / 2, padding - padding / 2),
+        };
+        let mut ret = String::with_capacity(value.len() + padding);
+        ret.extend(std::iter::repeat(' ').take(left));
+        ret.push_str(&value);
+        ret.extend(std::iter::repeat(' ').take(right));
+        ret
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Field(FieldSpec),
+}
+
+/// A parsed row template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexFormat {
+    source: String,
+    lines: Vec<Vec<Token>>,
+}
+
+impl IndexFormat {
+    pub fn new(source: &str) -> Result<Self> {
+        let mut lines = vec![vec![]];
+        let mut literal = String::new();
+        let mut chars = source
+            .trim_end_matches(|c| c == '\n' || c == '\r')
+            .chars()
+            .peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => {
+                                return Err(MeliError::new(format!(
+                                    "Unterminated field `{{{}` in index format.",
+                                    field
+                                )))
+                            }
+                        }
+                    }
+                    let field = FieldSpec::parse(&field)?;
+                    let line = lines.last_mut().unwrap();
+                    if !literal.is_empty() {
+                        line.push(Token::Literal(std::mem::replace(
+                            &mut literal,
+                            String::new(),
+                        )));
+                    }
+                    line.push(Token::Field(field));
+                }
+                '}' => {
+                    return Err(MeliError::new(
+                        "Unmatched `}` in index format. Literal braces are written as `}}`.",
+                    ));
+                }
+                '\n' => {
+                    if !literal.is_empty() {
+                        lines
+                            .last_mut()
+                            .unwrap()
+                            .push(Token::Literal(std::mem::replace(
+                                &mut literal,
+                                String::new(),
+                            )));
+                    }
+                    lines.push(vec![]);
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            lines.last_mut().unwrap().push(Token::Literal(literal));
+        }
+        Ok(IndexFormat {
+            source: source.to_string(),
+            lines,
+        })
+    }
+
+    /// Number of lines of the template.
+    pub fn lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Renders line `line` of the template into segments of text, along with the field each
+    /// segment came from. `value` returns the value of a field given its argument.
+    pub fn render<F>(&self, line: usize, mut value: F) -> Vec<(Option<IndexField>, String)>
+    where
+        F: FnMut(IndexField, Option<&str>) -> String,
+    {
+        let tokens = match self.lines.get(line) {
+            Some(tokens) => tokens,
+            None => return vec![],
+        };
+        tokens
+            .iter()
+            .map(|t| match t {
+                Token::Literal(s) => (None, s.clone()),
+                Token::Field(spec) => (
+                    Some(spec.field),
+                    spec.format(&value(
+                        spec.field,
+                        spec.argument.as_ref().map(String::as_str),
+                    )),
+                ),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for IndexFormat {
+    type Err = MeliError;
+    fn from_str(s: &str) -> Result<Self> {
+        IndexFormat::new(s)
+    }
+}
+
+impl fmt::Display for IndexFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for IndexFormat {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <String>::deserialize(deserializer)?;
+        IndexFormat::new(&s).map_err(|err| de::Error::custom(err.to_string()))
+    }
+}
+
+impl Serialize for IndexFormat {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl DotAddressable for IndexFormat {}
+
+#[test]
+fn test_index_format() {
+    let format =
+        IndexFormat::new("{index:>3} {date:<8.8|%Y-%m-%d} {{{from:^7}}}\n{subject:.6}").unwrap();
+    assert_eq!(format.lines(), 2);
+    let value = |field: IndexField, arg: Option<&str>| match field {
+        IndexField::Index => "7".to_string(),
+        IndexField::Date => format!("date {}", arg.unwrap_or_default()),
+        IndexField::From => "me".to_string(),
+        IndexField::Subject => "Ünïcödé subject".to_string(),
+        _ => unreachable!(),
+    };
+    let render = |line: usize| {
+        format
+            .render(line, value)
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect::<String>()
+    };
+    assert_eq!(render(0), "  7 date %Y… {  me   }");
+    assert_eq!(render(1), "Ünïcö…");
+    assert_eq!(render(2), "");
+    assert_eq!(
+        format.render(1, value),
+        vec![(Some(IndexField::Subject), "Ünïcö…".to_string())]
+    );
+    assert_eq!(
+        format.to_string(),
+        "{index:>3} {date:<8.8|%Y-%m-%d} {{{from:^7}}}\n{subject:.6}"
+    );
+
+    assert_eq!(IndexFormat::new("{subject}\n").unwrap().lines(), 1);
+    assert!(IndexFormat::new("{nonexistent}").is_err());
+    assert!(IndexFormat::new("{from").is_err());
+    assert!(IndexFormat::new("from}").is_err());
+    assert!(IndexFormat::new("{from:<x}").is_err());
+    assert_eq!(
+        IndexFormat::new("{thread_count}").unwrap().lines[0],
+        vec![Token::Field(FieldSpec {
+            field: IndexField::ThreadCount,
+            alignment: Alignment::Right,
+            min_width: 0,
+            max_width: None,
+            argument: None,
+        })]
+    );
+}
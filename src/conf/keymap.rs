@@ -0,0 +1,313 @@
+/*
+ * meli - configuration module.
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! User defined key sequences, bound to commands or to other keys.
+
+use super::DotAddressable;
+use crate::terminal::Key;
+use indexmap::IndexMap;
+use melib::{MeliError, Result};
+use serde::de::{self, IntoDeserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// What typing a key sequence does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyBinding {
+    /// A command, as accepted in COMMAND mode.
+    Command(String),
+    /// Keys processed as if they were typed. They are not looked up in keymaps again.
+    Keys { keys: Vec<Key> },
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyBinding::Command(cmd) => write!(f, "{}", cmd),
+            KeyBinding::Keys { keys } => write!(f, "{}", key_sequence_to_string(keys)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct KeymapNode {
+    binding: Option<KeyBinding>,
+    children: Vec<(Key, KeymapNode)>,
+}
+
+/// A trie of key sequences and their bindings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Keymap {
+    root: KeymapNode,
+}
+
+impl Keymap {
+    pub fn is_empty(&self) -> bool {
+        self.root.children.is_empty()
+    }
+
+    pub fn insert(&mut self, sequence: &[Key], binding: KeyBinding) {
+        let mut node = &mut self.root;
+        for key in sequence {
+            let pos = match node.children.iter().position(|(k, _)| k == key) {
+                Some(pos) => pos,
+                None => {
+                    node.children.push((key.clone(), KeymapNode::default()));
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[pos].1;
+        }
+        node.binding = Some(binding);
+    }
+
+    fn node(&self, sequence: &[Key]) -> Option<&KeymapNode> {
+        let mut node = &self.root;
+        for key in sequence {
+            node = &node.children.iter().find(|(k, _)| k == key)?.1;
+        }
+        Some(node)
+    }
+
+    /// Returns the binding of `sequence`, if any.
+    pub fn get(&self, sequence: &[Key]) -> Option<&KeyBinding> {
+        self.node(sequence).and_then(|node| node.binding.as_ref())
+    }
+
+    /// Returns whether `sequence` is the start of a longer bound sequence.
+    pub fn is_prefix(&self, sequence: &[Key]) -> bool {
+        self.node(sequence)
+            .map(|node| !node.children.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Returns the keys that complete `prefix` into a bound sequence, along with their binding.
+    pub fn completions(&self, prefix: &[Key]) -> Vec<(Vec<Key>, &KeyBinding)> {
+        fn collect<'a>(
+            node: &'a KeymapNode,
+            keys: &mut Vec<Key>,
+            ret: &mut Vec<(Vec<Key>, &'a KeyBinding)>,
+        ) {
+            for (key, child) in node.children.iter() {
+                keys.push(key.clone());
+                if let Some(ref binding) = child.binding {
+                    ret.push((keys.clone(), binding));
+                }
+                collect(child, keys, ret);
+                keys.pop();
+            }
+        }
+        let mut ret = vec![];
+        if let Some(node) = self.node(prefix) {
+            collect(node, &mut vec![], &mut ret);
+        }
+        ret
+    }
+
+    /// Returns every bound sequence along with its binding.
+    pub fn bindings(&self) -> Vec<(Vec<Key>, &KeyBinding)> {
+        self.completions(&[])
+    }
+}
+
+/// Parses a key sequence such as `g g`, `C-x C-s` or `gi`.
+///
+/// Keys are separated by whitespace. A sequence without whitespace that is not a single key name
+/// is read as one key per character.
+pub fn parse_key_sequence(input: &str) -> Result<Vec<Key>> {
+    fn parse_key(input: &str) -> Result<Key> {
+        match input {
+            "Space" | "space" => Ok(Key::Char(' ')),
+            _ => {
+                let deserializer: de::value::StrDeserializer<de::value::Error> =
+                    input.into_deserializer();
+                Key::deserialize(deserializer).map_err(|err| MeliError::new(err.to_string()))
+            }
+        }
+    }
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(MeliError::new("Key sequence is empty."));
+    }
+    if input.contains(char::is_whitespace) {
+        return input.split_whitespace().map(parse_key).collect();
+    }
+    Ok(parse_key(input)
+        .map(|key| vec![key])
+        .unwrap_or_else(|_| input.chars().map(Key::Char).collect()))
+}
+
+/// Shortcuts that read the count typed before them as an argument, such as the number of the
+/// attachment to open, instead of as a number of repetitions.
+pub const COUNT_ARGUMENT_SHORTCUTS: &[&str] = &["open_attachment", "open_mailcap", "go_to_url"];
+
+/// Repeat counts are capped so that a mistyped count can't hang the UI.
+const MAX_COUNT: usize = 9999;
+
+/// Adds digit `c` to a repeat count. Returns `None` if `c` doesn't start or continue a count.
+pub fn push_count_digit(count: Option<usize>, c: char) -> Option<usize> {
+    let digit = c.to_digit(10)? as usize;
+    if digit == 0 && count.is_none() {
+        return None;
+    }
+    Some(std::cmp::min(
+        count.unwrap_or(0).saturating_mul(10).saturating_add(digit),
+        MAX_COUNT,
+    ))
+}
+
+/// Returns the keys to process for `keys` typed after `count`, when they are not bound in any
+/// keymap: either `keys` repeated `count` times, or the count's digits followed by `keys` once
+/// if the shortcut reads the count as an argument.
+pub fn expand_count(count: usize, keys: &[Key], count_is_argument: bool) -> Vec<Key> {
+    if count_is_argument {
+        count
+            .to_string()
+            .chars()
+            .map(Key::Char)
+            .chain(keys.iter().cloned())
+            .collect()
+    } else {
+        keys.iter()
+            .cloned()
+            .cycle()
+            .take(keys.len() * count)
+            .collect()
+    }
+}
+
+pub fn key_sequence_to_string(sequence: &[Key]) -> String {
+    sequence
+        .iter()
+        .map(Key::to_string)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl<'de> Deserialize<'de> for Keymap {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bindings = <IndexMap<String, KeyBinding>>::deserialize(deserializer)?;
+        let mut ret = Keymap::default();
+        for (sequence, binding) in bindings {
+            let keys = parse_key_sequence(&sequence).map_err(|err| {
+                de::Error::custom(format!("Invalid key sequence `{}`: {}", sequence, err))
+            })?;
+            ret.insert(&keys, binding);
+        }
+        Ok(ret)
+    }
+}
+
+impl Serialize for Keymap {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.bindings()
+            .into_iter()
+            .map(|(keys, binding)| (key_sequence_to_string(&keys), binding))
+            .collect::<IndexMap<String, &KeyBinding>>()
+            .serialize(serializer)
+    }
+}
+
+impl DotAddressable for Keymap {}
+
+#[test]
+fn test_keymap() {
+    #[derive(Debug, Deserialize)]
+    struct V {
+        keymap: Keymap,
+    }
+    let V { keymap } = toml::from_str(
+        r#"
+[keymap]
+"g g" = { keys = ["Home"] }
+gi = "go-to-mailbox personal INBOX"
+"C-x C-s" = "toggle thread_snooze"
+d = "delete"
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        keymap.get(&[Key::Char('g'), Key::Char('g')]),
+        Some(&KeyBinding::Keys {
+            keys: vec![Key::Home]
+        })
+    );
+    assert!(keymap.is_prefix(&[Key::Char('g')]));
+    assert!(keymap.get(&[Key::Char('g')]).is_none());
+    assert!(!keymap.is_prefix(&[Key::Char('d')]));
+    assert_eq!(
+        keymap.get(&[Key::Char('d')]),
+        Some(&KeyBinding::Command("delete".to_string()))
+    );
+    assert!(keymap.get(&[Key::Ctrl('x'), Key::Ctrl('s')]).is_some());
+    assert_eq!(
+        keymap
+            .completions(&[Key::Char('g')])
+            .into_iter()
+            .map(|(keys, binding)| format!("{} {}", key_sequence_to_string(&keys), binding))
+            .collect::<Vec<String>>(),
+        vec![
+            "g Home".to_string(),
+            "i go-to-mailbox personal INBOX".to_string()
+        ]
+    );
+    assert_eq!(keymap.bindings().len(), 4);
+    assert_eq!(
+        parse_key_sequence("F5 Space").unwrap(),
+        vec![Key::F(5), Key::Char(' ')]
+    );
+    assert!(parse_key_sequence(" ").is_err());
+    assert!(toml::from_str::<V>("[keymap]\n\"g F13\" = \"delete\"").is_err());
+}
+
+#[test]
+fn test_keymap_count() {
+    let count = "5".chars().fold(None, push_count_digit);
+    assert_eq!(count, Some(5));
+    assert_eq!(
+        expand_count(count.unwrap(), &[Key::Char('j')], false),
+        vec![Key::Char('j'); 5]
+    );
+    assert_eq!(
+        expand_count(12, &[Key::Char('a')], true),
+        vec![Key::Char('1'), Key::Char('2'), Key::Char('a')]
+    );
+    assert_eq!(
+        expand_count(2, &[Key::Char('g'), Key::Char('j')], false),
+        vec![
+            Key::Char('g'),
+            Key::Char('j'),
+            Key::Char('g'),
+            Key::Char('j')
+        ]
+    );
+    assert_eq!(push_count_digit(None, '0'), None);
+    assert_eq!(push_count_digit(Some(1), '0'), Some(10));
+    assert_eq!(push_count_digit(None, 'j'), None);
+    assert_eq!(push_count_digit(Some(9999), '9'), Some(9999));
+}
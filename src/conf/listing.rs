@@ -19,7 +19,9 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use super::{default_vals::*, DotAddressable, IndexStyle};
+use super::{
+    default_vals::*, index_format::IndexFormat, DotAddressable, IndexStyle, ListingLayout,
+};
 use melib::search::Query;
 use melib::{MeliError, Result};
 
@@ -80,6 +82,12 @@ pub struct ListingSettings {
     #[serde(default, alias = "index-style")]
     pub index_style: IndexStyle,
 
+    /// Template of listing rows, see meli.conf(5) for its fields. Conversations use the first
+    /// line for their first row and the second line, if any, for their second row.
+    /// Default: None
+    #[serde(default = "none", alias = "index-format")]
+    pub index_format: Option<IndexFormat>,
+
     ///Default: " "
     #[serde(default = "none")]
     pub sidebar_mailbox_tree_has_sibling: Option<String>,
@@ -99,12 +107,28 @@ pub struct ListingSettings {
     ///Default: ' '
     #[serde(default = "default_divider")]
     pub sidebar_divider: char,
+
+    /// Where to preview the message under the cursor: `default` doesn't, `three_columns` shows
+    /// it right of the list and `stacked` below it.
+    /// Default: "default"
+    #[serde(default)]
+    pub layout: ListingLayout,
+
+    /// Width or height of the preview of the `three_columns` and `stacked` layouts, as a
+    /// percentage of the area right of the sidebar.
+    /// Default: 50
+    #[serde(default = "default_preview_ratio", alias = "preview-ratio")]
+    pub preview_ratio: usize,
 }
 
 const fn default_divider() -> char {
     ' '
 }
 
+const fn default_preview_ratio() -> usize {
+    50
+}
+
 impl Default for ListingSettings {
     fn default() -> Self {
         Self {
@@ -114,11 +138,14 @@ impl Default for ListingSettings {
             recent_dates: true,
             filter: None,
             index_style: IndexStyle::default(),
+            index_format: None,
             sidebar_mailbox_tree_has_sibling: None,
             sidebar_mailbox_tree_no_sibling: None,
             sidebar_mailbox_tree_has_sibling_leaf: None,
             sidebar_mailbox_tree_no_sibling_leaf: None,
             sidebar_divider: default_divider(),
+            layout: ListingLayout::default(),
+            preview_ratio: default_preview_ratio(),
         }
     }
 }
@@ -135,6 +162,7 @@ impl DotAddressable for ListingSettings {
                     "recent_dates" => self.recent_dates.lookup(field, tail),
                     "filter" => self.filter.lookup(field, tail),
                     "index_style" => self.index_style.lookup(field, tail),
+                    "index_format" => self.index_format.lookup(field, tail),
                     "sidebar_mailbox_tree_has_sibling" => {
                         self.sidebar_mailbox_tree_has_sibling.lookup(field, tail)
                     }
@@ -148,6 +176,8 @@ impl DotAddressable for ListingSettings {
                         .sidebar_mailbox_tree_no_sibling_leaf
                         .lookup(field, tail),
                     "sidebar_divider" => self.sidebar_divider.lookup(field, tail),
+                    "layout" => self.layout.lookup(field, tail),
+                    "preview_ratio" => self.preview_ratio.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
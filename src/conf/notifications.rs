@@ -20,8 +20,12 @@
  */
 
 use super::default_vals::{internal_value_false, none, true_val};
+use super::filters::{check_envelope_query, envelope_matches};
 use super::DotAddressable;
-use melib::{MeliError, Result, ToggleFlag};
+use melib::search::Query;
+use melib::{Envelope, MeliError, Result, ToggleFlag};
+
+pub const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
 
 /// Settings for the notifications function.
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -44,6 +48,20 @@ pub struct NotificationsSettings {
     pub play_sound: ToggleFlag,
     #[serde(default = "none", alias = "sound-file")]
     pub sound_file: Option<String>,
+    /// Rules for new mail notifications. The first rule that matches a new message decides if
+    /// and how it's notified about.
+    /// Default: []
+    #[serde(default)]
+    pub rules: Vec<NotificationRule>,
+    /// Times when new mail isn't notified about, except for rules with `urgency = "critical"`.
+    /// Default: []
+    #[serde(default, alias = "do-not-disturb")]
+    pub do_not_disturb: Vec<TimeWindow>,
+    /// Seconds to wait for more new mail before notifying, so that it's notified about
+    /// together. With 0, each message is notified about as soon as it arrives.
+    /// Default: 0
+    #[serde(default, alias = "batch-delay")]
+    pub batch_delay: u64,
 }
 
 impl Default for NotificationsSettings {
@@ -54,9 +72,177 @@ impl Default for NotificationsSettings {
             xbiff_file_path: None,
             play_sound: ToggleFlag::InternalVal(false),
             sound_file: None,
+            rules: vec![],
+            do_not_disturb: vec![],
+            batch_delay: 0,
+        }
+    }
+}
+
+impl NotificationsSettings {
+    pub fn validate(&self) -> Result<()> {
+        validate(&self.rules, &self.do_not_disturb)
+    }
+}
+
+/// Check notification rules and `do_not_disturb` times, of the global settings or of an account.
+pub fn validate(rules: &[NotificationRule], do_not_disturb: &[TimeWindow]) -> Result<()> {
+    for rule in rules {
+        if let Some(ref query) = rule.query {
+            check_envelope_query(query).map_err(|err| {
+                MeliError::new(format!(
+                    "notifications.rules: invalid query `{:?}`: {}",
+                    query, err
+                ))
+            })?;
+        }
+    }
+    for window in do_not_disturb {
+        window.validate()?;
+    }
+    Ok(())
+}
+
+/// A new mail notification rule, e.g.
+///
+/// ```toml
+/// [[notifications.rules]]
+/// query = "from:manager@example.com"
+/// urgency = "critical"
+///
+/// [[notifications.rules]]
+/// mailboxes = ["INBOX/lists/noisy"]
+/// enable = false
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationRule {
+    /// Condition a new message must match. Without one, every message in `mailboxes` matches.
+    #[serde(default = "none")]
+    pub query: Option<Query>,
+    /// Paths of the mailboxes the rule applies to. If empty, all mailboxes.
+    #[serde(default)]
+    pub mailboxes: Vec<String>,
+    /// Set to false to not notify about matching messages.
+    #[serde(default = "true_val")]
+    pub enable: bool,
+    /// Overrides `script`.
+    #[serde(default = "none")]
+    pub script: Option<String>,
+    /// Overrides `play_sound`.
+    #[serde(default = "none", alias = "play-sound")]
+    pub play_sound: Option<bool>,
+    /// Overrides `sound_file`.
+    #[serde(default = "none", alias = "sound-file")]
+    pub sound_file: Option<String>,
+    #[serde(default)]
+    pub urgency: Urgency,
+}
+
+impl NotificationRule {
+    pub fn is_match(&self, envelope: &Envelope, mailbox_path: &str) -> bool {
+        (self.mailboxes.is_empty() || self.mailboxes.iter().any(|m| m == mailbox_path))
+            && self
+                .query
+                .as_ref()
+                .map(|q| envelope_matches(envelope, q))
+                .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Urgency {
+    Low,
+    Normal,
+    /// Notified about even during `do_not_disturb` times.
+    Critical,
+}
+
+impl Default for Urgency {
+    fn default() -> Self {
+        Urgency::Normal
+    }
+}
+
+impl std::fmt::Display for Urgency {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Urgency::Low => write!(f, "low"),
+            Urgency::Normal => write!(f, "normal"),
+            Urgency::Critical => write!(f, "critical"),
         }
     }
 }
+
+/// A daily time window such as `{ start = "22:00", end = "07:30" }`, optionally only on some
+/// days of the week: `days = ["sat", "sun"]`. A window that ends before it starts goes past
+/// midnight.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeWindow {
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub days: Vec<String>,
+}
+
+impl TimeWindow {
+    fn minutes(time: &str) -> Result<u32> {
+        let mut parts = time.trim().splitn(2, ':');
+        match (
+            parts.next().and_then(|h| h.parse::<u32>().ok()),
+            parts.next().and_then(|m| m.parse::<u32>().ok()),
+        ) {
+            (Some(h), Some(m)) if h < 24 && m < 60 => Ok(h * 60 + m),
+            _ => Err(MeliError::new(format!(
+                "notifications.do_not_disturb: invalid time `{}`, expected HH:MM.",
+                time
+            ))),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        Self::minutes(&self.start)?;
+        Self::minutes(&self.end)?;
+        for day in &self.days {
+            if !WEEKDAYS.contains(&day.to_lowercase().as_str()) {
+                return Err(MeliError::new(format!(
+                    "notifications.do_not_disturb: invalid day `{}`, expected mon, tue, etc.",
+                    day
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `weekday`, eg. `mon`, at `minutes` past midnight is within the window. The part
+    /// of a window that goes past midnight belongs to the day it started on. Times are checked
+    /// with `validate` when the configuration is loaded.
+    pub fn contains(&self, weekday: &str, minutes: u32) -> bool {
+        let (start, end) = match (Self::minutes(&self.start), Self::minutes(&self.end)) {
+            (Ok(start), Ok(end)) => (start, end),
+            _ => return false,
+        };
+        let start_day = if start <= end {
+            if minutes < start || minutes >= end {
+                return false;
+            }
+            weekday
+        } else if start <= minutes {
+            weekday
+        } else if minutes < end {
+            WEEKDAYS
+                .iter()
+                .position(|d| d.eq_ignore_ascii_case(weekday))
+                .map(|i| WEEKDAYS[(i + 6) % 7])
+                .unwrap_or(weekday)
+        } else {
+            return false;
+        };
+        self.days.is_empty() || self.days.iter().any(|d| d.eq_ignore_ascii_case(start_day))
+    }
+}
 impl DotAddressable for NotificationsSettings {
     fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
         match path.first() {
@@ -68,6 +254,9 @@ impl DotAddressable for NotificationsSettings {
                     "xbiff_file_path" => self.xbiff_file_path.lookup(field, tail),
                     "play_sound" => self.play_sound.lookup(field, tail),
                     "sound_file" => self.sound_file.lookup(field, tail),
+                    "rules" => self.rules.lookup(field, tail),
+                    "do_not_disturb" => self.do_not_disturb.lookup(field, tail),
+                    "batch_delay" => self.batch_delay.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -78,3 +267,80 @@ impl DotAddressable for NotificationsSettings {
         }
     }
 }
+
+impl DotAddressable for NotificationRule {}
+impl DotAddressable for TimeWindow {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_window() {
+        let night = TimeWindow {
+            start: "22:00".to_string(),
+            end: "7:30".to_string(),
+            days: vec![],
+        };
+        assert!(night.validate().is_ok());
+        assert!(night.contains("mon", 23 * 60));
+        assert!(night.contains("mon", 60));
+        assert!(!night.contains("mon", 7 * 60 + 30));
+        assert!(!night.contains("mon", 12 * 60));
+
+        let weekend = TimeWindow {
+            start: "00:00".to_string(),
+            end: "23:59".to_string(),
+            days: vec!["Sat".to_string(), "sun".to_string()],
+        };
+        assert!(weekend.contains("sat", 12 * 60));
+        assert!(!weekend.contains("fri", 12 * 60));
+
+        let friday_night = TimeWindow {
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+            days: vec!["fri".to_string()],
+        };
+        assert!(friday_night.contains("fri", 23 * 60));
+        assert!(friday_night.contains("sat", 60));
+        assert!(!friday_night.contains("fri", 60));
+        assert!(!friday_night.contains("sat", 23 * 60));
+        assert!(!friday_night.contains("sat", 12 * 60));
+
+        for (start, days) in &[("24:00", vec![]), ("9", vec![]), ("09:00", vec!["someday"])] {
+            let window = TimeWindow {
+                start: start.to_string(),
+                end: "10:00".to_string(),
+                days: days.iter().map(|d| d.to_string()).collect(),
+            };
+            assert!(window.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_notification_rule_match() {
+        use std::convert::TryFrom;
+        /* No other headers, as with envelopes fetched from IMAP or JMAP */
+        let mut envelope = Envelope::new(0);
+        envelope.set_from(
+            melib::email::parser::address::rfc2822address_list(b"Boss <manager@example.com>")
+                .unwrap()
+                .1,
+        );
+        envelope.set_subject(b"Urgent: quarterly report".to_vec());
+        let rule = |query: &str, mailboxes: &[&str]| NotificationRule {
+            query: Some(Query::try_from(query).unwrap()),
+            mailboxes: mailboxes.iter().map(|m| m.to_string()).collect(),
+            enable: true,
+            script: None,
+            play_sound: None,
+            sound_file: None,
+            urgency: Urgency::Critical,
+        };
+        assert!(rule("from:manager@example.com", &[]).is_match(&envelope, "INBOX"));
+        assert!(rule("subject:Urgent", &["INBOX"]).is_match(&envelope, "INBOX"));
+        assert!(!rule("subject:Urgent", &["Lists"]).is_match(&envelope, "INBOX"));
+        assert!(!rule("from:someone", &[]).is_match(&envelope, "INBOX"));
+        assert!(validate(&[rule("from:a or words", &[])], &[]).is_err());
+    }
+}
@@ -76,6 +76,29 @@ pub struct PagerSettingsOverride {
     #[serde(alias = "auto-choose-multipart-alternative")]
     #[serde(default)]
     pub auto_choose_multipart_alternative: Option<ToggleFlag>,
+    #[doc = " Verify DKIM signatures and ARC chains of viewed messages, looking up signing keys in DNS."]
+    #[doc = " Default: false"]
+    #[serde(alias = "verify-dkim")]
+    #[serde(default)]
+    pub verify_dkim: Option<bool>,
+    #[doc = " A file with the DKIM key records to use instead of DNS, one"]
+    #[doc = " `selector._domainkey.domain record` per line."]
+    #[doc = " Default: None"]
+    #[serde(deserialize_with = "non_empty_string", alias = "dkim-key-file")]
+    #[serde(default)]
+    pub dkim_key_file: Option<Option<String>>,
+    #[doc = " The authserv-id your receiving server writes in the `Authentication-Results` headers it"]
+    #[doc = " adds. The SPF result is only shown from headers with this id, since senders can add"]
+    #[doc = " their own."]
+    #[doc = " Default: None"]
+    #[serde(deserialize_with = "non_empty_string", alias = "authserv-id")]
+    #[serde(default)]
+    pub authserv_id: Option<Option<String>>,
+    #[doc = " How `search` matches text: `case_sensitive`, `case_insensitive` or `regex`."]
+    #[doc = " Default: case_sensitive"]
+    #[serde(alias = "search-mode")]
+    #[serde(default)]
+    pub search_mode: Option<PagerSearchMode>,
 }
 impl Default for PagerSettingsOverride {
     fn default() -> Self {
@@ -90,6 +113,10 @@ impl Default for PagerSettingsOverride {
             split_long_lines: None,
             minimum_width: None,
             auto_choose_multipart_alternative: None,
+            verify_dkim: None,
+            dkim_key_file: None,
+            authserv_id: None,
+            search_mode: None,
         }
     }
 }
@@ -123,6 +150,12 @@ pub struct ListingSettingsOverride {
     #[serde(alias = "index-style")]
     #[serde(default)]
     pub index_style: Option<IndexStyle>,
+    #[doc = " Template of listing rows, see meli.conf(5) for its fields. Conversations use the first"]
+    #[doc = " line for their first row and the second line, if any, for their second row."]
+    #[doc = " Default: None"]
+    #[serde(alias = "index-format")]
+    #[serde(default)]
+    pub index_format: Option<Option<IndexFormat>>,
     #[doc = "Default: \" \""]
     #[serde(default)]
     pub sidebar_mailbox_tree_has_sibling: Option<Option<String>>,
@@ -138,6 +171,17 @@ pub struct ListingSettingsOverride {
     #[doc = "Default: ' '"]
     #[serde(default)]
     pub sidebar_divider: Option<char>,
+    #[doc = " Where to preview the message under the cursor: `default` doesn't, `three_columns` shows"]
+    #[doc = " it right of the list and `stacked` below it."]
+    #[doc = " Default: \"default\""]
+    #[serde(default)]
+    pub layout: Option<ListingLayout>,
+    #[doc = " Width or height of the preview of the `three_columns` and `stacked` layouts, as a"]
+    #[doc = " percentage of the area right of the sidebar."]
+    #[doc = " Default: 50"]
+    #[serde(alias = "preview-ratio")]
+    #[serde(default)]
+    pub preview_ratio: Option<usize>,
 }
 impl Default for ListingSettingsOverride {
     fn default() -> Self {
@@ -148,11 +192,14 @@ impl Default for ListingSettingsOverride {
             recent_dates: None,
             filter: None,
             index_style: None,
+            index_format: None,
             sidebar_mailbox_tree_has_sibling: None,
             sidebar_mailbox_tree_no_sibling: None,
             sidebar_mailbox_tree_has_sibling_leaf: None,
             sidebar_mailbox_tree_no_sibling_leaf: None,
             sidebar_divider: None,
+            layout: None,
+            preview_ratio: None,
         }
     }
 }
@@ -180,6 +227,22 @@ pub struct NotificationsSettingsOverride {
     #[serde(alias = "sound-file")]
     #[serde(default)]
     pub sound_file: Option<Option<String>>,
+    #[doc = " Rules for new mail notifications. The first rule that matches a new message decides if"]
+    #[doc = " and how it's notified about."]
+    #[doc = " Default: []"]
+    #[serde(default)]
+    pub rules: Option<Vec<NotificationRule>>,
+    #[doc = " Times when new mail isn't notified about, except for rules with `urgency = \"critical\"`."]
+    #[doc = " Default: []"]
+    #[serde(alias = "do-not-disturb")]
+    #[serde(default)]
+    pub do_not_disturb: Option<Vec<TimeWindow>>,
+    #[doc = " Seconds to wait for more new mail before notifying, so that it's notified about"]
+    #[doc = " together. With 0, each message is notified about as soon as it arrives."]
+    #[doc = " Default: 0"]
+    #[serde(alias = "batch-delay")]
+    #[serde(default)]
+    pub batch_delay: Option<u64>,
 }
 impl Default for NotificationsSettingsOverride {
     fn default() -> Self {
@@ -189,6 +252,9 @@ impl Default for NotificationsSettingsOverride {
             xbiff_file_path: None,
             play_sound: None,
             sound_file: None,
+            rules: None,
+            do_not_disturb: None,
+            batch_delay: None,
         }
     }
 }
@@ -216,6 +282,11 @@ pub struct ShortcutsOverride {
     pub thread_view: Option<ThreadViewShortcuts>,
     #[serde(default)]
     pub pager: Option<PagerShortcuts>,
+    #[serde(default)]
+    pub sieve: Option<SieveShortcuts>,
+    #[serde(alias = "sequence-timeout")]
+    #[serde(default)]
+    pub sequence_timeout: Option<u64>,
 }
 impl Default for ShortcutsOverride {
     fn default() -> Self {
@@ -228,6 +299,8 @@ impl Default for ShortcutsOverride {
             envelope_view: None,
             thread_view: None,
             pager: None,
+            sieve: None,
+            sequence_timeout: None,
         }
     }
 }
@@ -346,6 +419,21 @@ pub struct PGPSettingsOverride {
     #[serde(alias = "remote-lookup-mechanisms")]
     #[serde(default)]
     pub remote_lookup_mechanisms: Option<melib::gpgme::LocateKey>,
+    #[doc = " S/MIME certificate to sign sent e-mail with, instead of OpenPGP."]
+    #[doc = " Default: None"]
+    #[serde(alias = "smime-sign-key")]
+    #[serde(default)]
+    pub smime_sign_key: Option<Option<String>>,
+    #[doc = " Enable Autocrypt: add an `Autocrypt` header with the account's key to sent e-mail, and"]
+    #[doc = " keep track of peers' keys from incoming `Autocrypt` headers."]
+    #[doc = " Default: false"]
+    #[serde(default)]
+    pub autocrypt: Option<bool>,
+    #[doc = " Autocrypt `prefer-encrypt=mutual`: ask peers to encrypt replies."]
+    #[doc = " Default: false"]
+    #[serde(alias = "autocrypt-prefer-encrypt")]
+    #[serde(default)]
+    pub autocrypt_prefer_encrypt: Option<bool>,
 }
 #[cfg(feature = "gpgme")]
 impl Default for PGPSettingsOverride {
@@ -360,6 +448,9 @@ impl Default for PGPSettingsOverride {
             encrypt_key: None,
             allow_remote_lookup: None,
             remote_lookup_mechanisms: None,
+            smime_sign_key: None,
+            autocrypt: None,
+            autocrypt_prefer_encrypt: None,
         }
     }
 }
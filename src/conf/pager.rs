@@ -23,7 +23,7 @@
 
 use super::default_vals::*;
 use super::deserializers::*;
-use super::DotAddressable;
+use super::{DotAddressable, PagerSearchMode};
 use melib::{MeliError, Result, ToggleFlag};
 
 /// Settings for the pager function.
@@ -87,6 +87,37 @@ pub struct PagerSettings {
         alias = "auto-choose-multipart-alternative"
     )]
     pub auto_choose_multipart_alternative: ToggleFlag,
+
+    /// Verify DKIM signatures and ARC chains of viewed messages, looking up signing keys in DNS.
+    /// Default: false
+    #[serde(default = "false_val", alias = "verify-dkim")]
+    pub verify_dkim: bool,
+
+    /// A file with the DKIM key records to use instead of DNS, one
+    /// `selector._domainkey.domain record` per line.
+    /// Default: None
+    #[serde(
+        default = "none",
+        deserialize_with = "non_empty_string",
+        alias = "dkim-key-file"
+    )]
+    pub dkim_key_file: Option<String>,
+
+    /// The authserv-id your receiving server writes in the `Authentication-Results` headers it
+    /// adds. The SPF result is only shown from headers with this id, since senders can add
+    /// their own.
+    /// Default: None
+    #[serde(
+        default = "none",
+        deserialize_with = "non_empty_string",
+        alias = "authserv-id"
+    )]
+    pub authserv_id: Option<String>,
+
+    /// How `search` matches text: `case_sensitive`, `case_insensitive` or `regex`.
+    /// Default: case_sensitive
+    #[serde(default, alias = "search-mode")]
+    pub search_mode: PagerSearchMode,
 }
 
 impl Default for PagerSettings {
@@ -102,6 +133,10 @@ impl Default for PagerSettings {
             split_long_lines: true,
             minimum_width: 80,
             auto_choose_multipart_alternative: ToggleFlag::InternalVal(true),
+            verify_dkim: false,
+            dkim_key_file: None,
+            authserv_id: None,
+            search_mode: PagerSearchMode::default(),
         }
     }
 }
@@ -124,6 +159,10 @@ impl DotAddressable for PagerSettings {
                     "auto_choose_multipart_alternative" => {
                         self.auto_choose_multipart_alternative.lookup(field, tail)
                     }
+                    "verify_dkim" => self.verify_dkim.lookup(field, tail),
+                    "dkim_key_file" => self.dkim_key_file.lookup(field, tail),
+                    "authserv_id" => self.authserv_id.lookup(field, tail),
+                    "search_mode" => self.search_mode.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -74,6 +74,22 @@ pub struct PGPSettings {
         alias = "remote-lookup-mechanisms"
     )]
     pub remote_lookup_mechanisms: melib::gpgme::LocateKey,
+
+    /// S/MIME certificate to sign sent e-mail with, instead of OpenPGP.
+    /// Default: None
+    #[serde(default = "none", alias = "smime-sign-key")]
+    pub smime_sign_key: Option<String>,
+
+    /// Enable Autocrypt: add an `Autocrypt` header with the account's key to sent e-mail, and
+    /// keep track of peers' keys from incoming `Autocrypt` headers.
+    /// Default: false
+    #[serde(default = "false_val")]
+    pub autocrypt: bool,
+
+    /// Autocrypt `prefer-encrypt=mutual`: ask peers to encrypt replies.
+    /// Default: false
+    #[serde(default = "false_val", alias = "autocrypt-prefer-encrypt")]
+    pub autocrypt_prefer_encrypt: bool,
 }
 
 #[cfg(feature = "gpgme")]
@@ -94,6 +110,9 @@ impl Default for PGPSettings {
             encrypt_key: None,
             allow_remote_lookup: internal_value_false::<ToggleFlag>(),
             remote_lookup_mechanisms: default_lookup_mechanism(),
+            smime_sign_key: None,
+            autocrypt: false,
+            autocrypt_prefer_encrypt: false,
         }
     }
 }
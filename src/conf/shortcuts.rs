@@ -19,6 +19,7 @@
  * along with meli. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use super::keymap::Keymap;
 use super::DotAddressable;
 use crate::terminal::Key;
 use indexmap::IndexMap;
@@ -53,6 +54,16 @@ pub struct Shortcuts {
     pub thread_view: ThreadViewShortcuts,
     #[serde(default)]
     pub pager: PagerShortcuts,
+    #[serde(default)]
+    pub sieve: SieveShortcuts,
+    /// Milliseconds to wait for the next key of a key sequence. 0 waits indefinitely.
+    /// Default: 1000
+    #[serde(default = "sequence_timeout_default", alias = "sequence-timeout")]
+    pub sequence_timeout: u64,
+}
+
+fn sequence_timeout_default() -> u64 {
+    1000
 }
 
 impl Default for Shortcuts {
@@ -66,10 +77,32 @@ impl Default for Shortcuts {
             envelope_view: EnvelopeViewShortcuts::default(),
             thread_view: ThreadViewShortcuts::default(),
             pager: PagerShortcuts::default(),
+            sieve: SieveShortcuts::default(),
+            sequence_timeout: sequence_timeout_default(),
         }
     }
 }
 
+impl Shortcuts {
+    /// Returns the keymap of a `ShortcutMaps` section, as named by `Component::get_shortcuts`.
+    pub fn keymap(&self, section: &str) -> Option<&Keymap> {
+        Some(match section {
+            "general" => &self.general.keymap,
+            "listing" => &self.listing.keymap,
+            "composing" => &self.composing.keymap,
+            "compact listing" | "conversations listing" | "plain listing" => {
+                &self.compact_listing.keymap
+            }
+            "contact list" => &self.contact_list.keymap,
+            "view mail" => &self.envelope_view.keymap,
+            "thread view" => &self.thread_view.keymap,
+            "pager" => &self.pager.keymap,
+            "sieve scripts" => &self.sieve.keymap,
+            _ => return None,
+        })
+    }
+}
+
 impl DotAddressable for Shortcuts {
     fn lookup(&self, parent_field: &str, path: &[&str]) -> Result<String> {
         match path.first() {
@@ -86,6 +119,10 @@ impl DotAddressable for Shortcuts {
                     "envelope_view" | "envelope-view" => self.envelope_view.lookup(field, tail),
                     "thread_view" | "thread-view" => self.thread_view.lookup(field, tail),
                     "pager" => self.pager.lookup(field, tail),
+                    "sieve" => self.sieve.lookup(field, tail),
+                    "sequence_timeout" | "sequence-timeout" => {
+                        self.sequence_timeout.lookup(field, tail)
+                    }
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -109,7 +146,10 @@ macro_rules! shortcut_key_values {
         #[serde(default)]
         #[serde(rename = $cname)]
         pub struct $name {
-            $(pub $fname : Key),*
+            $(pub $fname : Key,)*
+            /// Key sequences bound to commands or keys, see `Keymap`.
+            #[serde(skip_serializing_if = "Keymap::is_empty")]
+            pub keymap: Keymap
         }
 
         impl $name {
@@ -131,7 +171,8 @@ macro_rules! shortcut_key_values {
         impl Default for $name {
             fn default() -> Self {
                 Self {
-                    $($fname: $default),*
+                    $($fname: $default,)*
+                    keymap: Keymap::default()
                 }
             }
         }
@@ -143,6 +184,7 @@ macro_rules! shortcut_key_values {
                         let tail = &path[1..];
                         match *field {
                             $(stringify!($fname) => self.$fname.lookup(field, tail),)*
+                            "keymap" => self.keymap.lookup(field, tail),
                             other => Err(MeliError::new(format!(
                                         "{} has no field named {}",
                                         parent_field, other
@@ -184,7 +226,10 @@ shortcut_key_values! { "listing",
         diff_modifier |> "Difference modifier." |> Key::Ctrl('d'),
         intersection_modifier |> "Intersection modifier." |> Key::Ctrl('i'),
         select_entry |> "Select thread entry." |> Key::Char('v'),
-        toggle_menu_visibility |> "Toggle visibility of side menu in mail list." |> Key::Char('`')
+        toggle_menu_visibility |> "Toggle visibility of side menu in mail list." |> Key::Char('`'),
+        undo |> "Undo the last action on messages." |> Key::Char('z'),
+        redo |> "Redo the last undone action on messages." |> Key::Char('Z'),
+        archive |> "Move thread to the Archive mailbox." |> Key::Char('A')
     }
 }
 
@@ -212,6 +257,21 @@ shortcut_key_values! { "pager",
     }
 }
 
+shortcut_key_values! { "sieve",
+    /// Shortcut listing for the sieve script manager
+    pub struct SieveShortcuts {
+        scroll_up |> "Scroll up list." |> Key::Up,
+        scroll_down |> "Scroll down list." |> Key::Down,
+        new_script |> "Create new script." |> Key::Char('n'),
+        edit_script |> "Edit script under cursor." |> Key::Char('e'),
+        check_script |> "Validate script under cursor." |> Key::Char('c'),
+        toggle_active |> "Activate or deactivate script under cursor." |> Key::Char('a'),
+        delete_script |> "Delete script under cursor." |> Key::Char('d'),
+        vacation |> "Set up a vacation auto-reply." |> Key::Char('v'),
+        refresh |> "Reload list of scripts." |> Key::F(5)
+    }
+}
+
 shortcut_key_values! { "general",
     pub struct GeneralShortcuts {
         toggle_help |> "Toggle help and shortcuts view." |> Key::Char('?'),
@@ -219,6 +279,9 @@ shortcut_key_values! { "general",
         quit |> "Quit meli." |> Key::Char('q'),
         go_to_tab |> "Go to the nth tab" |> Key::Alt('n'),
         next_tab |> "Next tab." |> Key::Char('T'),
+        next_pane |> "Focus the next pane of a split tab." |> Key::Ctrl('w'),
+        grow_pane |> "Grow the focused pane or message preview." |> Key::Alt('+'),
+        shrink_pane |> "Shrink the focused pane or message preview." |> Key::Alt('-'),
         scroll_right |> "Generic scroll right (catch-all setting)" |> Key::Right,
         scroll_left |> "Generic scroll left (catch-all setting)" |> Key::Left,
         scroll_up |> "Generic scroll up (catch-all setting)" |> Key::Up,
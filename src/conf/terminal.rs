@@ -24,6 +24,7 @@
 use super::deserializers::non_empty_string;
 use super::DotAddressable;
 use super::Themes;
+use crate::terminal::ImageProtocol;
 use melib::{MeliError, Result, ToggleFlag};
 
 /// Settings for terminal display
@@ -52,6 +53,9 @@ pub struct TerminalSettings {
     /// Default: 0
     #[serde(default)]
     pub progress_spinner_sequence: Option<ProgressSpinnerSequence>,
+    /// Graphics protocol for displaying images inline: auto, kitty, sixel, half_blocks or none.
+    /// Default: auto
+    pub image_protocol: ImageProtocol,
 }
 
 impl Default for TerminalSettings {
@@ -66,6 +70,7 @@ impl Default for TerminalSettings {
             window_title: Some("meli".to_string()),
             file_picker_command: None,
             progress_spinner_sequence: None,
+            image_protocol: ImageProtocol::Auto,
         }
     }
 }
@@ -99,6 +104,7 @@ impl DotAddressable for TerminalSettings {
                     "progress_spinner_sequence" => {
                         self.progress_spinner_sequence.lookup(field, tail)
                     }
+                    "image_protocol" => self.image_protocol.lookup(field, tail),
                     other => Err(MeliError::new(format!(
                         "{} has no field named {}",
                         parent_field, other
@@ -118,3 +124,5 @@ pub enum ProgressSpinnerSequence {
 }
 
 impl DotAddressable for ProgressSpinnerSequence {}
+
+impl DotAddressable for ImageProtocol {}
@@ -304,6 +304,19 @@ const DEFAULT_KEYS: &[&str] = &[
     "mail.listing.attachment_flag",
     "mail.listing.thread_snooze_flag",
     "mail.listing.tag_default",
+    "mail.listing.field.index",
+    "mail.listing.field.date",
+    "mail.listing.field.from",
+    "mail.listing.field.to",
+    "mail.listing.field.correspondent",
+    "mail.listing.field.subject",
+    "mail.listing.field.size",
+    "mail.listing.field.attachments",
+    "mail.listing.field.tags",
+    "mail.listing.field.thread_count",
+    "mail.listing.field.list_id",
+    "mail.listing.field.mailbox",
+    "mail.listing.field.flags",
     "pager.highlight_search",
     "pager.highlight_search_current",
 ];
@@ -502,10 +515,10 @@ pub struct Theme {
     pub keys: IndexMap<Cow<'static, str>, ThemeAttributeInner>,
 }
 
-#[cfg(feature = "regexp")]
-pub use regexp::text_format_regexps;
 #[cfg(feature = "regexp")]
 use regexp::*;
+#[cfg(feature = "regexp")]
+pub use regexp::{text_format_regexps, RegexpWrapper};
 
 #[cfg(feature = "regexp")]
 mod regexp {
@@ -560,7 +573,7 @@ mod regexp {
     }
 
     impl RegexpWrapper {
-        pub(super) fn new(
+        pub fn new(
             pattern: &str,
             caseless: bool,
             dotall: bool,
@@ -1704,6 +1717,21 @@ impl Default for Themes {
             }
         );
 
+        /* Fields of `listing.index_format`. `Default` colors and attributes are those of the row. */
+        add!("mail.listing.field.index", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.date", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.from", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.to", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.correspondent", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.subject", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.size", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.attachments", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.tags", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.thread_count", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.list_id", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.mailbox", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+        add!("mail.listing.field.flags", light = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT }, dark = { fg: Color::Default, bg: Color::Default, attrs: Attr::DEFAULT });
+
         add!("pager.highlight_search", light = { fg: Color::White, bg: Color::Byte(6) /* Teal */, attrs: Attr::BOLD }, dark = { fg: Color::White, bg: Color::Byte(6) /* Teal */, attrs: Attr::BOLD });
         add!("pager.highlight_search_current", light = { fg: Color::White, bg: Color::Byte(17) /* NavyBlue */, attrs: Attr::BOLD }, dark = { fg: Color::White, bg: Color::Byte(17) /* NavyBlue */, attrs: Attr::BOLD });
         Themes {
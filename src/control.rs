@@ -0,0 +1,447 @@
+/*
+ * meli - control socket
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Remote control of a running instance over a Unix socket.
+ *
+ * Clients write one request per line and read one line of JSON per request, either
+ * `{"result": ...}` or `{"error": "..."}`. A request is one of the queries `unread`, `status` and
+ * `selection`, or any command accepted in command mode, eg. `compose alice@example.com`.
+ * Requests longer than 64KiB are rejected.
+ */
+
+use crate::state::Context;
+use crate::types::ThreadEvent;
+use crossbeam::channel::{bounded, Sender};
+use melib::error::{MeliError, Result};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a client waits for the UI to answer a request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest request line accepted, including its newline.
+const MAX_REQUEST_LEN: usize = 64 * 1024;
+
+/// A request read from the control socket, answered by the main loop.
+#[derive(Debug)]
+pub struct ControlRequest {
+    pub request: String,
+    reply: Sender<std::result::Result<Value, String>>,
+}
+
+/// What a request asks for: one of the queries, or else a command.
+#[derive(Debug, PartialEq)]
+pub enum Query<'a> {
+    Unread,
+    Status,
+    Selection,
+    Command(&'a str),
+}
+
+impl ControlRequest {
+    pub fn query(&self) -> Query<'_> {
+        match self.request.as_str() {
+            "unread" => Query::Unread,
+            "status" => Query::Status,
+            "selection" => Query::Selection,
+            cmd => Query::Command(cmd),
+        }
+    }
+
+    pub fn reply(&self, result: std::result::Result<Value, String>) {
+        let _ = self.reply.try_send(result);
+    }
+}
+
+/// Returns `$XDG_RUNTIME_DIR/meli/control.sock`, creating its directory with permissions for
+/// the owner only. There is no fallback if `$XDG_RUNTIME_DIR` isn't set, since anyone who can
+/// connect to the socket can run commands.
+pub fn socket_path() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("meli")
+        .map_err(|err| MeliError::new(format!("Could not find runtime directory: {}", err)))?;
+    let path = xdg_dirs
+        .place_runtime_file("control.sock")
+        .map_err(|err| MeliError::new(format!("Could not create control socket: {}", err)))?;
+    if let Some(dir) = path.parent() {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)).map_err(|err| {
+            MeliError::new(format!("Could not restrict {}: {}", dir.display(), err))
+        })?;
+    }
+    Ok(path)
+}
+
+/// Accepts connections on the control socket and forwards their requests to the main loop.
+///
+/// The socket is removed when the server is dropped.
+#[derive(Debug)]
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl ControlServer {
+    pub fn new(sender: Sender<ThreadEvent>) -> Result<Self> {
+        let path = socket_path()?;
+        if UnixStream::connect(&path).is_ok() {
+            return Err(MeliError::new(format!(
+                "Another meli instance is listening on {}",
+                path.display()
+            )));
+        }
+        /* Remove a stale socket left by an instance that crashed. */
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .map_err(|err| MeliError::new(format!("Could not bind {}: {}", path.display(), err)))?;
+        if let Err(err) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)) {
+            let _ = std::fs::remove_file(&path);
+            return Err(MeliError::new(format!(
+                "Could not restrict {}: {}",
+                path.display(),
+                err
+            )));
+        }
+        std::thread::Builder::new()
+            .name("control-socket".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            let sender = sender.clone();
+                            std::thread::spawn(move || {
+                                if let Err(err) = serve(stream, sender) {
+                                    debug!("control socket client error: {}", err);
+                                }
+                            });
+                        }
+                        Err(err) => {
+                            debug!("control socket error: {}", err);
+                        }
+                    }
+                }
+            })?;
+        Ok(ControlServer { path })
+    }
+}
+
+fn serve(stream: UnixStream, sender: Sender<ThreadEvent>) -> std::io::Result<()> {
+    let writer = stream.try_clone()?;
+    handle_requests(BufReader::new(stream), writer, &sender)
+}
+
+/// Forwards each request line of `reader` to the main loop and writes its reply to `writer`.
+///
+/// A line longer than `MAX_REQUEST_LEN` is answered with an error and closes the connection.
+fn handle_requests(
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+    sender: &Sender<ThreadEvent>,
+) -> std::io::Result<()> {
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if reader
+            .by_ref()
+            .take(MAX_REQUEST_LEN as u64 + 1)
+            .read_until(b'\n', &mut line)?
+            == 0
+        {
+            return Ok(());
+        }
+        if line.len() > MAX_REQUEST_LEN {
+            writeln!(
+                writer,
+                "{}",
+                json!({ "error": format!("requests must be at most {} bytes long", MAX_REQUEST_LEN) })
+            )?;
+            return Ok(());
+        }
+        let request = match std::str::from_utf8(&line) {
+            Ok(request) => request.trim(),
+            Err(_) => {
+                writeln!(
+                    writer,
+                    "{}",
+                    json!({ "error": "request is not valid UTF-8" })
+                )?;
+                continue;
+            }
+        };
+        if request.is_empty() {
+            continue;
+        }
+        let (reply, rx) = bounded(1);
+        let response = if sender
+            .send(ThreadEvent::Control(ControlRequest {
+                request: request.to_string(),
+                reply,
+            }))
+            .is_err()
+        {
+            json!({ "error": "meli is shutting down" })
+        } else {
+            match rx.recv_timeout(REPLY_TIMEOUT) {
+                Ok(Ok(result)) => json!({ "result": result }),
+                Ok(Err(err)) => json!({ "error": err }),
+                Err(crossbeam::channel::RecvTimeoutError::Timeout) => {
+                    json!({ "error": "timed out waiting for a reply" })
+                }
+                Err(crossbeam::channel::RecvTimeoutError::Disconnected) => {
+                    json!({ "error": format!("`{}` is not available in the current view", request) })
+                }
+            }
+        };
+        writeln!(writer, "{}", response)?;
+    }
+}
+
+/// Sends `request` to a running instance and prints the result to stdout.
+///
+/// String results are printed as they are and other values as JSON.
+pub fn client(path: Option<&Path>, request: &str) -> Result<()> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => socket_path()?,
+    };
+    let mut stream = UnixStream::connect(&path).map_err(|err| {
+        MeliError::new(format!(
+            "Could not connect to {}: {}. Is meli running?",
+            path.display(),
+            err
+        ))
+    })?;
+    writeln!(stream, "{}", request)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    if let Some(result) = parse_response(&response)? {
+        println!("{}", result);
+    }
+    Ok(())
+}
+
+/// Returns what to print for a response line: strings as they are and other results as JSON.
+fn parse_response(response: &str) -> Result<Option<String>> {
+    let response: Value = serde_json::from_str(response)
+        .map_err(|err| MeliError::new(format!("Invalid response: {}", err)))?;
+    match response {
+        Value::Object(mut map) => {
+            if let Some(err) = map.remove("error") {
+                return Err(MeliError::new(err.as_str().unwrap_or_default().to_string()));
+            }
+            Ok(match map.remove("result") {
+                Some(Value::Null) | None => None,
+                Some(Value::String(s)) => Some(s),
+                Some(result) => Some(serde_json::to_string_pretty(&result).unwrap_or_default()),
+            })
+        }
+        _ => Err(MeliError::new("Invalid response")),
+    }
+}
+
+/// Unread and total message counts of every mailbox.
+pub fn unread(context: &Context) -> Value {
+    Value::Array(
+        context
+            .accounts
+            .values()
+            .map(|account| {
+                let mut account_unseen = 0;
+                let mailboxes = account
+                    .mailboxes_order
+                    .iter()
+                    .map(|mailbox_hash| {
+                        let entry = &account[mailbox_hash];
+                        let (unseen, total) = entry.ref_mailbox.count().unwrap_or((0, 0));
+                        account_unseen += unseen;
+                        json!({
+                            "name": entry.name(),
+                            "path": entry.ref_mailbox.path(),
+                            "unseen": unseen,
+                            "total": total,
+                        })
+                    })
+                    .collect::<Vec<Value>>();
+                json!({
+                    "account": account.name(),
+                    "unseen": account_unseen,
+                    "mailboxes": mailboxes,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Connection state and number of background jobs of every account.
+pub fn status(context: &Context) -> Value {
+    Value::Array(
+        context
+            .accounts
+            .values()
+            .map(|account| {
+                json!({
+                    "account": account.name(),
+                    "online": account.is_online.is_ok(),
+                    "error": account.is_online.as_ref().err().map(|err| err.to_string()),
+                    "jobs": account.active_jobs.len(),
+                })
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossbeam::channel::unbounded;
+
+    fn request(request: &str) -> ControlRequest {
+        ControlRequest {
+            request: request.to_string(),
+            reply: bounded(1).0,
+        }
+    }
+
+    /// Runs `handle_requests` on `input` with a main loop that answers commands with an error
+    /// and queries with their name, and returns the response lines.
+    fn responses(input: &[u8]) -> Vec<Value> {
+        let (sender, receiver) = unbounded();
+        let main_loop = std::thread::spawn(move || {
+            for event in receiver.iter() {
+                if let ThreadEvent::Control(request) = event {
+                    match request.query() {
+                        Query::Command(cmd) => {
+                            let err = format!("Unknown command: {}", cmd);
+                            request.reply(Err(err))
+                        }
+                        query => request.reply(Ok(json!(format!("{:?}", query)))),
+                    }
+                }
+            }
+        });
+        let mut output = vec![];
+        handle_requests(input, &mut output, &sender).unwrap();
+        drop(sender);
+        main_loop.join().unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_control_query() {
+        assert_eq!(request("unread").query(), Query::Unread);
+        assert_eq!(request("status").query(), Query::Status);
+        assert_eq!(request("selection").query(), Query::Selection);
+        assert_eq!(
+            request("compose alice@example.com").query(),
+            Query::Command("compose alice@example.com")
+        );
+        /* Requests are plain lines, not JSON */
+        assert_eq!(
+            request(r#"{"method": "unread"}"#).query(),
+            Query::Command(r#"{"method": "unread"}"#)
+        );
+    }
+
+    #[test]
+    fn test_control_handle_requests() {
+        assert_eq!(
+            responses(b"unread\n\n  status  \nfrobnicate\n{\"method\": \"unread\"\n"),
+            vec![
+                json!({ "result": "Unread" }),
+                json!({ "result": "Status" }),
+                json!({ "error": "Unknown command: frobnicate" }),
+                json!({ "error": "Unknown command: {\"method\": \"unread\"" }),
+            ]
+        );
+        /* The last line doesn't need a newline */
+        assert_eq!(responses(b"status"), vec![json!({ "result": "Status" })]);
+        assert_eq!(
+            responses(b"\xff\xfe\nstatus\n"),
+            vec![
+                json!({ "error": "request is not valid UTF-8" }),
+                json!({ "result": "Status" }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_control_oversized_request() {
+        let mut input = vec![b'a'; MAX_REQUEST_LEN - 1];
+        input.push(b'\n');
+        input.extend_from_slice(&input.clone());
+        input.insert(0, b'a');
+        input.extend_from_slice(b"status\n");
+        let responses = responses(&input);
+        /* The first line is one byte too long: it's rejected and the connection is closed */
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("requests must be at most"));
+
+        let mut input = vec![b'a'; MAX_REQUEST_LEN - 1];
+        input.push(b'\n');
+        assert_eq!(
+            responses(&input),
+            vec![
+                json!({ "error": format!("Unknown command: {}", "a".repeat(MAX_REQUEST_LEN - 1)) })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_control_parse_response() {
+        assert_eq!(parse_response(r#"{"result": null}"#).unwrap(), None);
+        assert_eq!(
+            parse_response(r#"{"result": "3 unread"}"#).unwrap(),
+            Some("3 unread".to_string())
+        );
+        assert_eq!(
+            parse_response(r#"{"result": [1]}"#).unwrap(),
+            Some("[\n  1\n]".to_string())
+        );
+        assert_eq!(
+            parse_response(r#"{"error": "Unknown command"}"#)
+                .unwrap_err()
+                .to_string(),
+            "Unknown command"
+        );
+        assert!(parse_response(r#"{"result": "#)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid response: "));
+        assert_eq!(
+            parse_response("[]").unwrap_err().to_string(),
+            "Invalid response"
+        );
+    }
+}
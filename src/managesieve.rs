@@ -62,8 +62,13 @@ use crate::conf::*;
 #[cfg(feature = "sqlite3")]
 pub mod sqlite3;
 
+#[cfg(all(feature = "sqlite3", feature = "gpgme"))]
+pub mod autocrypt;
+
+pub mod control;
 pub mod jobs;
 pub mod mailcap;
+pub mod undo;
 pub mod plugins;
 
 use futures::executor::block_on;
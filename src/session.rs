@@ -0,0 +1,344 @@
+/*
+ * meli - session persistence
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Saving and restoring the open tabs and listing positions across restarts.
+ *
+ * The session is saved to `$XDG_DATA_HOME/meli/session.json` periodically and on exit, and read
+ * back on startup. Components describe themselves in `Component::save_session`. Compose tabs
+ * write their draft, attachments included, to `$XDG_DATA_HOME/meli/drafts/` as JSON.
+ */
+
+use crate::components::SplitDirection;
+use crate::conf::ListingLayout;
+use melib::error::{MeliError, Result, ResultIntoMeliError};
+use melib::{log, AttachmentBuilder, Draft, HeaderMap, HeaderName, WARN};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often the session is saved.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A mailbox and the cursor position of its listing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MailboxPosition {
+    pub account: String,
+    pub mailbox: String,
+    pub cursor: usize,
+}
+
+/// The preview layout of the listing tab.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ListingLayoutSession {
+    pub layout: ListingLayout,
+    pub preview_ratio: usize,
+}
+
+/// A tab other than the listing and contact list tabs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TabSession {
+    /// A message opened with `open-in-tab`.
+    Envelope {
+        account: String,
+        mailbox: String,
+        message_id: String,
+    },
+    /// A thread opened with `open-in-tab`, expanded at the message `message_id`.
+    Thread {
+        account: String,
+        mailbox: String,
+        message_id: String,
+    },
+    /// A compose tab, and the file its draft was saved to if it had changes.
+    Compose {
+        account: String,
+        draft: Option<PathBuf>,
+    },
+    /// A contact list opened with `split contacts` or `vsplit contacts`.
+    Contacts { account: String },
+    /// Tabs shown in the panes of one tab, with the percentage of the tab each one takes.
+    Split {
+        direction: SplitDirection,
+        sizes: Vec<usize>,
+        panes: Vec<TabSession>,
+    },
+}
+
+impl TabSession {
+    pub fn account(&self) -> &str {
+        match self {
+            TabSession::Envelope { ref account, .. }
+            | TabSession::Thread { ref account, .. }
+            | TabSession::Compose { ref account, .. }
+            | TabSession::Contacts { ref account } => account,
+            TabSession::Split { ref panes, .. } => {
+                panes.first().map(TabSession::account).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Removes the auto-saved drafts of the compose tabs in this tab, for tabs that won't be
+    /// restored.
+    pub fn remove_drafts(&self) {
+        match self {
+            TabSession::Compose {
+                draft: Some(ref path),
+                ..
+            } if path.exists() => {
+                if let Err(err) = std::fs::remove_file(path) {
+                    log(
+                        format!("Could not remove draft {}: {}", path.display(), err),
+                        WARN,
+                    );
+                }
+            }
+            TabSession::Split { ref panes, .. } => {
+                for pane in panes {
+                    pane.remove_drafts();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// What is restored on the next start.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// The mailbox shown in the listing.
+    #[serde(default)]
+    pub listing: Option<MailboxPosition>,
+    /// The mailbox last shown in each account.
+    #[serde(default)]
+    pub accounts: Vec<MailboxPosition>,
+    #[serde(default)]
+    pub listing_layout: Option<ListingLayoutSession>,
+    #[serde(default)]
+    pub tabs: Vec<TabSession>,
+}
+
+fn data_dir() -> Result<xdg::BaseDirectories> {
+    xdg::BaseDirectories::with_prefix("meli")
+        .map_err(|err| MeliError::new(format!("Could not find data directory: {}", err)))
+}
+
+/// Writes `content` to a temporary file in the directory of `path` and renames it to `path`, so
+/// that a crash while writing doesn't leave a truncated file behind.
+fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    std::fs::write(&tmp_path, content)
+        .and_then(|()| std::fs::rename(&tmp_path, path))
+        .map_err(|err| {
+            let _ = std::fs::remove_file(&tmp_path);
+            err
+        })
+        .chain_err_summary(|| format!("Could not write {}", path.display()))
+}
+
+impl Session {
+    /// Reads the saved session, if there is one.
+    pub fn load() -> Result<Option<Session>> {
+        let path = match data_dir()?.find_data_file("session.json") {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let content = std::fs::read_to_string(&path)
+            .chain_err_summary(|| format!("Could not read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|err| MeliError::new(format!("Could not parse {}: {}", path.display(), err)))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = data_dir()?
+            .place_data_file("session.json")
+            .chain_err_summary(|| "Could not create session file")?;
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| MeliError::new(format!("Could not serialize session: {}", err)))?;
+        write_atomically(&path, content.as_bytes())
+    }
+}
+
+/// Returns the path of the auto-saved draft of compose tab `id`.
+pub fn draft_path(id: crate::components::ComponentId) -> Result<PathBuf> {
+    data_dir()?
+        .place_data_file(format!("drafts/{}.json", id))
+        .chain_err_summary(|| "Could not create drafts directory")
+}
+
+/// An auto-saved draft. Unlike the text form of `Draft`, header values stay decoded and
+/// attachments are kept as they were added, so restoring it gives back the same `Draft`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedDraft {
+    headers: Vec<(String, String)>,
+    body: String,
+    #[serde(default)]
+    attachments: Vec<AttachmentBuilder>,
+}
+
+impl From<&Draft> for SavedDraft {
+    fn from(draft: &Draft) -> Self {
+        SavedDraft {
+            headers: draft
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.as_str().to_string(), v.to_string()))
+                .collect(),
+            body: draft.body.clone(),
+            attachments: draft.attachments.clone(),
+        }
+    }
+}
+
+impl From<SavedDraft> for Draft {
+    fn from(saved: SavedDraft) -> Self {
+        let mut headers = HeaderMap::default();
+        for (k, v) in saved.headers {
+            headers.insert(HeaderName::new_unchecked(&k), v);
+        }
+        Draft {
+            headers,
+            body: saved.body,
+            attachments: saved.attachments,
+        }
+    }
+}
+
+pub fn save_draft(path: &Path, draft: &Draft) -> Result<()> {
+    let content = serde_json::to_string(&SavedDraft::from(draft))
+        .map_err(|err| MeliError::new(format!("Could not serialize draft: {}", err)))?;
+    write_atomically(path, content.as_bytes())
+}
+
+pub fn load_draft(path: &Path) -> Result<Draft> {
+    let content = std::fs::read_to_string(path)
+        .chain_err_summary(|| format!("Could not read {}", path.display()))?;
+    serde_json::from_str::<SavedDraft>(&content)
+        .map(Draft::from)
+        .map_err(|err| MeliError::new(format!("Could not parse {}: {}", path.display(), err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_serialization() {
+        let session = Session {
+            listing: Some(MailboxPosition {
+                account: "personal".to_string(),
+                mailbox: "INBOX".to_string(),
+                cursor: 4,
+            }),
+            accounts: vec![],
+            listing_layout: Some(ListingLayoutSession {
+                layout: ListingLayout::ThreeColumns,
+                preview_ratio: 40,
+            }),
+            tabs: vec![
+                TabSession::Thread {
+                    account: "personal".to_string(),
+                    mailbox: "INBOX".to_string(),
+                    message_id: "<a@example.com>".to_string(),
+                },
+                TabSession::Split {
+                    direction: SplitDirection::Vertical,
+                    sizes: vec![70, 30],
+                    panes: vec![
+                        TabSession::Compose {
+                            account: "work".to_string(),
+                            draft: None,
+                        },
+                        TabSession::Contacts {
+                            account: "work".to_string(),
+                        },
+                    ],
+                },
+            ],
+        };
+        let json = serde_json::to_string(&session).unwrap();
+        assert!(json.contains(r#""kind":"thread""#));
+        assert!(json.contains(r#""layout":"three_columns""#));
+        assert_eq!(session.tabs[1].account(), "work");
+        assert_eq!(serde_json::from_str::<Session>(&json).unwrap(), session);
+        assert_eq!(
+            serde_json::from_str::<Session>("{}").unwrap(),
+            Session::default()
+        );
+    }
+
+    #[test]
+    fn test_saved_draft_round_trip() {
+        let mut draft = Draft::default();
+        draft
+            .set_header("To", "Ζωή <zoi@example.com>".to_string())
+            .set_header("Subject", "Καλημέρα, ça va?".to_string());
+        draft.body = "Grüße\n\n-- \nsignature\n".to_string();
+        let mut attachment = AttachmentBuilder::default();
+        attachment
+            .set_raw(vec![0, 1, 2, 255, b'\n'])
+            .set_content_type_from_bytes(b"application/octet-stream; name=\"data.bin\"");
+        draft.attachments.push(attachment);
+
+        let json = serde_json::to_string(&SavedDraft::from(&draft)).unwrap();
+        assert!(!json.contains("=?UTF-8?"));
+        let restored = Draft::from(serde_json::from_str::<SavedDraft>(&json).unwrap());
+        assert_eq!(restored, draft);
+        assert_eq!(restored.headers()["Subject"], "Καλημέρα, ça va?");
+        assert_eq!(restored.attachments[0].raw(), &[0, 1, 2, 255, b'\n'][..]);
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("meli-session-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("session.json");
+        write_atomically(&path, b"old").unwrap();
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert!(!dir.join("session.json.tmp").exists());
+        assert!(write_atomically(&dir.join("missing").join("session.json"), b"").is_err());
+        assert!(!dir.join("missing").exists());
+
+        let draft = dir.join("draft.json");
+        std::fs::write(&draft, "{}").unwrap();
+        let tab = TabSession::Split {
+            direction: SplitDirection::Horizontal,
+            sizes: vec![50, 50],
+            panes: vec![
+                TabSession::Compose {
+                    account: "work".to_string(),
+                    draft: Some(draft.clone()),
+                },
+                TabSession::Compose {
+                    account: "work".to_string(),
+                    draft: Some(dir.join("already-removed.json")),
+                },
+            ],
+        };
+        tab.remove_drafts();
+        assert!(!draft.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -33,6 +33,7 @@ use super::*;
 use melib::backends::{AccountHash, BackendEventConsumer};
 
 use crate::jobs::JobExecutor;
+use crate::session::{Session, TabSession};
 use crossbeam::channel::{unbounded, Receiver, Sender};
 use indexmap::IndexMap;
 use smallvec::SmallVec;
@@ -116,6 +117,8 @@ pub struct Context {
     pub children: Vec<std::process::Child>,
 
     pub temp_files: Vec<File>,
+    /// Image escape sequences to write after the next redraw, see `terminal::graphics`.
+    pub pending_graphics: Vec<GraphicsPlacement>,
 }
 
 impl Context {
@@ -192,6 +195,12 @@ pub struct State {
     display_messages_initialised: bool,
     display_messages_pos: usize,
     display_messages_area: Area,
+    /// Images currently on screen; they are removed when a dirty area overlaps them.
+    visible_graphics: Vec<GraphicsPlacement>,
+    /// Saves the session periodically.
+    session_timer: Option<crate::jobs::Timer>,
+    /// Tabs of the previous session whose mailbox hasn't been loaded yet.
+    pending_tabs: Vec<TabSession>,
 }
 
 #[derive(Debug)]
@@ -339,12 +348,16 @@ impl State {
             display_messages_dirty: false,
             display_messages_initialised: false,
             display_messages_area: ((0, 0), (0, 0)),
+            visible_graphics: Vec::new(),
+            session_timer: None,
+            pending_tabs: Vec::new(),
             context: Context {
                 accounts,
                 settings: settings,
                 dirty_areas: VecDeque::with_capacity(5),
                 replies: VecDeque::with_capacity(5),
                 temp_files: Vec::new(),
+                pending_graphics: Vec::new(),
                 job_executor,
                 children: vec![],
 
@@ -404,8 +417,11 @@ impl State {
             } = &mut self.context;
 
             if let Some(notification) = accounts[&account_hash].reload(event, mailbox_hash) {
-                if let UIEvent::Notification(_, _, _) = notification {
-                    self.rcv_event(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+                match notification {
+                    UIEvent::Notification(_, _, _) | UIEvent::NewMail(_, _, _, _) => {
+                        self.rcv_event(UIEvent::MailboxUpdate((account_hash, mailbox_hash)));
+                    }
+                    _ => {}
                 }
                 self.rcv_event(notification);
             }
@@ -577,6 +593,27 @@ impl State {
                     || displ_bot.0 < top_x);
             }
         }
+        /* Images are not part of the grid: remove any that are about to be drawn over */
+        {
+            let State {
+                ref mut visible_graphics,
+                ref mut stdout,
+                ..
+            } = self;
+            visible_graphics.retain(|g| {
+                let ((g_top_x, g_top_y), (g_bottom_x, g_bottom_y)) = g.area;
+                let overlaps = areas.iter().any(|&((top_x, top_y), (bottom_x, bottom_y))| {
+                    !(bottom_y < g_top_y
+                        || g_bottom_y < top_y
+                        || bottom_x < g_top_x
+                        || g_bottom_x < top_x)
+                });
+                if overlaps && g.protocol == ImageProtocol::Kitty {
+                    write!(stdout.as_mut().unwrap(), "{}", kitty_delete_sequence(g.id)).unwrap();
+                }
+                !overlaps
+            });
+        }
         /* draw each dirty area */
         let rows = self.rows;
         for y in 0..rows {
@@ -634,6 +671,21 @@ impl State {
             }
         }
 
+        for g in self.context.pending_graphics.drain(..) {
+            let (x, y) = upper_left!(g.area);
+            write!(
+                self.stdout.as_mut().unwrap(),
+                "{}{}",
+                cursor::Goto(x as u16 + 1, y as u16 + 1),
+                g.sequence
+            )
+            .unwrap();
+            self.visible_graphics.push(GraphicsPlacement {
+                sequence: String::new(),
+                ..g
+            });
+        }
+
         if self.display_messages_dirty && self.display_messages_active {
             if let Some(DisplayMessage {
                 ref timestamp,
@@ -889,6 +941,171 @@ impl State {
         self.components.push(component);
     }
 
+    /// Restores the tabs of the previous session and starts saving the current one periodically.
+    pub fn start_session(&mut self, session: Option<Session>) {
+        self.session_timer = Some(
+            self.context
+                .job_executor
+                .clone()
+                .create_timer(crate::session::SAVE_INTERVAL, crate::session::SAVE_INTERVAL),
+        );
+        if let Some(session) = session {
+            self.pending_tabs = session.tabs;
+            self.restore_tabs();
+        }
+    }
+
+    /// Saves the session, if it was started with `start_session`.
+    pub fn save_session(&self) {
+        if self.session_timer.is_none() {
+            return;
+        }
+        let mut session = Session::default();
+        for c in self.components.iter() {
+            c.save_session(&mut session, &self.context);
+        }
+        session.tabs.extend(self.pending_tabs.iter().cloned());
+        if let Err(err) = session.save() {
+            log(format!("Could not save session: {}", err.to_string()), WARN);
+        }
+    }
+
+    /// Opens the pending tabs of the previous session whose mailbox is loaded.
+    fn restore_tabs(&mut self) {
+        let tabs = std::mem::replace(&mut self.pending_tabs, Vec::new());
+        for tab in tabs {
+            match self.restore_tab(&tab) {
+                Ok(Some(component)) => {
+                    self.rcv_event(UIEvent::Action(Tab(Restore(Some(component)))));
+                }
+                Ok(None) => self.pending_tabs.push(tab),
+                Err(err) => {
+                    log(
+                        format!("Could not restore tab {:?}: {}", tab, err.to_string()),
+                        WARN,
+                    );
+                    tab.remove_drafts();
+                }
+            }
+        }
+    }
+
+    /// Returns `Ok(None)` if the tab's mailbox isn't available yet.
+    fn restore_tab(&mut self, tab: &TabSession) -> Result<Option<Box<dyn Component>>> {
+        if let TabSession::Split {
+            direction,
+            sizes,
+            panes,
+        } = tab
+        {
+            let mut components = Vec::with_capacity(panes.len());
+            for pane in panes {
+                match self.restore_tab(pane) {
+                    Ok(Some(component)) => components.push(component),
+                    Ok(None) => return Ok(None),
+                    Err(err) => {
+                        log(
+                            format!("Could not restore pane {:?}: {}", pane, err.to_string()),
+                            WARN,
+                        );
+                        pane.remove_drafts();
+                    }
+                }
+            }
+            if components.is_empty() {
+                return Err(MeliError::new(
+                    "None of the split tab's panes could be restored.",
+                ));
+            }
+            let mut split = Panes::new(*direction, components, &self.context);
+            split.set_sizes(sizes);
+            return Ok(Some(Box::new(split)));
+        }
+        let account_hash = match self
+            .context
+            .accounts
+            .values()
+            .find(|a| a.name() == tab.account())
+        {
+            Some(account) => account.hash(),
+            None => {
+                return Err(MeliError::new(format!(
+                    "No account named `{}`.",
+                    tab.account()
+                )))
+            }
+        };
+        let (mailbox, message_id) = match tab {
+            TabSession::Compose { ref draft, .. } => {
+                return Ok(Some(Box::new(Composer::restore(
+                    account_hash,
+                    draft.clone(),
+                    &self.context,
+                )?)));
+            }
+            TabSession::Contacts { .. } => {
+                let account_pos = self
+                    .context
+                    .accounts
+                    .get_index_of(&account_hash)
+                    .unwrap_or_default();
+                return Ok(Some(Box::new(ContactList::for_account(
+                    account_pos,
+                    &self.context,
+                ))));
+            }
+            TabSession::Split { .. } => unreachable!(),
+            TabSession::Envelope {
+                ref mailbox,
+                ref message_id,
+                ..
+            }
+            | TabSession::Thread {
+                ref mailbox,
+                ref message_id,
+                ..
+            } => (mailbox, message_id),
+        };
+        if self.context.is_online(account_hash).is_err() {
+            return Ok(None);
+        }
+        let mailbox_hash = self.context.accounts[&account_hash].mailbox_by_path(mailbox)?;
+        if self.context.accounts[&account_hash]
+            .load(mailbox_hash)
+            .is_err()
+        {
+            return Ok(None);
+        }
+        let account = &self.context.accounts[&account_hash];
+        let env_hash = account
+            .collection
+            .get_mailbox(mailbox_hash)
+            .iter()
+            .cloned()
+            .find(|h| account.collection.get_env(*h).message_id_display() == message_id.as_str())
+            .ok_or_else(|| MeliError::new(format!("Message {} not found.", message_id)))?;
+        if let TabSession::Thread { .. } = tab {
+            let thread_node = account.collection.get_env(env_hash).thread();
+            let thread_group = {
+                let threads = account.collection.get_threads(mailbox_hash);
+                threads.find_group(threads.thread_nodes()[&thread_node].group)
+            };
+            Ok(Some(Box::new(ThreadView::new(
+                (account_hash, mailbox_hash, 0),
+                thread_group,
+                Some(thread_node),
+                &self.context,
+            ))))
+        } else {
+            Ok(Some(Box::new(MailView::new(
+                (account_hash, mailbox_hash, env_hash),
+                None,
+                None,
+                &mut self.context,
+            ))))
+        }
+    }
+
     /// Convert user commands to actions/method calls.
     fn exec_command(&mut self, cmd: Action) {
         match cmd {
@@ -1018,6 +1235,56 @@ impl State {
                     return;
                 }
             }
+            AccountAction(ref account_name, ManageSieveScripts) => {
+                let account_hash = if let Some((account_hash, _)) = self
+                    .context
+                    .accounts
+                    .iter()
+                    .find(|(_, acc)| acc.name() == account_name)
+                {
+                    *account_hash
+                } else {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!("Account {} was not found.", account_name),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                };
+                if self.context.accounts[&account_hash]
+                    .settings
+                    .account()
+                    .format()
+                    != "imap"
+                {
+                    self.context.replies.push_back(UIEvent::Notification(
+                        None,
+                        format!(
+                            "Account {} is not an IMAP account; ManageSieve is only supported for IMAP accounts.",
+                            account_name
+                        ),
+                        Some(NotificationType::Error(ErrorKind::None)),
+                    ));
+                    return;
+                }
+                match SieveScripts::new(account_hash, &self.context) {
+                    Ok(manager) => {
+                        self.context
+                            .replies
+                            .push_back(UIEvent::Action(Tab(New(Some(Box::new(manager))))));
+                    }
+                    Err(err) => {
+                        self.context.replies.push_back(UIEvent::Notification(
+                            Some(format!(
+                                "Could not open ManageSieve session: {}",
+                                account_name
+                            )),
+                            err.to_string(),
+                            Some(NotificationType::Error(err.kind)),
+                        ));
+                    }
+                }
+            }
             PrintSetting(ref setting) => {
                 let path = setting.split(".").collect::<SmallVec<[&str; 16]>>();
                 self.context
@@ -1050,6 +1317,116 @@ impl State {
         }
     }
 
+    /// Executes a command line, expanding user defined aliases and `source` commands.
+    ///
+    /// `alias` is the alias being expanded, which isn't expanded again so that aliases can
+    /// override commands of the same name.
+    fn run_command(&mut self, cmd: &str, depth: usize, alias: Option<&str>) -> Result<()> {
+        const MAX_DEPTH: usize = 16;
+        let cmd = cmd.trim();
+        if depth > MAX_DEPTH {
+            return Err(MeliError::new(format!(
+                "{}: aliases or sourced files are nested too deeply",
+                cmd
+            )));
+        }
+        if let Some(name) = self.context.settings.commands.alias_name(cmd) {
+            if alias != Some(name) {
+                let name = name.to_string();
+                let expansion = self.context.settings.commands.expand(cmd).unwrap()?;
+                for cmd in expansion {
+                    self.run_command(&cmd, depth + 1, Some(&name))?;
+                }
+                return Ok(());
+            }
+        }
+        let action = parse_command(&cmd.as_bytes())
+            .map_err(|_| MeliError::new(format!("invalid command: {}", cmd)))?;
+        if let Action::Source(ref path) = action {
+            let path = std::path::Path::new(path).expand();
+            let contents = std::fs::read_to_string(&path).map_err(|err| {
+                MeliError::new(format!("Could not read {}: {}", path.display(), err))
+            })?;
+            for (i, line) in contents.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                self.run_command(line, depth + 1, None).map_err(|err| {
+                    MeliError::new(format!("{}:{}: {}", path.display(), i + 1, err))
+                })?;
+            }
+            return Ok(());
+        }
+        if action.needs_confirmation() {
+            self.overlay.push(Box::new(UIConfirmationDialog::new(
+                "You sure?",
+                vec![(true, "yes".to_string()), (false, "no".to_string())],
+                true,
+                Some(Box::new(move |id: ComponentId, result: bool| {
+                    Some(UIEvent::FinishedUIDialog(
+                        id,
+                        Box::new(if result { Some(action) } else { None }),
+                    ))
+                })),
+                &mut self.context,
+            )));
+        } else if let Action::ReloadConfiguration = action {
+            match Settings::new().and_then(|new_settings| {
+                let old_accounts = self
+                    .context
+                    .settings
+                    .accounts
+                    .keys()
+                    .collect::<std::collections::HashSet<&String>>();
+                let new_accounts = new_settings
+                    .accounts
+                    .keys()
+                    .collect::<std::collections::HashSet<&String>>();
+                if old_accounts != new_accounts {
+                    return Err(
+                        "cannot reload account configuration changes; restart meli instead.".into(),
+                    );
+                }
+                for (key, acc) in new_settings.accounts.iter() {
+                    if toml::Value::try_from(&acc)
+                        != toml::Value::try_from(&self.context.settings.accounts[key])
+                    {
+                        return Err(
+                            "cannot reload account configuration changes; restart meli instead."
+                                .into(),
+                        );
+                    }
+                }
+                if toml::Value::try_from(&new_settings)
+                    == toml::Value::try_from(&self.context.settings)
+                {
+                    return Err("No changes detected.".into());
+                }
+                Ok(new_settings)
+            }) {
+                Ok(new_settings) => {
+                    let old_settings = std::mem::replace(&mut self.context.settings, new_settings);
+                    self.context
+                        .replies
+                        .push_back(UIEvent::ConfigReload { old_settings });
+                    self.context.replies.push_back(UIEvent::Resize);
+                }
+                Err(err) => {
+                    self.context.replies.push_back(UIEvent::StatusEvent(
+                        StatusEvent::DisplayMessage(format!(
+                            "Could not load configuration: {}",
+                            err
+                        )),
+                    ));
+                }
+            }
+        } else {
+            self.exec_command(action);
+        }
+        Ok(())
+    }
+
     /// The application's main loop sends `UIEvents` to state via this method.
     pub fn rcv_event(&mut self, mut event: UIEvent) {
         if let UIEvent::Input(_) = event {
@@ -1061,59 +1438,9 @@ impl State {
         match event {
             // Command type is handled only by State.
             UIEvent::Command(cmd) => {
-                if let Ok(action) = parse_command(&cmd.as_bytes()) {
-                    if action.needs_confirmation() {
-                        self.overlay.push(Box::new(UIConfirmationDialog::new(
-                            "You sure?",
-                            vec![(true, "yes".to_string()), (false, "no".to_string())],
-                            true,
-                            Some(Box::new(move |id: ComponentId, result: bool| {
-                                Some(UIEvent::FinishedUIDialog(
-                                    id,
-                                    Box::new(if result { Some(action) } else { None }),
-                                ))
-                            })),
-                            &mut self.context,
-                        )));
-                    } else if let Action::ReloadConfiguration = action {
-                        match Settings::new().and_then(|new_settings| {
-                            let old_accounts = self.context.settings.accounts.keys().collect::<std::collections::HashSet<&String>>();
-                            let new_accounts = new_settings.accounts.keys().collect::<std::collections::HashSet<&String>>();
-                            if old_accounts != new_accounts {
-                                return Err("cannot reload account configuration changes; restart meli instead.".into());
-                            }
-                            for (key, acc) in new_settings.accounts.iter() {
-                                if toml::Value::try_from(&acc) != toml::Value::try_from(&self.context.settings.accounts[key]) {
-                                    return Err("cannot reload account configuration changes; restart meli instead.".into());
-                                }
-                            }
-                            if toml::Value::try_from(&new_settings) == toml::Value::try_from(&self.context.settings) {
-                                return Err("No changes detected.".into());
-                            }
-                            Ok(new_settings)
-                        }) {
-                            Ok(new_settings) => {
-                                let old_settings = std::mem::replace(&mut self.context.settings, new_settings);
-                                self.context.replies.push_back(UIEvent::ConfigReload {
-                                    old_settings
-                                });
-                                self.context.replies.push_back(UIEvent::Resize);
-                            }
-                            Err(err) => {
-                                self.context.replies.push_back(UIEvent::StatusEvent(
-                                        StatusEvent::DisplayMessage(format!(
-                                                "Could not load configuration: {}",
-                                                err
-                                        )),
-                                ));
-                            }
-                        }
-                    } else {
-                        self.exec_command(action);
-                    }
-                } else {
+                if let Err(err) = self.run_command(&cmd, 0, None) {
                     self.context.replies.push_back(UIEvent::StatusEvent(
-                        StatusEvent::DisplayMessage("invalid command".to_string()),
+                        StatusEvent::DisplayMessage(err.to_string()),
                     ));
                 }
                 return;
@@ -1178,6 +1505,44 @@ impl State {
                 self.redraw();
                 return;
             }
+            UIEvent::Timer(id) if self.session_timer.as_ref().map(|t| t.id()) == Some(id) => {
+                self.restore_tabs();
+                self.save_session();
+                return;
+            }
+            UIEvent::Timer(id)
+                if self
+                    .context
+                    .accounts
+                    .values()
+                    .any(|a| a.snooze_timer_id() == Some(id)) =>
+            {
+                for account in self.context.accounts.values_mut() {
+                    if account.snooze_timer_id() == Some(id) {
+                        account.wake_snoozed();
+                    }
+                }
+                return;
+            }
+            UIEvent::Timer(id)
+                if self
+                    .context
+                    .accounts
+                    .values()
+                    .any(|a| a.trash_timer_id() == Some(id)) =>
+            {
+                for account in self.context.accounts.values_mut() {
+                    if account.trash_timer_id() == Some(id) {
+                        account.empty_trash();
+                    }
+                }
+                return;
+            }
+            UIEvent::MailboxUpdate(_) | UIEvent::AccountStatusChange(_)
+                if !self.pending_tabs.is_empty() =>
+            {
+                self.restore_tabs();
+            }
             UIEvent::Input(Key::Alt('<')) => {
                 self.display_messages_expiration_start = Some(melib::datetime::now());
                 self.display_messages_active = true;
@@ -1254,6 +1619,25 @@ impl State {
         }
     }
 
+    /// Answers a request from the control socket.
+    pub fn control_request(&mut self, request: crate::control::ControlRequest) {
+        use crate::control::Query;
+        match request.query() {
+            Query::Unread => request.reply(Ok(crate::control::unread(&self.context))),
+            Query::Status => request.reply(Ok(crate::control::status(&self.context))),
+            /* Only the mail listing knows what is selected. */
+            Query::Selection => self.rcv_event(UIEvent::ControlQuery(request)),
+            Query::Command(cmd) => {
+                let result = self.run_command(cmd, 0, None);
+                request.reply(
+                    result
+                        .map(|()| serde_json::Value::Null)
+                        .map_err(|err| err.to_string()),
+                );
+            }
+        }
+    }
+
     pub fn try_wait_on_child(&mut self) -> Option<bool> {
         let should_return_flag = match self.child {
             Some(ForkType::NewDraft(_, ref mut c)) => {
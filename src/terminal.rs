@@ -35,8 +35,10 @@ mod cells;
 #[macro_use]
 mod keys;
 pub mod embed;
+mod graphics;
 mod text_editing;
 pub use self::cells::*;
+pub use self::graphics::*;
 pub use self::keys::*;
 pub use self::position::*;
 pub use self::text_editing::*;
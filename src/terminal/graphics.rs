@@ -0,0 +1,409 @@
+/*
+ * meli - terminal graphics
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Inline image rendering.
+ *
+ * Images are drawn with the [kitty graphics
+ * protocol](https://sw.kovidgoyal.net/kitty/graphics-protocol.html) or with DEC sixel graphics
+ * when the terminal supports them. Otherwise each cell is drawn as a `▀` character with the upper
+ * pixel as foreground and the lower pixel as background color.
+ *
+ * Protocol escape sequences can't be stored in a `CellBuffer`, so components push a
+ * `GraphicsPlacement` to `Context::pending_graphics` and `State` writes it to the terminal after
+ * the grid is flushed.
+ */
+
+use super::{Area, Cell, CellBuffer, Color};
+use melib::{MeliError, Result};
+use std::fmt::Write;
+
+/// Graphics protocol to use when displaying images.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageProtocol {
+    /// Guess from the environment.
+    Auto,
+    Kitty,
+    Sixel,
+    /// Unicode half blocks with true color.
+    HalfBlocks,
+    /// Don't display images inline.
+    None,
+}
+
+impl Default for ImageProtocol {
+    fn default() -> Self {
+        ImageProtocol::Auto
+    }
+}
+
+impl ImageProtocol {
+    /// Resolve `Auto` to a concrete protocol by inspecting the environment.
+    ///
+    /// Querying the terminal with `DA1` would be more reliable but the input thread owns stdin
+    /// and answers would end up as key presses.
+    pub fn resolve(self) -> Self {
+        if self != ImageProtocol::Auto {
+            return self;
+        }
+        let term = std::env::var("TERM").unwrap_or_default();
+        let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+        if std::env::var("KITTY_WINDOW_ID").is_ok() || term == "xterm-kitty" {
+            return ImageProtocol::Kitty;
+        }
+        if ["mlterm", "foot", "yaft", "contour"]
+            .iter()
+            .any(|t| term.starts_with(t))
+            || ["WezTerm", "mintty", "iTerm.app"]
+                .iter()
+                .any(|t| term_program == *t)
+        {
+            return ImageProtocol::Sixel;
+        }
+        ImageProtocol::HalfBlocks
+    }
+}
+
+/// A terminal escape sequence that draws an image, positioned at the upper left cell of `area`.
+#[derive(Debug, Clone)]
+pub struct GraphicsPlacement {
+    /// The cells covered by the image.
+    pub area: Area,
+    pub protocol: ImageProtocol,
+    /// Image id, for protocols that can address images (kitty).
+    pub id: u32,
+    pub sequence: String,
+}
+
+/// Decoded image in 8-bit RGBA.
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    #[cfg(feature = "inline-images")]
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let img = image::load_from_memory(bytes)
+            .map_err(|err| MeliError::new(format!("Could not decode image: {}", err)))?
+            .into_rgba();
+        let (width, height) = img.dimensions();
+        Ok(RgbaImage {
+            width: width as usize,
+            height: height as usize,
+            pixels: img.into_raw(),
+        })
+    }
+
+    #[cfg(not(feature = "inline-images"))]
+    pub fn decode(_bytes: &[u8]) -> Result<Self> {
+        Err(MeliError::new(
+            "Cannot display image: meli must be compiled with the inline-images feature.",
+        ))
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = 4 * (y * self.width + x);
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    /// Scale down (never up) with nearest neighbour sampling so that the image fits in
+    /// `max_width`x`max_height` pixels, keeping the aspect ratio.
+    pub fn fit(&self, max_width: usize, max_height: usize) -> RgbaImage {
+        if self.width == 0 || self.height == 0 || max_width == 0 || max_height == 0 {
+            return RgbaImage {
+                width: 0,
+                height: 0,
+                pixels: vec![],
+            };
+        }
+        if self.width <= max_width && self.height <= max_height {
+            return self.clone();
+        }
+        let ratio = f64::min(
+            max_width as f64 / self.width as f64,
+            max_height as f64 / self.height as f64,
+        );
+        let width = std::cmp::max(1, (self.width as f64 * ratio) as usize);
+        let height = std::cmp::max(1, (self.height as f64 * ratio) as usize);
+        let mut pixels = Vec::with_capacity(4 * width * height);
+        for y in 0..height {
+            let src_y = std::cmp::min(self.height - 1, (y as f64 / ratio) as usize);
+            for x in 0..width {
+                let src_x = std::cmp::min(self.width - 1, (x as f64 / ratio) as usize);
+                pixels.extend_from_slice(&self.pixel(src_x, src_y));
+            }
+        }
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Size of a terminal cell in pixels, as reported by `TIOCGWINSZ`. Falls back to 8x16 when the
+/// terminal doesn't report pixel dimensions.
+pub fn cell_size() -> (usize, usize) {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) };
+    if ret != 0 || ws.ws_col == 0 || ws.ws_row == 0 || ws.ws_xpixel == 0 || ws.ws_ypixel == 0 {
+        return (8, 16);
+    }
+    (
+        ws.ws_xpixel as usize / ws.ws_col as usize,
+        ws.ws_ypixel as usize / ws.ws_row as usize,
+    )
+}
+
+/// Draw `img` in `area` of `grid` with upper half block characters, two pixels per cell. `img`
+/// should already be scaled to at most `width(area)`x`2*height(area)` pixels.
+pub fn draw_half_blocks(img: &RgbaImage, grid: &mut CellBuffer, area: Area) {
+    let ((x_start, y_start), (x_end, y_end)) = area;
+    let blend = |[r, g, b, a]: [u8; 4]| -> Option<Color> {
+        if a < 128 {
+            None
+        } else {
+            Some(Color::Rgb(r, g, b))
+        }
+    };
+    for (row, y) in (y_start..=y_end).enumerate() {
+        let top_y = 2 * row;
+        if top_y >= img.height {
+            break;
+        }
+        for (col, x) in (x_start..=x_end).enumerate() {
+            if col >= img.width {
+                break;
+            }
+            let top = blend(img.pixel(col, top_y));
+            let bottom = if top_y + 1 < img.height {
+                blend(img.pixel(col, top_y + 1))
+            } else {
+                None
+            };
+            if let Some(c) = grid.get_mut(x, y) {
+                *c = Cell::default();
+                c.set_ch('▀')
+                    .set_fg(top.unwrap_or(Color::Default))
+                    .set_bg(bottom.unwrap_or(Color::Default));
+                if top.is_none() && bottom.is_none() {
+                    c.set_ch(' ');
+                }
+            }
+        }
+    }
+}
+
+/// Kitty escape sequence that transmits and displays `img` at the cursor position.
+#[cfg(feature = "inline-images")]
+pub fn kitty_sequence(img: &RgbaImage, id: u32) -> String {
+    const CHUNK_SIZE: usize = 4096;
+    let payload = data_encoding::BASE64.encode(&img.pixels);
+    let mut ret = String::with_capacity(payload.len() + 64 * (payload.len() / CHUNK_SIZE + 1));
+    let chunks = payload
+        .as_bytes()
+        .chunks(CHUNK_SIZE)
+        .collect::<Vec<&[u8]>>();
+    let last = chunks.len().saturating_sub(1);
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        /* q=2 suppresses responses, which would otherwise arrive on stdin as key presses. C=1
+         * keeps the cursor in place. */
+        if i == 0 {
+            let _ = write!(
+                ret,
+                "\x1b_Ga=T,q=2,C=1,f=32,i={},s={},v={},m={};",
+                id,
+                img.width,
+                img.height,
+                if i == last { 0 } else { 1 }
+            );
+        } else {
+            let _ = write!(ret, "\x1b_Gm={};", if i == last { 0 } else { 1 });
+        }
+        ret.push_str(unsafe { std::str::from_utf8_unchecked(chunk) });
+        ret.push_str("\x1b\\");
+    }
+    ret
+}
+
+#[cfg(not(feature = "inline-images"))]
+pub fn kitty_sequence(_img: &RgbaImage, _id: u32) -> String {
+    String::new()
+}
+
+/// Kitty escape sequence that deletes image `id` and frees its data.
+pub fn kitty_delete_sequence(id: u32) -> String {
+    format!("\x1b_Ga=d,d=I,q=2,i={}\x1b\\", id)
+}
+
+/// DEC sixel escape sequence that displays `img` at the cursor position. Colors are quantized
+/// to a 6x6x6 cube and transparent pixels are left unpainted.
+pub fn sixel_sequence(img: &RgbaImage) -> String {
+    let quantize = |v: u8| -> usize { (v as usize * 5 + 127) / 255 };
+    let index = |[r, g, b, a]: [u8; 4]| -> Option<usize> {
+        if a < 128 {
+            None
+        } else {
+            Some(36 * quantize(r) + 6 * quantize(g) + quantize(b))
+        }
+    };
+    let mut ret = String::with_capacity(img.width * img.height / 2);
+    /* P2 = 1: pixels with value zero stay transparent. */
+    let _ = write!(ret, "\x1bP0;1;0q\"1;1;{};{}", img.width, img.height);
+    for i in 0..216 {
+        let _ = write!(
+            ret,
+            "#{};2;{};{};{}",
+            i,
+            (i / 36) * 20,
+            ((i / 6) % 6) * 20,
+            (i % 6) * 20
+        );
+    }
+    let mut band = vec![None; img.width * 6];
+    let mut used = [false; 216];
+    for band_y in (0..img.height).step_by(6) {
+        used.iter_mut().for_each(|u| *u = false);
+        for dy in 0..6 {
+            for x in 0..img.width {
+                band[dy * img.width + x] = if band_y + dy < img.height {
+                    index(img.pixel(x, band_y + dy))
+                } else {
+                    None
+                };
+                if let Some(i) = band[dy * img.width + x] {
+                    used[i] = true;
+                }
+            }
+        }
+        for color in (0..216).filter(|c| used[*c]) {
+            let _ = write!(ret, "#{}", color);
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..img.width {
+                let mut bits = 0u8;
+                for dy in 0..6 {
+                    if band[dy * img.width + x] == Some(color) {
+                        bits |= 1 << dy;
+                    }
+                }
+                let ch = (63 + bits) as char;
+                match run {
+                    Some((prev, ref mut n)) if prev == ch => *n += 1,
+                    _ => {
+                        if let Some((prev, n)) = run.take() {
+                            push_sixel_run(&mut ret, prev, n);
+                        }
+                        run = Some((ch, 1));
+                    }
+                }
+            }
+            if let Some((prev, n)) = run {
+                push_sixel_run(&mut ret, prev, n);
+            }
+            /* Graphics carriage return: overlay the next color on the same band */
+            ret.push('$');
+        }
+        /* Graphics new line: move to the next band */
+        ret.push('-');
+    }
+    ret.push_str("\x1b\\");
+    ret
+}
+
+fn push_sixel_run(s: &mut String, ch: char, n: usize) {
+    if n > 3 {
+        let _ = write!(s, "!{}{}", n, ch);
+    } else {
+        for _ in 0..n {
+            s.push(ch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: usize, height: usize, pixel: [u8; 4]) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: pixel
+                .iter()
+                .cycle()
+                .take(4 * width * height)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    #[cfg(feature = "inline-images")]
+    #[test]
+    fn test_kitty_sequence() {
+        assert_eq!(
+            kitty_sequence(&image(1, 1, [255, 0, 0, 255]), 7),
+            "\x1b_Ga=T,q=2,C=1,f=32,i=7,s=1,v=1,m=0;/wAA/w==\x1b\\"
+        );
+        /* 4096 bytes of pixels are 5464 bytes of base64, sent in two chunks */
+        let seq = kitty_sequence(&image(32, 32, [0, 0, 0, 255]), 1);
+        assert!(seq.starts_with("\x1b_Ga=T,q=2,C=1,f=32,i=1,s=32,v=32,m=1;"));
+        assert_eq!(seq.matches("\x1b_G").count(), 2);
+        assert_eq!(seq.matches("\x1b\\").count(), 2);
+        assert!(seq.contains("\x1b\\\x1b_Gm=0;"));
+        assert_eq!(kitty_delete_sequence(7), "\x1b_Ga=d,d=I,q=2,i=7\x1b\\");
+    }
+
+    #[test]
+    fn test_sixel_sequence() {
+        let red = [255, 0, 0, 255];
+        let seq = sixel_sequence(&image(1, 1, red));
+        assert!(seq.starts_with("\x1bP0;1;0q\"1;1;1;1#0;2;0;0;0#1;2;0;0;20"));
+        assert!(seq.contains("#180;2;100;0;0#"));
+        assert!(seq.ends_with("#215;2;100;100;100#180@$-\x1b\\"));
+        /* Runs longer than three sixels are compressed */
+        assert!(sixel_sequence(&image(5, 1, red)).ends_with("#180!5@$-\x1b\\"));
+        assert!(sixel_sequence(&image(3, 1, red)).ends_with("#180@@@$-\x1b\\"));
+        /* Seven rows take two bands of six */
+        assert!(sixel_sequence(&image(1, 7, red)).ends_with("#180~$-#180@$-\x1b\\"));
+        /* Transparent pixels aren't painted */
+        let transparent = [255, 0, 0, 0];
+        assert!(sixel_sequence(&image(2, 2, transparent)).ends_with("#215;2;100;100;100-\x1b\\"));
+    }
+
+    #[test]
+    fn test_image_fit() {
+        let img = image(40, 20, [0, 0, 0, 255]);
+        let fitted = img.fit(10, 10);
+        assert_eq!((fitted.width, fitted.height), (10, 5));
+        assert_eq!(fitted.pixels.len(), 4 * 10 * 5);
+        /* Images are never scaled up */
+        let fitted = img.fit(100, 100);
+        assert_eq!((fitted.width, fitted.height), (40, 20));
+    }
+}
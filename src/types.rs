@@ -73,6 +73,8 @@ pub enum ThreadEvent {
     Pulse,
     //Decode { _ }, // For gpg2 signature check
     JobFinished(JobId),
+    /// A request from the control socket.
+    Control(crate::control::ControlRequest),
 }
 
 impl From<RefreshEvent> for ThreadEvent {
@@ -96,6 +98,8 @@ pub enum NotificationType {
     Info,
     Error(melib::error::ErrorKind),
     NewMail,
+    /// New mail in a watched thread, notified even if notifications are disabled.
+    WatchedThread,
     SentMail,
     Saved,
 }
@@ -107,6 +111,7 @@ impl core::fmt::Display for NotificationType {
             NotificationType::Error(melib::error::ErrorKind::None) => write!(f, "error"),
             NotificationType::Error(kind) => write!(f, "error: {}", kind),
             NotificationType::NewMail => write!(f, "new mail"),
+            NotificationType::WatchedThread => write!(f, "watched thread"),
             NotificationType::SentMail => write!(f, "sent mail"),
             NotificationType::Saved => write!(f, "saved"),
         }
@@ -127,6 +132,8 @@ pub enum UIEvent {
     ChangeMode(UIMode),
     Command(String),
     Notification(Option<String>, String, Option<NotificationType>),
+    /// A new message that may be notified about, depending on the `notifications` settings.
+    NewMail(AccountHash, MailboxHash, EnvelopeHash, NotificationType),
     Action(Action),
     StatusEvent(StatusEvent),
     MailboxUpdate((AccountHash, MailboxHash)), // (account_idx, mailbox_idx)
@@ -149,6 +156,10 @@ pub enum UIEvent {
     ConfigReload {
         old_settings: crate::conf::Settings,
     },
+    /// A control socket query that only a component can answer, eg. the current selection.
+    ControlQuery(crate::control::ControlRequest),
+    /// The pattern of a `search` command as it is typed, or `None` once it is run or cancelled.
+    IncrementalSearch(Option<String>),
 }
 
 pub struct CallbackFn(pub Box<dyn FnOnce(&mut crate::Context) -> () + Send + 'static>);
@@ -0,0 +1,272 @@
+/*
+ * meli - undo journal
+ *
+ * Copyright 2020 Manos Pitsidianakis
+ *
+ * This file is part of meli.
+ *
+ * meli is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * meli is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with meli. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/*! Per-account undo/redo journal of envelope operations.
+ *
+ * Envelope hashes are not stable: moving a message or, on some backends, changing its flags gives
+ * it a new hash. Operations therefore refer to envelopes by their `Message-ID` and are resolved
+ * against the collection right before being executed.
+ */
+
+use melib::backends::MailboxHash;
+use melib::email::Flag;
+use smallvec::SmallVec;
+use std::collections::VecDeque;
+
+/// A flag or tag change as passed to `MailBackend::set_flags`.
+pub type FlagOp = (std::result::Result<Flag, String>, bool);
+
+#[derive(Debug, Clone)]
+pub enum EnvelopeOperation {
+    /// Apply `flags` to the envelopes in `mailbox_hash`.
+    SetFlags {
+        mailbox_hash: MailboxHash,
+        message_ids: Vec<Vec<u8>>,
+        flags: SmallVec<[FlagOp; 8]>,
+    },
+    /// Move envelopes from `source` to `destination`.
+    Move {
+        source: MailboxHash,
+        destination: MailboxHash,
+        message_ids: Vec<Vec<u8>>,
+    },
+}
+
+impl EnvelopeOperation {
+    pub fn inverse(&self) -> Option<Self> {
+        match self {
+            EnvelopeOperation::Move {
+                source,
+                destination,
+                message_ids,
+            } => Some(EnvelopeOperation::Move {
+                source: *destination,
+                destination: *source,
+                message_ids: message_ids.clone(),
+            }),
+            /* The inverse of a flag change depends on the flags each envelope had before, so it
+             * has to be computed when the change is recorded. */
+            EnvelopeOperation::SetFlags { .. } => None,
+        }
+    }
+}
+
+/// A user action and the operations that take it back.
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub description: String,
+    pub operations: Vec<EnvelopeOperation>,
+    pub inverse: Vec<EnvelopeOperation>,
+}
+
+impl JournalEntry {
+    /// Undoing ran `inverse` backwards and stopped at `failed`: keep only the inverse operations
+    /// that weren't carried out, so that undoing again doesn't repeat the others.
+    pub fn undo_failed_at(&mut self, failed: usize) {
+        self.inverse.truncate(failed + 1);
+    }
+
+    /// Redoing ran `operations` and stopped at `failed`: keep only the operations that weren't
+    /// carried out, so that redoing again doesn't repeat the others.
+    pub fn redo_failed_at(&mut self, failed: usize) {
+        self.operations.drain(..failed);
+    }
+}
+
+#[derive(Debug)]
+pub struct UndoJournal {
+    undo: VecDeque<JournalEntry>,
+    redo: Vec<JournalEntry>,
+    limit: usize,
+}
+
+impl Default for UndoJournal {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_LIMIT)
+    }
+}
+
+impl UndoJournal {
+    pub const DEFAULT_LIMIT: usize = 64;
+
+    /// A journal that keeps the last `limit` actions, set with the account's `undo_limit`. A
+    /// `limit` of 0 disables it.
+    pub fn new(limit: usize) -> Self {
+        UndoJournal {
+            undo: VecDeque::with_capacity(std::cmp::min(limit, Self::DEFAULT_LIMIT)),
+            redo: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Record a new action. This discards everything that could be redone.
+    pub fn record(&mut self, entry: JournalEntry) {
+        if entry.inverse.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.push_undo(entry);
+    }
+
+    pub fn pop_undo(&mut self) -> Option<JournalEntry> {
+        self.undo.pop_back()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<JournalEntry> {
+        self.redo.pop()
+    }
+
+    /// Put back an entry that has been undone, so that it can be redone.
+    pub fn push_redo(&mut self, entry: JournalEntry) {
+        if self.limit == 0 {
+            return;
+        }
+        self.redo.push(entry);
+    }
+
+    /// Put back an entry that has been redone (or failed to be undone).
+    pub fn push_undo(&mut self, entry: JournalEntry) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.undo.len() == self.limit {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn move_entry(
+        description: &str,
+        source: MailboxHash,
+        destination: MailboxHash,
+    ) -> JournalEntry {
+        let operation = EnvelopeOperation::Move {
+            source,
+            destination,
+            message_ids: vec![b"<a@example.com>".to_vec()],
+        };
+        JournalEntry {
+            description: description.to_string(),
+            inverse: operation.inverse().into_iter().collect(),
+            operations: vec![operation],
+        }
+    }
+
+    #[test]
+    fn test_undo_journal() {
+        let mut journal = UndoJournal::new(2);
+        journal.record(move_entry("first", 1, 2));
+        journal.record(move_entry("second", 1, 3));
+        journal.record(move_entry("third", 1, 4));
+        /* The oldest entry was dropped */
+        let third = journal.pop_undo().unwrap();
+        assert_eq!(third.description, "third");
+        journal.push_redo(third);
+        assert_eq!(journal.pop_undo().unwrap().description, "second");
+        assert!(journal.pop_undo().is_none());
+        let third = journal.pop_redo().unwrap();
+        assert_eq!(third.description, "third");
+        journal.push_undo(third);
+        journal.push_redo(move_entry("undone", 1, 5));
+        /* Recording a new action discards what could be redone */
+        journal.record(move_entry("fourth", 1, 6));
+        assert!(journal.pop_redo().is_none());
+        assert_eq!(journal.pop_undo().unwrap().description, "fourth");
+        /* Actions that can't be taken back aren't recorded */
+        journal.record(JournalEntry {
+            description: "flags".to_string(),
+            operations: vec![],
+            inverse: vec![],
+        });
+        assert_eq!(journal.pop_undo().unwrap().description, "third");
+
+        let mut disabled = UndoJournal::new(0);
+        disabled.record(move_entry("first", 1, 2));
+        disabled.push_undo(move_entry("second", 1, 2));
+        disabled.push_redo(move_entry("third", 1, 2));
+        assert!(disabled.pop_undo().is_none());
+        assert!(disabled.pop_redo().is_none());
+    }
+
+    #[test]
+    fn test_undo_inverse() {
+        match move_entry("move", 1, 2).inverse.as_slice() {
+            [EnvelopeOperation::Move {
+                source,
+                destination,
+                message_ids,
+            }] => {
+                assert_eq!((*source, *destination), (2, 1));
+                assert_eq!(message_ids, &vec![b"<a@example.com>".to_vec()]);
+            }
+            other => panic!("unexpected inverse {:?}", other),
+        }
+        assert!(EnvelopeOperation::SetFlags {
+            mailbox_hash: 1,
+            message_ids: vec![],
+            flags: smallvec::smallvec![(Ok(Flag::SEEN), true)],
+        }
+        .inverse()
+        .is_none());
+
+        let mut entry = JournalEntry {
+            description: "archive".to_string(),
+            operations: move_entry("", 1, 2)
+                .operations
+                .into_iter()
+                .chain(move_entry("", 1, 3).operations)
+                .chain(move_entry("", 1, 4).operations)
+                .collect(),
+            inverse: vec![],
+        };
+        entry.inverse = entry
+            .operations
+            .iter()
+            .filter_map(|op| op.inverse())
+            .collect();
+        /* Undoing runs backwards: the last two inverse operations succeeded, the first failed */
+        let mut undo = entry.clone();
+        undo.undo_failed_at(0);
+        assert_eq!(undo.inverse.len(), 1);
+        /* Redoing runs forwards: the first operation succeeded, the second failed */
+        let mut redo = entry;
+        redo.redo_failed_at(1);
+        assert_eq!(redo.operations.len(), 2);
+        match redo.operations[0] {
+            EnvelopeOperation::Move { destination, .. } => assert_eq!(destination, 3),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_undo_journal_disabled() {
+        let mut journal = UndoJournal::new(0);
+        journal.record(move_entry("first", 1, 2));
+        assert!(journal.pop_undo().is_none());
+        journal.push_redo(move_entry("undone", 1, 3));
+        assert!(journal.pop_redo().is_none());
+    }
+}